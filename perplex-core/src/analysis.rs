@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::language::LanguageStats;
+
+/// Ranks at which the cumulative probability of the predictions is kept
+/// for each token, roughly evenly spaced on a log scale.
+pub const CURVE_RANKS: [usize; 12] = [1, 2, 3, 5, 10, 20, 30, 50, 100, 200, 500, 1000];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnalyzedToken {
+    pub id: i32,
+    /// Shared with the other tokens of the same text; see [`Interner`].
+    pub text: Arc<str>,
+    pub rank: usize,
+    /// Most likely next tokens as `(token id, probability)`; resolve the
+    /// display text through the result's [`Vocab`].
+    pub top_predictions: Vec<(i32, f32)>,
+    pub probability: f32,
+    /// Log-probability gap in nats between the model's top prediction and
+    /// the actual token; zero when the token was the top prediction.
+    pub top1_margin: f32,
+    /// Surprisal in bits of this token's frequency within the document, as
+    /// a context-free baseline for the model's surprisal.
+    pub unigram_surprisal: f32,
+    /// Part of an n-gram that already occurred earlier in the text.
+    pub repeated: bool,
+    /// Badge label of a control token (BOS, EOS, chat template markers).
+    /// Its text is a marker rather than part of the document.
+    #[serde(default)]
+    pub special: Option<String>,
+    /// Index of the first token the model could see when predicting this
+    /// one; nonzero only when the text was scored in windows.
+    #[serde(default)]
+    pub context_start: usize,
+    /// Cumulative probability of the top predictions at each rank in
+    /// [`CURVE_RANKS`]; empty for unscored tokens and for results saved
+    /// before it was recorded.
+    #[serde(default)]
+    pub rank_curve: Vec<f32>,
+}
+
+impl AnalyzedToken {
+    /// Model surprisal in bits.
+    pub fn surprisal(&self) -> f32 {
+        -self.probability.max(f32::MIN_POSITIVE).log2()
+    }
+
+    /// The token's contribution to the document text: empty for special
+    /// tokens.
+    pub fn document_text(&self) -> &str {
+        if self.special.is_some() {
+            ""
+        } else {
+            &self.text
+        }
+    }
+
+    /// Model surprisal minus the unigram baseline. Positive values mean the
+    /// model found the token less expected here than its frequency suggests.
+    pub fn relative_surprisal(&self) -> f32 {
+        self.surprisal() - self.unigram_surprisal
+    }
+}
+
+/// Hands out one shared allocation per distinct token text. A long
+/// document repeats the same few thousand texts over and over, so this
+/// saves most of their memory and makes cloning a result cheap.
+#[derive(Default)]
+pub struct Interner {
+    texts: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, text: &str) -> Arc<str> {
+        if let Some(shared) = self.texts.get(text) {
+            return shared.clone();
+        }
+        let shared: Arc<str> = Arc::from(text);
+        self.texts.insert(shared.clone());
+        shared
+    }
+}
+
+/// Makes equal texts in `tokens` share an allocation again, e.g. after
+/// deserializing, which gives every token its own.
+pub fn intern_texts(tokens: &mut [AnalyzedToken]) {
+    let mut interner = Interner::default();
+    for token in tokens {
+        token.text = interner.intern(&token.text);
+    }
+}
+
+/// Fills in `unigram_surprisal` from token frequencies in the document
+/// itself. The first token has no prediction and is left out of the counts.
+pub fn assign_unigram_baseline(tokens: &mut [AnalyzedToken]) {
+    let Some((_, scored)) = tokens.split_first_mut() else {
+        return;
+    };
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for token in scored.iter() {
+        *counts.entry(&*token.text).or_default() += 1;
+    }
+    let surprisals: Vec<f32> = scored
+        .iter()
+        .map(|t| -(counts[&*t.text] as f32 / scored.len() as f32).log2())
+        .collect();
+    for (token, surprisal) in scored.iter_mut().zip(surprisals) {
+        token.unigram_surprisal = surprisal;
+    }
+}
+
+/// Display text for every token id of a model, built once per model load
+/// and shared by all of its results so predictions only store ids.
+#[derive(Default)]
+pub struct Vocab {
+    pieces: Vec<String>,
+}
+
+impl Vocab {
+    pub fn new(pieces: Vec<String>) -> Self {
+        Self { pieces }
+    }
+
+    pub fn piece(&self, id: i32) -> &str {
+        usize::try_from(id)
+            .ok()
+            .and_then(|i| self.pieces.get(i))
+            .map_or("\u{FFFD}", String::as_str)
+    }
+}
+
+impl std::fmt::Debug for Vocab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Vocab({} entries)", self.pieces.len())
+    }
+}
+
+/// Length in tokens of the n-grams checked for repetition. Short enough to
+/// catch repeated phrases, long enough to ignore common word pairs.
+pub const REPEAT_NGRAM: usize = 4;
+
+/// Marks every token covered by an n-gram that appeared earlier in the text.
+/// The first occurrence stays unmarked, so a single mention never counts.
+pub fn assign_repetition(tokens: &mut [AnalyzedToken]) {
+    if tokens.len() < REPEAT_NGRAM {
+        return;
+    }
+    let texts: Vec<&str> = tokens.iter().map(|t| &*t.text).collect();
+    let mut seen: HashSet<&[&str]> = HashSet::new();
+    let mut repeated = vec![false; tokens.len()];
+    for (i, window) in texts.windows(REPEAT_NGRAM).enumerate() {
+        if !seen.insert(window) {
+            repeated[i..i + REPEAT_NGRAM].fill(true);
+        }
+    }
+    for (token, repeated) in tokens.iter_mut().zip(repeated) {
+        token.repeated = repeated;
+    }
+}
+
+/// Summary of self-repetition in a result.
+pub struct RepetitionStats {
+    /// Fraction of scored tokens inside a repeated n-gram.
+    pub score: f32,
+    /// Longest run of consecutive repeated tokens; long runs indicate a
+    /// degenerate loop rather than an occasional repeated phrase.
+    pub longest_run: usize,
+}
+
+/// Fewest scored tokens for a positional breakdown; below this each decile
+/// holds too few tokens to mean anything.
+const MIN_POSITIONAL_TOKENS: usize = 30;
+/// A third of the document is flagged when its perplexity differs from the
+/// rest of the text by more than this factor.
+const DRIFT_RATIO: f32 = 1.5;
+
+/// Perplexity across the document, by position.
+pub struct PositionalStats {
+    /// Perplexity of each tenth of the scored tokens.
+    pub deciles: Vec<f32>,
+    pub drift: Option<Drift>,
+}
+
+/// A third of the document whose perplexity stands out from the rest.
+pub struct Drift {
+    /// 0, 1 or 2 for the first, middle or final third.
+    pub third: usize,
+    pub perplexity: f32,
+    pub rest_perplexity: f32,
+}
+
+impl Drift {
+    pub fn third_name(&self) -> &'static str {
+        ["First", "Middle", "Final"][self.third]
+    }
+
+    /// How far apart the two perplexities are, as a ratio ≥ 1.
+    fn factor(&self) -> f32 {
+        let ratio = self.perplexity / self.rest_perplexity;
+        ratio.max(1.0 / ratio)
+    }
+}
+
+/// Sum of the negative log-likelihoods (in nats) of a run of scored tokens.
+fn nll(tokens: &[AnalyzedToken]) -> f32 {
+    tokens.iter().map(|t| -t.probability.ln()).sum()
+}
+
+/// Perplexity of a run of scored tokens.
+pub fn perplexity_of(tokens: &[AnalyzedToken]) -> f32 {
+    if tokens.is_empty() {
+        return 0.0;
+    }
+    (nll(tokens) / tokens.len() as f32).exp()
+}
+
+/// Splits `tokens` into `parts` contiguous chunks of near-equal length.
+fn split_even(tokens: &[AnalyzedToken], parts: usize) -> Vec<&[AnalyzedToken]> {
+    (0..parts)
+        .map(|i| &tokens[i * tokens.len() / parts..(i + 1) * tokens.len() / parts])
+        .collect()
+}
+
+/// A token scored while its analysis is still running, before the
+/// document-level statistics exist.
+#[derive(Clone, Debug, Serialize)]
+pub struct PartialToken {
+    /// Position in the final result's `tokens`.
+    pub index: usize,
+    pub text: String,
+    pub rank: usize,
+    pub probability: f32,
+}
+
+/// Text to analyze, optionally preceded by a prompt. The prompt conditions
+/// the model but its tokens are not scored.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct AnalysisRequest {
+    /// Empty for plain text analysis.
+    #[serde(default)]
+    pub prompt: String,
+    pub text: String,
+    /// Score only this many tokens at the end; the text before them
+    /// conditions the model like the prompt.
+    #[serde(default)]
+    pub tail_tokens: Option<usize>,
+    /// Start without the BOS token, which models are trained with; only
+    /// for measuring how much it matters.
+    #[serde(default)]
+    pub skip_bos: bool,
+    /// Logit biases in the format of [`crate::logit_bias::parse`]; empty
+    /// for none.
+    #[serde(default)]
+    pub logit_bias: String,
+}
+
+impl AnalysisRequest {
+    pub fn plain(text: String) -> Self {
+        Self {
+            prompt: String::new(),
+            text,
+            tail_tokens: None,
+            skip_bos: false,
+            logit_bias: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AnalysisResult {
+    pub tokens: Vec<AnalyzedToken>,
+    /// Leading tokens (BOS included) that belong to the prompt and are
+    /// shown for context only; zero without a prompt.
+    pub prompt_tokens: usize,
+    pub processing_time_ms: u64,
+    #[serde(skip)]
+    pub vocab: Arc<Vocab>,
+    /// Per-language breakdown, most frequent language first.
+    pub languages: Vec<LanguageStats>,
+    /// Scored with a rolling or constant context, which keeps the first
+    /// token in the context of every later one, whatever its
+    /// `context_start`.
+    pub keeps_first_token: bool,
+    /// Text of the tokens past the scoring limit, which follow `tokens`
+    /// but weren't analyzed.
+    pub unscored: Vec<String>,
+}
+
+/// The tokens the model conditioned on to predict one token.
+pub struct TokenContext {
+    /// The first token, when it was kept apart from `range`.
+    pub anchor: Option<usize>,
+    pub range: Range<usize>,
+}
+
+impl TokenContext {
+    pub fn token_count(&self) -> usize {
+        self.anchor.map_or(0, |_| 1) + self.range.len()
+    }
+
+    /// Tokens before the token that the model couldn't see.
+    pub fn hidden(&self) -> usize {
+        self.range.start - self.anchor.map_or(0, |_| 1)
+    }
+}
+
+impl AnalysisResult {
+    /// Index of the first token counted in metrics: past the prompt, and
+    /// never the first token, which has no prediction.
+    pub fn first_scored(&self) -> usize {
+        self.prompt_tokens.max(1).min(self.tokens.len())
+    }
+
+    /// What the model saw when predicting token `index`: every earlier
+    /// token, unless the text was scored in windows.
+    pub fn context_of(&self, index: usize) -> TokenContext {
+        let start = self.tokens.get(index).map_or(0, |t| t.context_start);
+        TokenContext {
+            anchor: (self.keeps_first_token && start > 0).then_some(0),
+            range: start..index,
+        }
+    }
+
+    /// Scored in windows, so some tokens were predicted without the whole
+    /// preceding text.
+    pub fn is_windowed(&self) -> bool {
+        self.tokens.iter().any(|t| t.context_start > 0)
+    }
+
+    /// The tokens that count toward the metrics, after the prompt and the
+    /// unpredicted first token.
+    pub fn scored_tokens(&self) -> &[AnalyzedToken] {
+        &self.tokens[self.first_scored()..]
+    }
+
+    /// The scored tokens preceded by the one token that conditions the
+    /// first of them. Per-token statistics take this shape and skip the
+    /// leading token, so without a prompt it is simply all tokens.
+    pub fn scored_with_context(&self) -> &[AnalyzedToken] {
+        &self.tokens[self.first_scored().saturating_sub(1)..]
+    }
+
+    /// Byte range in `text`, the completion this result was analyzed from,
+    /// of each scored token as `(token index, range)`. Tokens are matched in
+    /// order; tokenizers that prepend a space to the first word are
+    /// accounted for, and tokens that can't be located (or decode to
+    /// nothing, like partial UTF-8 sequences) are left out.
+    pub fn spans(&self, text: &str) -> Vec<(usize, Range<usize>)> {
+        let mut spans = Vec::new();
+        let mut cursor = 0;
+        for (i, token) in self.tokens.iter().enumerate().skip(self.prompt_tokens) {
+            let rest = &text[cursor..];
+            let piece = if rest.starts_with(&*token.text) {
+                &*token.text
+            } else if let Some(trimmed) =
+                token.text.strip_prefix(' ').filter(|t| rest.starts_with(t))
+            {
+                trimmed
+            } else {
+                continue;
+            };
+            if !piece.is_empty() && i >= self.first_scored() {
+                spans.push((i, cursor..cursor + piece.len()));
+            }
+            cursor += piece.len();
+        }
+        spans
+    }
+
+    pub fn has_prompt(&self) -> bool {
+        self.prompt_tokens > 0
+    }
+
+    /// Total log-likelihood in nats of the scored tokens.
+    pub fn log_likelihood(&self) -> f32 {
+        -nll(self.scored_tokens())
+    }
+
+    // Perplexity is the exponential of the average negative log-likelihood per token.
+    // Formula: exp( - (1/N) * Σ ln(P(word_i)) )
+    pub fn perplexity(&self) -> f32 {
+        perplexity_of(self.scored_tokens())
+    }
+
+    /// Perplexity per document decile, plus the third that drifts furthest
+    /// from the rest if it exceeds [`DRIFT_RATIO`]. `None` for short texts.
+    pub fn positional(&self) -> Option<PositionalStats> {
+        let scored = self.scored_tokens();
+        if scored.len() < MIN_POSITIONAL_TOKENS {
+            return None;
+        }
+
+        let deciles = split_even(scored, 10)
+            .into_iter()
+            .map(perplexity_of)
+            .collect();
+
+        let total_nll = nll(scored);
+        let drift = split_even(scored, 3)
+            .into_iter()
+            .enumerate()
+            .map(|(third, part)| {
+                let rest_len = (scored.len() - part.len()) as f32;
+                Drift {
+                    third,
+                    perplexity: perplexity_of(part),
+                    rest_perplexity: ((total_nll - nll(part)) / rest_len).exp(),
+                }
+            })
+            .max_by(|a, b| a.factor().total_cmp(&b.factor()))
+            .filter(|d| d.factor() > DRIFT_RATIO);
+
+        Some(PositionalStats { deciles, drift })
+    }
+
+    pub fn repetition(&self) -> RepetitionStats {
+        let scored = self.scored_tokens();
+        let mut repeated = 0;
+        let mut run = 0;
+        let mut longest_run = 0;
+        for token in scored {
+            if token.repeated {
+                repeated += 1;
+                run += 1;
+                longest_run = longest_run.max(run);
+            } else {
+                run = 0;
+            }
+        }
+        RepetitionStats {
+            score: if scored.is_empty() {
+                0.0
+            } else {
+                repeated as f32 / scored.len() as f32
+            },
+            longest_run,
+        }
+    }
+
+    /// Standard deviation of the surprisal of the scored tokens, in bits.
+    /// Human writing mixes predictable stretches with surprising turns, so
+    /// it is usually burstier than generated text.
+    pub fn burstiness(&self) -> f32 {
+        let scored = self.scored_tokens();
+        if scored.is_empty() {
+            return 0.0;
+        }
+        let n = scored.len() as f32;
+        let mean = scored.iter().map(|t| t.surprisal()).sum::<f32>() / n;
+        let variance = scored
+            .iter()
+            .map(|t| (t.surprisal() - mean).powi(2))
+            .sum::<f32>()
+            / n;
+        variance.sqrt()
+    }
+
+    pub fn text_entropy(&self) -> f32 {
+        if self.scored_tokens().is_empty() {
+            return 0.0;
+        }
+        let ppl = self.perplexity();
+        let n = self.scored_tokens().len() as f32;
+        n * ppl.log2()
+    }
+}
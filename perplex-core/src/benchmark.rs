@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use crate::analysis::AnalysisRequest;
+use crate::llamacpp::{InferenceParams, LlamaAnalyzer};
+
+/// Batch sizes tried in addition to the configured one.
+const BATCH_SIZES: [u32; 3] = [128, 512, 2048];
+/// GPU layer counts tried in addition to the configured one: CPU only and
+/// everything offloaded.
+const GPU_LAYERS: [u32; 2] = [0, 999];
+/// Times the workload paragraph is repeated, about 500 tokens in total.
+const WORKLOAD_REPEATS: usize = 6;
+const WORKLOAD_PARAGRAPH: &str = "The committee met on Thursday to review the \
+    quarterly figures. Revenue grew slightly, although shipping delays in the \
+    northern warehouses pushed several large orders into the next period. \
+    After a long discussion, the members agreed to revisit the supplier \
+    contracts in spring and to publish a short summary for the staff. ";
+
+/// Throughput of one parameter combination.
+#[derive(Clone, Debug)]
+pub struct BenchmarkRun {
+    pub params: InferenceParams,
+    pub tokens: usize,
+    pub elapsed_ms: u64,
+}
+
+impl BenchmarkRun {
+    pub fn tokens_per_sec(&self) -> f32 {
+        self.tokens as f32 * 1000.0 / self.elapsed_ms.max(1) as f32
+    }
+}
+
+/// The fixed text every combination scores.
+pub fn workload() -> String {
+    WORKLOAD_PARAGRAPH.repeat(WORKLOAD_REPEATS)
+}
+
+/// Every combination of thread count, batch size and GPU layers to try,
+/// including the values in `base`. Thread counts are half and all of the
+/// available cores. Combinations sharing a GPU layer count are adjacent so
+/// the model is loaded once per count.
+pub fn combinations(base: &InferenceParams) -> Vec<InferenceParams> {
+    let cores = std::thread::available_parallelism()
+        .map(|n| n.get() as i32)
+        .unwrap_or(4);
+    let threads = candidates(&[(cores / 2).max(1), cores], base.n_threads);
+    let batches = candidates(&BATCH_SIZES, Some(base.n_batch));
+    let gpu_layers = candidates(&GPU_LAYERS, base.n_gpu_layers);
+
+    let mut combinations = Vec::new();
+    for &n_gpu_layers in &gpu_layers {
+        for &n_threads in &threads {
+            for &n_batch in &batches {
+                combinations.push(InferenceParams {
+                    n_gpu_layers: Some(n_gpu_layers),
+                    n_threads: Some(n_threads),
+                    n_batch,
+                    ..base.clone()
+                });
+            }
+        }
+    }
+    combinations
+}
+
+fn candidates<T: Copy + Ord>(defaults: &[T], configured: Option<T>) -> Vec<T> {
+    let mut values: Vec<T> = defaults.iter().copied().chain(configured).collect();
+    values.sort();
+    values.dedup();
+    values
+}
+
+/// Scores the workload once per combination of [`combinations`] and calls
+/// `on_run` as each finishes. Each model load gets an untimed warm-up run.
+/// GPU layer counts whose load fails (usually for lack of VRAM) are skipped,
+/// as are combinations whose context can't be allocated; it's only an error
+/// if none of them runs.
+pub fn run(
+    model_path: &Path,
+    base: &InferenceParams,
+    mut on_run: impl FnMut(&BenchmarkRun),
+) -> Result<Vec<BenchmarkRun>, String> {
+    let request = AnalysisRequest::plain(workload());
+    let mut analyzer = LlamaAnalyzer::new();
+    let mut loaded_layers = None;
+    let mut runs = Vec::new();
+    let mut last_error = None;
+    let mut warmed_up = false;
+
+    for params in combinations(base) {
+        if loaded_layers != Some(params.n_gpu_layers) {
+            loaded_layers = Some(params.n_gpu_layers);
+            if let Err(e) = analyzer.load_model(model_path, params.clone()) {
+                log::warn!(
+                    "Benchmark: skipping {:?} GPU layers: {}",
+                    params.n_gpu_layers,
+                    e
+                );
+                last_error = Some(e);
+                continue;
+            }
+            warmed_up = false;
+        }
+        if !analyzer.is_loaded() {
+            continue;
+        }
+
+        analyzer.set_params(params.clone());
+        if !warmed_up {
+            if let Err(e) = analyzer.analyze(&request, None) {
+                log::warn!("Benchmark: skipping {}: {}", describe(&params), e);
+                last_error = Some(e);
+                continue;
+            }
+            warmed_up = true;
+        }
+        let result = match analyzer.analyze(&request, None) {
+            Ok(result) => result,
+            Err(e) => {
+                log::warn!("Benchmark: skipping {}: {}", describe(&params), e);
+                last_error = Some(e);
+                continue;
+            }
+        };
+        let run = BenchmarkRun {
+            params,
+            tokens: result.tokens.len(),
+            elapsed_ms: result.processing_time_ms,
+        };
+        on_run(&run);
+        runs.push(run);
+    }
+
+    match last_error {
+        Some(e) if runs.is_empty() => Err(e),
+        _ => Ok(runs),
+    }
+}
+
+fn describe(params: &InferenceParams) -> String {
+    format!(
+        "{:?} threads, n_batch {}, {:?} GPU layers",
+        params.n_threads, params.n_batch, params.n_gpu_layers
+    )
+}
+
+/// The run with the highest throughput.
+pub fn fastest(runs: &[BenchmarkRun]) -> Option<&BenchmarkRun> {
+    runs.iter()
+        .max_by(|a, b| a.tokens_per_sec().total_cmp(&b.tokens_per_sec()))
+}
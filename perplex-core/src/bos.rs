@@ -0,0 +1,138 @@
+use crate::analysis::{AnalysisRequest, AnalysisResult};
+
+/// One combination of the tokenizer settings that quietly change a score:
+/// whether the BOS token is prepended and whether the text starts with a
+/// space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BosSetting {
+    pub bos: bool,
+    pub leading_space: bool,
+}
+
+impl BosSetting {
+    pub const ALL: [BosSetting; 4] = [
+        BosSetting {
+            bos: true,
+            leading_space: false,
+        },
+        BosSetting {
+            bos: true,
+            leading_space: true,
+        },
+        BosSetting {
+            bos: false,
+            leading_space: false,
+        },
+        BosSetting {
+            bos: false,
+            leading_space: true,
+        },
+    ];
+
+    pub fn label(self) -> String {
+        format!(
+            "{}, {}",
+            if self.bos { "BOS" } else { "no BOS" },
+            if self.leading_space {
+                "leading space"
+            } else {
+                "no leading space"
+            }
+        )
+    }
+
+    /// `request` with this setting: its text trimmed at the start, then
+    /// given a single space if the setting has one.
+    pub fn apply(self, request: &AnalysisRequest) -> AnalysisRequest {
+        let text = request.text.trim_start_matches([' ', '\t']);
+        AnalysisRequest {
+            text: if self.leading_space {
+                format!(" {}", text)
+            } else {
+                text.to_string()
+            },
+            skip_bos: !self.bos,
+            ..request.clone()
+        }
+    }
+}
+
+/// Metrics of the text analyzed under one setting.
+#[derive(Clone, Debug)]
+pub struct BosScore {
+    pub setting: BosSetting,
+    pub tokens: usize,
+    pub perplexity: f32,
+    pub top1_accuracy: f32,
+    pub median_rank: usize,
+    /// Text of the first token, which shows how the leading space was
+    /// tokenized.
+    pub first_token: String,
+}
+
+impl BosScore {
+    fn new(setting: BosSetting, result: &AnalysisResult) -> Self {
+        let scored = result.scored_tokens();
+        let mut ranks: Vec<usize> = scored.iter().map(|t| t.rank).collect();
+        ranks.sort_unstable();
+        Self {
+            setting,
+            tokens: result.tokens.len(),
+            perplexity: result.perplexity(),
+            top1_accuracy: scored.iter().filter(|t| t.rank <= 1).count() as f32
+                / scored.len().max(1) as f32,
+            median_rank: ranks.get(ranks.len() / 2).copied().unwrap_or_default(),
+            first_token: result
+                .tokens
+                .get(result.prompt_tokens)
+                .map(|t| t.text.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Perplexity relative to the analysis shown, which used the default
+    /// settings on the text as typed.
+    pub fn ratio(&self, baseline: f32) -> f32 {
+        self.perplexity / baseline
+    }
+}
+
+/// How much the score of a text depends on the BOS token and on a leading
+/// space. Neither changes the text a reader sees, yet either can move
+/// perplexity by more than the gap between two models.
+#[derive(Clone, Debug)]
+pub struct BosSensitivity {
+    /// Perplexity of the analysis shown.
+    pub baseline: f32,
+    /// One per setting, in the order of [`BosSetting::ALL`].
+    pub scores: Vec<BosScore>,
+}
+
+impl BosSensitivity {
+    /// `results` holds one analysis per setting of [`BosSetting::ALL`].
+    pub fn new(baseline: &AnalysisResult, results: &[AnalysisResult]) -> Self {
+        Self {
+            baseline: baseline.perplexity(),
+            scores: BosSetting::ALL
+                .into_iter()
+                .zip(results)
+                .map(|(setting, result)| BosScore::new(setting, result))
+                .collect(),
+        }
+    }
+
+    /// Ratio of the highest to the lowest perplexity across the settings.
+    pub fn spread(&self) -> f32 {
+        let (low, high) = self
+            .scores
+            .iter()
+            .fold((f32::INFINITY, 0.0f32), |(low, high), s| {
+                (low.min(s.perplexity), high.max(s.perplexity))
+            });
+        if low.is_finite() && low > 0.0 {
+            high / low
+        } else {
+            1.0
+        }
+    }
+}
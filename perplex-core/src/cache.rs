@@ -0,0 +1,158 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{self, AnalysisRequest, AnalysisResult, AnalyzedToken, Vocab};
+use crate::language;
+use crate::llamacpp::InferenceParams;
+
+const CACHE_DIR_NAME: &str = ".perplex/cache";
+/// Bumped whenever the stored format or the analysis itself changes, so
+/// stale entries are never returned.
+const CACHE_VERSION: u32 = 2;
+/// Oldest entries are removed past this count.
+const MAX_ENTRIES: usize = 100;
+/// Bytes read from each end of the model file for its fingerprint.
+const FINGERPRINT_BYTES: u64 = 1 << 20;
+
+/// What's stored for a result; the vocabulary comes from the loaded model
+/// and the language breakdown is recomputed on load.
+#[derive(Serialize, Deserialize)]
+struct CachedResult<'a> {
+    tokens: Cow<'a, [AnalyzedToken]>,
+    prompt_tokens: usize,
+    processing_time_ms: u64,
+    #[serde(default)]
+    keeps_first_token: bool,
+    #[serde(default)]
+    unscored: Cow<'a, [String]>,
+}
+
+/// Identifies a model file by its size and its first and last megabyte.
+/// Hashing the whole file would take longer than most analyses, and GGUF
+/// files that share both ends and size are the same model in practice.
+pub fn model_fingerprint(path: &Path) -> std::io::Result<u64> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hasher = DefaultHasher::new();
+    size.hash(&mut hasher);
+
+    let mut buffer = Vec::new();
+    file.by_ref()
+        .take(FINGERPRINT_BYTES)
+        .read_to_end(&mut buffer)?;
+    let tail = size
+        .saturating_sub(FINGERPRINT_BYTES)
+        .max(FINGERPRINT_BYTES);
+    if tail < size {
+        file.seek(SeekFrom::Start(tail))?;
+        file.take(FINGERPRINT_BYTES).read_to_end(&mut buffer)?;
+    }
+    buffer.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Cache key of a request scored by the model with `fingerprint` and the
+/// given parameters.
+pub fn key(fingerprint: u64, params: &InferenceParams, request: &AnalysisRequest) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    CACHE_VERSION.hash(&mut hasher);
+    fingerprint.hash(&mut hasher);
+    params.n_gpu_layers.hash(&mut hasher);
+    params.n_ctx.hash(&mut hasher);
+    params.n_batch.hash(&mut hasher);
+    // Only when set, so results cached before the option existed still hit.
+    if let Some(window) = params.low_memory_window {
+        window.hash(&mut hasher);
+        params.rolling_context.hash(&mut hasher);
+    }
+    if let Some(k) = params.constant_context {
+        k.hash(&mut hasher);
+    }
+    if let Some(limit) = params.max_tokens {
+        limit.hash(&mut hasher);
+    }
+    request.prompt.hash(&mut hasher);
+    request.text.hash(&mut hasher);
+    if let Some(tail) = request.tail_tokens {
+        tail.hash(&mut hasher);
+    }
+    if request.skip_bos {
+        request.skip_bos.hash(&mut hasher);
+    }
+    if !request.logit_bias.is_empty() {
+        request.logit_bias.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn dir() -> PathBuf {
+    let home = env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("."));
+    home.join(CACHE_DIR_NAME)
+}
+
+fn file_path(key: u64) -> PathBuf {
+    dir().join(format!("{:016x}.json", key))
+}
+
+/// The stored result for `key`, if any, with the model's vocabulary.
+pub fn load(key: u64, vocab: Arc<Vocab>) -> Option<AnalysisResult> {
+    let content = fs::read_to_string(file_path(key)).ok()?;
+    let cached: CachedResult = match serde_json::from_str(&content) {
+        Ok(cached) => cached,
+        Err(e) => {
+            log::warn!("Ignoring unreadable cache entry {:016x}: {}", key, e);
+            return None;
+        }
+    };
+    let context_start = cached.prompt_tokens.saturating_sub(1);
+    let mut tokens = cached.tokens.into_owned();
+    analysis::intern_texts(&mut tokens);
+    Some(AnalysisResult {
+        languages: language::language_breakdown(&tokens[context_start..]),
+        tokens,
+        prompt_tokens: cached.prompt_tokens,
+        processing_time_ms: cached.processing_time_ms,
+        vocab,
+        keeps_first_token: cached.keeps_first_token,
+        unscored: cached.unscored.into_owned(),
+    })
+}
+
+/// Stores `result` under `key`, then removes the oldest entries beyond
+/// [`MAX_ENTRIES`].
+pub fn store(key: u64, result: &AnalysisResult) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir())?;
+    let cached = CachedResult {
+        tokens: Cow::Borrowed(&result.tokens),
+        prompt_tokens: result.prompt_tokens,
+        processing_time_ms: result.processing_time_ms,
+        keeps_first_token: result.keeps_first_token,
+        unscored: Cow::Borrowed(&result.unscored),
+    };
+    // Streamed, since the JSON of a long text is several times its size.
+    let mut writer = BufWriter::new(File::create(file_path(key))?);
+    serde_json::to_writer(&mut writer, &cached)?;
+    io::Write::flush(&mut writer)?;
+
+    let mut entries: Vec<(std::time::SystemTime, PathBuf)> = fs::read_dir(dir())?
+        .filter_map(Result::ok)
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    if entries.len() > MAX_ENTRIES {
+        entries.sort();
+        for (_, path) in &entries[..entries.len() - MAX_ENTRIES] {
+            let _ = fs::remove_file(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use llama_cpp_2::context::LlamaContext;
+use llama_cpp_2::token::LlamaToken;
+use serde::{Deserialize, Serialize};
+
+const CHECKPOINT_DIR_NAME: &str = ".perplex/checkpoints";
+const STATE_FILE: &str = "state.bin";
+const PROGRESS_FILE: &str = "progress.json";
+
+/// Texts shorter than this finish too quickly to be worth checkpointing.
+pub const MIN_TOKENS: usize = 8192;
+/// Time between checkpoints. Each one writes the whole KV cache, which for
+/// book-length texts is several gigabytes.
+pub const INTERVAL: Duration = Duration::from_secs(120);
+
+/// Rank, probability and top predictions of each position decoded so far,
+/// as collected by the analysis loop.
+pub type TokenMetrics = (usize, f32, Vec<(i32, f32)>);
+
+#[derive(Serialize, Deserialize)]
+pub struct Progress {
+    /// Tokens decoded, and so the length of `results`.
+    pub processed: usize,
+    pub results: Vec<TokenMetrics>,
+}
+
+/// On-disk state of one interrupted analysis: the llama.cpp context state
+/// and the metrics of the tokens it covers. Keyed like the result cache, so
+/// only the same text with the same model and settings resumes from it.
+pub struct Checkpoint {
+    dir: PathBuf,
+}
+
+impl Checkpoint {
+    pub fn new(key: u64) -> Self {
+        let home = env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("."));
+        Self {
+            dir: home.join(CHECKPOINT_DIR_NAME).join(format!("{:016x}", key)),
+        }
+    }
+
+    /// Writes the context state, then the metrics. The metrics file is
+    /// replaced atomically, so an interrupted write leaves the previous
+    /// checkpoint usable: [`Self::resume`] drops the extra cached tokens.
+    pub fn save(
+        &self,
+        ctx: &LlamaContext,
+        tokens: &[LlamaToken],
+        results: &[TokenMetrics],
+    ) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        ctx.state_save_file(self.dir.join(STATE_FILE), tokens)
+            .map_err(|e| e.to_string())?;
+        let progress = Progress {
+            processed: tokens.len(),
+            results: results.to_vec(),
+        };
+        let json = serde_json::to_string(&progress).map_err(|e| e.to_string())?;
+        let temp = self.dir.join(format!("{}.tmp", PROGRESS_FILE));
+        fs::write(&temp, json).map_err(|e| e.to_string())?;
+        fs::rename(&temp, self.dir.join(PROGRESS_FILE)).map_err(|e| e.to_string())
+    }
+
+    /// Restores a saved checkpoint into `ctx`, a fresh context for
+    /// `tokens`, and returns the metrics to continue from. `None` if there
+    /// is no usable checkpoint; `ctx` is then left empty.
+    pub fn resume(&self, ctx: &mut LlamaContext, tokens: &[LlamaToken]) -> Option<Progress> {
+        let json = fs::read_to_string(self.dir.join(PROGRESS_FILE)).ok()?;
+        let progress: Progress = serde_json::from_str(&json)
+            .map_err(|e| log::warn!("Ignoring unreadable checkpoint: {}", e))
+            .ok()?;
+        let restored = match ctx.state_load_file(self.dir.join(STATE_FILE), tokens.len()) {
+            Ok(restored) => restored,
+            Err(e) => {
+                log::warn!("Ignoring checkpoint: {}", e);
+                ctx.clear_kv_cache();
+                return None;
+            }
+        };
+        let processed = progress.processed;
+        if processed != progress.results.len()
+            || processed > restored.len()
+            || restored[..processed] != tokens[..processed]
+        {
+            log::warn!("Ignoring checkpoint saved for different tokens");
+            ctx.clear_kv_cache();
+            return None;
+        }
+        // The state may be newer than the metrics if saving was interrupted.
+        if restored.len() > processed
+            && ctx
+                .clear_kv_cache_seq(Some(0), Some(processed as u32), None)
+                .is_err()
+        {
+            ctx.clear_kv_cache();
+            return None;
+        }
+        Some(progress)
+    }
+
+    pub fn remove(&self) {
+        if self.dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&self.dir) {
+                log::warn!("Failed to remove checkpoint: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+/// A candidate prompt and the text's perplexity when conditioned on it.
+#[derive(Clone, Debug)]
+pub struct ConditionedScore {
+    pub name: String,
+    pub perplexity: f32,
+}
+
+impl ConditionedScore {
+    /// Perplexity relative to the unconditioned text; below 1 means the
+    /// prompt makes the text more predictable, i.e. the text is more
+    /// in-distribution for that persona or instruction.
+    pub fn ratio(&self, unconditioned: f32) -> f32 {
+        self.perplexity / unconditioned
+    }
+}
+
+/// Result of scoring the same text under several candidate prompts.
+#[derive(Clone, Debug)]
+pub struct ConditioningStats {
+    /// Perplexity of the text without any prompt.
+    pub unconditioned: f32,
+    /// Sorted from the most to the least predictable.
+    pub scores: Vec<ConditionedScore>,
+}
+
+impl ConditioningStats {
+    /// `perplexities` holds the unconditioned text first, then one entry
+    /// per name.
+    pub fn new(names: Vec<String>, perplexities: &[f32]) -> Option<Self> {
+        let (&unconditioned, conditioned) = perplexities.split_first()?;
+        let mut scores: Vec<ConditionedScore> = names
+            .into_iter()
+            .zip(conditioned)
+            .map(|(name, &perplexity)| ConditionedScore { name, perplexity })
+            .collect();
+        scores.sort_by(|a, b| a.perplexity.total_cmp(&b.perplexity));
+        Some(Self {
+            unconditioned,
+            scores,
+        })
+    }
+
+    /// The prompt under which the text is most predictable.
+    pub fn best(&self) -> Option<&ConditionedScore> {
+        self.scores.first()
+    }
+}
@@ -0,0 +1,238 @@
+use crate::analysis::AnalysisResult;
+
+/// Cyrillic and Greek letters that render like a Latin letter in most
+/// fonts, with that letter.
+const LOOKALIKES: [(char, char); 61] = [
+    ('а', 'a'),
+    ('е', 'e'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('і', 'i'),
+    ('ј', 'j'),
+    ('ѕ', 's'),
+    ('ԁ', 'd'),
+    ('һ', 'h'),
+    ('ӏ', 'l'),
+    ('ԛ', 'q'),
+    ('ԝ', 'w'),
+    ('ү', 'y'),
+    ('А', 'A'),
+    ('В', 'B'),
+    ('Е', 'E'),
+    ('К', 'K'),
+    ('М', 'M'),
+    ('Н', 'H'),
+    ('О', 'O'),
+    ('Р', 'P'),
+    ('С', 'C'),
+    ('Т', 'T'),
+    ('Х', 'X'),
+    ('І', 'I'),
+    ('Ј', 'J'),
+    ('Ѕ', 'S'),
+    ('Ү', 'Y'),
+    ('Ԛ', 'Q'),
+    ('Ԝ', 'W'),
+    ('Ӏ', 'I'),
+    ('ο', 'o'),
+    ('α', 'a'),
+    ('ν', 'v'),
+    ('ι', 'i'),
+    ('κ', 'k'),
+    ('ρ', 'p'),
+    ('υ', 'u'),
+    ('χ', 'x'),
+    ('γ', 'y'),
+    ('ϲ', 'c'),
+    ('ϳ', 'j'),
+    ('Α', 'A'),
+    ('Β', 'B'),
+    ('Ε', 'E'),
+    ('Ζ', 'Z'),
+    ('Η', 'H'),
+    ('Ι', 'I'),
+    ('Κ', 'K'),
+    ('Μ', 'M'),
+    ('Ν', 'N'),
+    ('Ο', 'O'),
+    ('Ρ', 'P'),
+    ('Τ', 'T'),
+    ('Υ', 'Y'),
+    ('Χ', 'X'),
+    ('Ϲ', 'C'),
+    ('Ϳ', 'J'),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+}
+
+impl Script {
+    pub const ALL: [Script; 3] = [Script::Latin, Script::Cyrillic, Script::Greek];
+
+    pub fn of(c: char) -> Option<Script> {
+        match c {
+            'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' if c != '×' && c != '÷' => {
+                Some(Script::Latin)
+            }
+            '\u{0400}'..='\u{052F}' => Some(Script::Cyrillic),
+            '\u{0370}'..='\u{03FF}' => Some(Script::Greek),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Script::Latin => "Latin",
+            Script::Cyrillic => "Cyrillic",
+            Script::Greek => "Greek",
+        }
+    }
+}
+
+/// A letter that looks like a letter of the script around it but isn't,
+/// like a Cyrillic `а` in an English word. Such swaps fool readers and
+/// filters alike, and make the model see rare tokens where the text looks
+/// ordinary.
+#[derive(Clone, Copy, Debug)]
+pub struct Confusable {
+    /// Byte offset in the text.
+    pub offset: usize,
+    pub char: char,
+    pub script: Script,
+    /// The letter it passes for.
+    pub looks_like: char,
+    pub expected: Script,
+    /// 1-based line and column.
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Confusable {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} '{}' (U+{:04X}) looks like {} '{}'",
+            self.script.label(),
+            self.char,
+            self.char as u32,
+            self.expected.label(),
+            self.looks_like
+        )
+    }
+}
+
+/// The lookalike letter of `script` for `c`, if any.
+fn lookalike(c: char, script: Script) -> Option<char> {
+    match (Script::of(c)?, script) {
+        (from, to) if from == to => None,
+        (_, Script::Latin) => LOOKALIKES.iter().find(|(k, _)| *k == c).map(|(_, v)| *v),
+        (Script::Latin, to) => LOOKALIKES
+            .iter()
+            .find(|(k, v)| *v == c && Script::of(*k) == Some(to))
+            .map(|(k, _)| *k),
+        _ => None,
+    }
+}
+
+/// Mixed-script lookalikes in `text`, in order. A word mixing scripts is
+/// flagged at its letters outside the word's main script; a word written
+/// entirely in lookalikes of the text's main script, like a Cyrillic `о`
+/// standing alone in English, is flagged whole.
+pub fn find(text: &str) -> Vec<Confusable> {
+    let main = main_script(text.chars());
+    let mut found = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    let mut word: Vec<(usize, char, usize, usize)> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        if c.is_alphabetic() {
+            word.push((offset, c, line, column));
+        }
+        let word_ends = chars.peek().is_none_or(|&(_, next)| !next.is_alphabetic());
+        if word_ends && !word.is_empty() {
+            flag_word(&word, main, &mut found);
+            word.clear();
+        }
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    found
+}
+
+fn flag_word(word: &[(usize, char, usize, usize)], main: Script, found: &mut Vec<Confusable>) {
+    let word_script = main_script(word.iter().map(|&(_, c, _, _)| c));
+    let mixed = word
+        .iter()
+        .filter_map(|&(_, c, _, _)| Script::of(c))
+        .any(|s| s != word_script);
+    let expected = if mixed {
+        word_script
+    } else if word_script != main
+        && word
+            .iter()
+            .all(|&(_, c, _, _)| lookalike(c, main).is_some())
+    {
+        main
+    } else {
+        return;
+    };
+    for &(offset, c, line, column) in word {
+        let (Some(script), Some(looks_like)) = (Script::of(c), lookalike(c, expected)) else {
+            continue;
+        };
+        found.push(Confusable {
+            offset,
+            char: c,
+            script,
+            looks_like,
+            expected,
+            line,
+            column,
+        });
+    }
+}
+
+/// The script with the most letters, Latin on a tie or without letters.
+fn main_script(chars: impl Iterator<Item = char>) -> Script {
+    let mut counts = [0usize; 3];
+    for script in chars.filter_map(Script::of) {
+        counts[Script::ALL.iter().position(|&s| s == script).unwrap_or(0)] += 1;
+    }
+    Script::ALL
+        .into_iter()
+        .zip(counts)
+        .fold((Script::Latin, 0), |best, (script, count)| {
+            if count > best.1 {
+                (script, count)
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+/// Lookalikes in the analyzed text of `result`, each with the index of the
+/// token holding it.
+pub fn in_tokens(result: &AnalysisResult) -> Vec<(usize, Confusable)> {
+    let start = result.prompt_tokens.min(result.tokens.len());
+    let mut text = String::new();
+    let mut ends = Vec::with_capacity(result.tokens.len() - start);
+    for token in &result.tokens[start..] {
+        text.push_str(token.document_text());
+        ends.push(text.len());
+    }
+    find(&text)
+        .into_iter()
+        .map(|c| (start + ends.partition_point(|&end| end <= c.offset), c))
+        .collect()
+}
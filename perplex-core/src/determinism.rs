@@ -0,0 +1,96 @@
+use crate::analysis::AnalysisResult;
+
+/// Probability differences at or below this are treated as identical;
+/// they're within the rounding of the log-softmax.
+const DIVERGENCE_EPSILON: f32 = 1e-6;
+/// Diverging tokens kept for display, largest difference first.
+const MAX_DIVERGENCES: usize = 20;
+
+/// A token whose probability differed between the two runs.
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    pub index: usize,
+    pub text: String,
+    pub first: f32,
+    pub second: f32,
+    pub rank_changed: bool,
+}
+
+impl Divergence {
+    pub fn difference(&self) -> f32 {
+        (self.first - self.second).abs()
+    }
+}
+
+/// Comparison of two analyses of the same request. Any divergence is
+/// noise from the backend (kernel selection, batch layout, atomics), and
+/// bounds how small a difference between results can be trusted.
+#[derive(Clone, Debug)]
+pub struct DeterminismStats {
+    pub compared: usize,
+    pub diverged: usize,
+    pub ranks_changed: usize,
+    pub max_difference: f32,
+    pub mean_difference: f32,
+    pub perplexities: (f32, f32),
+    /// The largest divergences, at most [`MAX_DIVERGENCES`].
+    pub worst: Vec<Divergence>,
+}
+
+impl DeterminismStats {
+    /// Compares the scored tokens of `first` and `second` position by
+    /// position. Fails if they weren't tokenized identically.
+    pub fn new(first: &AnalysisResult, second: &AnalysisResult) -> Result<Self, String> {
+        if first.tokens.len() != second.tokens.len() || first.prompt_tokens != second.prompt_tokens
+        {
+            return Err(format!(
+                "The runs produced different tokens ({} vs {})",
+                first.tokens.len(),
+                second.tokens.len()
+            ));
+        }
+
+        let start = first.first_scored();
+        let mut divergences = Vec::new();
+        let mut total_difference = 0.0;
+        let mut ranks_changed = 0;
+        for (index, (a, b)) in first
+            .tokens
+            .iter()
+            .zip(&second.tokens)
+            .enumerate()
+            .skip(start)
+        {
+            let difference = (a.probability - b.probability).abs();
+            total_difference += difference;
+            if a.rank != b.rank {
+                ranks_changed += 1;
+            }
+            if difference > DIVERGENCE_EPSILON || a.rank != b.rank {
+                divergences.push(Divergence {
+                    index,
+                    text: a.text.to_string(),
+                    first: a.probability,
+                    second: b.probability,
+                    rank_changed: a.rank != b.rank,
+                });
+            }
+        }
+
+        let compared = first.tokens.len().saturating_sub(start);
+        divergences.sort_by(|a, b| b.difference().total_cmp(&a.difference()));
+        Ok(Self {
+            compared,
+            diverged: divergences.len(),
+            ranks_changed,
+            max_difference: divergences.first().map_or(0.0, Divergence::difference),
+            mean_difference: total_difference / compared.max(1) as f32,
+            perplexities: (first.perplexity(), second.perplexity()),
+            worst: divergences.into_iter().take(MAX_DIVERGENCES).collect(),
+        })
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.diverged == 0
+    }
+}
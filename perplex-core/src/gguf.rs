@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"GGUF";
+/// Arrays longer than this (vocabularies, merges) are summarized rather
+/// than listed.
+const MAX_LISTED_ELEMENTS: u64 = 16;
+
+/// Reads the key-value metadata from the header of a GGUF file without
+/// loading the model, as `(key, value)` with values rendered as text.
+/// Only GGUF v2 and later (64-bit lengths) are supported.
+pub fn read_metadata(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).map_err(|e| e.to_string())?;
+    if &magic != MAGIC {
+        return Err("Not a GGUF file".to_string());
+    }
+    let version = read_u32(&mut reader).map_err(|e| e.to_string())?;
+    if version < 2 {
+        return Err(format!("Unsupported GGUF version {}", version));
+    }
+    let tensor_count = read_u64(&mut reader).map_err(|e| e.to_string())?;
+    let kv_count = read_u64(&mut reader).map_err(|e| e.to_string())?;
+
+    let mut metadata = vec![
+        ("gguf.version".to_string(), version.to_string()),
+        ("gguf.tensor_count".to_string(), tensor_count.to_string()),
+    ];
+    for _ in 0..kv_count {
+        let key = read_string(&mut reader).map_err(|e| e.to_string())?;
+        let value_type = read_u32(&mut reader).map_err(|e| e.to_string())?;
+        let value = read_value(&mut reader, value_type)
+            .map_err(|e| format!("Failed to read '{}': {}", key, e))?;
+        metadata.push((key, value));
+    }
+    Ok(metadata)
+}
+
+fn read_value<R: Read + Seek>(reader: &mut R, value_type: u32) -> io::Result<String> {
+    Ok(match value_type {
+        0 => read_bytes::<1, _>(reader)?[0].to_string(),
+        1 => (read_bytes::<1, _>(reader)?[0] as i8).to_string(),
+        2 => u16::from_le_bytes(read_bytes(reader)?).to_string(),
+        3 => i16::from_le_bytes(read_bytes(reader)?).to_string(),
+        4 => read_u32(reader)?.to_string(),
+        5 => i32::from_le_bytes(read_bytes(reader)?).to_string(),
+        6 => f32::from_le_bytes(read_bytes(reader)?).to_string(),
+        7 => (read_bytes::<1, _>(reader)?[0] != 0).to_string(),
+        8 => format!("{:?}", read_string(reader)?),
+        9 => {
+            let element_type = read_u32(reader)?;
+            let count = read_u64(reader)?;
+            if count > MAX_LISTED_ELEMENTS {
+                skip_array(reader, element_type, count)?;
+                return Ok(format!("[{} elements]", count));
+            }
+            let elements = (0..count)
+                .map(|_| read_value(reader, element_type))
+                .collect::<io::Result<Vec<_>>>()?;
+            format!("[{}]", elements.join(", "))
+        }
+        10 => read_u64(reader)?.to_string(),
+        11 => i64::from_le_bytes(read_bytes(reader)?).to_string(),
+        12 => f64::from_le_bytes(read_bytes(reader)?).to_string(),
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown value type {}", other),
+            ))
+        }
+    })
+}
+
+fn skip_array<R: Read + Seek>(reader: &mut R, element_type: u32, count: u64) -> io::Result<()> {
+    let size: i64 = match element_type {
+        0 | 1 | 7 => 1,
+        2 | 3 => 2,
+        4..=6 => 4,
+        10..=12 => 8,
+        8 => {
+            for _ in 0..count {
+                let len = read_u64(reader)?;
+                reader.seek_relative(len as i64)?;
+            }
+            return Ok(());
+        }
+        _ => {
+            // Nested arrays have no fixed size.
+            for _ in 0..count {
+                read_value(reader, element_type)?;
+            }
+            return Ok(());
+        }
+    };
+    reader.seek_relative(size * count as i64)?;
+    Ok(())
+}
+
+fn read_bytes<const N: usize, R: Read>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_u64(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
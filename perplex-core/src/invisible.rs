@@ -0,0 +1,97 @@
+/// Positions listed per character; the count covers the rest.
+const MAX_POSITIONS: usize = 10;
+
+/// An invisible or control character found in a text, with where it
+/// occurs. Such characters change tokenization without changing what a
+/// reader sees, and often reveal where a text was copied from or that it
+/// was tampered with.
+#[derive(Clone, Debug)]
+pub struct InvisibleChar {
+    pub char: char,
+    pub name: &'static str,
+    pub count: usize,
+    /// Line and column, both 1-based, of the first occurrences, at most
+    /// [`MAX_POSITIONS`].
+    pub positions: Vec<(usize, usize)>,
+}
+
+impl InvisibleChar {
+    /// Code point in `U+200B` notation.
+    pub fn code(&self) -> String {
+        format!("U+{:04X}", self.char as u32)
+    }
+}
+
+/// Name of `c` if it is invisible: zero-width and joiner characters, bidi
+/// controls, format characters and control codes other than tabs and line
+/// breaks. Variation selectors are left out since emoji need them.
+pub fn invisible_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{00AD}' => "soft hyphen",
+        '\u{034F}' => "combining grapheme joiner",
+        '\u{061C}' => "Arabic letter mark",
+        '\u{115F}' | '\u{1160}' | '\u{3164}' | '\u{FFA0}' => "Hangul filler",
+        '\u{180E}' => "Mongolian vowel separator",
+        '\u{200B}' => "zero-width space",
+        '\u{200C}' => "zero-width non-joiner",
+        '\u{200D}' => "zero-width joiner",
+        '\u{200E}' => "left-to-right mark",
+        '\u{200F}' => "right-to-left mark",
+        '\u{202A}' => "left-to-right embedding",
+        '\u{202B}' => "right-to-left embedding",
+        '\u{202C}' => "pop directional formatting",
+        '\u{202D}' => "left-to-right override",
+        '\u{202E}' => "right-to-left override",
+        '\u{2060}' => "word joiner",
+        '\u{2061}'..='\u{2064}' => "invisible math operator",
+        '\u{2066}' => "left-to-right isolate",
+        '\u{2067}' => "right-to-left isolate",
+        '\u{2068}' => "first strong isolate",
+        '\u{2069}' => "pop directional isolate",
+        '\u{206A}'..='\u{206F}' => "deprecated format character",
+        '\u{FEFF}' => "byte order mark",
+        '\u{FFF9}'..='\u{FFFB}' => "interlinear annotation",
+        '\u{E0000}'..='\u{E007F}' => "tag character",
+        '\t' | '\n' | '\r' => return None,
+        c if c.is_control() => "control character",
+        _ => return None,
+    })
+}
+
+/// The invisible characters in `text`, in order of first appearance.
+pub fn scan(text: &str) -> Vec<InvisibleChar> {
+    let mut found: Vec<InvisibleChar> = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    for c in text.chars() {
+        if let Some(name) = invisible_name(c) {
+            match found.iter_mut().find(|f| f.char == c) {
+                Some(existing) => {
+                    existing.count += 1;
+                    if existing.positions.len() < MAX_POSITIONS {
+                        existing.positions.push((line, column));
+                    }
+                }
+                None => found.push(InvisibleChar {
+                    char: c,
+                    name,
+                    count: 1,
+                    positions: vec![(line, column)],
+                }),
+            }
+        }
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    found
+}
+
+/// `text` without its invisible characters.
+pub fn remove(text: &str) -> String {
+    text.chars()
+        .filter(|&c| invisible_name(c).is_none())
+        .collect()
+}
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{AnalysisResult, AnalyzedToken};
+
+/// Segments shorter than this are left out: their isolated perplexity is
+/// dominated by the first token, which has no context at all.
+const MIN_SEGMENT_TOKENS: usize = 3;
+
+/// How the text is cut into independently scored segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SegmentMode {
+    Sentence,
+    Line,
+    Paragraph,
+}
+
+impl SegmentMode {
+    pub const ALL: [SegmentMode; 3] = [
+        SegmentMode::Sentence,
+        SegmentMode::Line,
+        SegmentMode::Paragraph,
+    ];
+}
+
+impl std::fmt::Display for SegmentMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SegmentMode::Sentence => write!(f, "Sentences"),
+            SegmentMode::Line => write!(f, "Lines"),
+            SegmentMode::Paragraph => write!(f, "Paragraphs"),
+        }
+    }
+}
+
+/// A run of scored tokens and their perplexity within the full document.
+#[derive(Clone, Debug)]
+pub struct Segment {
+    pub text: String,
+    pub tokens: usize,
+    pub in_context: f32,
+}
+
+/// A segment scored both with the full document before it and alone.
+#[derive(Clone, Debug)]
+pub struct IsolatedSegment {
+    pub text: String,
+    pub tokens: usize,
+    pub in_context: f32,
+    pub isolated: f32,
+}
+
+impl IsolatedSegment {
+    /// How many times more perplexing the segment is without the document
+    /// before it; above 1 means it benefits from context.
+    pub fn benefit(&self) -> f32 {
+        self.isolated / self.in_context
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IsolationStats {
+    pub segments: Vec<IsolatedSegment>,
+}
+
+impl IsolationStats {
+    pub fn new(segments: Vec<Segment>, isolated: &[f32]) -> Self {
+        Self {
+            segments: segments
+                .into_iter()
+                .zip(isolated)
+                .map(|(s, &isolated)| IsolatedSegment {
+                    text: s.text,
+                    tokens: s.tokens,
+                    in_context: s.in_context,
+                    isolated,
+                })
+                .collect(),
+        }
+    }
+
+    /// Token-weighted geometric mean of the per-segment benefit.
+    pub fn mean_benefit(&self) -> f32 {
+        let tokens: usize = self.segments.iter().map(|s| s.tokens).sum();
+        if tokens == 0 {
+            return 1.0;
+        }
+        let log_sum: f32 = self
+            .segments
+            .iter()
+            .map(|s| s.benefit().ln() * s.tokens as f32)
+            .sum();
+        (log_sum / tokens as f32).exp()
+    }
+}
+
+/// Whether `token` closes a segment. `after_newline` is true when the
+/// previous token ended a line, so a blank line splits paragraphs even
+/// when the newlines are separate tokens.
+fn ends_segment(token: &AnalyzedToken, mode: SegmentMode, after_newline: bool) -> bool {
+    let text = &*token.text;
+    match mode {
+        SegmentMode::Line => text.contains('\n'),
+        SegmentMode::Paragraph => {
+            text.contains("\n\n")
+                || (after_newline && text.trim_start_matches(' ').starts_with('\n'))
+        }
+        SegmentMode::Sentence => {
+            text.contains('\n')
+                || text
+                    .trim_end()
+                    .trim_end_matches(['"', '\'', '”', '’', ')'])
+                    .ends_with(['.', '!', '?', '…'])
+        }
+    }
+}
+
+/// Cuts the scored tokens of `result` into segments, with each segment's
+/// perplexity in the full-context analysis.
+pub fn segments(result: &AnalysisResult, mode: SegmentMode) -> Vec<Segment> {
+    let scored = result.scored_with_context().get(1..).unwrap_or_default();
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut after_newline = false;
+    for (i, token) in scored.iter().enumerate() {
+        let last = i + 1 == scored.len();
+        if ends_segment(token, mode, after_newline) || last {
+            push_segment(&mut segments, &scored[start..=i]);
+            start = i + 1;
+        }
+        after_newline = token.text.ends_with('\n');
+    }
+    segments
+}
+
+fn push_segment(segments: &mut Vec<Segment>, tokens: &[AnalyzedToken]) {
+    let text: String = tokens.iter().map(|t| &*t.text).collect();
+    let text = text.trim();
+    if text.is_empty() || tokens.len() < MIN_SEGMENT_TOKENS {
+        return;
+    }
+    let nll: f32 = tokens
+        .iter()
+        .map(|t| -t.probability.max(f32::MIN_POSITIVE).ln())
+        .sum();
+    segments.push(Segment {
+        text: text.to_string(),
+        tokens: tokens.len(),
+        in_context: (nll / tokens.len() as f32).exp(),
+    });
+}
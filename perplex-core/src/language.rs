@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::analysis::AnalyzedToken;
+
+/// Lines shorter than this are merged with the following ones before
+/// detection; very short samples give unreliable guesses.
+const MIN_SEGMENT_CHARS: usize = 40;
+
+/// Token count and perplexity of the tokens detected as one language.
+#[derive(Clone, Debug, Serialize)]
+pub struct LanguageStats {
+    pub language: &'static str,
+    pub tokens: usize,
+    pub perplexity: f32,
+}
+
+/// Splits the scored tokens into line-based segments, detects the language
+/// of each one, and aggregates the tokens per language. Segments that can't
+/// be detected reliably inherit the language of the previous segment.
+/// Sorted by token count, most frequent language first.
+pub fn language_breakdown(tokens: &[AnalyzedToken]) -> Vec<LanguageStats> {
+    let scored = tokens.get(1..).unwrap_or_default();
+
+    let mut segments: Vec<&[AnalyzedToken]> = Vec::new();
+    let mut start = 0;
+    let mut chars = 0;
+    for (i, token) in scored.iter().enumerate() {
+        chars += token.text.chars().count();
+        if token.text.contains('\n') && chars >= MIN_SEGMENT_CHARS {
+            segments.push(&scored[start..=i]);
+            start = i + 1;
+            chars = 0;
+        }
+    }
+    if start < scored.len() {
+        segments.push(&scored[start..]);
+    }
+
+    // Language -> (token count, sum of negative log-likelihoods).
+    let mut totals: HashMap<&'static str, (usize, f32)> = HashMap::new();
+    let mut current = "Unknown";
+    for segment in segments {
+        let text: String = segment.iter().map(AnalyzedToken::document_text).collect();
+        if let Some(info) = whatlang::detect(&text).filter(|i| i.is_reliable()) {
+            current = info.lang().eng_name();
+        }
+        let entry = totals.entry(current).or_default();
+        entry.0 += segment.len();
+        entry.1 += segment.iter().map(|t| -t.probability.ln()).sum::<f32>();
+    }
+
+    let mut stats: Vec<LanguageStats> = totals
+        .into_iter()
+        .map(|(language, (tokens, nll))| LanguageStats {
+            language,
+            tokens,
+            perplexity: (nll / tokens as f32).exp(),
+        })
+        .collect();
+    stats.sort_by_key(|s| std::cmp::Reverse(s.tokens));
+    stats
+}
@@ -0,0 +1,45 @@
+//! Analysis engine of Perplex, usable without the GUI.
+//!
+//! [`llamacpp::LlamaAnalyzer`] scores text with a GGUF model and returns an
+//! [`analysis::AnalysisResult`] with per-token ranks and probabilities.
+//! The remaining modules derive metrics from a result. To keep the model
+//! off the caller's thread, [`worker::WorkerManager`] runs an analyzer in
+//! the background and exchanges [`worker::WorkerCommand`]s and
+//! [`worker::WorkerMessage`]s with it; [`worker::SideJob`]s run beside an
+//! analysis on a second thread that shares the loaded model.
+
+pub mod analysis;
+pub mod benchmark;
+pub mod bos;
+pub mod cache;
+pub mod checkpoint;
+pub mod conditioning;
+pub mod confusables;
+pub mod determinism;
+pub mod gguf;
+pub mod invisible;
+pub mod isolation;
+pub mod language;
+pub mod llamacpp;
+pub mod logit_bias;
+pub mod perturbation;
+pub mod preprocess;
+pub mod quantization;
+pub mod reference;
+pub mod regeneration;
+pub mod resources;
+pub mod roc;
+pub mod scoring;
+pub mod scripting;
+pub mod shuffle;
+pub mod significance;
+pub mod speakers;
+pub mod special_tokens;
+pub mod template;
+pub mod token_cache;
+pub mod token_types;
+pub mod typography;
+pub mod utils;
+pub mod watermark;
+pub mod windowing;
+pub mod worker;
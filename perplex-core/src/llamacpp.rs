@@ -0,0 +1,1982 @@
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::context::LlamaContext;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::{LlamaModelParams, LlamaSplitMode};
+use llama_cpp_2::model::LlamaModel;
+use llama_cpp_2::token_type::LlamaTokenAttr;
+use llama_cpp_2::LlamaBackendDeviceType;
+use llama_cpp_2::TokenToStringError;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard, OnceLock};
+
+static LLAMA_BACKEND: OnceLock<LlamaBackend> = OnceLock::new();
+
+/// In low-memory mode, each window re-reads this fraction of the previous
+/// one as context for its first tokens. With a rolling context, this
+/// fraction of the oldest tokens is dropped whenever the context is full.
+const LOW_MEMORY_OVERLAP_DIVISOR: usize = 4;
+
+/// Window lengths tried by the context sweep, in addition to the full text.
+pub const CONTEXT_SWEEP_LENGTHS: [u32; 5] = [256, 512, 1024, 2048, 4096];
+
+fn get_backend() -> &'static LlamaBackend {
+    LLAMA_BACKEND.get_or_init(|| {
+        log::info!("Initializing Llama backend (one-time)...");
+        LlamaBackend::init().expect("Failed to initialize Llama backend")
+    })
+}
+
+/// Forwards llama.cpp and ggml log output to `tracing`. Call once, after
+/// installing a subscriber.
+pub fn send_logs_to_tracing() {
+    llama_cpp_2::send_logs_to_tracing(llama_cpp_2::LogOptions::default());
+}
+
+use crate::analysis::{
+    self, AnalysisRequest, AnalysisResult, AnalyzedToken, Interner, PartialToken, Vocab,
+    CURVE_RANKS,
+};
+use crate::cache;
+use crate::checkpoint::{self, Checkpoint, TokenMetrics};
+use crate::language;
+use crate::logit_bias::{self, BiasedToken};
+use crate::regeneration::{self, RegenerationDiff, RegenerationStats};
+use crate::resources::{self, DeviceMemory, ResourceMonitor};
+use crate::scoring::Scores;
+use crate::token_cache::TokenCache;
+use crate::windowing::{ChunkPoint, ChunkSweep, CHUNK_SWEEP_STRIDES, CHUNK_SWEEP_WINDOWS};
+use crate::worker::{SideCommand, SideJob, TokenizerCommand, WorkerCommand, WorkerMessage};
+
+/// User-tunable llama.cpp parameters, applied when a model is loaded and
+/// when the analysis context is created.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InferenceParams {
+    /// Number of layers to offload to the GPU. `None` keeps llama.cpp's default.
+    pub n_gpu_layers: Option<u32>,
+    /// Minimum context size; grown automatically to fit longer texts.
+    pub n_ctx: u32,
+    pub n_batch: u32,
+    /// CPU threads used for decoding. `None` lets llama.cpp decide.
+    pub n_threads: Option<i32>,
+    /// How offloaded layers are spread over several GPUs.
+    pub split_mode: GpuSplit,
+    /// GPU holding the whole model with [`GpuSplit::Single`], and the
+    /// intermediate results otherwise. Counts only GPUs, in llama.cpp's
+    /// device order.
+    pub main_gpu: u32,
+    /// Low-memory mode: score the text in windows of this many tokens,
+    /// starting each with an empty KV cache, so the context never grows
+    /// with the text. Tokens lose sight of anything before their window.
+    pub low_memory_window: Option<u32>,
+    /// In low-memory mode, shift the KV cache to keep a rolling context of
+    /// the most recent tokens instead of starting each window empty.
+    pub rolling_context: bool,
+    /// Constant-context mode: predict every token from exactly this many
+    /// preceding tokens, plus the first one, so positions are comparable.
+    /// Tokens with less before them are treated as prompt. Each token
+    /// needs a decode of its own, so this costs about this many times more
+    /// compute than scoring the text in one context. Overrides low-memory
+    /// mode.
+    pub constant_context: Option<u32>,
+    /// Score at most this many tokens, prompt included. The rest of the
+    /// text is tokenized and shown, but not analyzed.
+    pub max_tokens: Option<u32>,
+}
+
+impl InferenceParams {
+    /// True when going from `self` to `other` needs the model reloaded;
+    /// the other fields only need a new context, see
+    /// [`LlamaAnalyzer::set_params`].
+    pub fn needs_reload(&self, other: &InferenceParams) -> bool {
+        self.n_gpu_layers != other.n_gpu_layers
+            || self.split_mode != other.split_mode
+            || self.main_gpu != other.main_gpu
+    }
+}
+
+impl Default for InferenceParams {
+    fn default() -> Self {
+        Self {
+            n_gpu_layers: None,
+            n_ctx: 4096,
+            n_batch: 512,
+            n_threads: None,
+            split_mode: GpuSplit::default(),
+            main_gpu: 0,
+            low_memory_window: None,
+            rolling_context: false,
+            constant_context: None,
+            max_tokens: None,
+        }
+    }
+}
+
+/// Multi-GPU placement of the model, llama.cpp's `split_mode`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GpuSplit {
+    /// Whole layers per GPU, in proportion to each GPU's free memory.
+    #[default]
+    Layer,
+    /// Rows of each tensor across GPUs; faster on some backends.
+    Row,
+    /// Everything on the main GPU.
+    Single,
+}
+
+impl GpuSplit {
+    pub const ALL: [GpuSplit; 3] = [GpuSplit::Layer, GpuSplit::Row, GpuSplit::Single];
+}
+
+impl std::fmt::Display for GpuSplit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GpuSplit::Layer => "Split layers",
+            GpuSplit::Row => "Split rows",
+            GpuSplit::Single => "Main GPU only",
+        })
+    }
+}
+
+/// One token of a tokenizer playground run.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenInfo {
+    pub id: i32,
+    pub text: String,
+    /// Length of the raw token bytes; may be a partial UTF-8 sequence.
+    pub bytes: usize,
+    /// Control, user-defined or unknown token (BOS, chat markers, …).
+    pub special: bool,
+}
+
+/// A context kept by the worker between analyses, so iterating on a text
+/// doesn't pay for allocating the KV cache and compute buffers every run.
+/// It borrows the model, so it's held next to the analyzer rather than in
+/// it.
+struct KeptContext<'m> {
+    ctx: LlamaContext<'m>,
+    n_batch: u32,
+    n_threads: Option<i32>,
+}
+
+impl KeptContext<'_> {
+    /// Whether an analysis needing `n_ctx` tokens with `params` can use it.
+    fn fits(&self, n_ctx: u32, n_batch: u32, params: &InferenceParams) -> bool {
+        self.ctx.n_ctx() >= n_ctx && self.n_batch == n_batch && self.n_threads == params.n_threads
+    }
+}
+
+/// Clones share the loaded model. Each analysis creates a context of its
+/// own, so clones can run on two threads at once.
+#[derive(Clone)]
+pub struct LlamaAnalyzer {
+    model: Option<Arc<LlamaModel>>,
+    params: InferenceParams,
+    vocab: Arc<Vocab>,
+    /// Raw bytes of each vocabulary entry, indexed by token id.
+    piece_bytes: Arc<Vec<Vec<u8>>>,
+    /// Send `Tokens` with each decoded batch during `analyze`.
+    stream_tokens: bool,
+    /// [`cache::model_fingerprint`] of the loaded model; `None` disables
+    /// the result cache.
+    fingerprint: Option<u64>,
+    token_cache: Arc<TokenCache>,
+}
+
+impl Default for LlamaAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LlamaAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            model: None,
+            params: InferenceParams::default(),
+            vocab: Arc::default(),
+            piece_bytes: Arc::default(),
+            stream_tokens: false,
+            fingerprint: None,
+            token_cache: Arc::default(),
+        }
+    }
+
+    /// Makes `analyze` report every scored token through its progress
+    /// channel as soon as its batch is decoded, for live displays.
+    pub fn set_stream_tokens(&mut self, stream: bool) {
+        self.stream_tokens = stream;
+    }
+
+    /// Shares the tokenizations of `cache`, e.g. with the thread counting
+    /// the tokens of the input.
+    pub fn set_token_cache(&mut self, cache: Arc<TokenCache>) {
+        self.token_cache = cache;
+    }
+
+    /// The token cache with the key of the loaded model, if it has one.
+    fn token_cache(&self) -> Option<(&TokenCache, u64)> {
+        Some((&self.token_cache, self.fingerprint?))
+    }
+
+    pub fn load_model<P: AsRef<Path>>(
+        &mut self,
+        model_path: P,
+        params: InferenceParams,
+    ) -> Result<(), String> {
+        // Drop existing model first to free VRAM before loading the new one.
+        self.unload_model();
+
+        let path_str = model_path.as_ref().to_string_lossy().to_string();
+        let backend = get_backend();
+
+        log::info!("Loading model from: {}", path_str);
+
+        let mut model_params = LlamaModelParams::default();
+        if let Some(n_gpu_layers) = params.n_gpu_layers {
+            model_params = model_params.with_n_gpu_layers(n_gpu_layers);
+        }
+        model_params = model_params
+            .with_split_mode(match params.split_mode {
+                GpuSplit::Layer => LlamaSplitMode::Layer,
+                GpuSplit::Row => LlamaSplitMode::Row,
+                GpuSplit::Single => LlamaSplitMode::None,
+            })
+            .with_main_gpu(params.main_gpu as i32);
+
+        let model = LlamaModel::load_from_file(backend, &model_path, &model_params)
+            .map_err(|e| format!("Failed to load model: {}", e))?;
+
+        log::info!("Model loaded");
+        self.fingerprint = cache::model_fingerprint(model_path.as_ref())
+            .map_err(|e| log::warn!("Result cache disabled: {}", e))
+            .ok();
+        self.piece_bytes = Arc::new(Self::piece_bytes(&model));
+        self.vocab = Arc::new(Vocab::new(
+            self.piece_bytes
+                .iter()
+                .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                .collect(),
+        ));
+        self.model = Some(Arc::new(model));
+        self.params = params;
+        Ok(())
+    }
+
+    /// Reads every vocabulary entry once, in parallel, so predictions can be
+    /// displayed from their ids and results formatted without calling into
+    /// llama.cpp for every token.
+    fn piece_bytes(model: &LlamaModel) -> Vec<Vec<u8>> {
+        (0..model.n_vocab())
+            .into_par_iter()
+            .map(|id| {
+                let token = llama_cpp_2::token::LlamaToken(id);
+                match model.token_to_piece_bytes(token, 64, true, None) {
+                    Err(TokenToStringError::InsufficientBufferSpace(needed)) => model
+                        .token_to_piece_bytes(token, needed.unsigned_abs() as usize, true, None),
+                    bytes => bytes,
+                }
+                .unwrap_or_else(|_| format!("[{}]", id).into_bytes())
+            })
+            .collect()
+    }
+
+    /// Decodes the text of `token` into `out`, finishing any character the
+    /// tokens before it left incomplete in `decoder`.
+    fn decode_piece(
+        &self,
+        token: llama_cpp_2::token::LlamaToken,
+        decoder: &mut encoding_rs::Decoder,
+        out: &mut String,
+    ) {
+        out.clear();
+        let Some(bytes) = usize::try_from(token.0)
+            .ok()
+            .and_then(|id| self.piece_bytes.get(id))
+        else {
+            out.push_str(&format!("[{}]", token.0));
+            return;
+        };
+        out.reserve(
+            decoder
+                .max_utf8_buffer_length(bytes.len())
+                .unwrap_or(bytes.len()),
+        );
+        let _ = decoder.decode_to_string(bytes, out, false);
+    }
+
+    /// Replaces the context parameters used by later analyses without
+    /// reloading. `n_gpu_layers`, `split_mode` and `main_gpu` only take
+    /// effect on the next load.
+    pub fn set_params(&mut self, params: InferenceParams) {
+        self.params = params;
+    }
+
+    /// A stored result of the same request with this model and parameters.
+    pub fn cached(&self, request: &AnalysisRequest) -> Option<AnalysisResult> {
+        let key = cache::key(self.fingerprint?, &self.params, request);
+        cache::load(key, self.vocab.clone())
+    }
+
+    /// Stores `result` so analyzing `request` again returns it from [`Self::cached`].
+    pub fn store_cached(&self, request: &AnalysisRequest, result: &AnalysisResult) {
+        let Some(fingerprint) = self.fingerprint else {
+            return;
+        };
+        if let Err(e) = cache::store(cache::key(fingerprint, &self.params, request), result) {
+            log::warn!("Failed to cache result: {}", e);
+        }
+    }
+
+    pub fn unload_model(&mut self) {
+        self.vocab = Arc::default();
+        self.piece_bytes = Arc::default();
+        self.fingerprint = None;
+        if self.model.take().is_some() {
+            log::info!("Model unloaded, VRAM freed");
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.model.is_some()
+    }
+
+    fn model(&self) -> Result<&LlamaModel, String> {
+        self.model.as_deref().ok_or_else(|| "No model loaded".to_string())
+    }
+
+    pub fn analyze(
+        &self,
+        request: &AnalysisRequest,
+        progress_tx: Option<&mpsc::Sender<WorkerMessage>>,
+    ) -> Result<AnalysisResult, String> {
+        self.analyze_with(request, progress_tx, || Ok(()))
+    }
+
+    /// [`Self::analyze`], calling `between_batches` before each batch is
+    /// decoded. It may block, e.g. while the analysis is paused, and an
+    /// error from it aborts the analysis.
+    pub fn analyze_with(
+        &self,
+        request: &AnalysisRequest,
+        progress_tx: Option<&mpsc::Sender<WorkerMessage>>,
+        between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<AnalysisResult, String> {
+        self.analyze_reusing(request, progress_tx, &mut None, between_batches)
+    }
+
+    /// [`Self::analyze_with`], decoding in the context `kept` from an
+    /// earlier analysis when it's large enough instead of creating one,
+    /// and leaving its context there for the next.
+    fn analyze_reusing<'m>(
+        &'m self,
+        request: &AnalysisRequest,
+        progress_tx: Option<&mpsc::Sender<WorkerMessage>>,
+        kept: &mut Option<KeptContext<'m>>,
+        mut between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<AnalysisResult, String> {
+        let model = self.model()?;
+        let backend = get_backend();
+        let start_time = std::time::Instant::now();
+
+        if let Some(tx) = progress_tx {
+            let _ = tx.send(WorkerMessage::Progress {
+                current: 0,
+                total: 1,
+            });
+        }
+
+        let (mut tokens, prompt_tokens) = tokenize_request(model, self.token_cache(), request)?;
+        let bias = resolve_logit_bias(model, &request.logit_bias)?;
+        let unscored = match self.params.max_tokens {
+            Some(limit) if tokens.len() > limit as usize => tokens.split_off(limit as usize),
+            _ => Vec::new(),
+        };
+        if !unscored.is_empty() {
+            log::info!(
+                "Scoring the first {} tokens; {} are past the limit",
+                tokens.len(),
+                unscored.len()
+            );
+        }
+        // With a tail, everything before it is treated as prompt.
+        let prompt_tokens = match request.tail_tokens {
+            Some(tail) => prompt_tokens.max(tokens.len().saturating_sub(tail)),
+            None => prompt_tokens,
+        };
+        // Tokens without a full constant context are treated as prompt too.
+        let constant = self.params.constant_context.map(|k| k as usize);
+        let prompt_tokens = match constant {
+            Some(k) => prompt_tokens.max(k + 1),
+            None => prompt_tokens,
+        };
+
+        if tokens.is_empty() {
+            return Ok(AnalysisResult {
+                tokens: vec![],
+                prompt_tokens: 0,
+                processing_time_ms: start_time.elapsed().as_millis() as u64,
+                vocab: self.vocab.clone(),
+                languages: vec![],
+                keeps_first_token: false,
+                unscored: vec![],
+            });
+        }
+
+        let total_tokens = tokens.len();
+        if let Some(k) = constant.filter(|&k| total_tokens <= k + 1) {
+            return Err(format!(
+                "The text is too short for a constant context of {} tokens",
+                k
+            ));
+        }
+        log::info!("Analyzing {} tokens", total_tokens);
+
+        let window = self
+            .params
+            .low_memory_window
+            .map(|w| w as usize)
+            .filter(|&w| w < total_tokens && constant.is_none());
+        let n_ctx = match (constant, window) {
+            // Constant-context mode: the first token and K more.
+            (Some(k), _) => k as u32 + 1,
+            // Low-memory mode: the context only ever holds one window.
+            (None, Some(window)) => window as u32,
+            // Calculate context size needed: total tokens + some buffer (512).
+            // Ensure it's at least the configured minimum (4096 by default).
+            (None, None) => (total_tokens as u32 + 512).max(self.params.n_ctx),
+        };
+        let rolling = window.is_some() && self.params.rolling_context;
+        // A rolling context needs room for a whole batch after a shift.
+        let n_batch = self
+            .params
+            .n_batch
+            .clamp(1, if rolling { n_ctx / 2 } else { n_ctx });
+
+        // Only full-text analyses keep their context; the small ones of
+        // windowed modes are made for each run, and free a kept one.
+        let reusable = window.is_none() && constant.is_none();
+        if !reusable
+            || !kept
+                .as_ref()
+                .is_some_and(|k| k.fits(n_ctx, n_batch, &self.params))
+        {
+            *kept = None;
+        }
+        let mut fresh = None;
+        let ctx = match kept {
+            Some(kept) => {
+                log::info!(
+                    "Reusing the context of the last analysis (n_ctx={})",
+                    kept.ctx.n_ctx()
+                );
+                kept.ctx.clear_kv_cache();
+                &mut kept.ctx
+            }
+            None => {
+                log::info!(
+                    "Initializing context with n_ctx={}, n_batch={}",
+                    n_ctx,
+                    n_batch
+                );
+
+                let mut ctx_params = LlamaContextParams::default()
+                    .with_n_ctx(NonZeroU32::new(n_ctx))
+                    .with_n_batch(n_batch);
+                if let Some(n_threads) = self.params.n_threads {
+                    ctx_params = ctx_params
+                        .with_n_threads(n_threads)
+                        .with_n_threads_batch(n_threads);
+                }
+
+                let ctx = model
+                    .new_context(backend, ctx_params)
+                    .map_err(|e| format!("Failed to create context: {}", e))?;
+                if reusable {
+                    &mut kept
+                        .insert(KeptContext {
+                            ctx,
+                            n_batch,
+                            n_threads: self.params.n_threads,
+                        })
+                        .ctx
+                } else {
+                    fresh.insert(ctx)
+                }
+            }
+        };
+
+        // The context (KV cache, compute buffers) is the big allocation on
+        // top of the weights, so this is when memory pressure shows up.
+        if let Some(tx) = progress_tx {
+            let _ = tx.send(WorkerMessage::Memory(resources::sample_memory(
+                device_memory(),
+            )));
+        }
+
+        let mut scores = Scores::new(total_tokens);
+        // KV cache position of the next decoded token.
+        let mut kv_pos = 0;
+        // Earliest token in the KV cache, and the one each prediction saw
+        // first; only moves with a window, as otherwise it's always zero.
+        let mut first_visible = 0;
+
+        let mut processed_count = 0;
+
+        // Long analyses periodically save their progress, and pick it up
+        // again if they were interrupted. Not in low-memory or
+        // constant-context mode, whose KV cache doesn't cover the processed
+        // tokens.
+        let checkpoint = self
+            .fingerprint
+            .filter(|_| {
+                window.is_none() && constant.is_none() && total_tokens >= checkpoint::MIN_TOKENS
+            })
+            .map(|fingerprint| Checkpoint::new(cache::key(fingerprint, &self.params, request)));
+        if let Some(progress) = checkpoint.as_ref().and_then(|c| c.resume(ctx, &tokens)) {
+            log::info!(
+                "Resuming from a checkpoint at token {} of {}",
+                progress.processed,
+                total_tokens
+            );
+            processed_count = progress.processed;
+            kv_pos = progress.processed;
+            scores = Scores::resumed(total_tokens, progress.results);
+        }
+        let mut last_checkpoint = std::time::Instant::now();
+
+        let mut batch = LlamaBatch::new(n_batch as usize, 1);
+        let mut logits: Vec<(i32, f32)> = Vec::with_capacity(32000);
+        let mut monitor = progress_tx.map(|_| ResourceMonitor::new());
+        // Separate from the final formatting pass, which decodes from the
+        // start again.
+        let mut stream_decoder = encoding_rs::UTF_8.new_decoder();
+        let first_streamed = prompt_tokens.max(1);
+        if self.stream_tokens {
+            let _ = model.token_to_piece(tokens[processed_count], &mut stream_decoder, true, None);
+        }
+
+        // Constant-context mode decodes a fresh context for every token,
+        // leaving nothing to the batched pass below.
+        if let Some(k) = constant {
+            log::info!("Scoring every token with {} tokens of context", k);
+            for pos in 0..total_tokens {
+                let start = (pos + 1).saturating_sub(k);
+                let Some(target) = scores.target(pos).filter(|&t| t >= prompt_tokens) else {
+                    scores.skip(pos, start);
+                    continue;
+                };
+                between_batches()?;
+                if let Some(tx) = progress_tx {
+                    let _ = tx.send(WorkerMessage::Progress {
+                        current: pos,
+                        total: total_tokens,
+                    });
+                }
+                let last = decode_window(
+                    ctx,
+                    &mut batch,
+                    n_batch as usize,
+                    tokens[0],
+                    &tokens[start..=pos],
+                )?;
+                logits.clear();
+                logits.extend(ctx.candidates_ith(last).map(|td| (td.id().0, td.logit())));
+                let (metrics, curve) =
+                    Self::calculate_token_metrics(&mut logits, Some(tokens[target]), &bias);
+                scores.record(pos, start, metrics, curve);
+            }
+            processed_count = total_tokens;
+        }
+
+        log::info!("Decoding in batches...");
+
+        // Process tokens in batches to avoid overwhelming the context or memory.
+        // This loop decodes a chunk of tokens, then checks the model's prediction
+        // for each token against the *actual* next token in the sequence.
+        while processed_count < total_tokens {
+            // In low-memory mode every window starts from an empty cache and
+            // re-reads the end of the previous one as context.
+            let (decode_from, end) = match window {
+                Some(window) if !rolling => {
+                    ctx.clear_kv_cache();
+                    kv_pos = 0;
+                    let start = processed_count.saturating_sub(window / LOW_MEMORY_OVERLAP_DIVISOR);
+                    first_visible = start;
+                    (start, (start + window).min(total_tokens))
+                }
+                _ => (processed_count, total_tokens),
+            };
+
+            for (chunk_index, chunk) in tokens[decode_from..end]
+                .chunks(n_batch as usize)
+                .enumerate()
+            {
+                between_batches()?;
+
+                if let Some(tx) = progress_tx {
+                    let _ = tx.send(WorkerMessage::Progress {
+                        current: processed_count,
+                        total: total_tokens,
+                    });
+                }
+
+                if let Some(window) = window.filter(|_| rolling) {
+                    if kv_pos + chunk.len() > window {
+                        let discard = (window / LOW_MEMORY_OVERLAP_DIVISOR)
+                            .max(kv_pos + chunk.len() - window);
+                        shift_context(ctx, kv_pos, discard)?;
+                        kv_pos -= discard;
+                        // The first token stays, so the dropped ones follow it.
+                        first_visible = first_visible.max(1) + discard;
+                    }
+                }
+
+                let offset = decode_from + chunk_index * n_batch as usize;
+                batch.clear();
+
+                for (i, &token) in chunk.iter().enumerate() {
+                    let pos = offset + i;
+                    // Context re-read from the previous window was already
+                    // scored, and predictions of prompt tokens aren't shown.
+                    let wanted = pos >= processed_count && pos + 1 >= prompt_tokens;
+                    batch
+                        .add(token, (kv_pos + i) as i32, &[0], wanted)
+                        .map_err(|e| format!("Failed to add token to batch: {}", e))?;
+                }
+                kv_pos += chunk.len();
+
+                ctx.decode(&mut batch)
+                    .map_err(|e| format!("Failed to decode batch: {}", e))?;
+
+                // detailed_results extraction loop
+                // For each token we just decoded, we look at the logits generated.
+                // These logits represent the model's prediction for the NEXT token.
+                let first_new = processed_count;
+                for i in 0..chunk.len() {
+                    let global_pos = offset + i;
+                    if global_pos < first_new {
+                        continue;
+                    }
+                    let Some(target) = scores.target(global_pos).filter(|&t| t >= prompt_tokens)
+                    else {
+                        scores.skip(global_pos, first_visible);
+                        continue;
+                    };
+
+                    logits.clear();
+                    let candidates = ctx.candidates_ith(i as i32);
+                    logits.extend(candidates.map(|td| (td.id().0, td.logit())));
+
+                    let (metrics, curve) =
+                        Self::calculate_token_metrics(&mut logits, Some(tokens[target]), &bias);
+                    scores.record(global_pos, first_visible, metrics, curve);
+                }
+                processed_count = processed_count.max(offset + chunk.len());
+
+                if let Some(tx) = progress_tx.filter(|_| self.stream_tokens) {
+                    let scored = first_new + 1..(processed_count + 1).min(total_tokens);
+                    let partial = scored
+                        .filter_map(|index| {
+                            let text = model
+                                .token_to_piece(tokens[index], &mut stream_decoder, true, None)
+                                .unwrap_or_else(|_| format!("[{}]", tokens[index].0));
+                            let (rank, probability, _) = scores.of(index)?;
+                            Some(PartialToken {
+                                index,
+                                text,
+                                rank: *rank,
+                                probability: *probability,
+                            })
+                        })
+                        .filter(|t| t.index >= first_streamed)
+                        .collect();
+                    let _ = tx.send(WorkerMessage::Tokens(partial));
+                }
+
+                if let (Some(tx), Some(monitor)) = (progress_tx, monitor.as_mut()) {
+                    if let Some(sample) = monitor.sample(processed_count) {
+                        let _ = tx.send(WorkerMessage::Resources(sample));
+                    }
+                }
+
+                if let Some(ref checkpoint) = checkpoint {
+                    if processed_count < total_tokens
+                        && last_checkpoint.elapsed() >= checkpoint::INTERVAL
+                    {
+                        log::info!("Saving a checkpoint at token {}", processed_count);
+                        if let Err(e) =
+                            checkpoint.save(ctx, &tokens[..processed_count], scores.by_position())
+                        {
+                            log::warn!("Failed to save checkpoint: {}", e);
+                        }
+                        last_checkpoint = std::time::Instant::now();
+                    }
+                }
+            }
+        }
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.remove();
+        }
+
+        log::info!("Formatting token texts...");
+
+        if let Some(tx) = progress_tx {
+            let _ = tx.send(WorkerMessage::Progress {
+                current: total_tokens,
+                total: total_tokens,
+            });
+        }
+
+        let format_start = std::time::Instant::now();
+
+        // A character can be split across tokens, so texts are decoded in
+        // order; building the tokens around them runs in parallel.
+        let mut decoder = encoding_rs::UTF_8.new_decoder();
+        let mut interner = Interner::default();
+        let mut piece = String::new();
+        let texts: Vec<Arc<str>> = tokens
+            .iter()
+            .map(|&token| {
+                self.decode_piece(token, &mut decoder, &mut piece);
+                interner.intern(&piece)
+            })
+            .collect();
+        let unscored = unscored
+            .into_iter()
+            .map(|token| {
+                self.decode_piece(token, &mut decoder, &mut piece);
+                piece.clone()
+            })
+            .collect();
+        let scored: Vec<_> = (0..tokens.len()).map(|i| scores.take(i)).collect();
+
+        let mut analyzed_tokens: Vec<AnalyzedToken> = tokens
+            .par_iter()
+            .zip(texts)
+            .zip(scored)
+            .map(|((&token, text), scored)| {
+                let ((rank, prob, top_predictions), rank_curve, context_start) = scored;
+
+                let top1_margin = match top_predictions.first() {
+                    Some(&(_, top)) if rank > 1 => top.ln() - prob.max(f32::MIN_POSITIVE).ln(),
+                    _ => 0.0,
+                };
+
+                let special = is_special(model, token).then(|| special_label(model, token, &text));
+
+                AnalyzedToken {
+                    id: token.0,
+                    text,
+                    rank,
+                    top_predictions,
+                    probability: prob,
+                    top1_margin,
+                    unigram_surprisal: 0.0,
+                    repeated: false,
+                    special,
+                    context_start,
+                    rank_curve,
+                }
+            })
+            .collect();
+        // Per-token stats only cover the completion; keep the token right
+        // before it, which they skip, as its context.
+        let context_start = prompt_tokens.saturating_sub(1);
+        analysis::assign_unigram_baseline(&mut analyzed_tokens[context_start..]);
+        analysis::assign_repetition(&mut analyzed_tokens[context_start..]);
+
+        log::info!(
+            "Results formatted in {}ms",
+            format_start.elapsed().as_millis()
+        );
+
+        let elapsed = start_time.elapsed().as_millis() as u64;
+        log::info!("Analysis completed in {}ms", elapsed);
+
+        Ok(AnalysisResult {
+            languages: language::language_breakdown(&analyzed_tokens[context_start..]),
+            tokens: analyzed_tokens,
+            prompt_tokens,
+            processing_time_ms: elapsed,
+            vocab: self.vocab.clone(),
+            keeps_first_token: rolling || constant.is_some(),
+            unscored,
+        })
+    }
+
+    // Calculates rank, probability and top predictions for the target token
+    // using the raw logits, plus the cumulative probability at each of
+    // CURVE_RANKS. Performs a Softmax with the "max-trick" for numerical stability.
+    // The `bias` of each listed token is added to its logit first.
+    fn calculate_token_metrics(
+        logits: &mut [(i32, f32)],
+        target_token: Option<llama_cpp_2::token::LlamaToken>,
+        bias: &[(i32, f32)],
+    ) -> (TokenMetrics, Vec<f32>) {
+        if logits.is_empty() {
+            return ((1, 0.0, Vec::new()), Vec::new());
+        }
+
+        for &(id, shift) in bias {
+            // Candidates come in id order, so the index is usually the id.
+            let entry = match logits.get_mut(id as usize) {
+                Some(entry) if entry.0 == id => Some(entry),
+                _ => logits.iter_mut().find(|(i, _)| *i == id),
+            };
+            if let Some((_, logit)) = entry {
+                *logit += shift;
+            }
+        }
+
+        let max_logit = logits
+            .iter()
+            .map(|(_, l)| *l)
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        let sum_exp: f32 = logits.iter().map(|(_, l)| (l - max_logit).exp()).sum();
+
+        logits.sort_unstable_by(|(_, a), (_, b)| {
+            b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let mut rank = 1;
+        let mut probability = 0.0;
+
+        if let Some(target) = target_token {
+            let target_id = target.0;
+
+            if let Some(idx) = logits.iter().position(|(id, _)| *id == target_id) {
+                rank = idx + 1;
+                let val = logits[idx].1;
+
+                probability = (val - max_logit).exp() / sum_exp;
+            }
+        }
+
+        let top_preds = logits
+            .iter()
+            .take(5)
+            .map(|(id, l)| (*id, (l - max_logit).exp() / sum_exp))
+            .collect();
+
+        let mut curve = Vec::with_capacity(CURVE_RANKS.len());
+        let mut cumulative = 0.0;
+        let mut counted = 0;
+        for &rank in CURVE_RANKS.iter().take_while(|&&r| r <= logits.len()) {
+            cumulative += logits[counted..rank]
+                .iter()
+                .map(|(_, l)| (l - max_logit).exp())
+                .sum::<f32>();
+            counted = rank;
+            curve.push(cumulative / sum_exp);
+        }
+        ((rank, probability, top_preds), curve)
+    }
+
+    /// Re-scores `text` with each length in [`CONTEXT_SWEEP_LENGTHS`] shorter
+    /// than the text, plus its full length, and returns `(context length,
+    /// perplexity)` pairs. The text is tokenized once and every run scores
+    /// the same tokens. Limited runs slide a window of the given length with
+    /// a half-window stride, so each token sees between half and all of it.
+    /// `between_batches` works as in [`Self::analyze_with`].
+    pub fn context_sweep(
+        &self,
+        text: &str,
+        progress_tx: &mpsc::Sender<WorkerMessage>,
+        mut between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<Vec<(u32, f32)>, String> {
+        let model = self.model()?;
+        let tokens = model
+            .str_to_token(text, llama_cpp_2::model::AddBos::Always)
+            .map_err(|e| format!("Failed to tokenize: {}", e))?;
+        if tokens.len() < 2 {
+            return Err("Text is too short for a context sweep".to_string());
+        }
+
+        let total = tokens.len();
+        let mut lengths: Vec<usize> = CONTEXT_SWEEP_LENGTHS
+            .iter()
+            .map(|&l| l as usize)
+            .filter(|&l| l < total)
+            .collect();
+        lengths.push(total);
+
+        let work = lengths.len() * (total - 1);
+        let mut done = 0;
+        let mut results = Vec::with_capacity(lengths.len());
+        for window in lengths {
+            log::info!("Context sweep: scoring with a {}-token window", window);
+            let on_progress = |scored| {
+                let _ = progress_tx.send(WorkerMessage::Progress {
+                    current: done + scored,
+                    total: work,
+                });
+            };
+            let nll = self.windowed_nll(
+                &tokens,
+                window,
+                window / 2,
+                on_progress,
+                &mut between_batches,
+            )?;
+            done += total - 1;
+            results.push((window as u32, (nll / (total - 1) as f32).exp()));
+        }
+        Ok(results)
+    }
+
+    /// Scores the text with every window length and stride of the chunk
+    /// sweep, plus the full context. `between_batches` works as in
+    /// [`Self::analyze_with`].
+    pub fn chunk_sweep(
+        &self,
+        text: &str,
+        progress_tx: &mpsc::Sender<WorkerMessage>,
+        mut between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<ChunkSweep, String> {
+        let model = self.model()?;
+        let tokens = model
+            .str_to_token(text, llama_cpp_2::model::AddBos::Always)
+            .map_err(|e| format!("Failed to tokenize: {}", e))?;
+        if tokens.len() < 2 {
+            return Err("Text is too short for a chunk sweep".to_string());
+        }
+
+        let total = tokens.len();
+        let mut configs: Vec<(usize, usize)> = Vec::new();
+        for window in CHUNK_SWEEP_WINDOWS.map(|w| w as usize) {
+            if window < total {
+                for fraction in CHUNK_SWEEP_STRIDES {
+                    configs.push((window, ((window as f32 * fraction) as usize).max(1)));
+                }
+            }
+        }
+        configs.push((total, total));
+
+        let work = configs.len() * (total - 1);
+        let mut done = 0;
+        let mut points = Vec::with_capacity(configs.len());
+        for (window, stride) in configs {
+            log::info!("Chunk sweep: window {}, stride {}", window, stride);
+            let on_progress = |scored| {
+                let _ = progress_tx.send(WorkerMessage::Progress {
+                    current: done + scored,
+                    total: work,
+                });
+            };
+            let nll =
+                self.windowed_nll(&tokens, window, stride, on_progress, &mut between_batches)?;
+            done += total - 1;
+            points.push(ChunkPoint {
+                window: window as u32,
+                stride: stride as u32,
+                perplexity: (nll / (total - 1) as f32).exp(),
+            });
+        }
+        let full = points.pop().expect("the full context is always swept");
+        Ok(ChunkSweep {
+            points,
+            full_perplexity: full.perplexity,
+        })
+    }
+
+    /// Total negative log-likelihood of `tokens[1..]` when the model never
+    /// sees more than `window` tokens at once. Each window after the first
+    /// advances by `stride` tokens; the overlap conditions its first scored
+    /// token. `between_batches` runs before each decode, and an error from
+    /// it aborts the run.
+    fn windowed_nll(
+        &self,
+        tokens: &[llama_cpp_2::token::LlamaToken],
+        window: usize,
+        stride: usize,
+        mut on_progress: impl FnMut(usize),
+        mut between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<f32, String> {
+        let model = self.model()?;
+        let n_batch = (self.params.n_batch as usize).clamp(1, window);
+        let mut ctx_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(window as u32))
+            .with_n_batch(n_batch as u32);
+        if let Some(n_threads) = self.params.n_threads {
+            ctx_params = ctx_params
+                .with_n_threads(n_threads)
+                .with_n_threads_batch(n_threads);
+        }
+        let mut ctx = model
+            .new_context(get_backend(), ctx_params)
+            .map_err(|e| format!("Failed to create context: {}", e))?;
+        let mut batch = LlamaBatch::new(n_batch, 1);
+
+        let mut nll = 0.0;
+        // Index of the next token to score.
+        let mut next = 1;
+        while next < tokens.len() {
+            // Keep at least one token of overlap so every scored token has
+            // a prediction.
+            let start = if next == 1 {
+                0
+            } else {
+                next.saturating_sub(window.saturating_sub(stride).max(1))
+            };
+            let end = (start + window).min(tokens.len());
+            ctx.clear_kv_cache();
+
+            for (chunk_index, chunk) in tokens[start..end].chunks(n_batch).enumerate() {
+                between_batches()?;
+                let offset = start + chunk_index * n_batch;
+                batch.clear();
+                for (i, &token) in chunk.iter().enumerate() {
+                    let pos = offset + i - start;
+                    batch
+                        .add(token, pos as i32, &[0], true)
+                        .map_err(|e| format!("Failed to add token to batch: {}", e))?;
+                }
+                ctx.decode(&mut batch)
+                    .map_err(|e| format!("Failed to decode batch: {}", e))?;
+
+                // Logits at position i predict token i + 1.
+                for i in 0..chunk.len() {
+                    let target = offset + i + 1;
+                    if target < next || target >= end {
+                        continue;
+                    }
+                    nll -= log_softmax_at(ctx.get_logits_ith(i as i32), tokens[target].0);
+                }
+            }
+
+            next = end;
+            on_progress(next - 1);
+        }
+        Ok(nll)
+    }
+
+    /// Greedy-regenerates the text from its opening and compares the
+    /// regeneration with the original by the similarity of their mean
+    /// hidden states. `between_batches` works as in [`Self::analyze_with`].
+    pub fn regenerate(
+        &self,
+        text: &str,
+        progress_tx: &mpsc::Sender<WorkerMessage>,
+        mut between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<RegenerationStats, String> {
+        let model = self.model()?;
+        let tokens = model
+            .str_to_token(text, llama_cpp_2::model::AddBos::Always)
+            .map_err(|e| format!("Failed to tokenize: {}", e))?;
+        if tokens.len() < regeneration::MIN_TEXT_TOKENS {
+            return Err("Text is too short to regenerate".to_string());
+        }
+
+        let opening = regeneration::opening_len(tokens.len());
+        let original = &tokens[..tokens
+            .len()
+            .min(opening + regeneration::MAX_REGENERATED_TOKENS)];
+        let target = original.len() - opening;
+        log::info!(
+            "Regenerating {} tokens from a {}-token opening",
+            target,
+            opening
+        );
+        let generated = self.greedy_continuation(
+            &tokens[..opening],
+            target,
+            |done| {
+                let _ = progress_tx.send(WorkerMessage::Progress {
+                    current: done,
+                    total: target,
+                });
+            },
+            &mut between_batches,
+        )?;
+        if generated.is_empty() {
+            return Err("The model ended the text right after its opening".to_string());
+        }
+
+        let mut regenerated = tokens[..opening].to_vec();
+        regenerated.extend_from_slice(&generated);
+        let similarity = regeneration::cosine_similarity(
+            &self.mean_embedding(original, opening, &mut between_batches)?,
+            &self.mean_embedding(&regenerated, opening, &mut between_batches)?,
+        );
+        Ok(RegenerationStats {
+            opening_tokens: opening,
+            opening: detokenize(model, &tokens[..opening]),
+            regenerated: detokenize(model, &generated),
+            similarity,
+        })
+    }
+
+    /// Greedy-decodes a continuation of the request from token `start`, or
+    /// from the opening of the completion, as long as the actual rest of
+    /// the text (up to [`regeneration::MAX_REGENERATED_TOKENS`]), and diffs
+    /// the two word by word. `between_batches` works as in
+    /// [`Self::analyze_with`].
+    pub fn regenerate_diff(
+        &self,
+        request: &AnalysisRequest,
+        start: Option<usize>,
+        progress_tx: &mpsc::Sender<WorkerMessage>,
+        between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<RegenerationDiff, String> {
+        let model = self.model()?;
+        let (tokens, prompt_tokens) = tokenize_request(model, self.token_cache(), request)?;
+        let start = start
+            .unwrap_or(prompt_tokens + regeneration::opening_len(tokens.len() - prompt_tokens))
+            .max(1);
+        if start >= tokens.len() {
+            return Err("Nothing to regenerate after the chosen point".to_string());
+        }
+
+        let end = tokens
+            .len()
+            .min(start + regeneration::MAX_REGENERATED_TOKENS);
+        let target = end - start;
+        log::info!("Regenerating {} tokens from token {}", target, start);
+        let generated = self.greedy_continuation(
+            &tokens[..start],
+            target,
+            |done| {
+                let _ = progress_tx.send(WorkerMessage::Progress {
+                    current: done,
+                    total: target,
+                });
+            },
+            between_batches,
+        )?;
+
+        Ok(RegenerationDiff::new(
+            start,
+            detokenize(model, &tokens[prompt_tokens.min(start)..start]),
+            &detokenize(model, &tokens[start..end]),
+            &detokenize(model, &generated),
+        ))
+    }
+
+    /// Greedy-decodes up to `max_new` tokens after `prefix`, stopping early
+    /// at an end-of-generation token. `on_progress` gets the number of
+    /// tokens generated so far; `between_batches` runs before each decode.
+    fn greedy_continuation(
+        &self,
+        prefix: &[llama_cpp_2::token::LlamaToken],
+        max_new: usize,
+        mut on_progress: impl FnMut(usize),
+        mut between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<Vec<llama_cpp_2::token::LlamaToken>, String> {
+        let model = self.model()?;
+        let n_ctx = (prefix.len() + max_new) as u32;
+        let n_batch = self.params.n_batch.clamp(1, n_ctx);
+        let mut ctx_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(n_ctx))
+            .with_n_batch(n_batch);
+        if let Some(n_threads) = self.params.n_threads {
+            ctx_params = ctx_params
+                .with_n_threads(n_threads)
+                .with_n_threads_batch(n_threads);
+        }
+        let mut ctx = model
+            .new_context(get_backend(), ctx_params)
+            .map_err(|e| format!("Failed to create context: {}", e))?;
+        let mut batch = LlamaBatch::new(n_batch as usize, 1);
+
+        // Only the last prefix token's logits are needed.
+        for (chunk_index, chunk) in prefix.chunks(n_batch as usize).enumerate() {
+            let offset = chunk_index * n_batch as usize;
+            batch.clear();
+            for (i, &token) in chunk.iter().enumerate() {
+                let pos = offset + i;
+                batch
+                    .add(token, pos as i32, &[0], pos + 1 == prefix.len())
+                    .map_err(|e| format!("Failed to add token to batch: {}", e))?;
+            }
+            between_batches()?;
+            ctx.decode(&mut batch)
+                .map_err(|e| format!("Failed to decode batch: {}", e))?;
+        }
+
+        let mut generated = Vec::with_capacity(max_new);
+        let mut last = batch.n_tokens() - 1;
+        while generated.len() < max_new {
+            let Some(next) = argmax(ctx.get_logits_ith(last)) else {
+                break;
+            };
+            let token = llama_cpp_2::token::LlamaToken::new(next);
+            if model.is_eog_token(token) {
+                break;
+            }
+            generated.push(token);
+            on_progress(generated.len());
+
+            batch.clear();
+            batch
+                .add(
+                    token,
+                    (prefix.len() + generated.len() - 1) as i32,
+                    &[0],
+                    true,
+                )
+                .map_err(|e| format!("Failed to add token to batch: {}", e))?;
+            between_batches()?;
+            ctx.decode(&mut batch)
+                .map_err(|e| format!("Failed to decode batch: {}", e))?;
+            last = 0;
+        }
+        Ok(generated)
+    }
+
+    /// Mean final hidden state of `tokens[pool_from..]`, read with all of
+    /// `tokens` in context. `between_batches` runs before each decode.
+    fn mean_embedding(
+        &self,
+        tokens: &[llama_cpp_2::token::LlamaToken],
+        pool_from: usize,
+        mut between_batches: impl FnMut() -> Result<(), String>,
+    ) -> Result<Vec<f32>, String> {
+        let model = self.model()?;
+        let n_ctx = tokens.len() as u32;
+        let n_batch = self.params.n_batch.clamp(1, n_ctx);
+        let mut ctx_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(n_ctx))
+            .with_n_batch(n_batch)
+            .with_embeddings(true);
+        if let Some(n_threads) = self.params.n_threads {
+            ctx_params = ctx_params
+                .with_n_threads(n_threads)
+                .with_n_threads_batch(n_threads);
+        }
+        let mut ctx = model
+            .new_context(get_backend(), ctx_params)
+            .map_err(|e| format!("Failed to create context: {}", e))?;
+        let mut batch = LlamaBatch::new(n_batch as usize, 1);
+
+        let mut rows: Vec<Vec<f32>> = Vec::with_capacity(tokens.len() - pool_from);
+        for (chunk_index, chunk) in tokens.chunks(n_batch as usize).enumerate() {
+            let offset = chunk_index * n_batch as usize;
+            batch.clear();
+            for (i, &token) in chunk.iter().enumerate() {
+                batch
+                    .add(token, (offset + i) as i32, &[0], offset + i >= pool_from)
+                    .map_err(|e| format!("Failed to add token to batch: {}", e))?;
+            }
+            between_batches()?;
+            ctx.decode(&mut batch)
+                .map_err(|e| format!("Failed to decode batch: {}", e))?;
+            for i in 0..chunk.len() {
+                if offset + i >= pool_from {
+                    let row = ctx
+                        .embeddings_ith(i as i32)
+                        .map_err(|e| format!("Failed to read embeddings: {}", e))?;
+                    rows.push(row.to_vec());
+                }
+            }
+        }
+        Ok(regeneration::mean_pool(rows.iter().map(Vec::as_slice)))
+    }
+
+    /// Tokenizes `text` without running inference.
+    pub fn list_tokens(&self, text: &str, add_bos: bool) -> Result<Vec<TokenInfo>, String> {
+        list_tokens(self.model()?, text, add_bos)
+    }
+
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.model()
+            .map_or(0, |model| count_tokens(model, self.token_cache(), text))
+    }
+}
+
+/// Drops `discard` tokens from the KV cache after the first one, which
+/// anchors attention, and moves the later ones back to close the gap.
+fn shift_context(ctx: &mut LlamaContext, used: usize, discard: usize) -> Result<(), String> {
+    const KEEP: usize = 1;
+    let removed = ctx
+        .clear_kv_cache_seq(Some(0), Some(KEEP as u32), Some((KEEP + discard) as u32))
+        .map_err(|e| format!("Failed to shift the context: {}", e))?;
+    if !removed {
+        return Err("This model doesn't support a rolling context".to_string());
+    }
+    ctx.kv_cache_seq_add(
+        0,
+        Some((KEEP + discard) as u32),
+        Some(used as u32),
+        -(discard as i32),
+    )
+    .map_err(|e| format!("Failed to shift the context: {}", e))
+}
+
+/// Decodes `anchor` followed by `tokens` into an emptied context, with
+/// logits for the last token only, and returns that token's index in the
+/// final batch.
+fn decode_window(
+    ctx: &mut LlamaContext,
+    batch: &mut LlamaBatch,
+    n_batch: usize,
+    anchor: llama_cpp_2::token::LlamaToken,
+    tokens: &[llama_cpp_2::token::LlamaToken],
+) -> Result<i32, String> {
+    ctx.clear_kv_cache();
+    let window: Vec<_> = std::iter::once(anchor)
+        .chain(tokens.iter().copied())
+        .collect();
+    let mut last = 0;
+    for (chunk_index, chunk) in window.chunks(n_batch).enumerate() {
+        batch.clear();
+        for (i, &token) in chunk.iter().enumerate() {
+            let pos = chunk_index * n_batch + i;
+            batch
+                .add(token, pos as i32, &[0], pos + 1 == window.len())
+                .map_err(|e| format!("Failed to add token to batch: {}", e))?;
+            last = i as i32;
+        }
+        ctx.decode(batch)
+            .map_err(|e| format!("Failed to decode batch: {}", e))?;
+    }
+    Ok(last)
+}
+
+/// The token ids and biases of a logit bias `spec`. Texts must tokenize to
+/// exactly one token, and ids must be in the vocabulary.
+fn resolve_logit_bias(model: &LlamaModel, spec: &str) -> Result<Vec<(i32, f32)>, String> {
+    let n_vocab = model.n_vocab();
+    logit_bias::parse(spec)?
+        .into_iter()
+        .map(|entry| {
+            let id = match entry.token {
+                BiasedToken::Id(id) if (0..n_vocab).contains(&id) => id,
+                BiasedToken::Id(id) => {
+                    return Err(format!("Logit bias: token {} isn't in the vocabulary", id))
+                }
+                BiasedToken::Text(text) => {
+                    let tokens = model
+                        .str_to_token(&text, llama_cpp_2::model::AddBos::Never)
+                        .map_err(|e| format!("Failed to tokenize: {}", e))?;
+                    match tokens[..] {
+                        [token] => token.0,
+                        _ => {
+                            return Err(format!(
+                                "Logit bias: {:?} is {} tokens, not one",
+                                text,
+                                tokens.len()
+                            ))
+                        }
+                    }
+                }
+            };
+            Ok((id, entry.bias))
+        })
+        .collect()
+}
+
+/// Tokens of the prompt followed by the completion, and how many belong to
+/// the prompt. The two are tokenized separately so the boundary between
+/// them is a token boundary.
+fn tokenize_request(
+    model: &LlamaModel,
+    cache: Option<(&TokenCache, u64)>,
+    request: &AnalysisRequest,
+) -> Result<(Vec<llama_cpp_2::token::LlamaToken>, usize), String> {
+    let add_bos = if request.skip_bos {
+        llama_cpp_2::model::AddBos::Never
+    } else {
+        llama_cpp_2::model::AddBos::Always
+    };
+    let (mut tokens, completion_add_bos) = if request.prompt.is_empty() {
+        (Vec::new(), add_bos)
+    } else {
+        let prompt = tokenize(model, cache, &request.prompt, add_bos)
+            .map_err(|e| format!("Failed to tokenize prompt: {}", e))?;
+        (prompt, llama_cpp_2::model::AddBos::Never)
+    };
+    let prompt_tokens = tokens.len();
+    tokens.extend(
+        tokenize(model, cache, &request.text, completion_add_bos)
+            .map_err(|e| format!("Failed to tokenize: {}", e))?,
+    );
+    Ok((tokens, prompt_tokens))
+}
+
+/// Tokens of `text`, reusing its tokens from `cache` when the model with
+/// that fingerprint tokenized it before. The cache holds tokens without
+/// special tokens; those added are the ones an empty text gets, BOS before
+/// the text and, for models that want it, EOS after.
+fn tokenize(
+    model: &LlamaModel,
+    cache: Option<(&TokenCache, u64)>,
+    text: &str,
+    add_bos: llama_cpp_2::model::AddBos,
+) -> Result<Vec<llama_cpp_2::token::LlamaToken>, String> {
+    let Some((cache, fingerprint)) = cache else {
+        return model.str_to_token(text, add_bos).map_err(|e| e.to_string());
+    };
+    let key = TokenCache::key(fingerprint, text);
+    let plain = match cache.get(key) {
+        Some(tokens) => tokens,
+        None => {
+            let tokens = model
+                .str_to_token(text, llama_cpp_2::model::AddBos::Never)
+                .map_err(|e| e.to_string())?;
+            let tokens = Arc::new(tokens);
+            cache.insert(key, tokens.clone());
+            tokens
+        }
+    };
+    if add_bos == llama_cpp_2::model::AddBos::Never {
+        return Ok(plain.to_vec());
+    }
+    let frame = model
+        .str_to_token("", llama_cpp_2::model::AddBos::Always)
+        .map_err(|e| e.to_string())?;
+    let leading = frame
+        .iter()
+        .take_while(|&&t| t == model.token_bos())
+        .count();
+    let mut tokens = Vec::with_capacity(frame.len() + plain.len());
+    tokens.extend_from_slice(&frame[..leading]);
+    tokens.extend_from_slice(&plain);
+    tokens.extend_from_slice(&frame[leading..]);
+    Ok(tokens)
+}
+
+/// Id of the most likely token, for greedy decoding.
+fn argmax(logits: &[f32]) -> Option<i32> {
+    logits
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(id, _)| id as i32)
+}
+
+/// Text of `tokens`, without special tokens like BOS.
+fn detokenize(model: &LlamaModel, tokens: &[llama_cpp_2::token::LlamaToken]) -> String {
+    let mut decoder = encoding_rs::UTF_8.new_decoder();
+    tokens
+        .iter()
+        .filter_map(|&token| model.token_to_piece(token, &mut decoder, false, None).ok())
+        .collect()
+}
+
+/// Log-probability of `id` under the softmax of `logits`.
+fn log_softmax_at(logits: &[f32], id: i32) -> f32 {
+    let Some(&logit) = usize::try_from(id).ok().and_then(|i| logits.get(i)) else {
+        return f32::NEG_INFINITY;
+    };
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum_exp: f32 = logits.iter().map(|l| (l - max).exp()).sum();
+    logit - max - sum_exp.ln()
+}
+
+/// VRAM usage of every GPU-like device llama.cpp can offload to.
+fn device_memory() -> Vec<DeviceMemory> {
+    llama_cpp_2::list_llama_ggml_backend_devices()
+        .into_iter()
+        .filter(|d| d.device_type != LlamaBackendDeviceType::Cpu && d.memory_total > 0)
+        .map(|d| DeviceMemory {
+            name: if d.description.is_empty() {
+                d.name
+            } else {
+                d.description
+            },
+            used: d.memory_total.saturating_sub(d.memory_free) as u64,
+            total: d.memory_total as u64,
+        })
+        .collect()
+}
+
+/// Persistent worker loop that handles model lifecycle and analysis commands.
+/// The worker starts with no model loaded and responds to LoadModel/UnloadModel
+/// commands, enabling future JIT model swapping to conserve VRAM.
+pub fn run_worker(
+    cmd_rx: mpsc::Receiver<WorkerCommand>,
+    msg_tx: mpsc::Sender<WorkerMessage>,
+    token_cache: Arc<TokenCache>,
+    shared: Arc<SharedModel>,
+) {
+    log::info!("Worker started, waiting for commands...");
+
+    let mut analyzer = LlamaAnalyzer::new();
+    analyzer.set_token_cache(token_cache);
+    // Commands that arrived during an analysis, run once it's over.
+    let mut deferred = VecDeque::new();
+
+    // The context `serve` keeps borrows the model, so it hands commands
+    // that replace the model back to be run here.
+    loop {
+        match serve(&analyzer, &cmd_rx, &msg_tx, &mut deferred) {
+            Some(WorkerCommand::LoadModel(path, params)) => {
+                shared.clear();
+                let loaded = analyzer.load_model(&path, params);
+                // Published before `ModelLoaded`, so side jobs sent once
+                // the app sees the model find it.
+                shared.finish_load(loaded.is_ok().then(|| analyzer.clone()));
+                match loaded {
+                    Ok(()) => {
+                        let _ = msg_tx.send(WorkerMessage::ModelLoaded);
+                    }
+                    Err(e) => {
+                        let _ = msg_tx.send(WorkerMessage::Error(
+                            format!("Failed to load model: {}", e),
+                        ));
+                    }
+                }
+            }
+            Some(WorkerCommand::SetParams(params)) => {
+                shared.set_params(&params);
+                analyzer.set_params(params);
+            }
+            Some(WorkerCommand::UnloadModel) => {
+                shared.clear();
+                analyzer.unload_model();
+                let _ = msg_tx.send(WorkerMessage::ModelUnloaded);
+            }
+            _ => break,
+        }
+    }
+}
+
+/// Runs commands with the loaded model, keeping the context of an analysis
+/// for the next, until one loads or unloads a model or changes its
+/// parameters, which is returned with the context dropped. `None` when the
+/// worker should shut down.
+fn serve<'m>(
+    analyzer: &'m LlamaAnalyzer,
+    cmd_rx: &mpsc::Receiver<WorkerCommand>,
+    msg_tx: &mpsc::Sender<WorkerMessage>,
+    deferred: &mut VecDeque<WorkerCommand>,
+) -> Option<WorkerCommand> {
+    let mut kept: Option<KeptContext<'m>> = None;
+    loop {
+        let command = match deferred.pop_front() {
+            Some(command) => Ok(command),
+            None => cmd_rx.recv(),
+        };
+        match command {
+            Ok(
+                command @ (WorkerCommand::LoadModel(..)
+                | WorkerCommand::SetParams(_)
+                | WorkerCommand::UnloadModel),
+            ) => {
+                return Some(command);
+            }
+            Ok(WorkerCommand::Pause | WorkerCommand::Resume | WorkerCommand::Cancel) => {}
+            Ok(WorkerCommand::Analyze(request)) => {
+                let _ = msg_tx.send(WorkerMessage::Started);
+
+                if let Some(result) = analyzer.cached(&request) {
+                    log::info!("Using the cached analysis of this text");
+                    let _ = msg_tx.send(WorkerMessage::Completed(result));
+                    continue;
+                }
+                let analysis = analyzer.analyze_reusing(&request, Some(msg_tx), &mut kept, || {
+                    handle_pause(cmd_rx, msg_tx, deferred)
+                });
+                match analysis {
+                    Ok(result) => {
+                        analyzer.store_cached(&request, &result);
+                        let _ = msg_tx.send(WorkerMessage::Completed(result));
+                    }
+                    Err(e) => {
+                        let _ = msg_tx.send(WorkerMessage::Error(e));
+                    }
+                }
+            }
+            Ok(WorkerCommand::Rescore(request)) => {
+                let _ = msg_tx.send(WorkerMessage::Started);
+                let analysis = analyzer.analyze_reusing(&request, Some(msg_tx), &mut kept, || {
+                    handle_pause(cmd_rx, msg_tx, deferred)
+                });
+                let _ = msg_tx.send(match analysis {
+                    Ok(result) => WorkerMessage::Rescored(result),
+                    Err(e) => WorkerMessage::Error(e),
+                });
+            }
+            Ok(WorkerCommand::Score(requests)) => {
+                let _ = msg_tx.send(WorkerMessage::Started);
+
+                let total = requests.len();
+                let mut perplexities = Vec::with_capacity(total);
+                let mut failed = None;
+                for (i, request) in requests.iter().enumerate() {
+                    let _ = msg_tx.send(WorkerMessage::Progress { current: i, total });
+                    let analysis = analyzer.analyze_reusing(request, None, &mut kept, || {
+                        handle_pause(cmd_rx, msg_tx, deferred)
+                    });
+                    match analysis {
+                        Ok(result) => perplexities.push(result.perplexity()),
+                        Err(e) => {
+                            failed = Some(e);
+                            break;
+                        }
+                    }
+                }
+
+                let _ = msg_tx.send(match failed {
+                    Some(e) => WorkerMessage::Error(format!("Scoring failed: {}", e)),
+                    None => WorkerMessage::Scored(perplexities),
+                });
+            }
+            Ok(WorkerCommand::AnalyzeVariants(requests)) => {
+                let _ = msg_tx.send(WorkerMessage::Started);
+
+                let total = requests.len();
+                let mut results = Vec::with_capacity(total);
+                let mut failed = None;
+                for (i, request) in requests.iter().enumerate() {
+                    let _ = msg_tx.send(WorkerMessage::Progress { current: i, total });
+                    let analysis = analyzer.analyze_reusing(request, None, &mut kept, || {
+                        handle_pause(cmd_rx, msg_tx, deferred)
+                    });
+                    match analysis {
+                        Ok(result) => results.push(result),
+                        Err(e) => {
+                            failed = Some(e);
+                            break;
+                        }
+                    }
+                }
+
+                let _ = msg_tx.send(match failed {
+                    Some(e) => WorkerMessage::Error(format!("Analysis failed: {}", e)),
+                    None => WorkerMessage::VariantsAnalyzed(results),
+                });
+            }
+            Ok(WorkerCommand::Shutdown) => {
+                log::info!("Worker received shutdown command");
+                return None;
+            }
+            Err(_) => {
+                log::info!("Worker channel closed, shutting down");
+                return None;
+            }
+        }
+    }
+}
+
+/// The worker thread's loaded model, shared with the side thread. Loads
+/// the manager has asked for but the worker hasn't finished are counted,
+/// so a side job sent right after `load_model` waits for the new model
+/// instead of failing or using the old one.
+#[derive(Default)]
+pub struct SharedModel {
+    state: Mutex<SharedState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct SharedState {
+    pending_loads: usize,
+    analyzer: Option<LlamaAnalyzer>,
+}
+
+impl SharedModel {
+    /// Counts a load sent to the worker thread.
+    pub fn begin_load(&self) {
+        self.lock().pending_loads += 1;
+    }
+
+    /// Ends a load counted by `begin_load`, publishing the analyzer if
+    /// the model loaded.
+    fn finish_load(&self, analyzer: Option<LlamaAnalyzer>) {
+        let mut state = self.lock();
+        state.pending_loads = state.pending_loads.saturating_sub(1);
+        state.analyzer = analyzer;
+        self.changed.notify_all();
+    }
+
+    fn clear(&self) {
+        self.lock().analyzer = None;
+    }
+
+    fn set_params(&self, params: &InferenceParams) {
+        if let Some(analyzer) = self.lock().analyzer.as_mut() {
+            analyzer.set_params(params.clone());
+        }
+    }
+
+    /// Stops waiting for loads once the worker thread has ended.
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.pending_loads = 0;
+        state.analyzer = None;
+        self.changed.notify_all();
+    }
+
+    /// The loaded model once pending loads are done; `None` if none is.
+    fn wait(&self) -> Option<LlamaAnalyzer> {
+        let mut state = self.lock();
+        while state.pending_loads > 0 {
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.analyzer.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SharedState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Body of the side thread. It runs sweeps, regenerations and other-model
+/// analyses with the model the worker thread loaded, in contexts of its
+/// own, so they don't wait behind a long analysis; while both run, both
+/// contexts are in memory. A model unloaded during a side job is freed
+/// when the job ends. Every job checks for `Cancel` and `Shutdown` before
+/// each decode, so closing the window never waits for one to finish.
+pub fn run_side_worker(
+    cmd_rx: mpsc::Receiver<SideCommand>,
+    msg_tx: mpsc::Sender<WorkerMessage>,
+    shared: Arc<SharedModel>,
+) {
+    // Commands that arrived during a cross-quant analysis.
+    let mut deferred = VecDeque::new();
+    loop {
+        let command = match deferred.pop_front() {
+            Some(command) => command,
+            None => match cmd_rx.recv() {
+                Ok(command) => command,
+                Err(_) => break,
+            },
+        };
+        let job = match command {
+            SideCommand::Run(job) => job,
+            SideCommand::Cancel => continue,
+            SideCommand::Shutdown => break,
+        };
+        let Some(analyzer) = shared.wait() else {
+            let _ = msg_tx.send(WorkerMessage::SideJobFailed("No model loaded".to_string()));
+            continue;
+        };
+
+        let _ = msg_tx.send(WorkerMessage::Started);
+        let reply = match job {
+            SideJob::ContextSweep(text) => analyzer
+                .context_sweep(&text, &msg_tx, || check_side_cancel(&cmd_rx, &mut deferred))
+                .map(WorkerMessage::ContextSwept)
+                .map_err(|e| format!("Context sweep failed: {}", e)),
+            SideJob::ChunkSweep(text) => analyzer
+                .chunk_sweep(&text, &msg_tx, || check_side_cancel(&cmd_rx, &mut deferred))
+                .map(WorkerMessage::ChunkSwept)
+                .map_err(|e| format!("Chunk sweep failed: {}", e)),
+            SideJob::Regenerate(text) => analyzer
+                .regenerate(&text, &msg_tx, || check_side_cancel(&cmd_rx, &mut deferred))
+                .map(WorkerMessage::Regenerated)
+                .map_err(|e| format!("Regeneration failed: {}", e)),
+            SideJob::RegenerateDiff { request, start } => analyzer
+                .regenerate_diff(&request, start, &msg_tx, || {
+                    check_side_cancel(&cmd_rx, &mut deferred)
+                })
+                .map(WorkerMessage::RegeneratedDiff)
+                .map_err(|e| format!("Regeneration failed: {}", e)),
+            SideJob::AnalyzeModels { models, request } => {
+                analyze_models(&analyzer, models, &request, &msg_tx, || {
+                    check_side_cancel(&cmd_rx, &mut deferred)
+                })
+                .map(WorkerMessage::ModelsAnalyzed)
+                .map_err(|e| format!("Cross-quant analysis failed: {}", e))
+            }
+        };
+        let _ = msg_tx.send(reply.unwrap_or_else(WorkerMessage::SideJobFailed));
+    }
+}
+
+/// Analyzes `request` with each of the models at `models`, loaded one at a
+/// time with the parameters of `analyzer`.
+fn analyze_models(
+    analyzer: &LlamaAnalyzer,
+    models: Vec<String>,
+    request: &AnalysisRequest,
+    msg_tx: &mpsc::Sender<WorkerMessage>,
+    mut between_batches: impl FnMut() -> Result<(), String>,
+) -> Result<Vec<(String, AnalysisResult)>, String> {
+    let total = models.len();
+    let mut results = Vec::with_capacity(total);
+    for (i, path) in models.into_iter().enumerate() {
+        let _ = msg_tx.send(WorkerMessage::Progress { current: i, total });
+        let mut other = LlamaAnalyzer::new();
+        let analysis = other
+            .load_model(&path, analyzer.params.clone())
+            .and_then(|()| {
+                if let Some(result) = other.cached(request) {
+                    return Ok(result);
+                }
+                let result = other.analyze_with(request, None, &mut between_batches)?;
+                other.store_cached(request, &result);
+                Ok(result)
+            });
+        match analysis {
+            Ok(result) => results.push((path, result)),
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        }
+    }
+    Ok(results)
+}
+
+/// Checks for `Cancel` before each decode of a side job, deferring other
+/// commands until it's over. `Shutdown` aborts it too.
+fn check_side_cancel(
+    cmd_rx: &mpsc::Receiver<SideCommand>,
+    deferred: &mut VecDeque<SideCommand>,
+) -> Result<(), String> {
+    while let Ok(command) = cmd_rx.try_recv() {
+        match command {
+            SideCommand::Cancel => {
+                log::info!("Side job cancelled");
+                return Err("Analysis cancelled".to_string());
+            }
+            SideCommand::Shutdown => {
+                deferred.push_back(SideCommand::Shutdown);
+                return Err("Analysis cancelled".to_string());
+            }
+            other => deferred.push_back(other),
+        }
+    }
+    Ok(())
+}
+
+/// Control, user-defined or unknown token (BOS, chat markers, …).
+fn is_special(model: &LlamaModel, token: llama_cpp_2::token::LlamaToken) -> bool {
+    let attrs = model.token_attr(token);
+    attrs.contains(LlamaTokenAttr::Control)
+        || attrs.contains(LlamaTokenAttr::UserDefined)
+        || attrs.contains(LlamaTokenAttr::Unknown)
+}
+
+/// Badge text for a special token: BOS and EOS by name, others by their
+/// piece without the `<|…|>`, `<…>` or `[…]` around it.
+fn special_label(model: &LlamaModel, token: llama_cpp_2::token::LlamaToken, piece: &str) -> String {
+    if token == model.token_bos() {
+        return "BOS".to_string();
+    }
+    if token == model.token_eos() {
+        return "EOS".to_string();
+    }
+    let name = piece
+        .trim()
+        .trim_start_matches(['<', '|', '['])
+        .trim_end_matches(['>', '|', ']']);
+    if name.is_empty() {
+        format!("#{}", token.0)
+    } else {
+        name.to_string()
+    }
+}
+
+fn list_tokens(model: &LlamaModel, text: &str, add_bos: bool) -> Result<Vec<TokenInfo>, String> {
+    let add_bos = if add_bos {
+        llama_cpp_2::model::AddBos::Always
+    } else {
+        llama_cpp_2::model::AddBos::Never
+    };
+    let tokens = model
+        .str_to_token(text, add_bos)
+        .map_err(|e| format!("Failed to tokenize: {}", e))?;
+    Ok(tokens
+        .into_iter()
+        .map(|token| {
+            let bytes = model
+                .token_to_piece_bytes(token, 256, true, None)
+                .unwrap_or_default();
+            TokenInfo {
+                id: token.0,
+                text: String::from_utf8_lossy(&bytes).into_owned(),
+                bytes: bytes.len(),
+                special: is_special(model, token),
+            }
+        })
+        .collect())
+}
+
+fn count_tokens(model: &LlamaModel, cache: Option<(&TokenCache, u64)>, text: &str) -> usize {
+    tokenize(model, cache, text, llama_cpp_2::model::AddBos::Never).map_or(0, |tokens| tokens.len())
+}
+
+/// Body of the tokenizer thread, which answers the quick requests that only
+/// need the vocabulary. Loading only the vocabulary takes a fraction of a
+/// second and little memory, and the full model's worker stays free for
+/// analyses: a token list doesn't queue behind a long decode. Its
+/// tokenizations go to `token_cache`, shared with the worker, so an
+/// analysis of the counted text skips tokenizing.
+pub fn run_tokenizer(
+    cmd_rx: mpsc::Receiver<TokenizerCommand>,
+    msg_tx: mpsc::Sender<WorkerMessage>,
+    token_cache: Arc<TokenCache>,
+) {
+    let mut model = None;
+    let mut fingerprint = None;
+    for command in cmd_rx {
+        match command {
+            TokenizerCommand::Load(path) => {
+                model = None;
+                let params = LlamaModelParams::default().with_vocab_only(true);
+                match LlamaModel::load_from_file(get_backend(), &path, &params) {
+                    Ok(loaded) => model = Some(loaded),
+                    Err(e) => log::warn!("Failed to load the vocabulary of {}: {}", path, e),
+                }
+                fingerprint = cache::model_fingerprint(Path::new(&path)).ok();
+            }
+            TokenizerCommand::Unload => model = None,
+            TokenizerCommand::Count(text) => {
+                let cache = fingerprint.map(|f| (&*token_cache, f));
+                let count = model.as_ref().map_or(0, |m| count_tokens(m, cache, &text));
+                let _ = msg_tx.send(WorkerMessage::TokenCount(count));
+            }
+            TokenizerCommand::ListTokens { text, add_bos } => {
+                let tokens = model
+                    .as_ref()
+                    .ok_or_else(|| "No model loaded".to_string())
+                    .and_then(|m| list_tokens(m, &text, add_bos));
+                let _ = msg_tx.send(WorkerMessage::TokenList(tokens));
+            }
+            TokenizerCommand::Shutdown => break,
+        }
+    }
+}
+
+/// Checks for commands between the batches of an analysis. `Pause` blocks
+/// until `Resume` arrives, leaving the model and context in memory; other
+/// commands are deferred until the analysis is over, except `Cancel` and
+/// `Shutdown`, which abort it.
+fn handle_pause(
+    cmd_rx: &mpsc::Receiver<WorkerCommand>,
+    msg_tx: &mpsc::Sender<WorkerMessage>,
+    deferred: &mut VecDeque<WorkerCommand>,
+) -> Result<(), String> {
+    let mut paused = false;
+    loop {
+        let command = if paused {
+            cmd_rx
+                .recv()
+                .map_err(|_| "Worker channel closed".to_string())?
+        } else {
+            match cmd_rx.try_recv() {
+                Ok(command) => command,
+                Err(_) => return Ok(()),
+            }
+        };
+        match command {
+            WorkerCommand::Pause if !paused => {
+                log::info!("Analysis paused");
+                paused = true;
+                let _ = msg_tx.send(WorkerMessage::Paused);
+            }
+            WorkerCommand::Resume if paused => {
+                log::info!("Analysis resumed");
+                paused = false;
+                let _ = msg_tx.send(WorkerMessage::Resumed);
+            }
+            WorkerCommand::Pause | WorkerCommand::Resume => {}
+            WorkerCommand::Cancel => {
+                log::info!("Analysis cancelled");
+                return Err("Analysis cancelled".to_string());
+            }
+            WorkerCommand::Shutdown => {
+                deferred.push_back(WorkerCommand::Shutdown);
+                return Err("Analysis cancelled".to_string());
+            }
+            other => deferred.push_back(other),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+/// A token whose logit is shifted before the softmax, to see how the
+/// rankings would change if the model favored or avoided it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogitBias {
+    pub token: BiasedToken,
+    /// Added to the logit; `-inf` bans the token.
+    pub bias: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BiasedToken {
+    Id(i32),
+    /// Text that must tokenize to a single token of the model.
+    Text(String),
+}
+
+/// Parses biases written as `token = bias`, separated by commas or line
+/// breaks. A token is an id or a double-quoted text, e.g.
+/// `" the" = -inf, 1234 = 2.5`; `\"` and `\\` escape inside quotes.
+pub fn parse(spec: &str) -> Result<Vec<LogitBias>, String> {
+    let mut biases = Vec::new();
+    let mut rest = spec.trim_start();
+    while !rest.is_empty() {
+        let (token, after) = parse_token(rest)?;
+        let after = after.trim_start();
+        let after = after
+            .strip_prefix('=')
+            .ok_or_else(|| format!("Expected '=' after {}", describe(&token)))?;
+        let end = after.find([',', '\n']).unwrap_or(after.len());
+        let value = after[..end].trim();
+        let bias = value
+            .parse::<f32>()
+            .ok()
+            .filter(|b| !b.is_nan())
+            .ok_or_else(|| format!("Invalid bias '{}' for {}", value, describe(&token)))?;
+        biases.push(LogitBias { token, bias });
+        rest = after[end..].trim_start_matches([',', '\n']).trim_start();
+    }
+    Ok(biases)
+}
+
+fn parse_token(input: &str) -> Result<(BiasedToken, &str), String> {
+    if let Some(quoted) = input.strip_prefix('"') {
+        let mut text = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((BiasedToken::Text(text), &quoted[i + 1..])),
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => text.push(escaped),
+                    None => break,
+                },
+                c => text.push(c),
+            }
+        }
+        return Err(format!("Unterminated quote in \"{}", quoted));
+    }
+    let end = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+        .unwrap_or(input.len());
+    let id = input[..end]
+        .parse()
+        .map_err(|_| format!("Expected a token id or a quoted text at '{}'", input))?;
+    Ok((BiasedToken::Id(id), &input[end..]))
+}
+
+fn describe(token: &BiasedToken) -> String {
+    match token {
+        BiasedToken::Id(id) => format!("token {}", id),
+        BiasedToken::Text(text) => format!("{:?}", text),
+    }
+}
@@ -0,0 +1,109 @@
+use crate::analysis::AnalysisResult;
+
+/// DetectGPT-style curvature: how much more likely the original text is
+/// than nearby rewrites of it. Machine-generated text tends to sit at a
+/// local maximum of the model's likelihood, so rewrites score noticeably
+/// worse; human text shows little difference.
+#[derive(Clone, Debug)]
+pub struct PerturbationStats {
+    /// Mean log-likelihood per token of the original text, in nats.
+    pub original: f32,
+    /// Mean log-likelihood per token of each perturbed variant.
+    pub perturbed: Vec<f32>,
+}
+
+impl PerturbationStats {
+    /// Builds the statistic from the original perplexity and the
+    /// perplexities of the perturbed variants.
+    pub fn new(original_ppl: f32, perturbed_ppls: &[f32]) -> Self {
+        Self {
+            original: -original_ppl.ln(),
+            perturbed: perturbed_ppls
+                .iter()
+                .filter(|p| p.is_finite() && **p > 0.0)
+                .map(|p| -p.ln())
+                .collect(),
+        }
+    }
+
+    pub fn mean_perturbed(&self) -> f32 {
+        self.perturbed.iter().sum::<f32>() / self.perturbed.len().max(1) as f32
+    }
+
+    /// Normalized curvature: the gap between the original and the mean
+    /// perturbed log-likelihood, in standard deviations of the perturbed
+    /// scores. `None` with fewer than two usable variants.
+    pub fn curvature(&self) -> Option<f32> {
+        if self.perturbed.len() < 2 {
+            return None;
+        }
+        let mean = self.mean_perturbed();
+        let variance = self
+            .perturbed
+            .iter()
+            .map(|p| (p - mean).powi(2))
+            .sum::<f32>()
+            / (self.perturbed.len() - 1) as f32;
+        Some((self.original - mean) / variance.sqrt().max(1e-6))
+    }
+}
+
+/// Builds `count` variants of the analyzed text, each replacing about `rate`
+/// of the tokens with one of the model's other top predictions at that
+/// position, drawn by probability. This is mask-and-refill with the scoring
+/// model itself as the filler, so no second model is needed. `original` is
+/// the analyzed input text.
+pub fn make_variants(
+    result: &AnalysisResult,
+    original: &str,
+    count: usize,
+    rate: f32,
+) -> Vec<String> {
+    // The first token is normally BOS, which isn't part of the input text;
+    // keep it only when the model didn't add one. Prompt tokens are never
+    // perturbed: variants are scored with the same prompt.
+    let skip = if result.has_prompt() {
+        result.prompt_tokens
+    } else {
+        match result.tokens.first() {
+            Some(first) if original.starts_with(&*first.text) => 0,
+            _ => 1,
+        }
+    };
+    let mut rng = fastrand::Rng::new();
+    (0..count)
+        .map(|_| {
+            let mut text = String::new();
+            for token in result.tokens.iter().skip(skip) {
+                let replacement = (rng.f32() < rate)
+                    .then(|| {
+                        let alternatives: Vec<(&str, f32)> = token
+                            .top_predictions
+                            .iter()
+                            .map(|&(id, p)| (result.vocab.piece(id), p))
+                            .filter(|(piece, _)| *piece != &*token.text)
+                            .collect();
+                        pick_weighted(&mut rng, &alternatives)
+                    })
+                    .flatten();
+                text.push_str(replacement.unwrap_or(&token.text));
+            }
+            text
+        })
+        .collect()
+}
+
+fn pick_weighted<'a>(rng: &mut fastrand::Rng, options: &[(&'a str, f32)]) -> Option<&'a str> {
+    let total: f32 = options.iter().map(|(_, p)| p).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut target = rng.f32() * total;
+    for &(piece, p) in options {
+        if target < p {
+            return Some(piece);
+        }
+        target -= p;
+    }
+    options.last().map(|(piece, _)| *piece)
+}
@@ -0,0 +1,163 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Normalization {
+    None,
+    /// Canonical composition: merges combining sequences like `e` + `◌́`.
+    Nfc,
+    /// Compatibility composition: additionally folds ligatures, full-width
+    /// forms, superscripts and the like.
+    Nfkc,
+}
+
+impl std::fmt::Display for Normalization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Normalization::None => write!(f, "None"),
+            Normalization::Nfc => write!(f, "NFC"),
+            Normalization::Nfkc => write!(f, "NFKC"),
+        }
+    }
+}
+
+/// Transformations applied to the input before tokenization, so that
+/// invisible formatting differences don't show up as perplexity differences.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreprocessOptions {
+    pub normalization: Normalization,
+    /// Replace curly quotes, primes and typographic dashes with ASCII.
+    pub plain_punctuation: bool,
+    /// Collapse runs of blank lines into a single blank line.
+    pub collapse_newlines: bool,
+    pub strip_markdown: bool,
+}
+
+impl Default for PreprocessOptions {
+    fn default() -> Self {
+        Self {
+            normalization: Normalization::None,
+            plain_punctuation: false,
+            collapse_newlines: false,
+            strip_markdown: false,
+        }
+    }
+}
+
+impl PreprocessOptions {
+    pub fn is_active(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+/// Runs the enabled steps in order: Markdown first (it relies on the
+/// original line structure), then Unicode normalization, punctuation and
+/// finally newline collapsing.
+pub fn apply(text: &str, options: &PreprocessOptions) -> String {
+    let mut text = if options.strip_markdown {
+        strip_markdown(text)
+    } else {
+        text.to_string()
+    };
+    text = match options.normalization {
+        Normalization::None => text,
+        Normalization::Nfc => text.nfc().collect(),
+        Normalization::Nfkc => text.nfkc().collect(),
+    };
+    if options.plain_punctuation {
+        text = text.chars().map(plain_punctuation).collect();
+    }
+    if options.collapse_newlines {
+        text = collapse_newlines(&text);
+    }
+    text
+}
+
+fn plain_punctuation(c: char) -> char {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => '\'',
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => '"',
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}'
+        | '\u{2212}' => '-',
+        '\u{00A0}' | '\u{202F}' => ' ',
+        c => c,
+    }
+}
+
+/// Trims trailing whitespace from lines and keeps at most one blank line
+/// between paragraphs. `\r\n` line endings become `\n`.
+fn collapse_newlines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank_run = 0;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    if !text.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+struct MarkdownPatterns {
+    block_prefix: Regex,
+    image_or_link: Regex,
+    emphasis: Regex,
+    inline_code: Regex,
+    rule: Regex,
+}
+
+fn markdown_patterns() -> &'static MarkdownPatterns {
+    static PATTERNS: OnceLock<MarkdownPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| MarkdownPatterns {
+        // Headings, block quotes and list markers.
+        block_prefix: Regex::new(r"^(\s*)(#{1,6}\s+|>\s?|[-*+]\s+|\d+[.)]\s+)").unwrap(),
+        image_or_link: Regex::new(r"!?\[([^\]]*)\]\([^)]*\)").unwrap(),
+        emphasis: Regex::new(r"(\*\*|__|\*|~~)([^*_~\n]+?)(\*\*|__|\*|~~)").unwrap(),
+        inline_code: Regex::new(r"`([^`\n]+)`").unwrap(),
+        rule: Regex::new(r"^\s*([-*_]\s*){3,}$").unwrap(),
+    })
+}
+
+/// Removes common Markdown syntax, keeping the visible text. Code blocks
+/// keep their contents but lose the fences.
+fn strip_markdown(text: &str) -> String {
+    let patterns = markdown_patterns();
+    let mut out = String::with_capacity(text.len());
+    let mut in_code = false;
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        if body.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            out.push_str(line);
+            continue;
+        }
+        if patterns.rule.is_match(body) {
+            out.push_str(ending);
+            continue;
+        }
+        let body = patterns.block_prefix.replace(body, "$1");
+        let body = patterns.image_or_link.replace_all(&body, "$1");
+        let body = patterns.inline_code.replace_all(&body, "$1");
+        let body = patterns.emphasis.replace_all(&body, "$2");
+        out.push_str(&body);
+        out.push_str(ending);
+    }
+    out
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use crate::analysis::AnalysisResult;
+use crate::token_types::TokenClass;
+
+/// Positions listed as the most damaged, largest variance first.
+const MAX_WORST: usize = 20;
+
+/// The two results scored the same token ids, so they can be compared
+/// position by position.
+pub fn same_tokens(a: &AnalysisResult, b: &AnalysisResult) -> bool {
+    a.prompt_tokens == b.prompt_tokens
+        && a.tokens.len() == b.tokens.len()
+        && a.tokens.iter().zip(&b.tokens).all(|(x, y)| x.id == y.id)
+}
+
+#[derive(Clone, Debug)]
+pub struct QuantScore {
+    pub name: String,
+    pub perplexity: f32,
+}
+
+/// Mean log-probability variance of the tokens in one group.
+#[derive(Clone, Debug)]
+pub struct GroupVariance {
+    pub name: String,
+    pub tokens: usize,
+    pub mean_variance: f32,
+}
+
+/// A position whose log-probability varied most across the quants.
+#[derive(Clone, Debug)]
+pub struct DamagedToken {
+    pub index: usize,
+    pub text: String,
+    pub variance: f32,
+    /// Probability under each quant, in the order of `quants`.
+    pub probabilities: Vec<f32>,
+}
+
+/// How much the log-probability of each token varies across quantizations
+/// of the same model scoring the same text. Positions where it varies a
+/// lot are the ones quantization damages; grouping them shows whether the
+/// damage concentrates in numbers, code, rare tokens and so on.
+#[derive(Clone, Debug)]
+pub struct QuantVariance {
+    pub quants: Vec<QuantScore>,
+    /// Variance of the log-probability (in nats²) per token of the first
+    /// result; zero for tokens that aren't scored.
+    pub variances: Vec<f32>,
+    pub max_variance: f32,
+    /// One row per token class, then one for tokens that occur once in the
+    /// text.
+    pub groups: Vec<GroupVariance>,
+    pub worst: Vec<DamagedToken>,
+}
+
+impl QuantVariance {
+    /// Fails with fewer than two results or when they weren't tokenized
+    /// identically, as with quants of different base models.
+    pub fn new(results: &[(String, &AnalysisResult)]) -> Result<Self, String> {
+        let Some(((_, first), rest)) = results.split_first() else {
+            return Err("No results to compare".to_string());
+        };
+        if rest.is_empty() {
+            return Err("Comparing quantizations takes at least two models".to_string());
+        }
+        for (name, result) in rest {
+            if !same_tokens(first, result) {
+                return Err(format!(
+                    "{} tokenized the text differently; compare quants of the same model",
+                    name
+                ));
+            }
+        }
+
+        let start = first.first_scored();
+        let mut variances = vec![0.0; first.tokens.len()];
+        for (index, variance) in variances.iter_mut().enumerate().skip(start) {
+            let log_probs: Vec<f32> = results
+                .iter()
+                .map(|(_, r)| r.tokens[index].probability.max(f32::MIN_POSITIVE).ln())
+                .collect();
+            let mean = log_probs.iter().sum::<f32>() / log_probs.len() as f32;
+            *variance = log_probs.iter().map(|l| (l - mean).powi(2)).sum::<f32>()
+                / (log_probs.len() - 1) as f32;
+        }
+
+        let scored = &first.tokens[start..];
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        for token in scored {
+            *occurrences.entry(&*token.text).or_default() += 1;
+        }
+        let mean_of = |name: String, filter: &dyn Fn(&str) -> bool| {
+            let values: Vec<f32> = scored
+                .iter()
+                .zip(&variances[start..])
+                .filter(|(t, _)| filter(&t.text))
+                .map(|(_, &v)| v)
+                .collect();
+            (!values.is_empty()).then(|| GroupVariance {
+                name,
+                tokens: values.len(),
+                mean_variance: values.iter().sum::<f32>() / values.len() as f32,
+            })
+        };
+        let mut groups: Vec<GroupVariance> = TokenClass::ALL
+            .into_iter()
+            .filter_map(|class| {
+                mean_of(class.to_string(), &|text: &str| {
+                    TokenClass::of(text) == class
+                })
+            })
+            .collect();
+        groups.extend(mean_of(
+            "Rare (once in the text)".to_string(),
+            &|text: &str| occurrences.get(text) == Some(&1),
+        ));
+
+        let mut order: Vec<usize> = (start..variances.len()).collect();
+        order.sort_by(|&a, &b| variances[b].total_cmp(&variances[a]));
+        let worst = order
+            .into_iter()
+            .take(MAX_WORST)
+            .map(|index| DamagedToken {
+                index,
+                text: first.tokens[index].text.to_string(),
+                variance: variances[index],
+                probabilities: results
+                    .iter()
+                    .map(|(_, r)| r.tokens[index].probability)
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Self {
+            quants: results
+                .iter()
+                .map(|(name, r)| QuantScore {
+                    name: name.clone(),
+                    perplexity: r.perplexity(),
+                })
+                .collect(),
+            max_variance: variances.iter().copied().fold(0.0, f32::max),
+            variances,
+            groups,
+            worst,
+        })
+    }
+}
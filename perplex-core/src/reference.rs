@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const REFERENCE_DIR_NAME: &str = ".perplex/references";
+
+/// Corpus segments are cut at paragraph boundaries once they reach this many
+/// characters, so a handful of long files still yields a usable distribution.
+const SEGMENT_TARGET_CHARS: usize = 2000;
+
+/// Kind of text a reference corpus stands for. Perplexity depends as much
+/// on the genre as on who wrote the text, so a news article is best judged
+/// against news and code against code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Domain {
+    #[default]
+    General,
+    News,
+    Fiction,
+    Code,
+    Academic,
+}
+
+impl Domain {
+    pub const ALL: [Domain; 5] = [
+        Domain::General,
+        Domain::News,
+        Domain::Fiction,
+        Domain::Code,
+        Domain::Academic,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Domain::General => "General",
+            Domain::News => "News",
+            Domain::Fiction => "Fiction",
+            Domain::Code => "Code",
+            Domain::Academic => "Academic",
+        }
+    }
+
+    /// Appended to the model name in the reference's file name. General
+    /// references keep the bare name they had before domains existed.
+    fn file_suffix(self) -> &'static str {
+        match self {
+            Domain::General => "",
+            Domain::News => ".news",
+            Domain::Fiction => ".fiction",
+            Domain::Code => ".code",
+            Domain::Academic => ".academic",
+        }
+    }
+}
+
+/// Perplexities of known-human reference texts of one domain, scored with
+/// one model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferenceStats {
+    pub model: String,
+    #[serde(default)]
+    pub domain: Domain,
+    /// Sorted ascending.
+    pub perplexities: Vec<f32>,
+}
+
+impl ReferenceStats {
+    pub fn new(model: &str, domain: Domain, mut perplexities: Vec<f32>) -> Self {
+        perplexities.retain(|p| p.is_finite() && *p > 0.0);
+        perplexities.sort_by(|a, b| a.total_cmp(b));
+        Self {
+            model: model.to_string(),
+            domain,
+            perplexities,
+        }
+    }
+
+    /// Percentage of reference texts with a perplexity at or below `ppl`.
+    pub fn percentile(&self, ppl: f32) -> Option<f32> {
+        if self.perplexities.is_empty() {
+            return None;
+        }
+        let below = self.perplexities.partition_point(|&p| p <= ppl);
+        Some(below as f32 / self.perplexities.len() as f32 * 100.0)
+    }
+
+    pub fn median(&self) -> Option<f32> {
+        self.perplexities.get(self.perplexities.len() / 2).copied()
+    }
+
+    /// How many standard deviations `ppl` lies above (positive) or below
+    /// the reference texts, on a log scale since perplexities are skewed.
+    /// `None` with fewer than two reference texts or no spread among them.
+    pub fn deviation(&self, ppl: f32) -> Option<f32> {
+        if self.perplexities.len() < 2 || !(ppl.is_finite() && ppl > 0.0) {
+            return None;
+        }
+        let logs: Vec<f32> = self.perplexities.iter().map(|p| p.ln()).collect();
+        let mean = logs.iter().sum::<f32>() / logs.len() as f32;
+        let variance =
+            logs.iter().map(|l| (l - mean).powi(2)).sum::<f32>() / (logs.len() - 1) as f32;
+        let std_dev = variance.sqrt();
+        (std_dev > 0.0).then(|| (ppl.ln() - mean) / std_dev)
+    }
+
+    fn dir() -> PathBuf {
+        let home = env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("."));
+        home.join(REFERENCE_DIR_NAME)
+    }
+
+    fn file_path(model: &str, domain: Domain) -> PathBuf {
+        Self::dir().join(format!("{}{}.json", model, domain.file_suffix()))
+    }
+
+    /// Loads the stored reference of a domain for a model, if one has been
+    /// built.
+    pub fn load(model: &str, domain: Domain) -> Option<Self> {
+        let content = fs::read_to_string(Self::file_path(model, domain)).ok()?;
+        match serde_json::from_str::<Self>(&content) {
+            Ok(stats) => Some(Self { domain, ..stats }),
+            Err(e) => {
+                log::warn!("Failed to parse reference for {}: {}", model, e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(Self::dir())?;
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(Self::file_path(&self.model, self.domain), content)?;
+        Ok(())
+    }
+
+    /// Reads a reference file (e.g. one shared by a colleague) and stores it
+    /// as the reference of `domain` for `model`.
+    pub fn import(
+        path: &Path,
+        model: &str,
+        domain: Domain,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let imported: ReferenceStats = serde_json::from_str(&content)?;
+        let stats = Self::new(model, domain, imported.perplexities);
+        stats.save()?;
+        Ok(stats)
+    }
+}
+
+/// Splits corpus texts into roughly equal segments along paragraph breaks.
+pub fn split_corpus(texts: &[String]) -> Vec<String> {
+    let mut segments = Vec::new();
+    for text in texts {
+        let first = segments.len();
+        let mut current = String::new();
+        for paragraph in text.split("\n\n") {
+            let paragraph = paragraph.trim();
+            if paragraph.is_empty() {
+                continue;
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.push_str(paragraph);
+            if current.len() >= SEGMENT_TARGET_CHARS {
+                segments.push(std::mem::take(&mut current));
+            }
+        }
+        // Keep a short tail only if it's the whole text; otherwise it would
+        // skew the distribution with a tiny, noisy sample.
+        if !current.is_empty()
+            && (segments.len() == first || current.len() >= SEGMENT_TARGET_CHARS / 2)
+        {
+            segments.push(current);
+        }
+    }
+    segments
+}
@@ -0,0 +1,195 @@
+/// The opening the model continues from is a fifth of the text, within
+/// these bounds in tokens.
+const MIN_OPENING_TOKENS: usize = 8;
+const MAX_OPENING_TOKENS: usize = 64;
+/// Longest regenerated continuation; greedy decoding goes one token at a
+/// time, so long documents are only compared on their first stretch.
+pub const MAX_REGENERATED_TOKENS: usize = 512;
+/// Fewest tokens a text needs for the opening and something to compare.
+pub const MIN_TEXT_TOKENS: usize = 2 * MIN_OPENING_TOKENS;
+/// Similarity above which the regeneration is considered to retell the
+/// original.
+pub const SIMILAR_THRESHOLD: f32 = 0.9;
+
+/// Comparison of a document with the model's own greedy continuation of
+/// its opening.
+#[derive(Clone, Debug)]
+pub struct RegenerationStats {
+    /// Tokens of the original the model was given, BOS included.
+    pub opening_tokens: usize,
+    /// The opening as text.
+    pub opening: String,
+    /// What the model wrote after the opening.
+    pub regenerated: String,
+    /// Cosine similarity between the mean hidden states of the original
+    /// continuation and the regenerated one, each read after the opening.
+    pub similarity: f32,
+}
+
+impl RegenerationStats {
+    /// The model, left to itself, writes something close to the document.
+    /// Together with a low perplexity, a strong sign of machine authorship.
+    pub fn is_similar(&self) -> bool {
+        self.similarity >= SIMILAR_THRESHOLD
+    }
+}
+
+/// Length of the opening for a text of `total` tokens.
+pub fn opening_len(total: usize) -> usize {
+    (total / 5).clamp(MIN_OPENING_TOKENS, MAX_OPENING_TOKENS)
+}
+
+/// Element-wise mean of equally long vectors.
+pub fn mean_pool<'a>(rows: impl IntoIterator<Item = &'a [f32]>) -> Vec<f32> {
+    let mut sum: Vec<f32> = Vec::new();
+    let mut count = 0;
+    for row in rows {
+        if sum.is_empty() {
+            sum = vec![0.0; row.len()];
+        }
+        for (s, &v) in sum.iter_mut().zip(row) {
+            *s += v;
+        }
+        count += 1;
+    }
+    for s in &mut sum {
+        *s /= count.max(1) as f32;
+    }
+    sum
+}
+
+/// Cosine similarity in [-1, 1]; zero when either vector is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Longest text, in words, diffed against its regeneration; the diff takes
+/// time quadratic in the length.
+const MAX_DIFF_WORDS: usize = 2000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffKind {
+    /// In both the text and the regeneration.
+    Same,
+    /// Only in the actual text.
+    Original,
+    /// Only in the regeneration.
+    Generated,
+}
+
+/// A run of words of one kind, with their trailing whitespace.
+#[derive(Clone, Debug)]
+pub struct DiffSpan {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+/// Word-level diff of the rest of a text against the model's greedy
+/// continuation from the same point.
+#[derive(Clone, Debug)]
+pub struct RegenerationDiff {
+    /// Token the continuation starts at.
+    pub start_token: usize,
+    /// The text before that point, which both share.
+    pub prefix: String,
+    pub spans: Vec<DiffSpan>,
+    /// Words of the actual continuation that the regeneration matched.
+    pub matched_words: usize,
+    pub original_words: usize,
+}
+
+impl RegenerationDiff {
+    pub fn new(start_token: usize, prefix: String, original: &str, generated: &str) -> Self {
+        let mut original = words(original);
+        original.truncate(MAX_DIFF_WORDS);
+        let mut generated = words(generated);
+        generated.truncate(MAX_DIFF_WORDS);
+        let (spans, matched_words) = diff_words(&original, &generated);
+        Self {
+            start_token,
+            prefix,
+            spans,
+            matched_words,
+            original_words: original.iter().filter(|w| !w.trim().is_empty()).count(),
+        }
+    }
+
+    /// Fraction of the actual continuation's words the model reproduced.
+    pub fn matched_fraction(&self) -> f32 {
+        self.matched_words as f32 / self.original_words.max(1) as f32
+    }
+}
+
+/// Words with their trailing whitespace, so joining them restores the text.
+/// Leading whitespace is a word of its own.
+fn words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut in_space = false;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            in_space = true;
+        } else if in_space {
+            words.push(&text[start..i]);
+            start = i;
+            in_space = false;
+        }
+    }
+    if start < text.len() {
+        words.push(&text[start..]);
+    }
+    words
+}
+
+/// Longest-common-subsequence diff, comparing words without their
+/// whitespace. Consecutive words of the same kind are merged into a span.
+/// Also returns the number of matched words.
+fn diff_words(original: &[&str], generated: &[&str]) -> (Vec<DiffSpan>, usize) {
+    let (n, m) = (original.len(), generated.len());
+    // lcs[i][j]: common subsequence length of original[i..] and generated[j..].
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if original[i].trim() == generated[j].trim() {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    let mut push = |kind: DiffKind, word: &str| match spans.last_mut() {
+        Some(last) if last.kind == kind => last.text.push_str(word),
+        _ => spans.push(DiffSpan {
+            kind,
+            text: word.to_string(),
+        }),
+    };
+    let (mut i, mut j) = (0, 0);
+    let mut matched = 0;
+    while i < n || j < m {
+        if i < n && j < m && original[i].trim() == generated[j].trim() {
+            push(DiffKind::Same, original[i]);
+            if !original[i].trim().is_empty() {
+                matched += 1;
+            }
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            push(DiffKind::Generated, generated[j]);
+            j += 1;
+        } else {
+            push(DiffKind::Original, original[i]);
+            i += 1;
+        }
+    }
+    (spans, matched)
+}
@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// Memory in use, sampled by the worker once its inference context exists.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryUsage {
+    /// Resident memory of this process, in bytes.
+    pub process_ram: u64,
+    pub system_ram_total: u64,
+    /// GPUs and other offload devices; empty on CPU-only builds.
+    pub devices: Vec<DeviceMemory>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceMemory {
+    pub name: String,
+    /// Bytes in use on the device, by any process.
+    pub used: u64,
+    pub total: u64,
+}
+
+/// Reads this process's RAM usage and pairs it with the device readings.
+pub fn sample_memory(devices: Vec<DeviceMemory>) -> MemoryUsage {
+    let mut system = System::new();
+    system.refresh_memory();
+    let process_ram = sysinfo::get_current_pid()
+        .ok()
+        .and_then(|pid| {
+            system.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&[pid]),
+                true,
+                ProcessRefreshKind::nothing().with_memory(),
+            );
+            system.process(pid).map(|p| p.memory())
+        })
+        .unwrap_or(0);
+    MemoryUsage {
+        process_ram,
+        system_ram_total: system.total_memory(),
+        devices,
+    }
+}
+
+/// Formats a byte count as GiB (or MiB below one GiB).
+pub fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    let mib = bytes as f64 / MIB;
+    if mib >= 1024.0 {
+        format!("{:.1} GiB", mib / 1024.0)
+    } else {
+        format!("{:.0} MiB", mib)
+    }
+}
+
+/// Minimum time between resource samples. CPU usage is a delta between two
+/// refreshes, so sampling faster than this just yields noise.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// One point of the resource monitor.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ResourceSample {
+    /// Seconds since the analysis started.
+    pub elapsed: f32,
+    /// Process CPU usage in cores (2.0 means two cores fully busy).
+    pub cpu_cores: f32,
+    /// Resident memory of this process, in bytes.
+    pub ram: u64,
+    pub tokens_per_sec: f32,
+}
+
+/// Samples this process's CPU, RAM and decode throughput during an analysis.
+pub struct ResourceMonitor {
+    system: System,
+    pid: Option<Pid>,
+    start: Instant,
+    last: (Instant, usize),
+}
+
+impl Default for ResourceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceMonitor {
+    pub fn new() -> Self {
+        let mut monitor = Self {
+            system: System::new(),
+            pid: sysinfo::get_current_pid().ok(),
+            start: Instant::now(),
+            last: (Instant::now(), 0),
+        };
+        // Prime the CPU counters so the first real sample has a baseline.
+        monitor.refresh();
+        monitor
+    }
+
+    fn refresh(&mut self) {
+        if let Some(pid) = self.pid {
+            self.system.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&[pid]),
+                true,
+                ProcessRefreshKind::nothing().with_cpu().with_memory(),
+            );
+        }
+    }
+
+    /// Takes a sample once [`SAMPLE_INTERVAL`] has passed since the last
+    /// one; `processed` is the number of tokens decoded so far.
+    pub fn sample(&mut self, processed: usize) -> Option<ResourceSample> {
+        let (last_time, last_processed) = self.last;
+        let dt = last_time.elapsed();
+        if dt < SAMPLE_INTERVAL {
+            return None;
+        }
+        self.refresh();
+        self.last = (Instant::now(), processed);
+        let process = self.pid.and_then(|pid| self.system.process(pid));
+        Some(ResourceSample {
+            elapsed: self.start.elapsed().as_secs_f32(),
+            cpu_cores: process.map_or(0.0, |p| p.cpu_usage() / 100.0),
+            ram: process.map_or(0, |p| p.memory()),
+            tokens_per_sec: processed.saturating_sub(last_processed) as f32 / dt.as_secs_f32(),
+        })
+    }
+}
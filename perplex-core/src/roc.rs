@@ -0,0 +1,155 @@
+/// One operating point: texts scoring at or past `threshold` are classified
+/// as positive.
+#[derive(Clone, Copy, Debug)]
+pub struct RocPoint {
+    pub threshold: f32,
+    pub true_positive_rate: f32,
+    pub false_positive_rate: f32,
+}
+
+impl RocPoint {
+    /// Youden's J: how far the point lies above the diagonal of a random
+    /// classifier.
+    pub fn youden(&self) -> f32 {
+        self.true_positive_rate - self.false_positive_rate
+    }
+}
+
+/// Receiver operating characteristic of a score used to tell two labelled
+/// groups apart, such as generated and human texts by their perplexity.
+#[derive(Clone, Debug)]
+pub struct RocCurve {
+    /// From the strictest threshold, which classifies nothing as positive,
+    /// to the loosest, which classifies everything.
+    pub points: Vec<RocPoint>,
+    /// Area under the curve: the chance that a random positive scores past
+    /// a random negative. 0.5 is no better than guessing.
+    pub auc: f32,
+    pub positives: usize,
+    pub negatives: usize,
+    /// Positives score below the threshold rather than above it, as
+    /// generated text does with perplexity.
+    pub lower_is_positive: bool,
+    scores: Vec<(f32, bool)>,
+}
+
+impl RocCurve {
+    /// `None` unless both groups have a finite score.
+    pub fn new(positives: &[f32], negatives: &[f32], lower_is_positive: bool) -> Option<Self> {
+        let mut scores: Vec<(f32, bool)> = positives
+            .iter()
+            .map(|&s| (s, true))
+            .chain(negatives.iter().map(|&s| (s, false)))
+            .filter(|(s, _)| s.is_finite())
+            .collect();
+        let total_positives = scores.iter().filter(|(_, p)| *p).count();
+        let total_negatives = scores.len() - total_positives;
+        if total_positives == 0 || total_negatives == 0 {
+            return None;
+        }
+        // Most positive-looking first, so lowering the bar adds texts in order.
+        scores.sort_by(|a, b| {
+            if lower_is_positive {
+                a.0.total_cmp(&b.0)
+            } else {
+                b.0.total_cmp(&a.0)
+            }
+        });
+
+        let mut points = vec![RocPoint {
+            threshold: if lower_is_positive {
+                f32::NEG_INFINITY
+            } else {
+                f32::INFINITY
+            },
+            true_positive_rate: 0.0,
+            false_positive_rate: 0.0,
+        }];
+        let (mut true_positives, mut false_positives) = (0, 0);
+        for (i, &(score, positive)) in scores.iter().enumerate() {
+            if positive {
+                true_positives += 1;
+            } else {
+                false_positives += 1;
+            }
+            // Tied scores are classified together.
+            if scores.get(i + 1).is_some_and(|next| next.0 == score) {
+                continue;
+            }
+            points.push(RocPoint {
+                threshold: score,
+                true_positive_rate: true_positives as f32 / total_positives as f32,
+                false_positive_rate: false_positives as f32 / total_negatives as f32,
+            });
+        }
+        let auc = points
+            .windows(2)
+            .map(|w| {
+                (w[1].false_positive_rate - w[0].false_positive_rate)
+                    * (w[1].true_positive_rate + w[0].true_positive_rate)
+                    / 2.0
+            })
+            .sum();
+
+        Some(Self {
+            points,
+            auc,
+            positives: total_positives,
+            negatives: total_negatives,
+            lower_is_positive,
+            scores,
+        })
+    }
+
+    /// The threshold that best separates the groups by Youden's J.
+    pub fn best(&self) -> RocPoint {
+        self.points
+            .iter()
+            .skip(1)
+            .copied()
+            .max_by(|a, b| a.youden().total_cmp(&b.youden()))
+            .unwrap_or(self.points[0])
+    }
+
+    /// Whether `score` is classified as positive at `threshold`.
+    pub fn is_positive(&self, score: f32, threshold: f32) -> bool {
+        classify(score, threshold, self.lower_is_positive)
+    }
+
+    /// The operating point of an arbitrary threshold.
+    pub fn at(&self, threshold: f32) -> RocPoint {
+        let (mut true_positives, mut false_positives) = (0, 0);
+        for &(score, positive) in &self.scores {
+            if self.is_positive(score, threshold) {
+                if positive {
+                    true_positives += 1;
+                } else {
+                    false_positives += 1;
+                }
+            }
+        }
+        RocPoint {
+            threshold,
+            true_positive_rate: true_positives as f32 / self.positives as f32,
+            false_positive_rate: false_positives as f32 / self.negatives as f32,
+        }
+    }
+
+    /// Lowest and highest score of either group.
+    pub fn score_range(&self) -> (f32, f32) {
+        self.scores
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &(s, _)| {
+                (lo.min(s), hi.max(s))
+            })
+    }
+}
+
+/// Whether `score` falls on the positive side of `threshold`.
+pub fn classify(score: f32, threshold: f32, lower_is_positive: bool) -> bool {
+    if lower_is_positive {
+        score <= threshold
+    } else {
+        score >= threshold
+    }
+}
@@ -0,0 +1,157 @@
+use crate::checkpoint::TokenMetrics;
+
+/// Metrics of the tokens of an analysis as its predictions come in. The
+/// logits at position `i` predict token `i + 1`, so token 0 is never scored
+/// and the last position scores nothing; this keeps that shift in one place.
+/// Decoding modes record predictions by the position they were made at, in
+/// order, and read metrics back by the token they score.
+pub struct Scores {
+    total: usize,
+    /// `metrics[i]` scores token `i + 1`.
+    metrics: Vec<TokenMetrics>,
+    /// Kept apart from the checkpointed metrics; resumed tokens have none.
+    curves: Vec<Vec<f32>>,
+    /// First token in context of the prediction at each position.
+    context_starts: Vec<usize>,
+}
+
+impl Scores {
+    /// Room for the predictions over `total` tokens.
+    pub fn new(total: usize) -> Self {
+        Self {
+            total,
+            metrics: Vec::with_capacity(total),
+            curves: Vec::with_capacity(total),
+            context_starts: Vec::with_capacity(total),
+        }
+    }
+
+    /// Picks up the metrics a checkpoint saved, which saw the whole text
+    /// from the start.
+    pub fn resumed(total: usize, metrics: Vec<TokenMetrics>) -> Self {
+        let predicted = metrics.len();
+        let mut scores = Self::new(total);
+        scores.metrics.extend(metrics);
+        scores.curves.resize(predicted, Vec::new());
+        scores.context_starts.resize(predicted, 0);
+        scores
+    }
+
+    /// Positions whose prediction has been recorded; the next one to
+    /// record.
+    pub fn predicted(&self) -> usize {
+        self.metrics.len()
+    }
+
+    /// Token scored by the prediction at `pos`, `None` for the last
+    /// position.
+    pub fn target(&self, pos: usize) -> Option<usize> {
+        (pos + 1 < self.total).then_some(pos + 1)
+    }
+
+    /// Records the prediction at `pos` of the token after it, made with
+    /// the tokens from `context_start` on in context.
+    pub fn record(
+        &mut self,
+        pos: usize,
+        context_start: usize,
+        metrics: TokenMetrics,
+        curve: Vec<f32>,
+    ) {
+        debug_assert_eq!(
+            pos,
+            self.predicted(),
+            "predictions must be recorded in order"
+        );
+        self.metrics.push(metrics);
+        self.curves.push(curve);
+        self.context_starts.push(context_start);
+    }
+
+    /// Records that the prediction at `pos` isn't scored: the token after
+    /// it belongs to the prompt, or there is none.
+    pub fn skip(&mut self, pos: usize, context_start: usize) {
+        self.record(pos, context_start, (1, 0.0, Vec::new()), Vec::new());
+    }
+
+    /// Metrics of token `index`; token 0 and tokens not yet reached have
+    /// none.
+    pub fn of(&self, index: usize) -> Option<&TokenMetrics> {
+        self.metrics.get(index.checked_sub(1)?)
+    }
+
+    /// Metrics by predicting position, as checkpoints store them.
+    pub fn by_position(&self) -> &[TokenMetrics] {
+        &self.metrics
+    }
+
+    /// Metrics, rank curve and context start of token `index`, moving them
+    /// out. Unscored tokens get rank 1 and probability zero.
+    pub fn take(&mut self, index: usize) -> (TokenMetrics, Vec<f32>, usize) {
+        let Some(pos) = index.checked_sub(1) else {
+            return ((1, 0.0, Vec::new()), Vec::new(), 0);
+        };
+        (
+            self.metrics.get_mut(pos).map_or((1, 0.0, Vec::new()), |m| {
+                std::mem::replace(m, (1, 0.0, Vec::new()))
+            }),
+            self.curves
+                .get_mut(pos)
+                .map(std::mem::take)
+                .unwrap_or_default(),
+            self.context_starts.get(pos).copied().unwrap_or(0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(rank: usize) -> TokenMetrics {
+        (rank, 0.5, vec![(rank as i32, 0.5)])
+    }
+
+    #[test]
+    fn last_position_scores_nothing() {
+        let scores = Scores::new(3);
+        assert_eq!(scores.target(0), Some(1));
+        assert_eq!(scores.target(1), Some(2));
+        assert_eq!(scores.target(2), None);
+    }
+
+    #[test]
+    fn first_token_is_never_scored() {
+        let mut scores = Scores::new(2);
+        scores.record(0, 0, metrics(7), vec![1.0]);
+        assert_eq!(scores.of(0), None);
+        assert_eq!(scores.of(1), Some(&metrics(7)));
+    }
+
+    #[test]
+    fn resumed_scores_continue_after_the_checkpoint() {
+        let mut scores = Scores::resumed(4, vec![metrics(1), metrics(2)]);
+        assert_eq!(scores.predicted(), 2);
+
+        scores.record(2, 1, metrics(3), vec![0.25]);
+        assert_eq!(scores.predicted(), 3);
+        assert_eq!(scores.of(1), Some(&metrics(1)));
+        assert_eq!(scores.of(3), Some(&metrics(3)));
+        assert_eq!(scores.by_position().len(), 3);
+
+        // Resumed tokens have no curve and saw the text from the start.
+        assert_eq!(scores.take(2), (metrics(2), Vec::new(), 0));
+        assert_eq!(scores.take(3), (metrics(3), vec![0.25], 1));
+    }
+
+    #[test]
+    fn take_past_the_end_is_unscored() {
+        let mut scores = Scores::new(2);
+        scores.record(0, 0, metrics(4), vec![1.0]);
+        let unscored = ((1, 0.0, Vec::new()), Vec::new(), 0);
+        assert_eq!(scores.take(0), unscored);
+        assert_eq!(scores.take(2), unscored);
+        assert_eq!(scores.take(100), unscored);
+        assert_eq!(scores.take(1), (metrics(4), vec![1.0], 0));
+    }
+}
@@ -0,0 +1,250 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::AnalysisResult;
+
+/// Cap on the operations of one script run, so a runaway loop fails the
+/// metric instead of freezing the window.
+const MAX_OPERATIONS: u64 = 50_000_000;
+
+/// A summary metric written as a Rhai script. The script sees one array per
+/// token property and evaluates to a number, e.g.
+/// `mean(surprisals.filter(|s| s > 10.0))`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomMetric {
+    pub name: String,
+    pub script: String,
+}
+
+/// Arrays in a script's scope, with what they hold, in the order shown in
+/// the settings.
+pub const VARIABLES: [(&str, &str); 7] = [
+    ("ranks", "rank of each token among the predictions, 1 = top"),
+    ("probs", "probability of each token"),
+    ("logprobs", "natural log of each probability"),
+    ("surprisals", "surprisal of each token in bits"),
+    (
+        "entropies",
+        "entropy of each prediction in bits, from the top predictions",
+    ),
+    (
+        "margins",
+        "log-probability gap to the top prediction in nats",
+    ),
+    ("texts", "text of each token"),
+];
+
+/// Variables of a color expression, which is evaluated once per token.
+pub const TOKEN_FIELDS: [(&str, &str); 10] = [
+    ("rank", "rank among the predictions, 1 = top"),
+    ("prob", "probability"),
+    ("logprob", "natural log of the probability"),
+    ("surprisal", "surprisal in bits"),
+    ("entropy", "entropy of the prediction in bits"),
+    (
+        "margin",
+        "log-probability gap to the top prediction in nats",
+    ),
+    (
+        "relative",
+        "surprisal minus the token's unigram surprisal in the text",
+    ),
+    ("repeated", "part of an n-gram seen earlier"),
+    ("position", "0 at the start of the text to 1 at the end"),
+    ("text", "the token's text"),
+];
+
+/// The value of a custom metric for one result, or why the script failed.
+#[derive(Debug, Clone)]
+pub struct MetricValue {
+    pub name: String,
+    pub value: Result<f64, String>,
+}
+
+impl CustomMetric {
+    pub fn check(&self) -> Result<(), String> {
+        check(&self.script)
+    }
+}
+
+/// Compiles a script without running it, to report syntax errors before
+/// it is saved.
+pub fn check(script: &str) -> Result<(), String> {
+    engine()
+        .compile(script)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Runs each metric's script over the scored tokens of `result`.
+pub fn evaluate(metrics: &[CustomMetric], result: &AnalysisResult) -> Vec<MetricValue> {
+    if metrics.is_empty() {
+        return Vec::new();
+    }
+    let engine = engine();
+    let scope = token_scope(result);
+    metrics
+        .iter()
+        .map(|metric| MetricValue {
+            name: metric.name.clone(),
+            value: engine
+                .compile(&metric.script)
+                .map_err(|e| e.to_string())
+                .and_then(|ast| run(&engine, &mut scope.clone(), &ast)),
+        })
+        .collect()
+}
+
+/// Evaluates `expression` for every token of `result`, prompt tokens
+/// included, so the values line up with `result.tokens`. Used to color
+/// tokens by a user formula such as `clamp(surprisal / 12)`. All tokens
+/// share one `MAX_OPERATIONS` budget, so a slow expression fails once
+/// rather than costing the full cap per token.
+pub fn token_values(expression: &str, result: &AnalysisResult) -> Result<Vec<f32>, String> {
+    let mut engine = engine();
+    let operations = Arc::new(AtomicU64::new(0));
+    let counter = Arc::clone(&operations);
+    engine.on_progress(move |_| {
+        (counter.fetch_add(1, Ordering::Relaxed) >= MAX_OPERATIONS).then_some(Dynamic::UNIT)
+    });
+    let ast = engine.compile(expression).map_err(|e| e.to_string())?;
+    let last = result.tokens.len().saturating_sub(1).max(1) as f64;
+    let mut scope = Scope::new();
+    result
+        .tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| {
+            scope.clear();
+            scope.push("rank", token.rank as i64);
+            scope.push("prob", token.probability as f64);
+            scope.push("logprob", log_prob(token.probability));
+            scope.push("surprisal", token.surprisal() as f64);
+            scope.push("entropy", prediction_entropy(&token.top_predictions));
+            scope.push("margin", token.top1_margin as f64);
+            scope.push("relative", token.relative_surprisal() as f64);
+            scope.push("repeated", token.repeated);
+            scope.push("position", i as f64 / last);
+            scope.push("text", token.text.clone());
+            run(&engine, &mut scope, &ast)
+                .map(|value| value as f32)
+                .map_err(|e| {
+                    if operations.load(Ordering::Relaxed) >= MAX_OPERATIONS {
+                        format!(
+                            "The expression ran out of its {} operations by token {}",
+                            MAX_OPERATIONS, i
+                        )
+                    } else {
+                        format!("Token {} ({:?}): {}", i, token.text, e)
+                    }
+                })
+        })
+        .collect()
+}
+
+fn run(engine: &Engine, scope: &mut Scope, ast: &AST) -> Result<f64, String> {
+    let value: Dynamic = engine
+        .eval_ast_with_scope(scope, ast)
+        .map_err(|e| e.to_string())?;
+    number(&value).ok_or_else(|| {
+        format!(
+            "The script returned {} instead of a number",
+            value.type_name()
+        )
+    })
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.register_fn("clamp", |x: f64| x.clamp(0.0, 1.0));
+    engine.register_fn("clamp", |x: i64| (x as f64).clamp(0.0, 1.0));
+    engine.register_fn("clamp", |x: f64, low: f64, high: f64| x.max(low).min(high));
+    engine.register_fn("sum", |values: Array| numbers(&values).sum::<f64>());
+    engine.register_fn("mean", |values: Array| {
+        let values: Vec<f64> = numbers(&values).collect();
+        values.iter().sum::<f64>() / values.len() as f64
+    });
+    engine.register_fn("median", |values: Array| {
+        let mut values: Vec<f64> = numbers(&values).collect();
+        values.sort_by(f64::total_cmp);
+        match values.len() {
+            0 => f64::NAN,
+            n if n % 2 == 0 => (values[n / 2 - 1] + values[n / 2]) / 2.0,
+            n => values[n / 2],
+        }
+    });
+    engine.register_fn("stdev", |values: Array| {
+        let values: Vec<f64> = numbers(&values).collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance =
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() as f64 - 1.0);
+        variance.sqrt()
+    });
+    engine
+}
+
+fn token_scope(result: &AnalysisResult) -> Scope<'static> {
+    let tokens = result.scored_tokens();
+    let array = |f: &dyn Fn(usize) -> Dynamic| -> Array { (0..tokens.len()).map(f).collect() };
+    let mut scope = Scope::new();
+    scope.push(
+        "ranks",
+        array(&|i| Dynamic::from_int(tokens[i].rank as i64)),
+    );
+    scope.push(
+        "probs",
+        array(&|i| Dynamic::from_float(tokens[i].probability as f64)),
+    );
+    scope.push(
+        "logprobs",
+        array(&|i| Dynamic::from_float(log_prob(tokens[i].probability))),
+    );
+    scope.push(
+        "surprisals",
+        array(&|i| Dynamic::from_float(tokens[i].surprisal() as f64)),
+    );
+    scope.push(
+        "entropies",
+        array(&|i| Dynamic::from_float(prediction_entropy(&tokens[i].top_predictions))),
+    );
+    scope.push(
+        "margins",
+        array(&|i| Dynamic::from_float(tokens[i].top1_margin as f64)),
+    );
+    scope.push("texts", array(&|i| Dynamic::from(tokens[i].text.clone())));
+    scope
+}
+
+fn log_prob(probability: f32) -> f64 {
+    (probability.max(f32::MIN_POSITIVE) as f64).ln()
+}
+
+/// Entropy in bits of the top predictions, with the probability they leave
+/// over counted as one more outcome. A lower bound of the full entropy.
+fn prediction_entropy(predictions: &[(i32, f32)]) -> f64 {
+    let rest = (1.0 - predictions.iter().map(|(_, p)| *p as f64).sum::<f64>()).max(0.0);
+    predictions
+        .iter()
+        .map(|(_, p)| *p as f64)
+        .chain(std::iter::once(rest))
+        .filter(|&p| p > 0.0)
+        .map(|p| -p * p.log2())
+        .sum()
+}
+
+fn number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|i| i as f64))
+}
+
+/// The numbers in an array, skipping anything else.
+fn numbers(values: &Array) -> impl Iterator<Item = f64> + '_ {
+    values.iter().filter_map(number)
+}
@@ -0,0 +1,76 @@
+/// Sentence ends: terminal punctuation, optionally followed by closing
+/// quotes or brackets.
+const TERMINATORS: [char; 4] = ['.', '!', '?', '…'];
+const CLOSERS: [char; 6] = ['"', '\'', ')', ']', '”', '’'];
+
+/// Perplexity of a text next to that of the same sentences in random order.
+/// Coherent prose relies on its sentence order, so shuffling it makes it
+/// noticeably more perplexing; a gap near zero means the model gets little
+/// from the order, as with lists or incoherent text.
+#[derive(Clone, Debug)]
+pub struct ShuffleStats {
+    pub original: f32,
+    pub shuffled: f32,
+    pub sentences: usize,
+}
+
+impl ShuffleStats {
+    /// How many times more perplexing the shuffled text is.
+    pub fn ratio(&self) -> f32 {
+        self.shuffled / self.original
+    }
+}
+
+/// Splits `text` into sentences, each with its trailing whitespace, so
+/// joining them restores the text.
+pub fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut ended = false;
+    let mut in_space = false;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            in_space = ended;
+        } else if in_space {
+            sentences.push(&text[start..i]);
+            start = i;
+            ended = false;
+            in_space = false;
+        } else if TERMINATORS.contains(&c) {
+            ended = true;
+        } else if !CLOSERS.contains(&c) {
+            ended = false;
+        }
+    }
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+    sentences
+}
+
+/// The sentences of `text` in a random order different from the original.
+/// `None` when the text has fewer than two distinct sentences.
+pub fn shuffle_sentences(text: &str) -> Option<(String, usize)> {
+    let mut parts: Vec<String> = sentences(text)
+        .into_iter()
+        .map(|s| {
+            // Every sentence ends in whitespace once moved, so the last one
+            // doesn't run into the next.
+            let mut s = s.to_string();
+            if !s.ends_with(char::is_whitespace) {
+                s.push(' ');
+            }
+            s
+        })
+        .collect();
+    if parts.iter().skip(1).all(|p| p == &parts[0]) {
+        return None;
+    }
+    let original = parts.clone();
+    let mut rng = fastrand::Rng::new();
+    while parts == original {
+        rng.shuffle(&mut parts);
+    }
+    let shuffled = parts.concat();
+    Some((shuffled.trim_end().to_string(), parts.len()))
+}
@@ -0,0 +1,124 @@
+use crate::analysis::AnalysisResult;
+
+/// p-value below which a perplexity difference is reported as significant.
+pub const SIGNIFICANCE_LEVEL: f32 = 0.05;
+/// Permutations drawn for a test, fewer for long texts so the test stays
+/// within [`MAX_WORK`] token visits.
+const MAX_PERMUTATIONS: usize = 10_000;
+const MIN_PERMUTATIONS: usize = 1_000;
+const MAX_WORK: usize = 20_000_000;
+/// Tokens each result needs before a test is worth running.
+const MIN_TOKENS: usize = 8;
+
+/// Permutation test of the difference in mean surprisal between two
+/// results. Same-text results from models with the same tokenizer are
+/// compared token by token (paired, by random sign flips of the per-token
+/// differences); otherwise the two sets of surprisals are pooled and
+/// randomly relabelled.
+#[derive(Clone, Debug)]
+pub struct SignificanceTest {
+    pub paired: bool,
+    pub perplexities: (f32, f32),
+    /// Scored tokens of each result.
+    pub tokens: (usize, usize),
+    /// Mean surprisal of the first result minus the second, in nats per
+    /// token; the log of the perplexity ratio.
+    pub difference: f32,
+    /// Two-sided: the chance of a difference at least this large if both
+    /// results came from the same distribution.
+    pub p_value: f32,
+    pub permutations: usize,
+}
+
+impl SignificanceTest {
+    /// `None` when either result has too few scored tokens.
+    pub fn new(first: &AnalysisResult, second: &AnalysisResult) -> Option<Self> {
+        let a = surprisals(first);
+        let b = surprisals(second);
+        if a.len() < MIN_TOKENS || b.len() < MIN_TOKENS {
+            return None;
+        }
+        let paired = first.prompt_tokens == second.prompt_tokens
+            && first.tokens.len() == second.tokens.len()
+            && first
+                .tokens
+                .iter()
+                .zip(&second.tokens)
+                .all(|(x, y)| x.text == y.text);
+        let permutations =
+            (MAX_WORK / (a.len() + b.len())).clamp(MIN_PERMUTATIONS, MAX_PERMUTATIONS);
+        let (difference, p_value) = if paired {
+            let differences: Vec<f32> = a.iter().zip(&b).map(|(x, y)| x - y).collect();
+            sign_flip_test(&differences, permutations)
+        } else {
+            relabel_test(&a, &b, permutations)
+        };
+        Some(Self {
+            paired,
+            perplexities: (first.perplexity(), second.perplexity()),
+            tokens: (a.len(), b.len()),
+            difference,
+            p_value,
+            permutations,
+        })
+    }
+
+    pub fn is_significant(&self) -> bool {
+        self.p_value < SIGNIFICANCE_LEVEL
+    }
+}
+
+/// Surprisal in nats of each token counted in the perplexity.
+fn surprisals(result: &AnalysisResult) -> Vec<f32> {
+    result.tokens[result.first_scored()..]
+        .iter()
+        .map(|t| -t.probability.max(f32::MIN_POSITIVE).ln())
+        .collect()
+}
+
+/// Observed mean difference and the share of random sign assignments whose
+/// mean is at least as far from zero.
+fn sign_flip_test(differences: &[f32], permutations: usize) -> (f32, f32) {
+    let n = differences.len() as f32;
+    let observed = differences.iter().sum::<f32>() / n;
+    let mut rng = fastrand::Rng::new();
+    let mut extreme = 0;
+    for _ in 0..permutations {
+        let mut sum = 0.0;
+        for chunk in differences.chunks(64) {
+            // One random bit per difference decides its sign.
+            let bits = rng.u64(..);
+            for (i, d) in chunk.iter().enumerate() {
+                sum += if bits >> i & 1 == 1 { *d } else { -d };
+            }
+        }
+        if (sum / n).abs() >= observed.abs() {
+            extreme += 1;
+        }
+    }
+    (observed, p_value(extreme, permutations))
+}
+
+/// Observed difference of means and the share of random splits of the
+/// pooled values whose difference is at least as large.
+fn relabel_test(a: &[f32], b: &[f32], permutations: usize) -> (f32, f32) {
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+    let observed = mean(a) - mean(b);
+    let mut pooled: Vec<f32> = a.iter().chain(b).copied().collect();
+    let mut rng = fastrand::Rng::new();
+    let mut extreme = 0;
+    for _ in 0..permutations {
+        rng.shuffle(&mut pooled);
+        let (x, y) = pooled.split_at(a.len());
+        if (mean(x) - mean(y)).abs() >= observed.abs() {
+            extreme += 1;
+        }
+    }
+    (observed, p_value(extreme, permutations))
+}
+
+/// Counts the observed assignment among the permutations, so the p-value
+/// is never zero.
+fn p_value(extreme: usize, permutations: usize) -> f32 {
+    (extreme + 1) as f32 / (permutations + 1) as f32
+}
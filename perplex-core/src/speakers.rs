@@ -0,0 +1,94 @@
+use regex::Regex;
+
+use crate::analysis::AnalyzedToken;
+
+/// Perplexity and surprisal of the tokens attributed to one speaker.
+#[derive(Clone, Debug)]
+pub struct SpeakerStats {
+    pub name: String,
+    pub tokens: usize,
+    pub perplexity: f32,
+    /// Mean and standard deviation of the per-token surprisal, in bits.
+    pub mean_surprisal: f32,
+    pub surprisal_std: f32,
+}
+
+/// Attributes scored tokens to speakers using `pattern`, matched against the
+/// start of each line. The first capture group is the speaker name (the whole
+/// match without its trailing colon when there is no group). Lines without a
+/// label continue the previous speaker; the label tokens themselves and any
+/// text before the first label are left out. Speakers appear in order of
+/// first appearance.
+pub fn speaker_breakdown(tokens: &[AnalyzedToken], pattern: &Regex) -> Vec<SpeakerStats> {
+    // (name, surprisals) per speaker.
+    let mut speakers: Vec<(String, Vec<f32>)> = Vec::new();
+    let mut current: Option<usize> = None;
+
+    for line in lines(tokens) {
+        let text: String = line.iter().map(|t| t.document_text()).collect();
+        let mut label_end = 0;
+        if let Some(caps) = pattern.captures(&text) {
+            let whole = caps.get(0).expect("group 0 is always present");
+            let name = caps
+                .get(1)
+                .map_or(whole.as_str().trim_end_matches(':'), |m| m.as_str())
+                .trim();
+            if !name.is_empty() {
+                label_end = whole.end();
+                current = Some(
+                    speakers
+                        .iter()
+                        .position(|(n, _)| n == name)
+                        .unwrap_or_else(|| {
+                            speakers.push((name.to_string(), Vec::new()));
+                            speakers.len() - 1
+                        }),
+                );
+            }
+        }
+
+        let Some(speaker) = current else {
+            continue;
+        };
+        let mut offset = 0;
+        for token in &line {
+            offset += token.document_text().len();
+            if offset <= label_end {
+                continue;
+            }
+            speakers[speaker].1.push(token.surprisal());
+        }
+    }
+
+    speakers
+        .into_iter()
+        .filter(|(_, s)| !s.is_empty())
+        .map(|(name, surprisals)| {
+            let n = surprisals.len() as f32;
+            let mean = surprisals.iter().sum::<f32>() / n;
+            let variance = surprisals.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / n;
+            SpeakerStats {
+                name,
+                tokens: surprisals.len(),
+                // Surprisal is in bits, so perplexity is 2^mean.
+                perplexity: mean.exp2(),
+                mean_surprisal: mean,
+                surprisal_std: variance.sqrt(),
+            }
+        })
+        .collect()
+}
+
+/// Groups the scored tokens into lines, each ending with the token that
+/// contains a newline. The first token (BOS) is unscored and left out so
+/// it can't hide a label on the first line.
+fn lines(tokens: &[AnalyzedToken]) -> Vec<Vec<&AnalyzedToken>> {
+    let mut lines = vec![Vec::new()];
+    for token in tokens.iter().skip(1) {
+        lines.last_mut().unwrap().push(token);
+        if token.text.contains('\n') {
+            lines.push(Vec::new());
+        }
+    }
+    lines
+}
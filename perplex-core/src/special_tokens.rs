@@ -0,0 +1,70 @@
+use crate::analysis::AnalysisResult;
+
+/// Positions listed per special token; the count covers the rest.
+const MAX_POSITIONS: usize = 10;
+
+/// A special token of the model that the text itself produced, usually a
+/// chat template marker pasted along with it. The model reads it as a
+/// control token, not as the characters it is written with.
+#[derive(Clone, Debug)]
+pub struct SpecialTokenUse {
+    /// Badge label, e.g. `EOS` or `im_end`.
+    pub label: String,
+    /// The characters in the text that became this token.
+    pub text: String,
+    pub count: usize,
+    /// Token index, line and column (both 1-based) of the first
+    /// occurrences, at most [`MAX_POSITIONS`].
+    pub positions: Vec<SpecialTokenPosition>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpecialTokenPosition {
+    pub index: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The special tokens in the analyzed text, past the prompt and the BOS
+/// token added in front of it, in order of first appearance. Lines and
+/// columns are counted in the text rebuilt from the tokens.
+pub fn audit(result: &AnalysisResult) -> Vec<SpecialTokenUse> {
+    let mut uses: Vec<SpecialTokenUse> = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    for (index, token) in result.tokens.iter().enumerate().skip(result.prompt_tokens) {
+        match &token.special {
+            Some(label) if !(index == 0 && label == "BOS") => {
+                let position = SpecialTokenPosition {
+                    index,
+                    line,
+                    column,
+                };
+                match uses.iter_mut().find(|u| *u.text == *token.text) {
+                    Some(existing) => {
+                        existing.count += 1;
+                        if existing.positions.len() < MAX_POSITIONS {
+                            existing.positions.push(position);
+                        }
+                    }
+                    None => uses.push(SpecialTokenUse {
+                        label: label.clone(),
+                        text: token.text.to_string(),
+                        count: 1,
+                        positions: vec![position],
+                    }),
+                }
+            }
+            Some(_) => continue,
+            None => {}
+        }
+        for c in token.text.chars() {
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+    }
+    uses
+}
@@ -0,0 +1,35 @@
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// `{{name}}`, with optional spaces inside the braces.
+fn placeholder() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z0-9_]+)\s*\}\}").unwrap())
+}
+
+/// Names of the placeholders in `template`, in order of first appearance.
+pub fn variables(template: &str) -> Vec<&str> {
+    let mut names: Vec<&str> = Vec::new();
+    for captures in placeholder().captures_iter(template) {
+        let name = captures.get(1).map_or("", |m| m.as_str());
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Replaces each placeholder with its value. Placeholders without a value,
+/// or with an empty one, are left as they are, so a forgotten field shows
+/// up in the prompt rather than silently disappearing.
+pub fn fill(template: &str, values: &BTreeMap<String, String>) -> String {
+    placeholder()
+        .replace_all(template, |captures: &Captures| {
+            match values.get(&captures[1]) {
+                Some(value) if !value.is_empty() => value.clone(),
+                _ => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
@@ -0,0 +1,51 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use llama_cpp_2::token::LlamaToken;
+
+/// Tokenizations kept; the live counter adds one per edit, so only the
+/// latest few are worth keeping.
+const MAX_ENTRIES: usize = 8;
+
+/// Recent tokenizations, without special tokens, shared by a slot's
+/// tokenizer and worker threads: the text the live counter just tokenized
+/// isn't tokenized again when it's analyzed, nor when it's analyzed again
+/// unchanged. On long inputs tokenizing takes a noticeable part of the wait.
+#[derive(Default)]
+pub struct TokenCache {
+    entries: Mutex<VecDeque<(u64, Arc<Vec<LlamaToken>>)>>,
+}
+
+impl TokenCache {
+    /// Key of `text` under the model with `fingerprint`
+    /// ([`crate::cache::model_fingerprint`]).
+    pub fn key(fingerprint: u64, text: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        fingerprint.hash(&mut hasher);
+        text.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn get(&self, key: u64) -> Option<Arc<Vec<LlamaToken>>> {
+        let mut entries = self.entries.lock().ok()?;
+        let index = entries.iter().position(|(k, _)| *k == key)?;
+        // Move to the back so the most recently used entries are kept.
+        let entry = entries.remove(index)?;
+        let tokens = entry.1.clone();
+        entries.push_back(entry);
+        Some(tokens)
+    }
+
+    pub fn insert(&self, key: u64, tokens: Arc<Vec<LlamaToken>>) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        entries.retain(|(k, _)| *k != key);
+        if entries.len() >= MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back((key, tokens));
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use crate::analysis::AnalyzedToken;
+
+/// Most frequent token types listed in the report.
+pub const FREQUENT_LIMIT: usize = 30;
+/// Occurrences needed before a type can be flagged as mispredicted; a
+/// first mention is always hard to predict.
+const MIN_OCCURRENCES: usize = 3;
+/// Ranks above this count as a miss.
+const MISS_RANK: usize = 5;
+/// Fraction of missed occurrences at which a type is flagged.
+const MISS_RATE: f32 = 0.75;
+
+/// How one distinct token fared across all its occurrences.
+#[derive(Clone, Debug)]
+pub struct TokenTypeStats {
+    pub text: String,
+    pub count: usize,
+    pub mean_rank: f32,
+    /// Mean surprisal in bits.
+    pub mean_surprisal: f32,
+    /// Fraction of occurrences ranked below [`MISS_RANK`].
+    pub miss_rate: f32,
+}
+
+impl TokenTypeStats {
+    /// The model keeps failing to predict this token even after seeing it
+    /// several times, which usually means it doesn't know the word.
+    pub fn mispredicted(&self) -> bool {
+        self.count >= MIN_OCCURRENCES && self.miss_rate >= MISS_RATE
+    }
+}
+
+/// Groups scored tokens by id, most frequent type first. The first token
+/// has no prediction and is skipped.
+pub fn token_type_report(tokens: &[AnalyzedToken]) -> Vec<TokenTypeStats> {
+    // id → (text, count, rank sum, surprisal sum, misses)
+    let mut types: HashMap<i32, (&str, usize, usize, f32, usize)> = HashMap::new();
+    for token in tokens.iter().skip(1) {
+        let entry = types.entry(token.id).or_insert((&token.text, 0, 0, 0.0, 0));
+        entry.1 += 1;
+        entry.2 += token.rank;
+        entry.3 += token.surprisal();
+        entry.4 += usize::from(token.rank > MISS_RANK);
+    }
+
+    let mut report: Vec<TokenTypeStats> = types
+        .into_values()
+        .map(|(text, count, ranks, surprisal, misses)| TokenTypeStats {
+            text: text.to_string(),
+            count,
+            mean_rank: ranks as f32 / count as f32,
+            mean_surprisal: surprisal / count as f32,
+            miss_rate: misses as f32 / count as f32,
+        })
+        .collect();
+    report.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.mean_surprisal.total_cmp(&a.mean_surprisal))
+    });
+    report
+}
+
+/// Coarse heuristic class of a token, from its text alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenClass {
+    Word,
+    Number,
+    Punctuation,
+    Whitespace,
+    Code,
+}
+
+impl TokenClass {
+    pub const ALL: [TokenClass; 5] = [
+        TokenClass::Word,
+        TokenClass::Number,
+        TokenClass::Punctuation,
+        TokenClass::Whitespace,
+        TokenClass::Code,
+    ];
+
+    pub fn of(text: &str) -> Self {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            TokenClass::Whitespace
+        } else if trimmed.chars().any(|c| c.is_ascii_digit())
+            && trimmed
+                .chars()
+                .all(|c| c.is_numeric() || matches!(c, '.' | ','))
+        {
+            TokenClass::Number
+        } else if trimmed.chars().any(char::is_alphabetic)
+            && trimmed
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '\'' | '’' | '-'))
+        {
+            TokenClass::Word
+        } else if trimmed.chars().all(is_prose_punctuation) {
+            TokenClass::Punctuation
+        } else {
+            TokenClass::Code
+        }
+    }
+}
+
+impl std::fmt::Display for TokenClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenClass::Word => write!(f, "Words"),
+            TokenClass::Number => write!(f, "Numbers"),
+            TokenClass::Punctuation => write!(f, "Punctuation"),
+            TokenClass::Whitespace => write!(f, "Whitespace"),
+            TokenClass::Code => write!(f, "Code symbols"),
+        }
+    }
+}
+
+/// Punctuation found in ordinary prose; anything else non-alphanumeric
+/// counts as a code symbol.
+fn is_prose_punctuation(c: char) -> bool {
+    matches!(
+        c,
+        '.' | ',' | ';' | ':' | '!' | '?' | '\'' | '"' | '(' | ')' | '-' | '…'
+    ) || (!c.is_ascii() && !c.is_alphanumeric())
+}
+
+#[derive(Clone, Debug)]
+pub struct ClassStats {
+    pub class: TokenClass,
+    pub tokens: usize,
+    /// Fraction of the scored tokens in this class.
+    pub share: f32,
+    pub perplexity: f32,
+}
+
+/// Perplexity per token class, in [`TokenClass::ALL`] order; classes with no
+/// tokens are left out. The first token has no prediction and is skipped.
+pub fn class_breakdown(tokens: &[AnalyzedToken]) -> Vec<ClassStats> {
+    let scored = tokens.get(1..).unwrap_or_default();
+    // (count, nll) per class
+    let mut totals = [(0usize, 0.0f32); TokenClass::ALL.len()];
+    for token in scored {
+        let class = TokenClass::of(&token.text);
+        let slot = &mut totals[TokenClass::ALL.iter().position(|&c| c == class).unwrap()];
+        slot.0 += 1;
+        slot.1 -= token.probability.max(f32::MIN_POSITIVE).ln();
+    }
+    TokenClass::ALL
+        .into_iter()
+        .zip(totals)
+        .filter(|&(_, (count, _))| count > 0)
+        .map(|(class, (count, nll))| ClassStats {
+            class,
+            tokens: count,
+            share: count as f32 / scored.len() as f32,
+            perplexity: (nll / count as f32).exp(),
+        })
+        .collect()
+}
@@ -0,0 +1,145 @@
+use crate::analysis::AnalysisResult;
+
+/// Em dashes per 1,000 words above which the rate is flagged. Edited human
+/// prose rarely goes past a few; chat models often do.
+const HIGH_EM_DASH_RATE: f32 = 5.0;
+
+/// Counts of spacing and punctuation habits. They come from the writer's
+/// keyboard, editor and style rather than the wording, so they often tell
+/// sources apart better than perplexity, and mixed habits hint at a text
+/// pasted together from several.
+#[derive(Clone, Debug, Default)]
+pub struct TypographyStats {
+    pub words: usize,
+    /// Runs of two or more spaces between words.
+    pub double_spaces: usize,
+    pub non_breaking_spaces: usize,
+    pub tabs: usize,
+    /// Lines ending in spaces or tabs.
+    pub trailing_whitespace: usize,
+    /// Spaces before `,`, `.`, `;`, `:`, `!` or `?`.
+    pub space_before_punctuation: usize,
+    pub straight_double_quotes: usize,
+    pub curly_double_quotes: usize,
+    /// Straight single quotes and apostrophes.
+    pub straight_single_quotes: usize,
+    pub curly_single_quotes: usize,
+    pub em_dashes: usize,
+    pub en_dashes: usize,
+    /// Hyphens with a space on both sides, used as a dash.
+    pub spaced_hyphens: usize,
+    pub ellipsis_chars: usize,
+    /// `...` written as three periods.
+    pub triple_dots: usize,
+}
+
+impl TypographyStats {
+    pub fn new(text: &str) -> Self {
+        let mut stats = Self {
+            words: text.split_whitespace().count(),
+            triple_dots: text.matches("...").count(),
+            spaced_hyphens: text.matches(" - ").count(),
+            ..Self::default()
+        };
+        for line in text.lines() {
+            if line.ends_with([' ', '\t']) {
+                stats.trailing_whitespace += 1;
+            }
+            let body = line.trim();
+            stats.double_spaces += body
+                .split(' ')
+                .skip(1)
+                .zip(body.split(' '))
+                .filter(|(piece, previous)| piece.is_empty() && !previous.is_empty())
+                .count();
+        }
+        let mut previous = '\n';
+        for c in text.chars() {
+            match c {
+                '\u{00A0}' | '\u{202F}' | '\u{2007}' => stats.non_breaking_spaces += 1,
+                '\t' => stats.tabs += 1,
+                '"' => stats.straight_double_quotes += 1,
+                '\u{201C}' | '\u{201D}' | '\u{201E}' => stats.curly_double_quotes += 1,
+                '\'' => stats.straight_single_quotes += 1,
+                '\u{2018}' | '\u{2019}' | '\u{201A}' => stats.curly_single_quotes += 1,
+                '\u{2014}' => stats.em_dashes += 1,
+                '\u{2013}' => stats.en_dashes += 1,
+                '\u{2026}' => stats.ellipsis_chars += 1,
+                ',' | '.' | ';' | ':' | '!' | '?' if previous == ' ' => {
+                    stats.space_before_punctuation += 1
+                }
+                _ => {}
+            }
+            previous = c;
+        }
+        stats
+    }
+
+    /// Statistics of the analyzed text of `result`, prompt excluded.
+    pub fn of(result: &AnalysisResult) -> Self {
+        let start = result.prompt_tokens.min(result.tokens.len());
+        let text: String = result.tokens[start..]
+            .iter()
+            .map(|t| t.document_text())
+            .collect();
+        Self::new(&text)
+    }
+
+    pub fn em_dash_rate(&self) -> f32 {
+        self.em_dashes as f32 * 1000.0 / self.words.max(1) as f32
+    }
+
+    /// Notable habits, one sentence each.
+    pub fn anomalies(&self) -> Vec<String> {
+        let mut anomalies = Vec::new();
+        if self.double_spaces > 0 {
+            anomalies.push(format!(
+                "{} double spaces between words",
+                self.double_spaces
+            ));
+        }
+        if self.non_breaking_spaces > 0 {
+            anomalies.push(format!(
+                "{} non-breaking spaces, usually from a word processor or web page",
+                self.non_breaking_spaces
+            ));
+        }
+        if self.straight_double_quotes > 0 && self.curly_double_quotes > 0 {
+            anomalies.push(format!(
+                "Straight and curly double quotes mixed ({} and {})",
+                self.straight_double_quotes, self.curly_double_quotes
+            ));
+        }
+        if self.straight_single_quotes > 0 && self.curly_single_quotes > 0 {
+            anomalies.push(format!(
+                "Straight and curly apostrophes mixed ({} and {})",
+                self.straight_single_quotes, self.curly_single_quotes
+            ));
+        }
+        if self.em_dash_rate() > HIGH_EM_DASH_RATE {
+            anomalies.push(format!(
+                "{:.1} em dashes per 1,000 words, a rate typical of generated text",
+                self.em_dash_rate()
+            ));
+        }
+        if self.em_dashes > 0 && self.spaced_hyphens > 0 {
+            anomalies.push("Both em dashes and spaced hyphens used as dashes".to_string());
+        }
+        if self.ellipsis_chars > 0 && self.triple_dots > 0 {
+            anomalies.push("Both … and ... used for ellipses".to_string());
+        }
+        if self.space_before_punctuation > 0 {
+            anomalies.push(format!(
+                "{} spaces before punctuation",
+                self.space_before_punctuation
+            ));
+        }
+        if self.trailing_whitespace > 0 {
+            anomalies.push(format!(
+                "{} lines end in whitespace",
+                self.trailing_whitespace
+            ));
+        }
+        anomalies
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+
+use crate::analysis::AnalysisResult;
+use crate::shuffle;
+
+/// Model-free statistics of a text, for reading the model's metrics
+/// against: a short or repetitive text scores differently for reasons that
+/// have nothing to do with its source.
+#[derive(Clone, Debug, Default)]
+pub struct TextStats {
+    pub characters: usize,
+    pub words: usize,
+    pub sentences: usize,
+    /// Distinct words, compared case-insensitively without surrounding
+    /// punctuation.
+    pub unique_words: usize,
+    /// Distinct token ids the model read the text as.
+    pub unique_tokens: usize,
+}
+
+impl TextStats {
+    /// Statistics of `text` alone; `unique_tokens` is left at zero.
+    pub fn new(text: &str) -> Self {
+        let words: Vec<String> = text
+            .split_whitespace()
+            .map(|w| {
+                w.trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase()
+            })
+            .filter(|w| !w.is_empty())
+            .collect();
+        Self {
+            characters: text.chars().count(),
+            words: words.len(),
+            sentences: shuffle::sentences(text)
+                .iter()
+                .filter(|s| !s.trim().is_empty())
+                .count(),
+            unique_words: words.iter().collect::<HashSet<_>>().len(),
+            unique_tokens: 0,
+        }
+    }
+
+    /// Statistics of the analyzed text of `result`, prompt excluded.
+    pub fn of(result: &AnalysisResult) -> Self {
+        let start = result.prompt_tokens.min(result.tokens.len());
+        let tokens = &result.tokens[start..];
+        let text: String = tokens.iter().map(|t| t.document_text()).collect();
+        Self {
+            unique_tokens: tokens
+                .iter()
+                .filter(|t| t.special.is_none())
+                .map(|t| t.id)
+                .collect::<HashSet<_>>()
+                .len(),
+            ..Self::new(&text)
+        }
+    }
+
+    /// Distinct words over words; lower means a more repetitive
+    /// vocabulary. Falls with length, so compare texts of similar size.
+    pub fn type_token_ratio(&self) -> f32 {
+        self.unique_words as f32 / self.words.max(1) as f32
+    }
+
+    pub fn mean_sentence_length(&self) -> f32 {
+        self.words as f32 / self.sentences.max(1) as f32
+    }
+}
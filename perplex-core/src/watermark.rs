@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::analysis::AnalyzedToken;
+
+/// Parameters of a green/red list watermark (Kirchenbauer et al., 2023).
+/// At each position the previous `context_width` token ids and the key seed
+/// a hash that puts a `gamma` fraction of the vocabulary on the green list;
+/// a watermarking generator favours green tokens.
+///
+/// Green membership is `splitmix64(key ^ context hash ^ token) < gamma`, so
+/// the test only detects generators configured with the same hash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatermarkConfig {
+    pub enabled: bool,
+    pub key: u64,
+    /// Fraction of the vocabulary on the green list.
+    pub gamma: f32,
+    /// Number of preceding tokens hashed into the seed.
+    pub context_width: usize,
+}
+
+impl Default for WatermarkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key: 15_485_863,
+            gamma: 0.25,
+            context_width: 1,
+        }
+    }
+}
+
+/// Outcome of the z-test on the share of green tokens.
+#[derive(Clone, Debug)]
+pub struct WatermarkStats {
+    pub green: usize,
+    /// Tokens counted; repeated (context, token) pairs are counted once.
+    pub scored: usize,
+    pub gamma: f32,
+    pub z_score: f32,
+}
+
+impl WatermarkStats {
+    /// Fraction of scored tokens on the green list.
+    pub fn green_fraction(&self) -> f32 {
+        self.green as f32 / self.scored.max(1) as f32
+    }
+}
+
+/// z-score above which a watermark is considered present, as in the paper.
+pub const Z_THRESHOLD: f32 = 4.0;
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+fn is_green(config: &WatermarkConfig, context: &[i32], token: i32) -> bool {
+    let seed = context
+        .iter()
+        .fold(config.key, |acc, &id| splitmix64(acc ^ id as u32 as u64));
+    let hash = splitmix64(seed ^ (token as u32 as u64).rotate_left(32));
+    (hash as f64 / u64::MAX as f64) < config.gamma as f64
+}
+
+/// Runs the green-list test over the scored tokens. `None` when the text is
+/// shorter than the context window.
+pub fn detect(tokens: &[AnalyzedToken], config: &WatermarkConfig) -> Option<WatermarkStats> {
+    let width = config.context_width.max(1);
+    // Include the first token as context for the second, but never score it.
+    let ids: Vec<i32> = tokens.iter().map(|t| t.id).collect();
+    if ids.len() <= width {
+        return None;
+    }
+
+    let mut seen = HashSet::new();
+    let mut green = 0;
+    for window in ids.windows(width + 1) {
+        if !seen.insert(window) {
+            continue;
+        }
+        let (context, token) = window.split_at(width);
+        if is_green(config, context, token[0]) {
+            green += 1;
+        }
+    }
+
+    let scored = seen.len();
+    let gamma = config.gamma.clamp(0.01, 0.99);
+    let expected = gamma * scored as f32;
+    let z_score = (green as f32 - expected) / (scored as f32 * gamma * (1.0 - gamma)).sqrt();
+    Some(WatermarkStats {
+        green,
+        scored,
+        gamma,
+        z_score,
+    })
+}
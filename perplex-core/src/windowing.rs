@@ -0,0 +1,70 @@
+/// Window lengths tried by the chunk-size sweep, in addition to the full
+/// text.
+pub const CHUNK_SWEEP_WINDOWS: [u32; 4] = [256, 512, 1024, 2048];
+/// Strides tried for each window, as fractions of the window length. A
+/// stride of 1 evaluates non-overlapping chunks.
+pub const CHUNK_SWEEP_STRIDES: [f32; 3] = [0.25, 0.5, 1.0];
+/// Relative perplexity change between neighbouring configurations above
+/// which the result is considered sensitive to the windowing choice.
+const STABILITY_TOLERANCE: f32 = 0.05;
+
+/// Perplexity with one window length and stride.
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkPoint {
+    pub window: u32,
+    pub stride: u32,
+    pub perplexity: f32,
+}
+
+/// Result of a chunk-size sweep. The analysis itself always scores the
+/// full text in one context, which `full_perplexity` reproduces.
+#[derive(Clone, Debug)]
+pub struct ChunkSweep {
+    pub points: Vec<ChunkPoint>,
+    /// Perplexity with the whole text in context.
+    pub full_perplexity: f32,
+}
+
+impl ChunkSweep {
+    /// Distinct window lengths, ascending.
+    pub fn windows(&self) -> Vec<u32> {
+        let mut windows: Vec<u32> = self.points.iter().map(|p| p.window).collect();
+        windows.dedup();
+        windows
+    }
+
+    /// Points of one window length, by ascending stride.
+    pub fn row(&self, window: u32) -> impl Iterator<Item = &ChunkPoint> {
+        self.points.iter().filter(move |p| p.window == window)
+    }
+
+    /// Relative spread (max / min − 1) of perplexity across strides for a
+    /// window length.
+    pub fn stride_spread(&self, window: u32) -> f32 {
+        let (min, max) = self.row(window).fold((f32::MAX, f32::MIN), |(lo, hi), p| {
+            (lo.min(p.perplexity), hi.max(p.perplexity))
+        });
+        if min <= 0.0 || min > max {
+            0.0
+        } else {
+            max / min - 1.0
+        }
+    }
+
+    pub fn is_stride_sensitive(&self, window: u32) -> bool {
+        self.stride_spread(window) > STABILITY_TOLERANCE
+    }
+
+    /// Relative perplexity change from the largest swept window (at the
+    /// densest stride) to the full context, when it exceeds the tolerance.
+    /// A large change means the full-text result hasn't converged: it
+    /// still depends on how much context the model sees.
+    pub fn full_context_drift(&self) -> Option<(u32, f32)> {
+        let largest = self
+            .points
+            .iter()
+            .max_by_key(|p| (p.window, u32::MAX - p.stride))?;
+        let change = (self.full_perplexity / largest.perplexity - 1.0).abs();
+        (change > STABILITY_TOLERANCE).then_some((largest.window, change))
+    }
+}
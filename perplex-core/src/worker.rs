@@ -0,0 +1,478 @@
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::analysis::{AnalysisRequest, AnalysisResult, PartialToken};
+use crate::llamacpp::{InferenceParams, SharedModel, TokenInfo};
+use crate::regeneration::{RegenerationDiff, RegenerationStats};
+use crate::resources::{MemoryUsage, ResourceSample};
+use crate::token_cache::TokenCache;
+use crate::windowing::ChunkSweep;
+
+#[derive(Debug)]
+pub enum WorkerMessage {
+    ModelLoaded,
+    ModelUnloaded,
+    Started,
+    /// The running analysis stopped between batches after `Pause`.
+    Paused,
+    Resumed,
+    Progress { current: usize, total: usize },
+    /// Tokens scored by the batch just decoded; only sent when the
+    /// analyzer streams tokens.
+    Tokens(Vec<PartialToken>),
+    Completed(AnalysisResult),
+    /// Reply to `Rescore`; unlike `Completed` it doesn't replace the result.
+    Rescored(AnalysisResult),
+    /// Perplexity of each text sent with `Score`, in order.
+    Scored(Vec<f32>),
+    /// Reply to `AnalyzeVariants`: the full result of each request, in order.
+    VariantsAnalyzed(Vec<AnalysisResult>),
+    /// `(context length, perplexity)` pairs from `ContextSweep`.
+    ContextSwept(Vec<(u32, f32)>),
+    ChunkSwept(ChunkSweep),
+    Regenerated(RegenerationStats),
+    RegeneratedDiff(RegenerationDiff),
+    /// Reply to `AnalyzeModels`: each model's path and result, in order.
+    ModelsAnalyzed(Vec<(String, AnalysisResult)>),
+    TokenCount(usize),
+    /// Reply to `ListTokens`. Failures come here rather than as `Error`,
+    /// which would end the analysis the worker may be running.
+    TokenList(Result<Vec<TokenInfo>, String>),
+    /// Memory in use after the analysis context was created.
+    Memory(MemoryUsage),
+    /// Periodic CPU/RAM/throughput reading while decoding.
+    Resources(ResourceSample),
+    Error(String),
+    /// A side job failed. Kept apart from `Error`, which ends the analysis
+    /// the worker thread may be running.
+    SideJobFailed(String),
+}
+
+#[derive(Debug)]
+pub enum WorkerCommand {
+    LoadModel(String, InferenceParams),
+    /// Replaces the parameters of the loaded model's context, for those
+    /// that don't need a reload; see [`InferenceParams::needs_reload`].
+    SetParams(InferenceParams),
+    UnloadModel,
+    Analyze(AnalysisRequest),
+    /// Holds the running analysis after its current batch; ignored when
+    /// idle. The model and context stay loaded.
+    Pause,
+    Resume,
+    /// Aborts the running analysis after its current batch, which then
+    /// fails with an error; ignored when idle.
+    Cancel,
+    /// Analyzes the request again, for comparison with an earlier result.
+    Rescore(AnalysisRequest),
+    /// Scores each request and replies with their perplexities.
+    Score(Vec<AnalysisRequest>),
+    /// Analyzes each request like `Score`, but replies with the full results.
+    AnalyzeVariants(Vec<AnalysisRequest>),
+    Shutdown,
+}
+
+/// Jobs for the side thread, which runs them with the worker's model while
+/// the worker thread is busy with an analysis. Each starts with `Started`
+/// and ends with its reply or `SideJobFailed`.
+#[derive(Debug)]
+pub enum SideJob {
+    /// Re-scores the text with several maximum context lengths.
+    ContextSweep(String),
+    /// Re-scores the text with several window lengths and strides.
+    ChunkSweep(String),
+    /// Greedy-regenerates the text from its opening and compares the two.
+    Regenerate(String),
+    /// Greedy-regenerates the rest of the request from token `start`, or
+    /// from the opening, and diffs it against the actual text.
+    RegenerateDiff {
+        request: AnalysisRequest,
+        start: Option<usize>,
+    },
+    /// Analyzes the request with each of the models at these paths, loaded
+    /// one at a time next to the worker's own, with its parameters.
+    AnalyzeModels {
+        models: Vec<String>,
+        request: AnalysisRequest,
+    },
+}
+
+#[derive(Debug)]
+pub enum SideCommand {
+    Run(SideJob),
+    /// Aborts the running side job before its next decode.
+    Cancel,
+    Shutdown,
+}
+
+/// Requests for the tokenizer thread, which keeps a vocabulary-only copy of
+/// the model so token counts and lists don't wait behind a running analysis.
+#[derive(Debug)]
+pub enum TokenizerCommand {
+    Load(String),
+    Unload,
+    /// Replies with `TokenCount` on the worker's message channel.
+    Count(String),
+    /// Tokenizes text for the tokenizer playground; replies with `TokenList`.
+    ListTokens {
+        text: String,
+        add_bos: bool,
+    },
+    Shutdown,
+}
+
+/// Manages a persistent background worker thread for LLM operations.
+///
+/// The worker thread is spawned once and kept alive for the duration of
+/// the manager. Model loading and unloading are handled via commands,
+/// allowing future JIT model swapping without restarting threads. Next to
+/// it run two more so nothing auxiliary queues behind a long analysis:
+/// the tokenizer thread answers the quick commands (token counts and lists)
+/// from a vocabulary-only copy of the model, and the side thread runs the
+/// jobs that need inference, sharing the loaded model. Those take minutes
+/// themselves, so they are cancellable like analyses rather than mixed
+/// with the quick commands. Model metadata is read from the file and needs
+/// neither.
+pub struct WorkerManager {
+    tx: Option<mpsc::Sender<WorkerCommand>>,
+    rx: Option<mpsc::Receiver<WorkerMessage>>,
+    handle: Option<thread::JoinHandle<()>>,
+    tokenizer_tx: Option<mpsc::Sender<TokenizerCommand>>,
+    tokenizer_handle: Option<thread::JoinHandle<()>>,
+    side_tx: Option<mpsc::Sender<SideCommand>>,
+    side_rx: Option<mpsc::Receiver<WorkerMessage>>,
+    side_handle: Option<thread::JoinHandle<()>>,
+    shared: Arc<SharedModel>,
+    pub is_loading: bool,
+    pub is_analyzing: bool,
+    pub is_paused: bool,
+    pub progress: Option<f32>,
+    /// Time left in the running job, extrapolated from its progress so
+    /// far. `None` until the first batch is done.
+    pub remaining: Option<Duration>,
+    rate: RateMeter,
+    pub has_model: bool,
+    /// Latest memory reading from an analysis. Cleared when the analysis
+    /// succeeds, but kept after an error since that's usually why it failed.
+    pub memory: Option<MemoryUsage>,
+    /// Resource monitor samples of the latest analysis.
+    pub resource_samples: Vec<ResourceSample>,
+    pub is_running_side_job: bool,
+    pub side_progress: Option<f32>,
+}
+
+/// Extrapolates the time left in a job from its progress messages.
+#[derive(Default)]
+struct RateMeter {
+    /// When measurement started, with the progress and total at that time.
+    start: Option<(Instant, usize, usize)>,
+}
+
+impl RateMeter {
+    fn remaining(&mut self, current: usize, total: usize) -> Option<Duration> {
+        match self.start {
+            Some((since, start, start_total)) if start_total == total => {
+                let done = current.checked_sub(start).filter(|&done| done > 0)?;
+                let per_unit = since.elapsed().as_secs_f64() / done as f64;
+                Some(Duration::from_secs_f64(
+                    per_unit * total.saturating_sub(current) as f64,
+                ))
+            }
+            // A new total means a new phase, or resumed progress a
+            // checkpoint skipped to.
+            _ => {
+                self.start = Some((Instant::now(), current, total));
+                None
+            }
+        }
+    }
+}
+
+impl Default for WorkerManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkerManager {
+    /// Creates a new manager and spawns its persistent worker, tokenizer
+    /// and side threads.
+    pub fn new() -> Self {
+        let (cmd_tx, cmd_rx) = mpsc::channel();
+        let (msg_tx, msg_rx) = mpsc::channel();
+        let (tokenizer_tx, tokenizer_rx) = mpsc::channel();
+        let (side_tx, side_cmd_rx) = mpsc::channel();
+        let (side_msg_tx, side_rx) = mpsc::channel();
+        let shared = Arc::new(SharedModel::default());
+
+        let tokenizer_msg_tx = msg_tx.clone();
+        let token_cache = Arc::new(TokenCache::default());
+        let tokenizer_cache = token_cache.clone();
+        let tokenizer_handle = thread::spawn(move || {
+            crate::llamacpp::run_tokenizer(tokenizer_rx, tokenizer_msg_tx, tokenizer_cache);
+        });
+        let side_shared = shared.clone();
+        let side_handle = thread::spawn(move || {
+            crate::llamacpp::run_side_worker(side_cmd_rx, side_msg_tx, side_shared);
+        });
+        let worker_shared = shared.clone();
+        let handle = thread::spawn(move || {
+            crate::llamacpp::run_worker(cmd_rx, msg_tx, token_cache, worker_shared);
+        });
+
+        Self {
+            tx: Some(cmd_tx),
+            rx: Some(msg_rx),
+            handle: Some(handle),
+            tokenizer_tx: Some(tokenizer_tx),
+            tokenizer_handle: Some(tokenizer_handle),
+            side_tx: Some(side_tx),
+            side_rx: Some(side_rx),
+            side_handle: Some(side_handle),
+            shared,
+            is_loading: false,
+            is_analyzing: false,
+            is_paused: false,
+            progress: None,
+            remaining: None,
+            rate: RateMeter::default(),
+            has_model: false,
+            memory: None,
+            resource_samples: Vec::new(),
+            is_running_side_job: false,
+            side_progress: None,
+        }
+    }
+
+    /// Sends a LoadModel command to the worker thread.
+    pub fn load_model(&mut self, path: String, params: InferenceParams) {
+        self.is_loading = true;
+        self.is_analyzing = false;
+        self.progress = None;
+        self.remaining = None;
+
+        if let Some(ref tx) = self.tokenizer_tx {
+            let _ = tx.send(TokenizerCommand::Load(path.clone()));
+        }
+        if let Some(ref tx) = self.tx {
+            self.shared.begin_load();
+            if tx.send(WorkerCommand::LoadModel(path, params)).is_err() {
+                self.shared.close();
+            }
+        }
+    }
+
+    /// Sends new context parameters to the worker thread, which applies
+    /// them from the next analysis on without reloading the model.
+    pub fn set_params(&self, params: InferenceParams) {
+        if let Some(ref tx) = self.tx {
+            let _ = tx.send(WorkerCommand::SetParams(params));
+        }
+    }
+
+    /// Sends an UnloadModel command to the worker thread.
+    pub fn unload_model(&mut self) {
+        if let Some(ref tx) = self.tokenizer_tx {
+            let _ = tx.send(TokenizerCommand::Unload);
+        }
+        if let Some(ref tx) = self.tx {
+            let _ = tx.send(WorkerCommand::UnloadModel);
+        }
+        self.has_model = false;
+    }
+
+    /// Counts the tokens of `text` on the tokenizer thread, even while an
+    /// analysis is running. The reply is a `TokenCount` message.
+    pub fn count_tokens(&self, text: String) -> Result<(), String> {
+        match self.tokenizer_tx {
+            Some(ref tx) => tx
+                .send(TokenizerCommand::Count(text))
+                .map_err(|e| format!("Failed to send command: {}", e)),
+            None => Err("No worker available".to_string()),
+        }
+    }
+
+    /// Lists the tokens of `text` on the tokenizer thread, even while an
+    /// analysis is running. The reply is a `TokenList` message.
+    pub fn list_tokens(&self, text: String, add_bos: bool) -> Result<(), String> {
+        match self.tokenizer_tx {
+            Some(ref tx) => tx
+                .send(TokenizerCommand::ListTokens { text, add_bos })
+                .map_err(|e| format!("Failed to send command: {}", e)),
+            None => Err("No worker available".to_string()),
+        }
+    }
+
+    /// Sends an Analyze command and marks the worker busy right away, so
+    /// callers see it as analyzing before the `Started` message arrives.
+    pub fn analyze(&mut self, request: AnalysisRequest) -> Result<(), String> {
+        self.send_command(WorkerCommand::Analyze(request))?;
+        self.is_analyzing = true;
+        Ok(())
+    }
+
+    /// Starts `job` on the side thread and marks it running right away,
+    /// like `analyze`. The reply comes with the worker's messages.
+    pub fn start_side_job(&mut self, job: SideJob) -> Result<(), String> {
+        match self.side_tx {
+            Some(ref tx) => tx
+                .send(SideCommand::Run(job))
+                .map_err(|e| format!("Failed to send command: {}", e))?,
+            None => return Err("No worker available".to_string()),
+        }
+        self.is_running_side_job = true;
+        Ok(())
+    }
+
+    /// Aborts the job running on the side thread, if any.
+    pub fn cancel_side_job(&self) {
+        if let Some(ref tx) = self.side_tx {
+            let _ = tx.send(SideCommand::Cancel);
+        }
+    }
+
+    /// Sends a command to the worker thread. Returns an error if no worker is active.
+    pub fn send_command(&self, cmd: WorkerCommand) -> Result<(), String> {
+        if let Some(ref tx) = self.tx {
+            tx.send(cmd)
+                .map_err(|e| format!("Failed to send command: {}", e))
+        } else {
+            Err("No worker available".to_string())
+        }
+    }
+
+    /// Drains all pending messages from the worker, updating internal state
+    /// (`is_loading`, `is_analyzing`, `progress`, `has_model`) as it goes.
+    ///
+    /// Returns the messages so the application can react to them
+    /// (e.g. storing results, displaying errors).
+    pub fn poll_messages(&mut self) -> Vec<WorkerMessage> {
+        let mut messages = Vec::new();
+
+        if let Some(ref rx) = self.rx {
+            while let Ok(msg) = rx.try_recv() {
+                match &msg {
+                    WorkerMessage::ModelLoaded => {
+                        self.is_loading = false;
+                        self.has_model = true;
+                    }
+                    WorkerMessage::ModelUnloaded => {
+                        self.has_model = false;
+                    }
+                    WorkerMessage::Started => {
+                        self.is_analyzing = true;
+                        self.progress = Some(0.0);
+                        self.remaining = None;
+                        self.rate = RateMeter::default();
+                        self.memory = None;
+                        self.resource_samples.clear();
+                    }
+                    WorkerMessage::Paused => self.is_paused = true,
+                    WorkerMessage::Resumed => {
+                        self.is_paused = false;
+                        self.rate = RateMeter::default();
+                    }
+                    WorkerMessage::Progress { current, total } => {
+                        self.progress = Some(*current as f32 / (*total).max(1) as f32);
+                        if let Some(remaining) = self.rate.remaining(*current, *total) {
+                            self.remaining = Some(remaining);
+                        }
+                    }
+                    WorkerMessage::Completed(_)
+                    | WorkerMessage::Rescored(_)
+                    | WorkerMessage::Scored(_)
+                    | WorkerMessage::VariantsAnalyzed(_) => {
+                        self.is_analyzing = false;
+                        self.is_paused = false;
+                        self.progress = None;
+                        self.remaining = None;
+                        self.memory = None;
+                    }
+                    WorkerMessage::Error(_) => {
+                        self.is_analyzing = false;
+                        self.is_paused = false;
+                        self.is_loading = false;
+                        self.progress = None;
+                        self.remaining = None;
+                    }
+                    WorkerMessage::Memory(usage) => {
+                        self.memory = Some(usage.clone());
+                    }
+                    WorkerMessage::Resources(sample) => {
+                        self.resource_samples.push(*sample);
+                    }
+                    WorkerMessage::TokenCount(_)
+                    | WorkerMessage::TokenList(_)
+                    | WorkerMessage::Tokens(_)
+                    | WorkerMessage::ContextSwept(_)
+                    | WorkerMessage::ChunkSwept(_)
+                    | WorkerMessage::Regenerated(_)
+                    | WorkerMessage::RegeneratedDiff(_)
+                    | WorkerMessage::ModelsAnalyzed(_)
+                    | WorkerMessage::SideJobFailed(_) => {}
+                }
+                messages.push(msg);
+            }
+        }
+
+        // The side thread's `Started` and `Progress` are about its own job,
+        // so they update the side job's state instead of the analysis's.
+        if let Some(ref rx) = self.side_rx {
+            while let Ok(msg) = rx.try_recv() {
+                match &msg {
+                    WorkerMessage::Started => self.side_progress = Some(0.0),
+                    WorkerMessage::Progress { current, total } => {
+                        self.side_progress = Some(*current as f32 / (*total).max(1) as f32);
+                    }
+                    WorkerMessage::ContextSwept(_)
+                    | WorkerMessage::ChunkSwept(_)
+                    | WorkerMessage::Regenerated(_)
+                    | WorkerMessage::RegeneratedDiff(_)
+                    | WorkerMessage::ModelsAnalyzed(_)
+                    | WorkerMessage::SideJobFailed(_) => {
+                        self.is_running_side_job = false;
+                        self.side_progress = None;
+                    }
+                    _ => {}
+                }
+                messages.push(msg);
+            }
+        }
+
+        messages
+    }
+
+    /// Returns `true` if a model is loaded and the worker is idle.
+    pub fn is_ready(&self) -> bool {
+        self.has_model && !self.is_loading
+    }
+
+    /// Sends a shutdown command and joins the worker threads. A running
+    /// side job sees the command before its next decode and stops there.
+    pub fn shutdown(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(WorkerCommand::Shutdown);
+        }
+        if let Some(tx) = self.tokenizer_tx.take() {
+            let _ = tx.send(TokenizerCommand::Shutdown);
+        }
+        if let Some(tx) = self.side_tx.take() {
+            let _ = tx.send(SideCommand::Shutdown);
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        // A side job waiting for a load the worker never finished.
+        self.shared.close();
+        for handle in [self.tokenizer_handle.take(), self.side_handle.take()]
+            .into_iter()
+            .flatten()
+        {
+            let _ = handle.join();
+        }
+        self.rx = None;
+        self.side_rx = None;
+        self.has_model = false;
+    }
+}
@@ -0,0 +1,267 @@
+use std::fs;
+
+use crate::llamacpp::GpuSplit;
+use crate::settings::{PreloadMode, Settings};
+
+const USAGE: &str = "\
+Usage: perplex [OPTIONS]
+
+Options:
+  --model <PATH>           GGUF model for slot A
+  --model-b <PATH>         GGUF model for slot B
+  --text <TEXT>            Preload the input field with TEXT
+  --text-file <PATH>       Preload the input field with the contents of PATH
+  --n-gpu-layers <N>       Number of layers to offload to the GPU
+  --n-ctx <N>              Minimum context size
+  --n-batch <N>            Decode batch size
+  --threads <N>            CPU threads used for decoding
+  --split-mode <MODE>      Spread the model over GPUs: layer, row or none
+  --main-gpu <N>           GPU to use with --split-mode none
+  --low-memory <N>         Score long texts in windows of N tokens to bound
+                           memory use
+  --rolling-context        With --low-memory, keep a rolling context instead
+                           of starting each window empty
+  --constant-context <N>   Predict every token from exactly N preceding
+                           tokens; about N times slower
+  --max-tokens <N>         Score only the first N tokens of the text
+  --preload <MODE>         Model preloading: all, single or none
+  --analyze                Start the analysis as soon as the models are ready
+  --paste-analyze          Analyze the clipboard in the running window, raising
+                           it, or in a new one if none is open; bind this to a
+                           system-wide shortcut
+  --serve <ADDR>           Run the HTTP analysis API on ADDR (e.g. 127.0.0.1:8080)
+                           with model A instead of opening the window
+  --allow-origin <ORIGIN>  With --serve, let web pages from ORIGIN (e.g.
+                           http://localhost:3000) call the API; repeatable
+  --stdio                  Answer JSON-lines requests on stdin/stdout with
+                           model A, for editor integrations
+  --benchmark              Time model A with combinations of threads, batch
+                           size and GPU layers and print the fastest
+  -h, --help               Print this help and exit";
+
+/// Command-line overrides applied on top of the saved settings at startup.
+/// They last for the session only: `unapply` keeps them out of the saved
+/// settings.
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub model_a: Option<String>,
+    pub model_b: Option<String>,
+    pub text: Option<String>,
+    pub n_gpu_layers: Option<u32>,
+    pub n_ctx: Option<u32>,
+    pub n_batch: Option<u32>,
+    pub n_threads: Option<i32>,
+    pub split_mode: Option<GpuSplit>,
+    pub main_gpu: Option<u32>,
+    pub low_memory_window: Option<u32>,
+    pub rolling_context: bool,
+    pub constant_context: Option<u32>,
+    pub max_tokens: Option<u32>,
+    pub preload_mode: Option<PreloadMode>,
+    pub auto_analyze: bool,
+    /// Analyze the clipboard, handing it to an already running window.
+    pub paste_analyze: bool,
+    /// Address to serve the HTTP API on; the GUI isn't started.
+    pub serve: Option<String>,
+    /// Origins whose web pages may call the HTTP API.
+    pub allow_origins: Vec<String>,
+    /// Answer editor requests over stdin/stdout; the GUI isn't started.
+    pub stdio: bool,
+    /// Run the throughput benchmark; the GUI isn't started.
+    pub benchmark: bool,
+}
+
+impl CliArgs {
+    /// Parses the process arguments, printing usage and exiting on `--help`
+    /// or on invalid input.
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(Some(args)) => args,
+            Ok(None) => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("error: {}\n\n{}", e, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// Returns `Ok(None)` when help was requested.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut cli = CliArgs::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", name))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--model" => cli.model_a = Some(value(&arg)?),
+                "--model-b" => cli.model_b = Some(value(&arg)?),
+                "--text" => cli.text = Some(value(&arg)?),
+                "--text-file" => {
+                    let path = value(&arg)?;
+                    let content = fs::read_to_string(&path)
+                        .map_err(|e| format!("failed to read {}: {}", path, e))?;
+                    cli.text = Some(content);
+                }
+                "--n-gpu-layers" => cli.n_gpu_layers = Some(parse_number(&arg, value(&arg)?)?),
+                "--n-ctx" => cli.n_ctx = Some(parse_number(&arg, value(&arg)?)?),
+                "--n-batch" => cli.n_batch = Some(parse_number(&arg, value(&arg)?)?),
+                "--threads" => cli.n_threads = Some(parse_number(&arg, value(&arg)?)?),
+                "--split-mode" => {
+                    cli.split_mode = Some(match value(&arg)?.as_str() {
+                        "layer" => GpuSplit::Layer,
+                        "row" => GpuSplit::Row,
+                        "none" => GpuSplit::Single,
+                        other => return Err(format!("unknown split mode '{}'", other)),
+                    })
+                }
+                "--main-gpu" => cli.main_gpu = Some(parse_number(&arg, value(&arg)?)?),
+                "--low-memory" => cli.low_memory_window = Some(parse_number(&arg, value(&arg)?)?),
+                "--rolling-context" => cli.rolling_context = true,
+                "--constant-context" => {
+                    cli.constant_context = Some(parse_number(&arg, value(&arg)?)?)
+                }
+                "--max-tokens" => cli.max_tokens = Some(parse_number(&arg, value(&arg)?)?),
+                "--preload" => {
+                    cli.preload_mode = Some(match value(&arg)?.as_str() {
+                        "all" => PreloadMode::PreloadAll,
+                        "single" => PreloadMode::PreloadSingle,
+                        "none" => PreloadMode::NoPreload,
+                        other => return Err(format!("unknown preload mode '{}'", other)),
+                    })
+                }
+                "--analyze" => cli.auto_analyze = true,
+                "--paste-analyze" => cli.paste_analyze = true,
+                "--serve" => cli.serve = Some(value(&arg)?),
+                "--allow-origin" => cli.allow_origins.push(value(&arg)?),
+                "--stdio" => cli.stdio = true,
+                "--benchmark" => cli.benchmark = true,
+                other => return Err(format!("unknown argument '{}'", other)),
+            }
+        }
+
+        Ok(Some(cli))
+    }
+
+    /// Overrides the loaded settings with any values given on the command line.
+    pub fn apply_to(&self, settings: &mut Settings) {
+        if let Some(ref path) = self.model_a {
+            settings.model_path_a = Some(path.clone());
+        }
+        if let Some(ref path) = self.model_b {
+            settings.model_path_b = Some(path.clone());
+        }
+        if let Some(mode) = self.preload_mode {
+            settings.preload_mode = mode;
+        }
+        if let Some(n) = self.n_gpu_layers {
+            settings.inference.n_gpu_layers = Some(n);
+        }
+        if let Some(n) = self.n_ctx {
+            settings.inference.n_ctx = n;
+        }
+        if let Some(n) = self.n_batch {
+            settings.inference.n_batch = n;
+        }
+        if let Some(n) = self.n_threads {
+            settings.inference.n_threads = Some(n);
+        }
+        if let Some(mode) = self.split_mode {
+            settings.inference.split_mode = mode;
+        }
+        if let Some(gpu) = self.main_gpu {
+            settings.inference.main_gpu = gpu;
+        }
+        if let Some(window) = self.low_memory_window {
+            settings.inference.low_memory_window = Some(window);
+        }
+        if self.rolling_context {
+            settings.inference.rolling_context = true;
+        }
+        if let Some(k) = self.constant_context {
+            settings.inference.constant_context = Some(k);
+        }
+        if let Some(limit) = self.max_tokens {
+            settings.inference.max_tokens = Some(limit);
+        }
+    }
+
+    /// Undoes `apply_to` on settings about to be saved: fields still holding
+    /// their command-line value get back the one from `saved`, the settings
+    /// as loaded, so a one-off flag never becomes a saved setting. Fields
+    /// changed since, in the settings window, keep the change.
+    pub fn unapply(&self, settings: &mut Settings, saved: &Settings) {
+        restore(
+            &mut settings.model_path_a,
+            self.model_a.clone().map(Some),
+            &saved.model_path_a,
+        );
+        restore(
+            &mut settings.model_path_b,
+            self.model_b.clone().map(Some),
+            &saved.model_path_b,
+        );
+        restore(
+            &mut settings.preload_mode,
+            self.preload_mode,
+            &saved.preload_mode,
+        );
+        let (inference, saved) = (&mut settings.inference, &saved.inference);
+        restore(
+            &mut inference.n_gpu_layers,
+            self.n_gpu_layers.map(Some),
+            &saved.n_gpu_layers,
+        );
+        restore(&mut inference.n_ctx, self.n_ctx, &saved.n_ctx);
+        restore(&mut inference.n_batch, self.n_batch, &saved.n_batch);
+        restore(
+            &mut inference.n_threads,
+            self.n_threads.map(Some),
+            &saved.n_threads,
+        );
+        restore(
+            &mut inference.split_mode,
+            self.split_mode,
+            &saved.split_mode,
+        );
+        restore(&mut inference.main_gpu, self.main_gpu, &saved.main_gpu);
+        restore(
+            &mut inference.low_memory_window,
+            self.low_memory_window.map(Some),
+            &saved.low_memory_window,
+        );
+        restore(
+            &mut inference.rolling_context,
+            self.rolling_context.then_some(true),
+            &saved.rolling_context,
+        );
+        restore(
+            &mut inference.constant_context,
+            self.constant_context.map(Some),
+            &saved.constant_context,
+        );
+        restore(
+            &mut inference.max_tokens,
+            self.max_tokens.map(Some),
+            &saved.max_tokens,
+        );
+    }
+}
+
+/// Puts `saved` back into `value` if it still holds the override `given`.
+fn restore<T: PartialEq + Clone>(value: &mut T, given: Option<T>, saved: &T) {
+    if given.as_ref() == Some(value) {
+        *value = saved.clone();
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, name))
+}
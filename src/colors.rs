@@ -126,6 +126,30 @@ pub fn prob_to_color(prob: f32) -> Color32 {
     }
 }
 
+/// Colors a top-1 margin (in nats): zero is the top pick, ~10 nats means
+/// the actual token was essentially unconsidered.
+/// Green at 0 through amber to red at 1, for values computed by a color
+/// expression; out-of-range values are clamped.
+pub fn scale_to_color(t: f32) -> Color32 {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    if t < 0.5 {
+        interpolate_color(RANK_PERFECT, RANK_MODERATE, t * 2.0)
+    } else {
+        interpolate_color(RANK_MODERATE, RANK_VERY_POOR, (t - 0.5) * 2.0)
+    }
+}
+
+pub fn margin_to_color(margin: f32) -> Color32 {
+    let m = margin.max(0.0);
+    if m < 1.0 {
+        interpolate_color(RANK_PERFECT, RANK_GOOD_START, m)
+    } else if m < 4.0 {
+        interpolate_color(RANK_GOOD_START, RANK_MODERATE, (m - 1.0) / 3.0)
+    } else {
+        interpolate_color(RANK_MODERATE, RANK_VERY_POOR, ((m - 4.0) / 6.0).min(1.0))
+    }
+}
+
 const DIVERGE_AGREE: Color32 = Color32::from_rgb(152, 190, 210);
 const DIVERGE_NEUTRAL: Color32 = Color32::from_rgb(195, 185, 195);
 const DIVERGE_DISAGREE: Color32 = Color32::from_rgb(195, 110, 110);
@@ -0,0 +1,254 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::analysis::AnalysisRequest;
+use crate::gguf;
+use crate::logs;
+use crate::settings::Settings;
+
+/// What goes into a diagnostic bundle beyond the logs and settings.
+#[derive(Clone, Copy)]
+pub struct BundleOptions {
+    /// Replace model paths and the home directory with placeholders.
+    pub redact_paths: bool,
+    /// Include the text and prompt of the failing request, not just their
+    /// lengths.
+    pub include_text: bool,
+}
+
+impl Default for BundleOptions {
+    fn default() -> Self {
+        Self {
+            redact_paths: true,
+            include_text: false,
+        }
+    }
+}
+
+/// Writes a zip with everything a bug report needs: the error, recent
+/// logs, the settings, the header metadata of each configured model and
+/// the parameters of the last analysis request.
+pub fn write_bundle(
+    path: &Path,
+    error: &str,
+    settings: &Settings,
+    request: Option<&AnalysisRequest>,
+    options: BundleOptions,
+) -> Result<(), String> {
+    let redactor = Redactor::new(settings, options.redact_paths);
+    let mut zip = ZipWriter::default();
+
+    zip.add("error.txt", redactor.apply(error).as_bytes());
+
+    let mut log_text = String::new();
+    for entry in logs::snapshot() {
+        let _ = writeln!(log_text, "{}", entry);
+    }
+    zip.add("logs.txt", redactor.apply(&log_text).as_bytes());
+
+    // Redacted before serializing, which escapes the backslashes of Windows
+    // paths.
+    let mut settings_value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    redactor.apply_to_value(&mut settings_value);
+    let settings_json = serde_json::to_string_pretty(&settings_value).map_err(|e| e.to_string())?;
+    zip.add("settings.json", settings_json.as_bytes());
+
+    for (label, model) in [("a", &settings.model_path_a), ("b", &settings.model_path_b)] {
+        if let Some(model) = model {
+            let info = model_info(Path::new(model));
+            zip.add(
+                &format!("model_{}.txt", label),
+                redactor.apply(&info).as_bytes(),
+            );
+        }
+    }
+
+    if let Some(request) = request {
+        let mut value = json!({
+            "prompt_chars": request.prompt.chars().count(),
+            "text_chars": request.text.chars().count(),
+            "inference": settings.inference,
+            "preprocess": settings.preprocess,
+        });
+        if options.include_text {
+            value["prompt"] = json!(request.prompt);
+            value["text"] = json!(request.text);
+        }
+        let request_json = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+        zip.add("request.json", request_json.as_bytes());
+    }
+
+    zip.add("system.txt", system_info().as_bytes());
+    std::fs::write(path, zip.finish()).map_err(|e| e.to_string())
+}
+
+fn model_info(path: &Path) -> String {
+    let mut info = format!("Path: {}\n", path.display());
+    match std::fs::metadata(path) {
+        Ok(metadata) => {
+            let _ = writeln!(info, "Size: {} bytes", metadata.len());
+        }
+        Err(e) => {
+            let _ = writeln!(info, "Not readable: {}", e);
+            return info;
+        }
+    }
+    match gguf::read_metadata(path) {
+        Ok(metadata) => {
+            info.push('\n');
+            for (key, value) in metadata {
+                let _ = writeln!(info, "{} = {}", key, value);
+            }
+        }
+        Err(e) => {
+            let _ = writeln!(info, "Failed to read GGUF metadata: {}", e);
+        }
+    }
+    info
+}
+
+fn system_info() -> String {
+    let threads = std::thread::available_parallelism().map_or(0, |n| n.get());
+    format!(
+        "Perplex {}\nOS: {} {}\nAvailable threads: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        threads
+    )
+}
+
+/// Replaces model paths with their file names and the home directory with
+/// `~`, so bundles don't reveal the user's directory layout. Paths are also
+/// replaced in their JSON- and `Debug`-escaped form, with doubled
+/// backslashes, which is how Windows paths appear in logged values.
+struct Redactor {
+    replacements: Vec<(String, String)>,
+}
+
+impl Redactor {
+    fn new(settings: &Settings, enabled: bool) -> Self {
+        let mut replacements = Vec::new();
+        if enabled {
+            let paths = [
+                &settings.model_path_a,
+                &settings.model_path_b,
+                &settings.preview_model_path,
+            ];
+            for path in paths.into_iter().flatten() {
+                let name = crate::model_name_from_path(Some(path)).unwrap_or("model");
+                push_path(&mut replacements, path, &format!("<model>/{}", name));
+            }
+            // `HOME` isn't set on Windows.
+            let home = ["HOME", "USERPROFILE"]
+                .into_iter()
+                .filter_map(|name| std::env::var(name).ok())
+                .find(|home| !home.is_empty());
+            if let Some(home) = home {
+                push_path(&mut replacements, &home, "~");
+            }
+        }
+        Self { replacements }
+    }
+
+    /// Redacts every string in `value`.
+    fn apply_to_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.apply(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.apply_to_value(item)),
+            Value::Object(fields) => fields
+                .values_mut()
+                .for_each(|field| self.apply_to_value(field)),
+            _ => {}
+        }
+    }
+
+    fn apply(&self, text: &str) -> String {
+        self.replacements
+            .iter()
+            .fold(text.to_string(), |text, (from, to)| text.replace(from, to))
+    }
+}
+
+/// Adds the replacement of `path`, and of its escaped form if it has
+/// backslashes.
+fn push_path(replacements: &mut Vec<(String, String)>, path: &str, to: &str) {
+    if path.contains('\\') {
+        replacements.push((path.replace('\\', "\\\\"), to.to_string()));
+    }
+    replacements.push((path.to_string(), to.to_string()));
+}
+
+/// Minimal zip archive writer with deflate-compressed entries.
+#[derive(Default)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    central: Vec<u8>,
+    count: u16,
+}
+
+impl ZipWriter {
+    pub fn add(&mut self, name: &str, content: &[u8]) {
+        let compressed = miniz_oxide::deflate::compress_to_vec(content, 6);
+        let crc = crc32(content);
+        let offset = self.data.len() as u32;
+
+        // Version 2.0, no flags, deflate, zero DOS time and date.
+        let fields = |out: &mut Vec<u8>| {
+            out.extend_from_slice(&20u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&8u16.to_le_bytes());
+            out.extend_from_slice(&0u32.to_le_bytes());
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        };
+
+        self.data.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        fields(&mut self.data);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(&compressed);
+
+        self.central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        self.central.extend_from_slice(&20u16.to_le_bytes());
+        fields(&mut self.central);
+        // Comment length, disk number, internal and external attributes.
+        self.central.extend_from_slice(&[0; 10]);
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        self.count += 1;
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let central_offset = self.data.len() as u32;
+        let central_size = self.central.len() as u32;
+        self.data.append(&mut self.central);
+        self.data.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        self.data.extend_from_slice(&[0; 4]);
+        self.data.extend_from_slice(&self.count.to_le_bytes());
+        self.data.extend_from_slice(&self.count.to_le_bytes());
+        self.data.extend_from_slice(&central_size.to_le_bytes());
+        self.data.extend_from_slice(&central_offset.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes());
+        self.data
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
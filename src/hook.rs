@@ -0,0 +1,212 @@
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::analysis::AnalysisResult;
+use crate::store::Metric;
+
+/// How the hook command receives the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HookInput {
+    /// The JSON is written to the command's standard input.
+    Stdin,
+    /// The JSON is saved to a temporary file whose path is passed as the
+    /// command's last argument.
+    TempFile,
+}
+
+impl HookInput {
+    pub const ALL: [HookInput; 2] = [HookInput::Stdin, HookInput::TempFile];
+}
+
+impl std::fmt::Display for HookInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookInput::Stdin => write!(f, "JSON on standard input"),
+            HookInput::TempFile => write!(f, "Path of a JSON file as last argument"),
+        }
+    }
+}
+
+/// A command run after every finished analysis; set in the settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookOptions {
+    pub enabled: bool,
+    /// Run through the shell (`sh -c` or `cmd /C`), so pipes and
+    /// redirections work.
+    pub command: String,
+    pub input: HookInput,
+}
+
+impl Default for HookOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: String::new(),
+            input: HookInput::Stdin,
+        }
+    }
+}
+
+impl HookOptions {
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.command.trim().is_empty()
+    }
+}
+
+/// Runs the hook command for a finished analysis in the background. The
+/// JSON holds the model, the text's name, the summary metrics and the full
+/// result; `PERPLEX_MODEL` and `PERPLEX_PERPLEXITY` are also set in the
+/// command's environment. Failures and the command's output are logged.
+pub fn run(options: &HookOptions, model: &str, name: &str, result: Arc<AnalysisResult>) {
+    if !options.is_active() {
+        return;
+    }
+    let (command, input) = (options.command.clone(), options.input);
+    let (model, name) = (model.to_string(), name.to_string());
+    std::thread::spawn(move || {
+        let payload = match serde_json::to_vec(&payload(&model, &name, &result)) {
+            Ok(payload) => payload,
+            Err(e) => {
+                log::warn!("Failed to serialize the result for the hook: {}", e);
+                return;
+            }
+        };
+        let perplexity = result.perplexity().to_string();
+        let outcome = match input {
+            HookInput::Stdin => run_with_stdin(&command, &payload, &model, &perplexity),
+            HookInput::TempFile => run_with_file(&command, &payload, &model, &perplexity),
+        };
+        if let Err(e) = outcome {
+            log::warn!("Post-analysis hook failed: {}", e);
+        }
+    });
+}
+
+/// The JSON describing one result: the model, the text's name, the summary
+/// metrics and the full result. Also written next to watched texts.
+pub fn payload(model: &str, name: &str, result: &AnalysisResult) -> serde_json::Value {
+    let metrics: serde_json::Map<String, serde_json::Value> = Metric::ALL
+        .iter()
+        .map(|metric| (metric_key(*metric).to_string(), json!(metric.of(result))))
+        .collect();
+    json!({
+        "model": model,
+        "name": name,
+        "metrics": metrics,
+        "result": result,
+    })
+}
+
+fn metric_key(metric: Metric) -> &'static str {
+    match metric {
+        Metric::Perplexity => "perplexity",
+        Metric::BitsPerChar => "bits_per_char",
+        Metric::Top1Accuracy => "top1_accuracy",
+        Metric::MedianRank => "median_rank",
+    }
+}
+
+fn run_with_stdin(command: &str, payload: &[u8], model: &str, ppl: &str) -> Result<(), String> {
+    let mut child = shell(command, None)
+        .env("PERPLEX_MODEL", model)
+        .env("PERPLEX_PERPLEXITY", ppl)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let stdin = child.stdin.take();
+    // Written from its own thread while the output is read, so a command
+    // that echoes its input can't fill the output pipe and block both.
+    let output = std::thread::scope(|scope| {
+        if let Some(mut stdin) = stdin {
+            // A command that ignores its input closes the pipe early;
+            // that's not an error of the hook.
+            scope.spawn(move || {
+                let _ = stdin.write_all(payload);
+            });
+        }
+        child.wait_with_output()
+    })
+    .map_err(|e| e.to_string())?;
+    log_output(&output)
+}
+
+fn run_with_file(command: &str, payload: &[u8], model: &str, ppl: &str) -> Result<(), String> {
+    let path = temp_path();
+    std::fs::write(&path, payload).map_err(|e| e.to_string())?;
+    let output = shell(command, Some(&path))
+        .env("PERPLEX_MODEL", model)
+        .env("PERPLEX_PERPLEXITY", ppl)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string());
+    let _ = std::fs::remove_file(&path);
+    log_output(&output?)
+}
+
+fn log_output(output: &std::process::Output) -> Result<(), String> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !stdout.trim().is_empty() {
+        log::info!("Hook output: {}", stdout.trim());
+    }
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn temp_path() -> PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    std::env::temp_dir().join(format!(
+        "perplex_result_{}_{}.json",
+        std::process::id(),
+        nanos
+    ))
+}
+
+#[cfg(target_os = "windows")]
+fn shell(command: &str, file: Option<&PathBuf>) -> Command {
+    let mut shell = Command::new("cmd");
+    let command = match file {
+        Some(file) => format!("{} \"{}\"", command, file.display()),
+        None => command.to_string(),
+    };
+    shell.args(["/C", &command]);
+    shell
+}
+
+/// The command run by the platform's shell, with `file` appended as its
+/// last argument.
+#[cfg(not(target_os = "windows"))]
+fn shell(command: &str, file: Option<&PathBuf>) -> Command {
+    let mut shell = Command::new("sh");
+    match file {
+        // The path is passed as a positional parameter rather than pasted
+        // into the command, so it needs no quoting.
+        Some(file) => {
+            shell
+                .arg("-c")
+                .arg(format!("{} \"$1\"", command))
+                .arg("perplex-hook")
+                .arg(file);
+        }
+        None => {
+            shell.arg("-c").arg(command);
+        }
+    }
+    shell
+}
@@ -0,0 +1,185 @@
+use std::path::Path;
+
+use egui::Color32;
+use serde_json::{json, Value};
+
+use crate::analysis::AnalysisResult;
+use crate::colors;
+use crate::report::{self, ReportSection, RANK_BUCKETS};
+
+/// Page skeleton; `__DATA__` is replaced with the JSON of every section.
+/// Everything is rendered client-side so the file works offline.
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Perplex report</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 960px; color: #222; }
+h1 { font-size: 1.6em; } h2 { margin-top: 2em; border-bottom: 1px solid #ccc; }
+.summary td { padding: 2px 16px 2px 0; } .summary td:first-child { color: #666; }
+.text { font-family: monospace; white-space: pre-wrap; line-height: 1.7; border: 1px solid #ddd; padding: 10px; }
+.text span { color: #000; border-radius: 2px; cursor: default; }
+.text span.unscored { color: #888; }
+#tip { position: fixed; display: none; background: #fff; border: 1px solid #999; padding: 6px 8px;
+       font: 12px monospace; box-shadow: 0 2px 6px rgba(0,0,0,.2); pointer-events: none; white-space: pre; }
+.tokens { border-collapse: collapse; font-size: 13px; width: 100%; }
+.tokens th { cursor: pointer; text-align: left; background: #eee; position: sticky; top: 0; }
+.tokens td, .tokens th { padding: 2px 8px; } .tokens tr:nth-child(even) td { background: #f7f7f7; }
+.table-wrap { max-height: 400px; overflow-y: auto; border: 1px solid #ddd; }
+svg text { font-size: 11px; fill: #444; }
+</style>
+</head>
+<body>
+<h1>Perplex report</h1>
+<div id="sections"></div>
+<div id="tip"></div>
+<script>
+const DATA = __DATA__;
+const tip = document.getElementById("tip");
+const esc = s => s.replace(/[&<>"]/g, c => ({"&": "&amp;", "<": "&lt;", ">": "&gt;", '"': "&quot;"}[c]));
+const show = s => JSON.stringify(s).slice(1, -1);
+
+function histogram(section) {
+  const w = 900, h = 160, max = Math.max(1, ...section.ranks.map(b => b.count));
+  const total = section.ranks.reduce((a, b) => a + b.count, 0) || 1, slot = w / section.ranks.length;
+  let svg = `<svg width="${w}" height="${h + 30}">`;
+  section.ranks.forEach((b, i) => {
+    const bh = h * b.count / max, x = i * slot + 10;
+    svg += `<rect x="${x}" y="${h - bh + 15}" width="${slot - 20}" height="${bh}" fill="${b.color}"><title>${b.count} tokens</title></rect>`;
+    svg += `<text x="${x}" y="${h - bh + 11}">${(100 * b.count / total).toFixed(0)}%</text>`;
+    svg += `<text x="${x}" y="${h + 28}">rank ${b.label}</text>`;
+  });
+  return svg + "</svg>";
+}
+
+function surprisalChart(section) {
+  const values = section.tokens.filter(t => !t.unscored).map(t => t.surprisal);
+  if (values.length < 2) return "";
+  const w = 900, h = 140, max = Math.ceil(Math.max(1, ...values));
+  const points = values.map((v, i) => `${(i * w / (values.length - 1)).toFixed(1)},${(h - h * v / max + 5).toFixed(1)}`);
+  return `<svg width="${w}" height="${h + 20}"><polyline fill="none" stroke="#b87a44" stroke-width="1" points="${points.join(" ")}"/>` +
+    `<line x1="0" y1="${h + 5}" x2="${w}" y2="${h + 5}" stroke="#999"/><text x="2" y="14">${max} bits</text></svg>`;
+}
+
+function tokenTable(section, id) {
+  const cols = [["#", "index"], ["Token", "text"], ["Rank", "rank"], ["Probability", "probability"], ["Surprisal", "surprisal"]];
+  let html = `<div class="table-wrap"><table class="tokens" id="${id}"><thead><tr>`;
+  cols.forEach(([name, key]) => html += `<th data-key="${key}">${name}</th>`);
+  return html + "</tr></thead><tbody></tbody></table></div>";
+}
+
+function fillTable(table, rows) {
+  table.tBodies[0].innerHTML = rows.map(t =>
+    `<tr><td>${t.index}</td><td><code>${esc(show(t.text))}</code></td><td>${t.rank}</td>` +
+    `<td>${(t.probability * 100).toFixed(2)}%</td><td>${t.surprisal.toFixed(2)}</td></tr>`).join("");
+}
+
+const root = document.getElementById("sections");
+DATA.forEach((section, s) => {
+  section.tokens.forEach((t, i) => t.index = i);
+  const div = document.createElement("section");
+  div.innerHTML = `<h2>${esc(section.name)}</h2>` +
+    `<table class="summary">${section.summary.map(([k, v]) => `<tr><td>${esc(k)}</td><td>${esc(v)}</td></tr>`).join("")}</table>` +
+    `<h3>Rank distribution</h3>${histogram(section)}` +
+    `<h3>Surprisal by position</h3>${surprisalChart(section)}` +
+    `<h3>Text</h3><div class="text">${section.tokens.map((t, i) =>
+      `<span data-s="${s}" data-i="${i}"${t.unscored ? ' class="unscored"' : ` style="background:${t.color}"`}>${esc(t.text)}</span>`).join("")}</div>` +
+    `<h3>Tokens</h3>${tokenTable(section, "table" + s)}`;
+  root.appendChild(div);
+
+  const table = div.querySelector("table.tokens");
+  const scored = section.tokens.filter(t => !t.unscored);
+  let sortKey = "index", ascending = true;
+  fillTable(table, scored);
+  table.querySelectorAll("th").forEach(th => th.addEventListener("click", () => {
+    const key = th.dataset.key;
+    ascending = key === sortKey ? !ascending : true;
+    sortKey = key;
+    const sorted = [...scored].sort((a, b) => {
+      const order = typeof a[key] === "string" ? a[key].localeCompare(b[key]) : a[key] - b[key];
+      return ascending ? order : -order;
+    });
+    fillTable(table, sorted);
+  }));
+});
+
+document.addEventListener("mouseover", e => {
+  const el = e.target.closest(".text span");
+  if (!el) { tip.style.display = "none"; return; }
+  const t = DATA[el.dataset.s].tokens[el.dataset.i];
+  let text = `"${show(t.text)}"`;
+  if (t.unscored) {
+    text += "\nnot scored";
+  } else {
+    text += `\nrank ${t.rank} · p ${(t.probability * 100).toFixed(2)}% · ${t.surprisal.toFixed(2)} bits`;
+    if (t.top.length) text += "\n\ntop predictions:\n" + t.top.map(([p, q]) => `  "${show(p)}" ${(q * 100).toFixed(2)}%`).join("\n");
+  }
+  tip.textContent = text;
+  tip.style.display = "block";
+});
+document.addEventListener("mousemove", e => {
+  tip.style.left = Math.min(e.clientX + 12, window.innerWidth - tip.offsetWidth - 4) + "px";
+  tip.style.top = (e.clientY + 16) + "px";
+});
+</script>
+</body>
+</html>
+"##;
+
+/// Writes a standalone HTML page embedding the token data, with hover
+/// tooltips, a sortable token table and the charts of the PDF report.
+pub fn write_html(path: &Path, sections: &[ReportSection]) -> Result<(), String> {
+    let data: Vec<Value> = sections.iter().map(section_json).collect();
+    let data = serde_json::to_string(&data).map_err(|e| e.to_string())?;
+    // Token text may contain `</script>`.
+    let html = TEMPLATE.replace("__DATA__", &data.replace("</", "<\\/"));
+    std::fs::write(path, html).map_err(|e| e.to_string())
+}
+
+fn section_json(section: &ReportSection) -> Value {
+    let result: &AnalysisResult = section.result;
+    let first_scored = result.prompt_tokens.max(1);
+    let tokens: Vec<Value> = result
+        .tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| {
+            let top: Vec<Value> = token
+                .top_predictions
+                .iter()
+                .map(|&(id, p)| json!([result.vocab.piece(id), p]))
+                .collect();
+            json!({
+                "text": token.text,
+                "unscored": i < first_scored,
+                "rank": token.rank,
+                "probability": token.probability,
+                "surprisal": token.surprisal(),
+                "color": hex(colors::rank_to_color(token.rank)),
+                "top": top,
+            })
+        })
+        .collect();
+    let ranks: Vec<Value> = RANK_BUCKETS
+        .iter()
+        .zip(report::rank_counts(result))
+        .map(|(&(label, _, color_rank), count)| {
+            json!({
+                "label": label,
+                "count": count,
+                "color": hex(colors::rank_to_color(color_rank)),
+            })
+        })
+        .collect();
+    json!({
+        "name": section.name,
+        "summary": report::summary(result, &section.format),
+        "ranks": ranks,
+        "tokens": tokens,
+    })
+}
+
+fn hex(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
@@ -0,0 +1,175 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::store;
+
+/// Column or field names accepted for each part of a labeled text, matched
+/// case-insensitively.
+const TEXT_COLUMNS: [&str; 3] = ["text", "content", "body"];
+const LABEL_COLUMNS: [&str; 5] = ["label", "labels", "tag", "tags", "class"];
+const NAME_COLUMNS: [&str; 3] = ["name", "id", "title"];
+
+/// One text of an evaluation set, with the tags to store its analysis under.
+pub struct LabeledText {
+    pub name: String,
+    pub text: String,
+    pub tags: Vec<String>,
+}
+
+/// Reads an evaluation set: a CSV or TSV file with a header row, or a JSONL
+/// file with one object per line. Each row needs a text and may have a
+/// label, which can hold several comma-separated tags, and a name. Rows
+/// without a name are named after the file and their line.
+pub fn read_labeled(path: &Path) -> Result<Vec<LabeledText>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let stem = path.file_stem().map_or_else(
+        || "import".to_string(),
+        |s| s.to_string_lossy().into_owned(),
+    );
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let texts = match extension.as_str() {
+        "jsonl" | "ndjson" => read_jsonl(&contents, &stem)?,
+        "tsv" => read_delimited(&contents, '\t', &stem)?,
+        _ => read_delimited(&contents, ',', &stem)?,
+    };
+    if texts.is_empty() {
+        return Err("No texts found".to_string());
+    }
+    Ok(texts)
+}
+
+fn read_jsonl(contents: &str, stem: &str) -> Result<Vec<LabeledText>, String> {
+    let mut texts = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value =
+            serde_json::from_str(line).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+        let Value::Object(object) = value else {
+            return Err(format!("Line {}: expected a JSON object", i + 1));
+        };
+        let field = |names: &[&str]| {
+            object
+                .iter()
+                .find(|(key, _)| names.iter().any(|n| key.eq_ignore_ascii_case(n)))
+                .map(|(_, value)| value)
+        };
+        let Some(text) = field(&TEXT_COLUMNS).and_then(Value::as_str) else {
+            return Err(format!("Line {}: no \"text\" field", i + 1));
+        };
+        let tags = match field(&LABEL_COLUMNS) {
+            Some(Value::Array(labels)) => labels.iter().flat_map(json_tags).collect(),
+            Some(label) => json_tags(label),
+            None => Vec::new(),
+        };
+        texts.push(LabeledText {
+            name: field(&NAME_COLUMNS)
+                .map(json_string)
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| format!("{} #{}", stem, i + 1)),
+            text: text.to_string(),
+            tags,
+        });
+    }
+    Ok(texts)
+}
+
+/// Labels are often numbers or booleans rather than strings.
+fn json_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn json_tags(value: &Value) -> Vec<String> {
+    store::parse_tags(&json_string(value))
+}
+
+fn read_delimited(contents: &str, delimiter: char, stem: &str) -> Result<Vec<LabeledText>, String> {
+    let mut rows = parse_delimited(contents, delimiter)?.into_iter();
+    let Some(header) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+    };
+    let Some(text_column) = column(&TEXT_COLUMNS) else {
+        return Err(format!(
+            "The header row has no \"text\" column (found: {})",
+            header.join(", ")
+        ));
+    };
+    let label_column = column(&LABEL_COLUMNS);
+    let name_column = column(&NAME_COLUMNS);
+
+    let mut texts = Vec::new();
+    for (i, row) in rows.enumerate() {
+        let cell = |column: Option<usize>| column.and_then(|c| row.get(c)).map(String::as_str);
+        let text = cell(Some(text_column)).unwrap_or_default();
+        if text.trim().is_empty() {
+            continue;
+        }
+        texts.push(LabeledText {
+            name: cell(name_column)
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map_or_else(|| format!("{} #{}", stem, i + 1), str::to_string),
+            text: text.to_string(),
+            tags: store::parse_tags(cell(label_column).unwrap_or_default()),
+        });
+    }
+    Ok(texts)
+}
+
+/// Splits CSV-style rows into fields. Quoted fields may contain the
+/// delimiter, line breaks and doubled quotes.
+fn parse_delimited(contents: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|f| !f.is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("A quoted field is never closed".to_string());
+    }
+    row.push(field);
+    if row.iter().any(|f| !f.is_empty()) {
+        rows.push(row);
+    }
+    Ok(rows)
+}
@@ -0,0 +1,96 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Holds the port and token of the running window.
+const INSTANCE_FILE_NAME: &str = ".perplex/instance";
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Requests reaching the window from outside it: later `perplex` launches,
+/// the global shortcut and the tray menu.
+pub enum RemoteCommand {
+    /// Raise the window and analyze the clipboard contents.
+    PasteAndAnalyze,
+    /// Raise the window.
+    #[cfg(feature = "tray")]
+    Show,
+    /// Close the window.
+    #[cfg(feature = "tray")]
+    Quit,
+}
+
+fn instance_file() -> PathBuf {
+    env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .join(INSTANCE_FILE_NAME)
+}
+
+/// Listens on a loopback port for commands from later `perplex` launches,
+/// recording the port in `~/.perplex/instance`. Each connection sends one
+/// line, `<token> <command>`; the random token keeps other local programs
+/// that didn't read the file from driving the window.
+pub fn listen(ctx: egui::Context, tx: mpsc::Sender<RemoteCommand>) -> Result<(), String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|e| e.to_string())?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let token = RandomState::new().build_hasher().finish();
+
+    let path = instance_file();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, format!("{} {:016x}", port, token)).map_err(|e| e.to_string())?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut line = String::new();
+            if BufReader::new(stream).read_line(&mut line).is_err() {
+                continue;
+            }
+            let command = match line.trim().split_once(' ') {
+                Some((t, command)) if t == format!("{:016x}", token) => command,
+                _ => {
+                    log::warn!("Ignoring a remote command with a wrong token");
+                    continue;
+                }
+            };
+            let command = match command {
+                "paste-analyze" => RemoteCommand::PasteAndAnalyze,
+                other => {
+                    log::warn!("Unknown remote command '{}'", other);
+                    continue;
+                }
+            };
+            if tx.send(command).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    });
+    Ok(())
+}
+
+/// Sends `paste-analyze` to a running window. Returns false when none is
+/// listening, e.g. after a crash left a stale instance file.
+pub fn send_paste_analyze() -> bool {
+    let Ok(content) = fs::read_to_string(instance_file()) else {
+        return false;
+    };
+    let Some((port, token)) = content
+        .trim()
+        .split_once(' ')
+        .and_then(|(port, token)| Some((port.parse::<u16>().ok()?, token)))
+    else {
+        return false;
+    };
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+        .and_then(|mut stream| writeln!(stream, "{} paste-analyze", token))
+        .is_ok()
+}
@@ -0,0 +1,233 @@
+use std::io::{BufRead, Write};
+
+use perplex_core::analysis::{AnalysisRequest, AnalysisResult};
+use perplex_core::llamacpp::LlamaAnalyzer;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::settings::Settings;
+
+/// One line of input: `{"id": 1, "method": "analyze", "params": {...}}`.
+/// The `id` is echoed back unchanged so clients can match replies.
+#[derive(Deserialize)]
+struct IpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct DiagnosticsParams {
+    #[serde(flatten)]
+    request: AnalysisRequest,
+    /// Tokens ranked better than this aren't reported.
+    #[serde(default = "default_min_rank")]
+    min_rank: usize,
+}
+
+fn default_min_rank() -> usize {
+    11
+}
+
+#[derive(Deserialize)]
+struct TokenizeParams {
+    text: String,
+    #[serde(default = "default_add_bos")]
+    add_bos: bool,
+}
+
+fn default_add_bos() -> bool {
+    true
+}
+
+/// Loads model A of `settings`, the model the headless modes serve.
+pub fn load_analyzer(settings: &Settings) -> Result<(LlamaAnalyzer, &str), String> {
+    let path = settings
+        .model_path_a
+        .as_deref()
+        .ok_or("No model configured; pass --model")?;
+    let mut analyzer = LlamaAnalyzer::new();
+    analyzer.load_model(path, settings.inference.clone())?;
+    Ok((
+        analyzer,
+        crate::model_name_from_path(Some(path)).unwrap_or(path),
+    ))
+}
+
+/// JSON-lines protocol over stdin/stdout for editor integrations. Each
+/// request line gets exactly one reply line, `{"id", "result"}` or
+/// `{"id", "error"}`; the process exits at end of input or on `shutdown`.
+/// Logs go to stderr so stdout carries only replies.
+///
+/// Methods:
+/// - `analyze` `{text, prompt?}`: perplexity and per-span annotations with
+///   byte offsets into `text`. Unlike the GUI, no preprocessing is applied
+///   so the offsets match the editor buffer.
+/// - `diagnostics` `{text, prompt?, min_rank?}`: the same analysis as LSP
+///   `Diagnostic` objects, for tokens ranked `min_rank` or worse.
+/// - `tokenize` `{text, add_bos?}`: the tokens of `text`.
+/// - `shutdown`: replies `null` and exits.
+pub fn run(settings: &Settings) -> Result<(), String> {
+    let (analyzer, model_name) = load_analyzer(settings)?;
+    log::info!("Serving {} over stdio", model_name);
+
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|e| format!("Failed to read stdin: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (id, reply, shutdown) = match serde_json::from_str::<IpcRequest>(&line) {
+            Ok(request) => {
+                let shutdown = request.method == "shutdown";
+                (
+                    request.id,
+                    handle(&analyzer, &request.method, request.params),
+                    shutdown,
+                )
+            }
+            Err(e) => (Value::Null, Err(format!("Invalid request: {}", e)), false),
+        };
+        let reply = match reply {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(error) => json!({ "id": id, "error": error }),
+        };
+        writeln!(stdout, "{}", reply)
+            .and_then(|_| stdout.flush())
+            .map_err(|e| format!("Failed to write stdout: {}", e))?;
+        if shutdown {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn handle(analyzer: &LlamaAnalyzer, method: &str, params: Value) -> Result<Value, String> {
+    match method {
+        "analyze" => {
+            let request: AnalysisRequest = parse_params(params)?;
+            let result = analyzer.analyze(&request, None)?;
+            Ok(json!({
+                "perplexity": result.perplexity(),
+                "spans": spans_json(&result, &request.text),
+            }))
+        }
+        "diagnostics" => {
+            let params: DiagnosticsParams = parse_params(params)?;
+            let result = analyzer.analyze(&params.request, None)?;
+            Ok(json!({
+                "perplexity": result.perplexity(),
+                "diagnostics": diagnostics_json(&result, &params.request.text, params.min_rank),
+            }))
+        }
+        "tokenize" => {
+            let params: TokenizeParams = parse_params(params)?;
+            let tokens = analyzer.list_tokens(&params.text, params.add_bos)?;
+            Ok(json!({ "tokens": tokens }))
+        }
+        "shutdown" => Ok(Value::Null),
+        other => Err(format!("Unknown method '{}'", other)),
+    }
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, String> {
+    serde_json::from_value(params).map_err(|e| format!("Invalid params: {}", e))
+}
+
+/// Scored tokens with their byte range in `text`, surprisal and the
+/// model's top predictions at that position.
+fn spans_json(result: &AnalysisResult, text: &str) -> Vec<Value> {
+    result
+        .spans(text)
+        .into_iter()
+        .map(|(i, range)| {
+            let token = &result.tokens[i];
+            let top: Vec<Value> = token
+                .top_predictions
+                .iter()
+                .map(|&(id, p)| json!([result.vocab.piece(id), p]))
+                .collect();
+            json!({
+                "start": range.start,
+                "end": range.end,
+                "text": token.text,
+                "rank": token.rank,
+                "probability": token.probability,
+                "surprisal": token.surprisal(),
+                "top": top,
+            })
+        })
+        .collect()
+}
+
+/// Spans ranked `min_rank` or worse as LSP diagnostics. Positions are
+/// zero-based lines and UTF-16 code units, as the protocol specifies;
+/// severity goes from hint (rank 11-50) to warning (above 100).
+fn diagnostics_json(result: &AnalysisResult, text: &str, min_rank: usize) -> Vec<Value> {
+    let mut position = Utf16Position::default();
+    result
+        .spans(text)
+        .into_iter()
+        .filter(|(i, _)| result.tokens[*i].rank >= min_rank)
+        .map(|(i, range)| {
+            let token = &result.tokens[i];
+            let start = position.advance(text, range.start);
+            let end = position.advance(text, range.end);
+            let severity = match token.rank {
+                0..=50 => 4,
+                51..=100 => 3,
+                _ => 2,
+            };
+            let mut message = format!(
+                "rank {} · p {:.2}% · {:.2} bits",
+                token.rank,
+                token.probability * 100.0,
+                token.surprisal()
+            );
+            if !token.top_predictions.is_empty() {
+                let expected: Vec<String> = token
+                    .top_predictions
+                    .iter()
+                    .map(|&(id, p)| format!("{:?} {:.2}%", result.vocab.piece(id), p * 100.0))
+                    .collect();
+                message += &format!("\nexpected: {}", expected.join(", "));
+            }
+            json!({
+                "range": { "start": start, "end": end },
+                "severity": severity,
+                "source": "perplex",
+                "message": message,
+                "data": {
+                    "rank": token.rank,
+                    "probability": token.probability,
+                    "surprisal": token.surprisal(),
+                },
+            })
+        })
+        .collect()
+}
+
+/// Running conversion of increasing byte offsets to LSP positions.
+#[derive(Default)]
+struct Utf16Position {
+    offset: usize,
+    line: usize,
+    character: usize,
+}
+
+impl Utf16Position {
+    fn advance(&mut self, text: &str, offset: usize) -> Value {
+        for c in text[self.offset..offset].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.character = 0;
+            } else {
+                self.character += c.len_utf16();
+            }
+        }
+        self.offset = offset;
+        json!({ "line": self.line, "character": self.character })
+    }
+}
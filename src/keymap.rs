@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+
+use egui::{Key, KeyboardShortcut, Modifiers};
+use serde::{Deserialize, Serialize};
+
+/// Actions that can be bound to a keyboard shortcut.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Analyze,
+    Cancel,
+    OpenFile,
+    Export,
+    Settings,
+    CommandPalette,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::Analyze,
+        Action::Cancel,
+        Action::OpenFile,
+        Action::Export,
+        Action::Settings,
+        Action::CommandPalette,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Analyze => "Analyze",
+            Action::Cancel => "Cancel analysis",
+            Action::OpenFile => "Open text file",
+            Action::Export => "Export HTML report",
+            Action::Settings => "Open settings",
+            Action::CommandPalette => "Command palette",
+        }
+    }
+
+    fn default_shortcut(self) -> &'static str {
+        match self {
+            Action::Analyze => "Ctrl+Enter",
+            Action::Cancel => "Escape",
+            Action::OpenFile => "Ctrl+O",
+            Action::Export => "Ctrl+E",
+            Action::Settings => "Ctrl+,",
+            Action::CommandPalette => "Ctrl+Shift+P",
+        }
+    }
+}
+
+/// Shortcut of each action as text like `Ctrl+Shift+P`, so the settings
+/// file stays hand-editable. An empty string leaves the action unbound;
+/// actions missing from the map use their default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Keymap(BTreeMap<Action, String>);
+
+impl Keymap {
+    pub fn binding_mut(&mut self, action: Action) -> &mut String {
+        self.0
+            .entry(action)
+            .or_insert_with(|| action.default_shortcut().to_string())
+    }
+
+    fn binding(&self, action: Action) -> &str {
+        self.0
+            .get(&action)
+            .map_or(action.default_shortcut(), String::as_str)
+    }
+
+    pub fn shortcut(&self, action: Action) -> Option<KeyboardShortcut> {
+        parse(self.binding(action)).ok().flatten()
+    }
+
+    /// Returns a human-readable message for every unparsable or duplicate
+    /// binding.
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut seen: Vec<(KeyboardShortcut, Action)> = Vec::new();
+        for action in Action::ALL {
+            match parse(self.binding(action)) {
+                Ok(Some(shortcut)) => {
+                    if let Some((_, other)) = seen.iter().find(|(s, _)| *s == shortcut) {
+                        errors.push(format!(
+                            "Shortcut {} is used by both {} and {}",
+                            self.binding(action),
+                            other.label(),
+                            action.label()
+                        ));
+                    }
+                    seen.push((shortcut, action));
+                }
+                Ok(None) => {}
+                Err(e) => errors.push(format!("{} shortcut: {}", action.label(), e)),
+            }
+        }
+        errors
+    }
+
+    /// Consumes the first pressed shortcut, trying the ones with more
+    /// modifiers first so `Ctrl+Shift+P` isn't taken for `Ctrl+P`.
+    pub fn consume(&self, input: &mut egui::InputState) -> Option<Action> {
+        let mut shortcuts: Vec<(KeyboardShortcut, Action)> = Action::ALL
+            .into_iter()
+            .filter_map(|action| Some((self.shortcut(action)?, action)))
+            .collect();
+        shortcuts.sort_by_key(|(s, _)| std::cmp::Reverse(modifier_count(s.modifiers)));
+        shortcuts
+            .into_iter()
+            .find(|(shortcut, _)| input.consume_shortcut(shortcut))
+            .map(|(_, action)| action)
+    }
+}
+
+fn modifier_count(modifiers: Modifiers) -> usize {
+    [modifiers.command, modifiers.shift, modifiers.alt]
+        .into_iter()
+        .filter(|&m| m)
+        .count()
+}
+
+/// Parses `Ctrl+Shift+P`-style text. `Ctrl` and `Cmd` both mean the
+/// platform command key; an empty string is no shortcut.
+fn parse(text: &str) -> Result<Option<KeyboardShortcut>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    // Split off the key first so `Ctrl++` binds the plus key.
+    let (mods, key) = match text.rsplit_once('+') {
+        Some((mods, "")) => (mods.strip_suffix('+').unwrap_or(mods), "+"),
+        Some((mods, key)) => (mods, key),
+        None => ("", text),
+    };
+    let key = Key::from_name(key.trim()).ok_or_else(|| format!("unknown key '{}'", key.trim()))?;
+    let mut modifiers = Modifiers::NONE;
+    for part in mods.split('+').map(str::trim).filter(|p| !p.is_empty()) {
+        modifiers = modifiers
+            | match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "cmd" | "command" => Modifiers::COMMAND,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "option" => Modifiers::ALT,
+                _ => return Err(format!("unknown modifier '{}'", part)),
+            };
+    }
+    Ok(Some(KeyboardShortcut::new(modifiers, key)))
+}
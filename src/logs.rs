@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+/// Maximum number of records kept for the in-app log viewer.
+const CAPACITY: usize = 2000;
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    /// Seconds since the application started.
+    pub elapsed: f32,
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:>8.3}s {:<5} {}] {}",
+            self.elapsed, self.level, self.target, self.message
+        )
+    }
+}
+
+static BUFFER: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+static START: OnceLock<Instant> = OnceLock::new();
+
+fn push(level: log::Level, target: &str, message: &str) {
+    let elapsed = START.get_or_init(Instant::now).elapsed().as_secs_f32();
+    let mut buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+    if buffer.len() == CAPACITY {
+        buffer.pop_front();
+    }
+    buffer.push_back(LogEntry {
+        elapsed,
+        level,
+        target: target.to_string(),
+        message: message.trim_end().to_string(),
+    });
+}
+
+/// Returns a copy of the captured records, oldest first.
+pub fn snapshot() -> Vec<LogEntry> {
+    let buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+    buffer.iter().cloned().collect()
+}
+
+/// Returns the most recent record at `level` or more severe.
+pub fn last_at_or_above(level: log::Level) -> Option<LogEntry> {
+    let buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+    buffer.iter().rev().find(|e| e.level <= level).cloned()
+}
+
+pub fn clear() {
+    BUFFER.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// `log` backend that records every message into the ring buffer and
+/// forwards it to env_logger for terminal output.
+struct CaptureLogger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Debug
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        push(record.level(), record.target(), &record.args().to_string());
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Receives llama.cpp's native log lines (routed through `tracing` by
+/// llama-cpp-2) and re-emits them as `log` records.
+struct LlamaLogLayer;
+
+#[derive(Default)]
+struct LlamaLogVisitor {
+    message: String,
+    module: Option<String>,
+}
+
+impl tracing::field::Visit for LlamaLogVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            "module" => self.module = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        }
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for LlamaLogLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = LlamaLogVisitor::default();
+        event.record(&mut visitor);
+
+        let level = match *event.metadata().level() {
+            tracing::Level::ERROR => log::Level::Error,
+            tracing::Level::WARN => log::Level::Warn,
+            tracing::Level::INFO => log::Level::Info,
+            tracing::Level::DEBUG => log::Level::Debug,
+            tracing::Level::TRACE => log::Level::Trace,
+        };
+        let target = visitor
+            .module
+            .as_deref()
+            .unwrap_or_else(|| event.metadata().target());
+
+        log::logger().log(
+            &log::Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", visitor.message))
+                .build(),
+        );
+    }
+}
+
+/// Installs the capturing logger and routes llama.cpp's logging into it.
+/// Safe to call more than once; only the first call has an effect.
+pub fn init() {
+    START.get_or_init(Instant::now);
+
+    let inner = env_logger::Builder::from_default_env().build();
+    if log::set_boxed_logger(Box::new(CaptureLogger { inner })).is_err() {
+        return;
+    }
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let subscriber = tracing_subscriber::registry().with(LlamaLogLayer);
+    if tracing::subscriber::set_global_default(subscriber).is_ok() {
+        perplex_core::llamacpp::send_logs_to_tracing();
+    }
+}
@@ -1,17 +1,88 @@
-mod analysis;
+mod cli;
 mod colors;
-mod llamacpp;
+mod diagnostics;
+mod hook;
+mod html_report;
+mod import;
+mod instance;
+mod ipc;
+mod keymap;
+mod logs;
+mod notify;
+mod number_format;
+mod report;
+mod result_stats;
+mod server;
 mod settings;
+mod store;
+mod template_report;
+mod text_report;
+mod throughput;
+mod tray;
+mod ui_logs;
 mod ui_main;
+mod ui_palette;
+mod ui_resources;
 mod ui_settings;
+mod ui_tokenizer;
 mod ui_tokens;
-mod worker;
+mod ui_trends;
+mod update;
+mod watch;
+mod xlsx_report;
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 
 use eframe::egui;
+use perplex_core::{
+    analysis, benchmark, bos, conditioning, confusables, determinism, gguf, invisible, isolation,
+    language, llamacpp, logit_bias, perturbation, preprocess, quantization, reference,
+    regeneration, resources, roc, scripting, shuffle, significance, speakers, special_tokens,
+    template, token_types, typography, utils, watermark, windowing, worker,
+};
+
+use crate::analysis::AnalysisRequest;
+use crate::bos::{BosSensitivity, BosSetting};
+use crate::cli::CliArgs;
+use crate::conditioning::ConditioningStats;
+use crate::determinism::DeterminismStats;
+use crate::diagnostics::BundleOptions;
+use crate::instance::RemoteCommand;
+use crate::isolation::{IsolationStats, Segment};
+use crate::perturbation::PerturbationStats;
+use crate::quantization::QuantVariance;
+use crate::reference::ReferenceStats;
+use crate::regeneration::{RegenerationDiff, RegenerationStats};
+use crate::report::ReportSection;
+use crate::result_stats::{ResultStats, StatsOptions};
+use crate::settings::{PrefixPreset, PreloadMode, Settings};
+use crate::shuffle::ShuffleStats;
+use crate::significance::SignificanceTest;
+use crate::store::{AnalysisStore, StoredAnalysis};
+use crate::throughput::Throughput;
+use crate::tray::{Tray, TrayOptions};
+use crate::ui_main::{
+    DisplayOptions, ErrorAction, ExportFormat, HistoryEntry, InputView, InspectedToken, MainTab,
+    ModelStatus, PromptInput, QueueAction, QueuedText, ResultTest, ResultView, RunProgress,
+    StoreAction, StoreView, TimeEstimate, TokenColorMode, UnifiedColorMode, ViewMode,
+};
+use crate::ui_palette::Command;
+use crate::ui_settings::{SettingsAction, SettingsDraft};
+use crate::update::Release;
+use crate::watch::FolderWatcher;
+use crate::windowing::ChunkSweep;
+use crate::worker::{SideJob, WorkerCommand, WorkerManager};
 
-use crate::settings::{PreloadMode, Settings};
-use crate::ui_main::{UnifiedColorMode, ViewMode};
-use crate::worker::{WorkerCommand, WorkerManager};
+/// Quiet period after the last edit before live token counts are refreshed.
+const TOKEN_COUNT_DEBOUNCE: Duration = Duration::from_millis(300);
+/// Idle time after the last edit before live mode re-runs the analysis.
+const LIVE_ANALYSIS_DELAY: Duration = Duration::from_secs(2);
+/// Per-slot token count cache size; the cache is reset when it fills up.
+const TOKEN_COUNT_CACHE_LIMIT: usize = 256;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum ModelSlot {
@@ -37,25 +108,125 @@ impl ModelSlot {
     }
 }
 
+/// What a slot's in-flight `Score` command was sent for.
+#[derive(Clone, Debug)]
+enum ScoreJob {
+    Reference,
+    Perturbation,
+    /// Segments of the result, scored in order without their context.
+    Isolation(Vec<Segment>),
+    /// Names of the prompt presets the text was scored under, after the
+    /// unconditioned text.
+    Conditioning(Vec<String>),
+    /// Number of sentences in the shuffled text.
+    Shuffle(usize),
+}
+
 /// Per-slot state: each model slot owns its worker, results, and UI buffers.
 struct SlotState {
     worker: WorkerManager,
-    result: Option<analysis::AnalysisResult>,
+    /// The model was unloaded from the header to free memory. It's loaded
+    /// again when an analysis needs it, but not by the preload policy.
+    unloaded: bool,
+    /// Shared with the threads that compute its statistics and store it.
+    result: Option<Arc<analysis::AnalysisResult>>,
     token_count: Option<usize>,
-    settings_path_buffer: String,
+    /// Token counts of previously seen texts for the loaded model, keyed by text hash.
+    token_count_cache: HashMap<u64, usize>,
+    /// Text hashes of in-flight token counts, in the order they were sent.
+    pending_token_counts: VecDeque<u64>,
+    /// Reference perplexity distribution for the configured model.
+    reference: Option<ReferenceStats>,
+    /// Statistics of `result`, empty until the background thread computing
+    /// them is done.
+    stats: ResultStats,
+    /// The `update_result_stats` call whose statistics `stats` should hold.
+    stats_generation: u64,
+    /// Perturbation test of `result`, if one has been run.
+    perturbation: Option<PerturbationStats>,
+    /// `(context length, perplexity)` pairs from a context sweep of `result`.
+    context_sweep: Option<Vec<(u32, f32)>>,
+    chunk_sweep: Option<ChunkSweep>,
+    /// Context-isolation test of `result`, if one has been run.
+    isolation: Option<IsolationStats>,
+    /// Perplexity of the text under each prompt preset.
+    conditioning: Option<ConditioningStats>,
+    /// Perplexity of the text with its sentences shuffled.
+    shuffle: Option<ShuffleStats>,
+    /// Comparison of `result` with a second analysis of the same text.
+    determinism: Option<DeterminismStats>,
+    /// Metrics of the text with and without BOS and a leading space.
+    bos_sensitivity: Option<BosSensitivity>,
+    /// Comparison of the text with the model's regeneration of it.
+    regeneration: Option<RegenerationStats>,
+    /// Word diff of the text against the model's continuation of it.
+    regeneration_diff: Option<RegenerationDiff>,
+    /// Per-token variance of `result` across other quants of the model.
+    quant_variance: Option<QuantVariance>,
+    score_job: Option<ScoreJob>,
 }
 
 impl Default for SlotState {
     fn default() -> Self {
         Self {
             worker: WorkerManager::new(),
+            unloaded: false,
             result: None,
             token_count: None,
-            settings_path_buffer: String::new(),
+            token_count_cache: HashMap::new(),
+            pending_token_counts: VecDeque::new(),
+            reference: None,
+            stats: ResultStats::default(),
+            stats_generation: 0,
+            perturbation: None,
+            context_sweep: None,
+            chunk_sweep: None,
+            isolation: None,
+            conditioning: None,
+            shuffle: None,
+            determinism: None,
+            bos_sensitivity: None,
+            regeneration: None,
+            regeneration_diff: None,
+            quant_variance: None,
+            score_job: None,
         }
     }
 }
 
+/// Optional small model whose quick result is shown until a configured
+/// model finishes.
+#[derive(Default)]
+struct PreviewState {
+    worker: WorkerManager,
+    result: Option<analysis::AnalysisResult>,
+}
+
+/// Statistics computed off the UI thread by `update_result_stats`.
+struct DerivedStats {
+    slot: ModelSlot,
+    generation: u64,
+    stats: ResultStats,
+    significance: Option<SignificanceTest>,
+}
+
+impl SlotState {
+    /// Drops the follow-up tests, which belong to the previous result.
+    fn clear_tests(&mut self) {
+        self.perturbation = None;
+        self.context_sweep = None;
+        self.chunk_sweep = None;
+        self.isolation = None;
+        self.conditioning = None;
+        self.shuffle = None;
+        self.determinism = None;
+        self.bos_sensitivity = None;
+        self.regeneration = None;
+        self.regeneration_diff = None;
+        self.quant_variance = None;
+    }
+}
+
 /// Tracks the sequential JIT analysis when models run one at a time.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 enum JitPhase {
@@ -72,41 +243,182 @@ enum JitPhase {
 
 struct PerplexApp {
     settings: Settings,
-    show_settings: bool,
-    settings_preload_buffer: PreloadMode,
+    /// Open settings dialog and its uncommitted edits.
+    settings_draft: Option<SettingsDraft>,
     input_text: String,
+    prompt: PromptInput,
     slots: [SlotState; 2],
     error_message: Option<String>,
-    view_mode: ViewMode,
-    unified_color_mode: UnifiedColorMode,
+    display: DisplayOptions,
     jit_phase: JitPhase,
-    jit_pending_text: String,
+    jit_pending: AnalysisRequest,
+    /// Set by `--analyze`: start the analysis once the models are ready.
+    /// Cleared when they fail to load.
+    auto_analyze: bool,
+    /// Command-line overrides layered over `settings` for this session.
+    cli: CliArgs,
+    /// Settings as loaded, before `cli` was applied; saving writes their
+    /// values back over the overrides.
+    loaded_settings: Settings,
+    log_panel: ui_logs::LogPanelState,
+    /// When set, live token counts are refreshed once this instant passes.
+    token_count_due: Option<Instant>,
+    /// When set (live mode only), the analysis re-runs once this instant
+    /// passes and no other work is in progress.
+    live_analysis_due: Option<Instant>,
+    /// Result tests still waiting to run. They run one at a time so JIT
+    /// setups never hold both models at once.
+    test_queue: VecDeque<(ModelSlot, ResultTest)>,
+    /// Models picked for the running cross-quant test.
+    quant_models: Vec<String>,
+    /// Permutation test of model A's result against model B's.
+    significance: Option<SignificanceTest>,
+    /// The `update_result_stats` call whose test `significance` should hold.
+    significance_generation: u64,
+    /// Counts `update_result_stats` calls, so statistics of a result or
+    /// settings replaced in the meantime are dropped when they arrive.
+    stats_generation: u64,
+    stats_tx: mpsc::Sender<DerivedStats>,
+    stats_rx: mpsc::Receiver<DerivedStats>,
+    /// Analyses `store_result` wrote on a background thread.
+    stored_tx: mpsc::Sender<StoredAnalysis>,
+    stored_rx: mpsc::Receiver<StoredAnalysis>,
+    /// Woken when background work for the window is done.
+    ctx: egui::Context,
+    /// Token queued regeneration diffs start from; `None` starts them
+    /// from the opening.
+    regenerate_start: Option<usize>,
+    /// Texts waiting to be analyzed one after another.
+    queue: VecDeque<QueuedText>,
+    queue_running: bool,
+    /// The queued text whose analysis is in progress.
+    queue_current: Option<QueuedText>,
+    /// Results of finished queued analyses, oldest first.
+    history: Vec<HistoryEntry>,
+    /// New texts in the watched folder, queued as they appear.
+    watcher: FolderWatcher,
+    store: AnalysisStore,
+    store_view: StoreView,
+    tab: MainTab,
+    tokenizer: ui_tokenizer::TokenizerState,
+    trends: ui_trends::TrendsState,
+    /// Open command palette and its search text.
+    palette: Option<ui_palette::PaletteState>,
+    /// When the current stretch of work started, and whether it included
+    /// an analysis rather than only model loading.
+    busy_since: Option<(Instant, bool)>,
+    /// Commands from later launches with `--paste-analyze`, the global
+    /// shortcut and the tray menu.
+    remote_rx: Option<mpsc::Receiver<RemoteCommand>>,
+    tray: Tray,
+    /// A clipboard paste was requested; its text replaces the input and is
+    /// analyzed.
+    paste_pending: bool,
+    /// Pending startup update check.
+    update_rx: Option<mpsc::Receiver<Option<Release>>>,
+    /// Newer release shown in a banner until dismissed.
+    update: Option<Release>,
+    /// The most recent analysis request, for diagnostic bundles.
+    last_request: Option<AnalysisRequest>,
+    /// Open "Save diagnostic bundle" dialog.
+    bundle_dialog: Option<BundleOptions>,
+    preview: PreviewState,
+    throughput: Throughput,
 }
 
 impl Default for PerplexApp {
     fn default() -> Self {
+        let (stats_tx, stats_rx) = mpsc::channel();
+        let (stored_tx, stored_rx) = mpsc::channel();
         Self {
             settings: Settings::default(),
-            show_settings: false,
-            settings_preload_buffer: PreloadMode::PreloadSingle,
+            settings_draft: None,
             input_text: String::new(),
+            prompt: PromptInput::default(),
             slots: Default::default(),
             error_message: None,
-            view_mode: ViewMode::Split,
-            unified_color_mode: UnifiedColorMode::AvgRank,
+            display: DisplayOptions::default(),
             jit_phase: JitPhase::Idle,
-            jit_pending_text: String::new(),
+            jit_pending: AnalysisRequest::default(),
+            auto_analyze: false,
+            cli: CliArgs::default(),
+            loaded_settings: Settings::default(),
+            log_panel: Default::default(),
+            token_count_due: None,
+            live_analysis_due: None,
+            test_queue: VecDeque::new(),
+            quant_models: Vec::new(),
+            significance: None,
+            significance_generation: 0,
+            stats_generation: 0,
+            stats_tx,
+            stats_rx,
+            stored_tx,
+            stored_rx,
+            ctx: egui::Context::default(),
+            regenerate_start: None,
+            queue: VecDeque::new(),
+            queue_running: false,
+            queue_current: None,
+            history: Vec::new(),
+            watcher: FolderWatcher::default(),
+            store: AnalysisStore::open(),
+            store_view: StoreView::default(),
+            tab: MainTab::Analyze,
+            tokenizer: Default::default(),
+            trends: Default::default(),
+            palette: None,
+            busy_since: None,
+            remote_rx: None,
+            tray: Tray::default(),
+            paste_pending: false,
+            update_rx: None,
+            update: None,
+            last_request: None,
+            bundle_dialog: None,
+            preview: PreviewState::default(),
+            throughput: Throughput::default(),
         }
     }
 }
 
 impl PerplexApp {
-    fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let _ = env_logger::try_init();
+    fn new(cc: &eframe::CreationContext<'_>, mut cli: CliArgs) -> Self {
+        logs::init();
 
         let mut app = Self::default();
+        app.ctx = cc.egui_ctx.clone();
         app.settings = Settings::load();
+        app.throughput = Throughput::load();
 
+        app.loaded_settings = app.settings.clone();
+        cli.apply_to(&mut app.settings);
+        if let Some(text) = cli.text.take() {
+            app.input_text = text;
+        }
+        app.auto_analyze = cli.auto_analyze;
+        let (remote_tx, remote_rx) = mpsc::channel();
+        if let Err(e) = instance::listen(cc.egui_ctx.clone(), remote_tx.clone()) {
+            log::warn!("Failed to listen for --paste-analyze requests: {}", e);
+        }
+        tray::forward_events(cc.egui_ctx.clone(), remote_tx);
+        app.remote_rx = Some(remote_rx);
+        let tray = app.settings.tray.clone();
+        app.apply_tray(&tray);
+        if cli.paste_analyze {
+            app.paste_and_analyze(&cc.egui_ctx);
+        }
+        app.cli = cli;
+        if app.settings.check_for_updates {
+            app.update_rx = Some(update::check_in_background(cc.egui_ctx.clone()));
+        }
+
+        cc.egui_ctx.set_theme(app.settings.theme);
+        app.settings.tooltips.apply(&cc.egui_ctx);
+        app.display.tooltips = app.settings.tooltips;
+        for slot in ModelSlot::ALL {
+            app.load_reference(slot);
+        }
         app.apply_preload_policy();
         app
     }
@@ -134,8 +446,10 @@ impl PerplexApp {
     fn set_model(&mut self, slot: ModelSlot, path: String) {
         *self.model_path_mut(slot) = Some(path);
         self.save_settings();
+        self.slots[slot.index()].unloaded = false;
         self.error_message = None;
         self.slots[slot.index()].result = None;
+        self.load_reference(slot);
 
         self.apply_preload_policy();
     }
@@ -145,15 +459,740 @@ impl PerplexApp {
         self.save_settings();
         let s = &mut self.slots[slot.index()];
         s.worker.unload_model();
+        s.unloaded = false;
         s.result = None;
+        s.reference = None;
+    }
+
+    /// Frees the memory of a slot's model but keeps it selected, with its
+    /// results; the next analysis loads it again.
+    fn unload_model(&mut self, slot: ModelSlot) {
+        let s = &mut self.slots[slot.index()];
+        if !s.worker.has_model || s.worker.is_analyzing || s.worker.is_running_side_job {
+            return;
+        }
+        s.worker.unload_model();
+        s.unloaded = true;
+    }
+
+    /// Loads the stored reference distribution of the selected domain for
+    /// the slot's model, if any.
+    fn load_reference(&mut self, slot: ModelSlot) {
+        let domain = self.settings.domain;
+        let reference = model_name_from_path(self.model_path(slot).map(String::as_str))
+            .and_then(|model| ReferenceStats::load(model, domain));
+        self.slots[slot.index()].reference = reference;
+    }
+
+    /// Scores a set of known-human texts with the slot's model and stores
+    /// the resulting perplexity distribution as its reference.
+    fn build_reference(&mut self, slot: ModelSlot) {
+        let Some(path) = self.model_path(slot).cloned() else {
+            self.append_error(format!(
+                "{}: apply a model before building its reference",
+                slot.label()
+            ));
+            return;
+        };
+        if self.is_busy() {
+            self.append_error("Wait for the current analysis to finish".to_string());
+            return;
+        }
+        let Some(files) = rfd::FileDialog::new()
+            .add_filter("Text", &["txt", "md"])
+            .set_title("Select Reference Texts")
+            .pick_files()
+        else {
+            return;
+        };
+
+        let mut texts = Vec::new();
+        for file in files {
+            match std::fs::read_to_string(&file) {
+                Ok(text) => texts.push(text),
+                Err(e) => self.append_error(format!("Failed to read {}: {}", file.display(), e)),
+            }
+        }
+        let segments = reference::split_corpus(&texts);
+        if segments.is_empty() {
+            self.append_error("The selected files contain no text".to_string());
+            return;
+        }
+
+        log::info!(
+            "Calibrating {} on {} reference segments",
+            slot.label(),
+            segments.len()
+        );
+        let requests = segments.into_iter().map(AnalysisRequest::plain).collect();
+        self.send_score(slot, path, ScoreJob::Reference, requests);
+    }
+
+    /// Loads the slot's model unless it is loaded or loading already.
+    fn ensure_loaded(&mut self, slot: ModelSlot, path: String) {
+        let s = &mut self.slots[slot.index()];
+        if !s.worker.has_model && !s.worker.is_loading {
+            s.worker.load_model(path, self.settings.inference.clone());
+        }
+    }
+
+    /// Sends `Score` to a slot, loading its model first if needed.
+    fn send_score(
+        &mut self,
+        slot: ModelSlot,
+        path: String,
+        job: ScoreJob,
+        requests: Vec<AnalysisRequest>,
+    ) {
+        self.ensure_loaded(slot, path);
+        let s = &mut self.slots[slot.index()];
+        if s.worker
+            .send_command(WorkerCommand::Score(requests))
+            .is_ok()
+        {
+            s.score_job = Some(job);
+        }
+    }
+
+    /// Queues `test` for every slot with a result and starts the first one.
+    fn start_result_test(&mut self, test: ResultTest) {
+        self.error_message = None;
+        self.regenerate_start = None;
+        self.test_queue = ModelSlot::ALL
+            .into_iter()
+            .filter(|&slot| self.slots[slot.index()].result.is_some())
+            .map(|slot| (slot, test))
+            .collect();
+        if test == ResultTest::CrossQuant {
+            let Some(files) = rfd::FileDialog::new()
+                .add_filter("GGUF", &["gguf"])
+                .set_title("Select Other Quants of the Model")
+                .pick_files()
+            else {
+                self.test_queue.clear();
+                return;
+            };
+            self.quant_models = files
+                .into_iter()
+                .map(|f| f.to_string_lossy().into_owned())
+                .collect();
+            // The other slot's result joins the comparison instead of
+            // running it twice.
+            self.test_queue.truncate(1);
+        }
+        self.run_next_test();
+    }
+
+    fn run_next_test(&mut self) {
+        while let Some((slot, test)) = self.test_queue.pop_front() {
+            let (Some(path), Some(result)) = (
+                self.model_path(slot).cloned(),
+                self.slots[slot.index()].result.as_ref(),
+            ) else {
+                continue;
+            };
+            match test {
+                ResultTest::Perturbation => {
+                    let AnalysisRequest {
+                        prompt,
+                        text,
+                        tail_tokens,
+                        skip_bos,
+                        logit_bias,
+                    } = self.analysis_request();
+                    let variants: Vec<AnalysisRequest> = perturbation::make_variants(
+                        result,
+                        &text,
+                        self.settings.perturbation_count,
+                        self.settings.perturbation_rate,
+                    )
+                    .into_iter()
+                    .map(|text| AnalysisRequest {
+                        prompt: prompt.clone(),
+                        text,
+                        tail_tokens,
+                        skip_bos,
+                        logit_bias: logit_bias.clone(),
+                    })
+                    .collect();
+                    log::info!(
+                        "Scoring {} perturbed variants with {}",
+                        variants.len(),
+                        slot.label()
+                    );
+                    self.send_score(slot, path, ScoreJob::Perturbation, variants);
+                }
+                ResultTest::ContextSweep => {
+                    self.ensure_loaded(slot, path);
+                    let _ = self.slots[slot.index()]
+                        .worker
+                        .start_side_job(SideJob::ContextSweep(self.analysis_request().text));
+                }
+                ResultTest::ChunkSweep => {
+                    self.ensure_loaded(slot, path);
+                    let _ = self.slots[slot.index()]
+                        .worker
+                        .start_side_job(SideJob::ChunkSweep(self.analysis_request().text));
+                }
+                ResultTest::Regeneration => {
+                    log::info!("Regenerating the text with {}", slot.label());
+                    self.ensure_loaded(slot, path);
+                    let _ = self.slots[slot.index()]
+                        .worker
+                        .start_side_job(SideJob::Regenerate(self.analysis_request().text));
+                }
+                ResultTest::RegenerateDiff => {
+                    log::info!("Regenerating the rest of the text with {}", slot.label());
+                    self.ensure_loaded(slot, path);
+                    let job = SideJob::RegenerateDiff {
+                        request: self.analysis_request(),
+                        start: self.regenerate_start,
+                    };
+                    let _ = self.slots[slot.index()].worker.start_side_job(job);
+                }
+                ResultTest::Isolation => {
+                    let segments = isolation::segments(result, self.settings.isolation_segments);
+                    if segments.is_empty() {
+                        self.append_error(format!(
+                            "{}: the text has no segments long enough to score",
+                            slot.label()
+                        ));
+                        continue;
+                    }
+                    log::info!(
+                        "Scoring {} isolated segments with {}",
+                        segments.len(),
+                        slot.label()
+                    );
+                    let requests = segments
+                        .iter()
+                        .map(|s| AnalysisRequest::plain(s.text.clone()))
+                        .collect();
+                    self.send_score(slot, path, ScoreJob::Isolation(segments), requests);
+                }
+                ResultTest::Conditioning => {
+                    let presets = &self.settings.prefix_presets;
+                    if presets.is_empty() {
+                        self.append_error(
+                            "Save prompts as presets to compare conditioning".to_string(),
+                        );
+                        self.test_queue.clear();
+                        return;
+                    }
+                    let request = self.analysis_request();
+                    let names = presets.iter().map(|p| p.name.clone()).collect();
+                    let mut requests = vec![AnalysisRequest {
+                        prompt: String::new(),
+                        ..request.clone()
+                    }];
+                    requests.extend(presets.iter().map(|preset| AnalysisRequest {
+                        prompt: preprocess::apply(
+                            &template::fill(&preset.text, &self.prompt.variables),
+                            &self.settings.preprocess,
+                        ),
+                        ..request.clone()
+                    }));
+                    log::info!(
+                        "Scoring the text under {} prompts with {}",
+                        presets.len(),
+                        slot.label()
+                    );
+                    self.send_score(slot, path, ScoreJob::Conditioning(names), requests);
+                }
+                ResultTest::Shuffle => {
+                    let request = self.analysis_request();
+                    let Some((text, sentences)) = shuffle::shuffle_sentences(&request.text) else {
+                        self.append_error(format!(
+                            "{}: the text needs at least two different sentences to shuffle",
+                            slot.label()
+                        ));
+                        continue;
+                    };
+                    log::info!(
+                        "Scoring the text with {} shuffled sentences with {}",
+                        sentences,
+                        slot.label()
+                    );
+                    let request = AnalysisRequest { text, ..request };
+                    self.send_score(slot, path, ScoreJob::Shuffle(sentences), vec![request]);
+                }
+                ResultTest::CrossQuant => {
+                    log::info!(
+                        "Analyzing the text with {} other quants next to {}",
+                        self.quant_models.len(),
+                        slot.label()
+                    );
+                    self.ensure_loaded(slot, path);
+                    let job = SideJob::AnalyzeModels {
+                        models: self.quant_models.clone(),
+                        request: self.analysis_request(),
+                    };
+                    let _ = self.slots[slot.index()].worker.start_side_job(job);
+                }
+                ResultTest::Determinism => {
+                    log::info!("Analyzing the text again with {}", slot.label());
+                    self.ensure_loaded(slot, path);
+                    let _ = self.slots[slot.index()]
+                        .worker
+                        .send_command(WorkerCommand::Rescore(self.analysis_request()));
+                }
+                ResultTest::BosSensitivity => {
+                    log::info!(
+                        "Analyzing the text with and without BOS and a leading space with {}",
+                        slot.label()
+                    );
+                    let request = self.analysis_request();
+                    let requests = BosSetting::ALL
+                        .into_iter()
+                        .map(|setting| setting.apply(&request))
+                        .collect();
+                    self.ensure_loaded(slot, path);
+                    let _ = self.slots[slot.index()]
+                        .worker
+                        .send_command(WorkerCommand::AnalyzeVariants(requests));
+                }
+            }
+            return;
+        }
+    }
+
+    fn can_run_tests(&self) -> bool {
+        ModelSlot::ALL.iter().any(|&slot| {
+            self.slots[slot.index()].result.is_some() && self.model_path(slot).is_some()
+        })
+    }
+
+    /// Whether `test` can start now: not while another test runs or JIT
+    /// swaps models, and during an analysis only if it runs beside it.
+    fn can_start_test(&self, test: ResultTest) -> bool {
+        self.can_run_tests()
+            && self.test_queue.is_empty()
+            && self.jit_phase == JitPhase::Idle
+            && !self.slots.iter().any(|s| s.worker.is_running_side_job)
+            && (!self.is_busy() || test.runs_beside_analyses())
+    }
+
+    fn finish_reference(&mut self, slot: ModelSlot, perplexities: Vec<f32>) {
+        let name = model_name_from_path(self.model_path(slot).map(String::as_str))
+            .unwrap_or(slot.label())
+            .to_string();
+        let stats = ReferenceStats::new(&name, self.settings.domain, perplexities);
+        log::info!(
+            "{} reference built from {} segments",
+            slot.label(),
+            stats.perplexities.len()
+        );
+        if let Err(e) = stats.save() {
+            self.append_error(format!("Failed to save reference: {}", e));
+        }
+        self.slots[slot.index()].reference = Some(stats);
+    }
+
+    fn import_reference(&mut self, slot: ModelSlot) {
+        let Some(model) =
+            model_name_from_path(self.model_path(slot).map(String::as_str)).map(str::to_string)
+        else {
+            self.append_error(format!(
+                "{}: apply a model before importing a reference",
+                slot.label()
+            ));
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("Import Reference")
+            .pick_file()
+        else {
+            return;
+        };
+        match ReferenceStats::import(&path, &model, self.settings.domain) {
+            Ok(stats) => self.slots[slot.index()].reference = Some(stats),
+            Err(e) => self.append_error(format!("Failed to import reference: {}", e)),
+        }
+    }
+
+    /// Compares the slot's result with the other quants' results, and with
+    /// the other slot's when it scored the same tokens.
+    fn finish_cross_quant(
+        &mut self,
+        slot: ModelSlot,
+        others: Vec<(String, analysis::AnalysisResult)>,
+    ) {
+        let Some(own) = self.slots[slot.index()].result.as_ref() else {
+            return;
+        };
+        let name = |path: Option<&String>, fallback: &str| {
+            model_name_from_path(path.map(String::as_str))
+                .unwrap_or(fallback)
+                .to_string()
+        };
+        let mut results = vec![(name(self.model_path(slot), slot.label()), &**own)];
+        let other_slot = ModelSlot::ALL[1 - slot.index()];
+        if let Some(other) = self.slots[other_slot.index()].result.as_deref() {
+            if quantization::same_tokens(own, other) {
+                results.push((name(self.model_path(other_slot), other_slot.label()), other));
+            }
+        }
+        results.extend(
+            others
+                .iter()
+                .map(|(path, result)| (name(Some(path), path), result)),
+        );
+        match QuantVariance::new(&results) {
+            Ok(variance) => self.slots[slot.index()].quant_variance = Some(variance),
+            Err(e) => self.append_error(format!("{}: {}", slot.label(), e)),
+        }
+    }
+
+    /// Regenerates from a token picked in the context inspector, in the
+    /// result it was picked in, or in both in the unified view.
+    fn regenerate_from(&mut self, token: InspectedToken) {
+        if !self.can_start_test(ResultTest::RegenerateDiff) {
+            self.append_error("Wait for the running test to finish".to_string());
+            return;
+        }
+        self.error_message = None;
+        self.regenerate_start = Some(token.index);
+        self.test_queue = ModelSlot::ALL
+            .into_iter()
+            .filter(|&slot| self.slots[slot.index()].result.is_some())
+            .enumerate()
+            .filter(|&(i, _)| token.result.is_none_or(|r| r == i))
+            .map(|(_, slot)| (slot, ResultTest::RegenerateDiff))
+            .collect();
+        self.run_next_test();
+    }
+
+    /// Saves the prompt under its preset name, or deletes the preset of
+    /// that name, and persists the change.
+    fn update_prefix_presets(&mut self, save: bool) {
+        let name = self.prompt.preset_name.trim().to_string();
+        let presets = &mut self.settings.prefix_presets;
+        presets.retain(|p| p.name != name);
+        if save {
+            presets.push(PrefixPreset {
+                name,
+                text: self.prompt.text.clone(),
+            });
+            presets.sort_by(|a, b| a.name.cmp(&b.name));
+        } else {
+            self.prompt.preset_name.clear();
+        }
+        self.save_settings();
     }
 
     fn save_settings(&self) {
-        if let Err(e) = self.settings.save() {
+        let mut settings = self.settings.clone();
+        self.cli.unapply(&mut settings, &self.loaded_settings);
+        if let Err(e) = settings.save() {
             log::warn!("Failed to save settings: {}", e);
         }
     }
 
+    /// Commits new settings: unloads models whose path or load-time
+    /// inference parameters changed and passes the other parameters to the
+    /// loaded ones, then reapplies the preload policy and persists.
+    fn apply_settings(&mut self, ctx: &egui::Context, new: Settings) {
+        let reload = new.inference.needs_reload(&self.settings.inference);
+        let params_changed = new.inference != self.settings.inference;
+        let stats_changed = new.speaker_pattern != self.settings.speaker_pattern
+            || new.watermark != self.settings.watermark
+            || new.custom_metrics != self.settings.custom_metrics
+            || new.color_expression != self.settings.color_expression;
+        if new.preprocess != self.settings.preprocess {
+            self.token_count_due = Some(Instant::now());
+        }
+        if new.watch.folder != self.settings.watch.folder {
+            self.watcher.reset();
+        }
+        if new.tray != self.settings.tray {
+            self.apply_tray(&new.tray);
+        }
+
+        for slot in ModelSlot::ALL {
+            let new_path = match slot {
+                ModelSlot::A => new.model_path_a.as_ref(),
+                ModelSlot::B => new.model_path_b.as_ref(),
+            };
+            let path_changed = self.model_path(slot) != new_path;
+            let s = &mut self.slots[slot.index()];
+            if s.worker.has_model || s.worker.is_loading {
+                if path_changed || reload {
+                    s.worker.unload_model();
+                } else if params_changed {
+                    s.worker.set_params(new.inference.clone());
+                }
+            }
+            if path_changed {
+                s.result = None;
+            }
+        }
+
+        if self.preview.worker.has_model || self.preview.worker.is_loading {
+            if new.preview_model_path != self.settings.preview_model_path || reload {
+                self.preview.worker.unload_model();
+                self.preview.result = None;
+            } else if params_changed {
+                self.preview.worker.set_params(new.inference.clone());
+            }
+        }
+
+        if new.theme != self.settings.theme {
+            ctx.set_theme(new.theme);
+        }
+        new.tooltips.apply(ctx);
+        self.display.tooltips = new.tooltips;
+
+        self.settings = new;
+        for slot in ModelSlot::ALL {
+            self.load_reference(slot);
+            if stats_changed {
+                self.update_result_stats(slot);
+            }
+        }
+        self.apply_preload_policy();
+        self.save_settings();
+    }
+
+    fn export_settings(&mut self, settings: &Settings) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("Export Settings")
+            .set_file_name("perplex_settings.json")
+            .save_file()
+        else {
+            return;
+        };
+        if let Err(e) = settings.save_to(&path) {
+            self.append_error(format!("Failed to export settings: {}", e));
+        }
+    }
+
+    fn save_diagnostic_bundle(&mut self, options: BundleOptions) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Zip", &["zip"])
+            .set_title("Save Diagnostic Bundle")
+            .set_file_name("perplex_diagnostics.zip")
+            .save_file()
+        else {
+            return;
+        };
+        let error = self.error_message.clone().unwrap_or_default();
+        match diagnostics::write_bundle(
+            &path,
+            &error,
+            &self.settings,
+            self.last_request.as_ref(),
+            options,
+        ) {
+            Ok(()) => log::info!("Saved diagnostic bundle to {}", path.display()),
+            Err(e) => self.append_error(format!("Failed to save {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Asks for a destination and writes every slot's result to it.
+    fn export_results(&mut self, format: ExportFormat) {
+        let template = match format {
+            ExportFormat::Template => match self.pick_export_template() {
+                Some(template) => Some(template),
+                None => return,
+            },
+            _ => None,
+        };
+        let (filter, extension) = match format {
+            ExportFormat::PdfReport => ("PDF", "pdf"),
+            ExportFormat::HtmlReport => ("HTML", "html"),
+            ExportFormat::AnnotatedText => ("Text", "txt"),
+            ExportFormat::Workbook => ("Excel", "xlsx"),
+            ExportFormat::Template => (
+                "Report",
+                template
+                    .as_deref()
+                    .map_or("txt", template_report::output_extension),
+            ),
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(filter, &[extension])
+            .set_title("Export Results")
+            .set_file_name(format!("perplex_report.{}", extension))
+            .save_file()
+        else {
+            return;
+        };
+        let sections = self.report_sections();
+        let written = match format {
+            ExportFormat::PdfReport => report::write_pdf(&path, &sections),
+            ExportFormat::HtmlReport => html_report::write_html(&path, &sections),
+            ExportFormat::AnnotatedText => {
+                text_report::write_annotated(&path, &sections, &self.settings.text_export)
+            }
+            ExportFormat::Workbook => {
+                xlsx_report::write_xlsx(&path, &sections, &self.export_metadata())
+            }
+            ExportFormat::Template => template_report::write_templated(
+                &path,
+                template.as_deref().unwrap_or(Path::new("")),
+                &sections,
+                &self.export_metadata(),
+            ),
+        };
+        if let Err(e) = written {
+            self.append_error(format!("Failed to export {}: {}", path.display(), e));
+        }
+    }
+
+    /// The current result of each slot, named after its model.
+    fn report_sections(&self) -> Vec<ReportSection<'_>> {
+        let paths = [
+            self.settings.model_path_a.as_deref(),
+            self.settings.model_path_b.as_deref(),
+        ];
+        ModelSlot::ALL
+            .into_iter()
+            .filter_map(|slot| {
+                let result = self.slots[slot.index()].result.as_ref()?;
+                Some(ReportSection {
+                    name: model_name_from_path(paths[slot.index()]).unwrap_or(slot.label()),
+                    result,
+                    format: self.settings.number_format,
+                })
+            })
+            .collect()
+    }
+
+    /// Asks for a Handlebars template, starting next to the last one used,
+    /// and remembers the choice.
+    fn pick_export_template(&mut self) -> Option<std::path::PathBuf> {
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("Handlebars template", &["hbs", "handlebars"])
+            .add_filter("Any file", &["*"])
+            .set_title("Select Export Template");
+        if let Some(dir) = self
+            .settings
+            .export_template
+            .as_deref()
+            .and_then(|p| Path::new(p).parent())
+        {
+            dialog = dialog.set_directory(dir);
+        }
+        let path = dialog.pick_file()?;
+        self.settings.export_template = Some(path.display().to_string());
+        self.save_settings();
+        Some(path)
+    }
+
+    /// The settings behind the current results as `(field, value)` rows:
+    /// the models, then the inference and preprocessing options and the
+    /// prompt of the last analysis.
+    fn export_metadata(&self) -> Vec<(String, String)> {
+        let mut rows = Vec::new();
+        for slot in ModelSlot::ALL {
+            let path = [&self.settings.model_path_a, &self.settings.model_path_b][slot.index()];
+            if let Some(path) = path {
+                rows.push((slot.label().to_string(), path.clone()));
+            }
+        }
+        for (group, value) in [
+            ("Inference", serde_json::to_value(&self.settings.inference)),
+            (
+                "Preprocessing",
+                serde_json::to_value(&self.settings.preprocess),
+            ),
+        ] {
+            if let Ok(serde_json::Value::Object(fields)) = value {
+                for (field, value) in fields {
+                    let value = match value {
+                        serde_json::Value::String(s) => s,
+                        serde_json::Value::Null => "default".to_string(),
+                        other => other.to_string(),
+                    };
+                    rows.push((format!("{}: {}", group, field), value));
+                }
+            }
+        }
+        if let Some(request) = self.last_request.as_ref().filter(|r| !r.prompt.is_empty()) {
+            rows.push(("Prompt".to_string(), request.prompt.clone()));
+        }
+        rows
+    }
+
+    fn import_settings(&mut self) -> Option<Settings> {
+        let path = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("Import Settings")
+            .pick_file()?;
+        match Settings::load_from(&path) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                self.append_error(format!("Failed to import settings: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Recomputes the settings-dependent statistics of a slot's result and
+    /// the significance test between the slots on a background thread;
+    /// `poll_result_stats` takes them in. Until then none are shown.
+    fn update_result_stats(&mut self, slot: ModelSlot) {
+        self.stats_generation += 1;
+        let generation = self.stats_generation;
+        self.significance_generation = generation;
+        self.significance = None;
+        let s = &mut self.slots[slot.index()];
+        s.stats_generation = generation;
+        s.stats = ResultStats::default();
+        let Some(result) = s.result.clone() else {
+            return;
+        };
+        let other = self.slots[1 - slot.index()].result.clone();
+        let options = StatsOptions::new(&self.settings);
+        let (tx, ctx) = (self.stats_tx.clone(), self.ctx.clone());
+        std::thread::spawn(move || {
+            let stats = ResultStats::of(&result, &options);
+            let significance = other.and_then(|other| match slot {
+                ModelSlot::A => SignificanceTest::new(&result, &other),
+                ModelSlot::B => SignificanceTest::new(&other, &result),
+            });
+            let _ = tx.send(DerivedStats {
+                slot,
+                generation,
+                stats,
+                significance,
+            });
+            ctx.request_repaint();
+        });
+    }
+
+    /// Takes in statistics computed by `update_result_stats`, unless the
+    /// result or settings changed again since.
+    fn poll_result_stats(&mut self) {
+        while let Ok(derived) = self.stats_rx.try_recv() {
+            let s = &mut self.slots[derived.slot.index()];
+            if derived.generation == s.stats_generation {
+                s.stats = derived.stats;
+            }
+            if derived.generation == self.significance_generation {
+                self.significance = derived.significance;
+            }
+        }
+        while let Ok(record) = self.stored_rx.try_recv() {
+            self.store.push(record);
+        }
+    }
+
+    /// Registers the global shortcut and shows the tray icon as `options`
+    /// ask, reporting what failed.
+    fn apply_tray(&mut self, options: &TrayOptions) {
+        let results = [
+            self.tray.set_hotkey(options),
+            self.tray.set_icon(options.icon),
+        ];
+        for e in results.into_iter().filter_map(Result::err) {
+            log::warn!("{}", e);
+            self.append_error(e);
+        }
+    }
+
     fn append_error(&mut self, msg: String) {
         if let Some(ref mut existing) = self.error_message {
             existing.push('\n');
@@ -163,16 +1202,36 @@ impl PerplexApp {
         }
     }
 
+    /// The preprocessed input as an analysis request; the prompt is only
+    /// sent in prompt mode, and the tail length in tail mode.
+    fn analysis_request(&self) -> AnalysisRequest {
+        let options = &self.settings.preprocess;
+        AnalysisRequest {
+            prompt: if self.prompt.enabled {
+                let prompt = template::fill(&self.prompt.text, &self.prompt.variables);
+                preprocess::apply(&prompt, options)
+            } else {
+                String::new()
+            },
+            text: preprocess::apply(&self.input_text, options),
+            tail_tokens: self.prompt.tail_tokens,
+            skip_bos: false,
+            logit_bias: self.prompt.logit_bias.clone().unwrap_or_default(),
+        }
+    }
+
     fn start_analysis(&mut self) {
-        let text = self.input_text.clone();
+        let request = self.analysis_request();
         self.error_message = None;
+        self.last_request = Some(request.clone());
+        self.start_preview(&request);
 
         let both_configured = self.settings.model_path_a.is_some()
             && self.settings.model_path_b.is_some();
 
         if both_configured && !self.is_parallel() {
             // JIT: load → analyze → unload, one model at a time.
-            self.jit_pending_text = text.clone();
+            self.jit_pending = request.clone();
             self.slots[0].result = None;
             self.slots[1].result = None;
 
@@ -180,10 +1239,10 @@ impl PerplexApp {
             let path = self.settings.model_path_a.clone().unwrap();
             let a = &mut self.slots[ModelSlot::A.index()];
             if !a.worker.has_model {
-                a.worker.load_model(path);
+                a.worker.load_model(path, self.settings.inference.clone());
             }
             // Queued after LoadModel — runs once loading completes.
-            let _ = a.worker.send_command(WorkerCommand::Analyze(text));
+            let _ = a.worker.send_command(WorkerCommand::Analyze(request));
         } else {
             // Single model or parallel: send analyze to each ready/configured slot.
             // If a model isn't loaded yet, load it first.
@@ -191,49 +1250,837 @@ impl PerplexApp {
                 if let Some(path) = self.model_path(slot).cloned() {
                     let s = &mut self.slots[slot.index()];
                     if !s.worker.has_model && !s.worker.is_loading {
-                        s.worker.load_model(path);
+                        s.worker.load_model(path, self.settings.inference.clone());
                     }
-                    let _ = s.worker.send_command(WorkerCommand::Analyze(text.clone()));
+                    let _ = s.worker.analyze(request.clone());
                 }
             }
         }
     }
 
-    fn process_worker_messages(&mut self) {
-        let input_text = self.input_text.clone();
+    /// Runs the action of a pressed keyboard shortcut. Shortcuts are off
+    /// while the settings dialog or the command palette is open so text can
+    /// be typed there.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if self.settings_draft.is_some() || self.palette.is_some() {
+            return;
+        }
+        let Some(action) = ctx.input_mut(|input| self.settings.keymap.consume(input)) else {
+            return;
+        };
+        let command = match action {
+            keymap::Action::Analyze => Command::Analyze,
+            keymap::Action::Cancel => Command::Cancel,
+            keymap::Action::OpenFile => Command::OpenFile,
+            keymap::Action::Export => Command::Export(ExportFormat::HtmlReport),
+            keymap::Action::Settings => Command::Settings,
+            keymap::Action::CommandPalette => {
+                self.palette = Some(Default::default());
+                return;
+            }
+        };
+        self.run_command(ctx, command);
+    }
 
+    /// The palette commands that apply in the current state.
+    fn palette_commands(&self) -> Vec<Command> {
+        let busy = self.is_busy();
+        let has_results = self.slots.iter().any(|s| s.result.is_some());
+        let mut commands = Vec::new();
+        if self.can_analyze() {
+            commands.push(Command::Analyze);
+        }
+        if self.has_any_model() && (!busy || self.settings.live_analysis) {
+            commands.push(Command::PasteAndAnalyze);
+        }
+        if busy || self.slots.iter().any(|s| s.worker.is_running_side_job) {
+            commands.push(Command::Cancel);
+        }
+        if self.slots.iter().any(|s| s.worker.is_analyzing) {
+            commands.push(Command::TogglePause);
+        }
+        if !busy || self.settings.live_analysis {
+            commands.push(Command::OpenFile);
+        }
+        for slot in ModelSlot::ALL {
+            commands.push(Command::LoadModel(slot));
+            let worker = &self.slots[slot.index()].worker;
+            if worker.has_model && !worker.is_analyzing && !worker.is_running_side_job {
+                commands.push(Command::UnloadModel(slot));
+            }
+            if self.model_path(slot).is_some() {
+                commands.push(Command::EjectModel(slot));
+            }
+        }
+        if has_results && !busy {
+            commands.extend(ExportFormat::ALL.map(Command::Export));
+        }
+        commands.extend(
+            ResultTest::ALL
+                .into_iter()
+                .filter(|&test| self.can_start_test(test))
+                .map(Command::Test),
+        );
+        let display = &self.display;
+        commands.extend(
+            [ViewMode::Split, ViewMode::Unified]
+                .into_iter()
+                .filter(|&mode| mode != display.view_mode)
+                .map(Command::ViewMode),
+        );
+        commands.extend(
+            TokenColorMode::ALL
+                .into_iter()
+                .filter(|&mode| mode != display.color_mode)
+                .map(Command::ColorMode),
+        );
+        commands.extend(
+            [
+                UnifiedColorMode::AvgRank,
+                UnifiedColorMode::AvgProbability,
+                UnifiedColorMode::AvgMargin,
+                UnifiedColorMode::RankDivergence,
+                UnifiedColorMode::ProbDivergence,
+            ]
+            .into_iter()
+            .filter(|&mode| mode != display.unified_color_mode)
+            .map(Command::UnifiedColorMode),
+        );
+        commands.extend([Command::ToggleRepeats, Command::ToggleLive]);
+        if !self.input_text.is_empty() {
+            commands.push(Command::AddToQueue);
+        }
+        if !self.queue.is_empty() && self.can_analyze_queue() {
+            commands.push(Command::RunQueue);
+        }
+        commands.extend(
+            MainTab::ALL
+                .into_iter()
+                .filter(|&tab| tab != self.tab)
+                .map(Command::Tab),
+        );
+        commands.extend([
+            Command::Settings,
+            Command::ToggleLogs,
+            Command::ToggleResourceMonitor,
+        ]);
+        commands
+    }
+
+    fn run_command(&mut self, ctx: &egui::Context, command: Command) {
+        match command {
+            Command::Analyze => {
+                if self.can_analyze() {
+                    self.live_analysis_due = None;
+                    self.start_analysis();
+                }
+            }
+            Command::PasteAndAnalyze => self.paste_and_analyze(ctx),
+            Command::Cancel => self.cancel_analysis(),
+            Command::TogglePause => self.toggle_pause(),
+            Command::OpenFile => self.open_text_file(),
+            Command::LoadModel(slot) => self.select_model(slot),
+            Command::EjectModel(slot) => self.clear_model(slot),
+            Command::UnloadModel(slot) => self.unload_model(slot),
+            Command::Export(format) => {
+                if !self.is_busy() && self.slots.iter().any(|s| s.result.is_some()) {
+                    self.export_results(format);
+                }
+            }
+            Command::Test(test) => self.start_result_test(test),
+            Command::ViewMode(mode) => self.display.view_mode = mode,
+            Command::ColorMode(mode) => self.display.color_mode = mode,
+            Command::UnifiedColorMode(mode) => self.display.unified_color_mode = mode,
+            Command::ToggleRepeats => {
+                self.display.highlight_repeats = !self.display.highlight_repeats;
+            }
+            Command::ToggleLive => {
+                self.settings.live_analysis = !self.settings.live_analysis;
+                if !self.settings.live_analysis {
+                    self.live_analysis_due = None;
+                }
+                self.save_settings();
+            }
+            Command::AddToQueue => self.handle_queue_action(QueueAction {
+                add_current: true,
+                ..Default::default()
+            }),
+            Command::RunQueue => self.handle_queue_action(QueueAction {
+                run: true,
+                ..Default::default()
+            }),
+            Command::Tab(tab) => self.tab = tab,
+            Command::Settings => self.settings_draft = Some(SettingsDraft::new(&self.settings)),
+            Command::ToggleLogs => self.log_panel.open = !self.log_panel.open,
+            Command::ToggleResourceMonitor => {
+                self.settings.show_resource_monitor = !self.settings.show_resource_monitor;
+                self.save_settings();
+            }
+        }
+    }
+
+    /// Sends a desktop notification when a long stretch of work ends while
+    /// the window is in the background.
+    fn notify_when_done(&mut self, ctx: &egui::Context) {
+        if self.is_busy() {
+            let analyzing = self.jit_phase != JitPhase::Idle
+                || self.slots.iter().any(|s| s.worker.is_analyzing);
+            let (_, analyzed) = self.busy_since.get_or_insert((Instant::now(), false));
+            *analyzed |= analyzing;
+            return;
+        }
+        let Some((since, analyzed)) = self.busy_since.take() else {
+            return;
+        };
+        let elapsed = since.elapsed();
+        let focused = ctx.input(|i| i.viewport().focused).unwrap_or(true);
+        if !self.settings.notify_when_done
+            || focused
+            || elapsed < Duration::from_secs(self.settings.notify_after_secs)
+        {
+            return;
+        }
+
+        let work = if analyzed {
+            "Analysis"
+        } else {
+            "Model loading"
+        };
+        let took = ui_main::format_duration(elapsed);
+        let (title, body) = match self.error_message {
+            Some(ref error) => (
+                format!("{} failed", work),
+                error.lines().next().unwrap_or_default().to_string(),
+            ),
+            None if analyzed => {
+                let perplexities: Vec<String> = ModelSlot::ALL
+                    .into_iter()
+                    .filter_map(|slot| {
+                        let result = self.slots[slot.index()].result.as_ref()?;
+                        Some(format!("{}: {:.2}", slot.label(), result.perplexity()))
+                    })
+                    .collect();
+                (
+                    "Analysis finished".to_string(),
+                    format!("Perplexity {} (took {})", perplexities.join(", "), took),
+                )
+            }
+            None => ("Model loaded".to_string(), format!("Ready after {}", took)),
+        };
+        notify::send(&format!("Perplex: {}", title), &body);
+    }
+
+    /// Raises the window and asks for the clipboard; `take_paste` then
+    /// analyzes it once the models are ready.
+    fn paste_and_analyze(&mut self, ctx: &egui::Context) {
+        raise_window(ctx);
+        ctx.send_viewport_cmd(egui::ViewportCommand::RequestPaste);
+        self.paste_pending = true;
+    }
+
+    /// Handles remote commands and a requested paste. The paste event is
+    /// removed so it doesn't also land in a focused text field.
+    fn take_paste(&mut self, ctx: &egui::Context) {
+        let commands: Vec<RemoteCommand> =
+            self.remote_rx.iter().flat_map(|rx| rx.try_iter()).collect();
+        for command in commands {
+            match command {
+                RemoteCommand::PasteAndAnalyze => self.paste_and_analyze(ctx),
+                #[cfg(feature = "tray")]
+                RemoteCommand::Show => raise_window(ctx),
+                #[cfg(feature = "tray")]
+                RemoteCommand::Quit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            }
+        }
+        if !self.paste_pending {
+            return;
+        }
+        let pasted = ctx.input_mut(|input| {
+            let index = input
+                .events
+                .iter()
+                .position(|e| matches!(e, egui::Event::Paste(_)))?;
+            match input.events.remove(index) {
+                egui::Event::Paste(text) => Some(text),
+                _ => None,
+            }
+        });
+        let Some(text) = pasted else {
+            return;
+        };
+        self.paste_pending = false;
+        if text.trim().is_empty() || (self.is_busy() && !self.settings.live_analysis) {
+            return;
+        }
+        self.input_text = text;
+        self.tab = MainTab::Analyze;
+        self.token_count_due = Some(Instant::now());
+        self.auto_analyze = true;
+    }
+
+    /// Aborts the running analysis and side jobs, and anything queued
+    /// after them.
+    fn cancel_analysis(&mut self) {
+        self.live_analysis_due = None;
+        self.queue_running = false;
+        self.test_queue.clear();
+        for s in &self.slots {
+            if s.worker.is_analyzing || s.worker.is_loading {
+                let _ = s.worker.send_command(WorkerCommand::Cancel);
+            }
+            if s.worker.is_running_side_job {
+                s.worker.cancel_side_job();
+            }
+        }
+        self.cancel_preview();
+    }
+
+    /// Runs `request` on the preview model, if one is configured, loading it
+    /// on first use. It stays loaded since it's meant to be small.
+    fn start_preview(&mut self, request: &AnalysisRequest) {
+        self.preview.result = None;
+        let Some(path) = self.settings.preview_model_path.clone() else {
+            return;
+        };
+        self.cancel_preview();
+        let worker = &mut self.preview.worker;
+        if !worker.has_model && !worker.is_loading {
+            worker.load_model(path, self.settings.inference.clone());
+        }
+        let _ = worker.analyze(request.clone());
+    }
+
+    fn cancel_preview(&self) {
+        if self.preview.worker.is_analyzing {
+            let _ = self.preview.worker.send_command(WorkerCommand::Cancel);
+        }
+    }
+
+    /// Replaces the input with the contents of a text file.
+    fn open_text_file(&mut self) {
+        if self.is_busy() && !self.settings.live_analysis {
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Text", &["txt", "md"])
+            .set_title("Open Text")
+            .pick_file()
+        else {
+            return;
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                self.input_text = text;
+                self.token_count_due = Some(Instant::now());
+            }
+            Err(e) => self.append_error(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    fn handle_queue_action(&mut self, action: QueueAction) {
+        if action.add_current && !self.input_text.is_empty() {
+            self.queue.push_back(QueuedText {
+                name: short_name(&self.input_text),
+                text: self.input_text.clone(),
+                tags: Vec::new(),
+                source: None,
+            });
+        }
+        if action.add_files {
+            if let Some(files) = rfd::FileDialog::new()
+                .add_filter("Text", &["txt", "md"])
+                .set_title("Queue Texts")
+                .pick_files()
+            {
+                for file in files {
+                    match std::fs::read_to_string(&file) {
+                        Ok(text) => self.queue.push_back(QueuedText {
+                            name: file.file_name().map_or_else(
+                                || file.display().to_string(),
+                                |n| n.to_string_lossy().into_owned(),
+                            ),
+                            text,
+                            tags: Vec::new(),
+                            source: None,
+                        }),
+                        Err(e) => {
+                            self.append_error(format!("Failed to read {}: {}", file.display(), e))
+                        }
+                    }
+                }
+            }
+        }
+        if action.import {
+            self.import_labeled();
+        }
+        if let Some(i) = action.remove {
+            self.queue.remove(i);
+        }
+        if action.run {
+            self.queue_running = true;
+            self.run_next_queued();
+        }
+        if action.stop {
+            self.queue_running = false;
+        }
+        if let Some(i) = action.show {
+            self.show_history(i);
+        }
+        if action.clear_history {
+            self.history.clear();
+        }
+    }
+
+    /// Queues every text of a labeled evaluation set, so its analyses are
+    /// stored under their labels for the trends and ROC views.
+    fn import_labeled(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Labeled texts", &["csv", "tsv", "jsonl", "ndjson"])
+            .set_title("Import Labeled Texts")
+            .pick_file()
+        else {
+            return;
+        };
+        match import::read_labeled(&path) {
+            Ok(texts) => {
+                if !self.settings.store.enabled {
+                    self.append_error(
+                        "The analysis store is off, so imported texts won't be stored with \
+                         their labels; turn it on in Settings → Export"
+                            .to_string(),
+                    );
+                }
+                log::info!("Queued {} texts from {}", texts.len(), path.display());
+                self.queue.extend(texts.into_iter().map(|t| QueuedText {
+                    name: t.name,
+                    text: t.text,
+                    tags: t.tags,
+                    source: None,
+                }));
+            }
+            Err(e) => self.append_error(format!("Failed to import {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Starts analyzing the next queued text, or stops the queue when it's
+    /// empty.
+    fn run_next_queued(&mut self) {
+        if !self.can_analyze_queue() {
+            return;
+        }
+        let Some(item) = self.queue.pop_front() else {
+            self.queue_running = false;
+            return;
+        };
+        log::info!("Analyzing queued text '{}'", item.name);
+        self.input_text = item.text.clone();
+        self.live_analysis_due = None;
+        self.start_analysis();
+        self.queue_current = Some(item);
+    }
+
+    fn can_analyze_queue(&self) -> bool {
+        self.has_any_model() && !self.is_busy()
+    }
+
+    fn handle_store_action(&mut self, action: StoreAction) {
+        if let Some(id) = action.show {
+            match self.store.load(id) {
+                Ok((text, result)) => {
+                    self.input_text = text;
+                    let results = [Some(Arc::new(result)), None];
+                    for (slot, result) in ModelSlot::ALL.into_iter().zip(results) {
+                        let s = &mut self.slots[slot.index()];
+                        s.result = result;
+                        s.clear_tests();
+                        self.update_result_stats(slot);
+                    }
+                    self.token_count_due = Some(Instant::now());
+                }
+                Err(e) => self.append_error(format!("Failed to load stored analysis: {}", e)),
+            }
+        }
+        let updated = match (action.delete, action.set_tags) {
+            (Some(id), _) => self.store.delete(id),
+            (None, Some((id, tags))) => self.store.set_tags(id, tags),
+            (None, None) => Ok(()),
+        };
+        if let Err(e) = updated {
+            self.append_error(format!("Failed to update the analysis store: {}", e));
+        }
+    }
+
+    /// Adds a slot's finished analysis to the store.
+    fn store_result(&mut self, slot: ModelSlot) {
+        let options = self.settings.store;
+        let path = self.model_path(slot).cloned();
+        let (true, Some(result), Some(path), Some(request)) = (
+            options.enabled,
+            self.slots[slot.index()].result.clone(),
+            path,
+            self.last_request.as_ref(),
+        ) else {
+            return;
+        };
+        let name = self.analysis_name(request);
+        let mut tags = store::parse_tags(&self.store_view.new_tags);
+        for tag in self.queue_current.iter().flat_map(|item| &item.tags) {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        let (database, request) = (self.store.path().to_path_buf(), request.clone());
+        let (tx, ctx) = (self.stored_tx.clone(), self.ctx.clone());
+        std::thread::spawn(move || {
+            match store::record(&database, name, tags, &path, &request, &result, options) {
+                Ok(record) => {
+                    let _ = tx.send(record);
+                    ctx.request_repaint();
+                }
+                Err(e) => log::warn!("Failed to store the analysis: {}", e),
+            }
+        });
+    }
+
+    /// Name of the analysis of `request`: the queued text it came from or
+    /// the first line of the input.
+    fn analysis_name(&self, request: &AnalysisRequest) -> String {
+        self.queue_current
+            .as_ref()
+            .map_or_else(|| short_name(&request.text), |item| item.name.clone())
+    }
+
+    /// Hands a slot's finished analysis to the post-analysis command.
+    fn run_hook(&self, slot: ModelSlot) {
+        let (Some(result), Some(request)) = (
+            self.slots[slot.index()].result.clone(),
+            self.last_request.as_ref(),
+        ) else {
+            return;
+        };
+        let model =
+            model_name_from_path(self.model_path(slot).map(String::as_str)).unwrap_or(slot.label());
+        hook::run(
+            &self.settings.hook,
+            model,
+            &self.analysis_name(request),
+            result,
+        );
+    }
+
+    /// Records the finished queued analysis in the history and moves on to
+    /// the next text while the queue is running.
+    fn advance_queue(&mut self) {
+        if self.is_busy() {
+            return;
+        }
+        if let Some(item) = self.queue_current.take() {
+            if let Some(source) = &item.source {
+                let sections = self.report_sections();
+                match watch::write_outputs(&self.settings.watch, source, &sections) {
+                    Ok(()) => log::info!("Wrote the results of {}", source.display()),
+                    Err(e) => {
+                        log::warn!("Failed to write the results of {}: {}", source.display(), e)
+                    }
+                }
+            }
+            self.history.push(HistoryEntry {
+                name: item.name,
+                text: item.text,
+                results: [self.slots[0].result.clone(), self.slots[1].result.clone()],
+            });
+        }
+        if self.queue_running {
+            self.run_next_queued();
+        }
+    }
+
+    /// Queues the texts that appeared in the watched folder and starts the
+    /// queue for them.
+    fn poll_watched_folder(&mut self, ctx: &egui::Context) {
+        let Some(folder) = self.settings.watch.folder().map(Path::to_path_buf) else {
+            return;
+        };
+        ctx.request_repaint_after(watch::POLL_INTERVAL);
+        let files = match self.watcher.poll(&folder) {
+            Ok(files) => files,
+            Err(e) => {
+                log::warn!(
+                    "Failed to read the watched folder {}: {}",
+                    folder.display(),
+                    e
+                );
+                return;
+            }
+        };
+        if files.is_empty() {
+            return;
+        }
+        for file in files {
+            match std::fs::read_to_string(&file) {
+                Ok(text) if !text.trim().is_empty() => {
+                    log::info!("Queued {} from the watched folder", file.display());
+                    self.queue.push_back(QueuedText {
+                        name: file.file_name().map_or_else(
+                            || file.display().to_string(),
+                            |n| n.to_string_lossy().into_owned(),
+                        ),
+                        text,
+                        tags: Vec::new(),
+                        source: Some(file),
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to read {}: {}", file.display(), e),
+            }
+        }
+        if !self.queue_running {
+            self.queue_running = true;
+            self.run_next_queued();
+        }
+    }
+
+    /// Shows a history entry's text and results as the current analysis.
+    fn show_history(&mut self, index: usize) {
+        let Some(entry) = self.history.get(index) else {
+            return;
+        };
+        self.input_text = entry.text.clone();
+        let results = entry.results.clone();
+        for (slot, result) in ModelSlot::ALL.into_iter().zip(results) {
+            let s = &mut self.slots[slot.index()];
+            s.result = result;
+            s.clear_tests();
+            self.update_result_stats(slot);
+        }
+        self.token_count_due = Some(Instant::now());
+    }
+
+    /// Tokenizes the playground text with the selected slot's model,
+    /// loading it first if needed.
+    fn request_token_list(&mut self) {
+        let slot = self.tokenizer.slot;
+        let Some(path) = self.model_path(slot).cloned() else {
+            return;
+        };
+        self.ensure_loaded(slot, path);
+        let worker = &self.slots[slot.index()].worker;
+        let text = self.tokenizer.text.clone();
+        if worker.list_tokens(text, self.tokenizer.add_bos).is_ok() {
+            self.tokenizer.pending = true;
+        }
+    }
+
+    /// Requests a token count from every ready slot that has no cached count
+    /// for the current text and isn't already counting it.
+    fn request_token_counts(&mut self) {
+        self.token_count_due = None;
+
+        let text = preprocess::apply(&self.input_text, &self.settings.preprocess);
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        for s in &mut self.slots {
+            if !s.worker.is_ready() {
+                continue;
+            }
+            if let Some(&count) = s.token_count_cache.get(&hash) {
+                s.token_count = Some(count);
+            } else if s.pending_token_counts.back() != Some(&hash)
+                && s.worker.count_tokens(text.clone()).is_ok()
+            {
+                s.pending_token_counts.push_back(hash);
+            }
+        }
+    }
+
+    fn process_worker_messages(&mut self) {
+        for msg in self.preview.worker.poll_messages() {
+            match msg {
+                // Dropped if a configured model already finished.
+                worker::WorkerMessage::Completed(result)
+                    if self.slots.iter().all(|s| s.result.is_none()) =>
+                {
+                    self.preview.result = Some(result);
+                }
+                worker::WorkerMessage::Error(error) => log::warn!("Preview model: {}", error),
+                _ => {}
+            }
+        }
         for slot in ModelSlot::ALL {
             let messages = self.slots[slot.index()].worker.poll_messages();
             for msg in messages {
                 match msg {
                     worker::WorkerMessage::ModelLoaded => {
                         log::info!("{} loaded and ready", slot.label());
-                        if self.jit_phase == JitPhase::Idle && !input_text.is_empty() {
-                            let _ = self.slots[slot.index()]
-                                .worker
-                                .send_command(WorkerCommand::Tokenize(input_text.clone()));
+                        self.slots[slot.index()].unloaded = false;
+                        self.slots[slot.index()].token_count_cache.clear();
+                        if self.jit_phase == JitPhase::Idle && !self.input_text.is_empty() {
+                            self.request_token_counts();
                         }
                     }
                     worker::WorkerMessage::ModelUnloaded => {
                         log::info!("{} unloaded", slot.label());
-                        self.slots[slot.index()].token_count = None;
+                        let s = &mut self.slots[slot.index()];
+                        s.token_count = None;
+                        s.token_count_cache.clear();
                         self.advance_jit_on_unload(slot);
                     }
                     worker::WorkerMessage::TokenCount(count) => {
-                        self.slots[slot.index()].token_count = Some(count);
+                        let s = &mut self.slots[slot.index()];
+                        if let Some(hash) = s.pending_token_counts.pop_front() {
+                            if s.token_count_cache.len() >= TOKEN_COUNT_CACHE_LIMIT {
+                                s.token_count_cache.clear();
+                            }
+                            s.token_count_cache.insert(hash, count);
+                        }
+                        s.token_count = Some(count);
+                    }
+                    worker::WorkerMessage::TokenList(tokens) => {
+                        self.tokenizer.pending = false;
+                        match tokens {
+                            Ok(tokens) => self.tokenizer.tokens = Some((slot, tokens)),
+                            Err(error) => self.append_error(format!("{}: {}", slot.label(), error)),
+                        }
+                        // Release a model that was only loaded to tokenize.
+                        self.apply_preload_policy();
                     }
                     worker::WorkerMessage::Completed(result) => {
-                        self.slots[slot.index()].result = Some(result);
+                        self.record_throughput(slot, &result);
+                        let s = &mut self.slots[slot.index()];
+                        if let Some(previous) = s.result.replace(Arc::new(result)) {
+                            drop_in_background(previous);
+                        }
+                        s.clear_tests();
+                        self.preview.result = None;
+                        self.cancel_preview();
+                        self.update_result_stats(slot);
+                        self.store_result(slot);
+                        self.run_hook(slot);
                         self.advance_jit_on_complete(slot);
                     }
+                    worker::WorkerMessage::Rescored(second) => {
+                        let stats = self.slots[slot.index()]
+                            .result
+                            .as_ref()
+                            .map(|first| DeterminismStats::new(first, &second));
+                        match stats {
+                            Some(Ok(stats)) => {
+                                log::info!(
+                                    "{}: {} of {} tokens diverged between runs",
+                                    slot.label(),
+                                    stats.diverged,
+                                    stats.compared
+                                );
+                                self.slots[slot.index()].determinism = Some(stats);
+                            }
+                            Some(Err(e)) => self.append_error(format!("{}: {}", slot.label(), e)),
+                            None => {}
+                        }
+                        self.apply_preload_policy();
+                        self.run_next_test();
+                    }
+                    worker::WorkerMessage::Scored(perplexities) => {
+                        match self.slots[slot.index()].score_job.take() {
+                            Some(ScoreJob::Reference) => self.finish_reference(slot, perplexities),
+                            Some(ScoreJob::Perturbation) => {
+                                let s = &mut self.slots[slot.index()];
+                                if let Some(ref result) = s.result {
+                                    s.perturbation = Some(PerturbationStats::new(
+                                        result.perplexity(),
+                                        &perplexities,
+                                    ));
+                                }
+                            }
+                            Some(ScoreJob::Isolation(segments)) => {
+                                self.slots[slot.index()].isolation =
+                                    Some(IsolationStats::new(segments, &perplexities));
+                            }
+                            Some(ScoreJob::Conditioning(names)) => {
+                                self.slots[slot.index()].conditioning =
+                                    ConditioningStats::new(names, &perplexities);
+                            }
+                            Some(ScoreJob::Shuffle(sentences)) => {
+                                let s = &mut self.slots[slot.index()];
+                                if let (Some(result), Some(&shuffled)) =
+                                    (&s.result, perplexities.first())
+                                {
+                                    s.shuffle = Some(ShuffleStats {
+                                        original: result.perplexity(),
+                                        shuffled,
+                                        sentences,
+                                    });
+                                }
+                            }
+                            None => {}
+                        }
+                        // Release a model that was only loaded for scoring.
+                        self.apply_preload_policy();
+                        self.run_next_test();
+                    }
+                    worker::WorkerMessage::VariantsAnalyzed(results) => {
+                        let s = &mut self.slots[slot.index()];
+                        if let Some(ref result) = s.result {
+                            let stats = BosSensitivity::new(result, &results);
+                            log::info!(
+                                "{}: perplexity varies {:.2}× across BOS and leading-space settings",
+                                slot.label(),
+                                stats.spread()
+                            );
+                            s.bos_sensitivity = Some(stats);
+                        }
+                        self.apply_preload_policy();
+                        self.run_next_test();
+                    }
+                    worker::WorkerMessage::ContextSwept(points) => {
+                        self.slots[slot.index()].context_sweep = Some(points);
+                        self.apply_preload_policy();
+                        self.run_next_test();
+                    }
+                    worker::WorkerMessage::ChunkSwept(sweep) => {
+                        self.slots[slot.index()].chunk_sweep = Some(sweep);
+                        self.apply_preload_policy();
+                        self.run_next_test();
+                    }
+                    worker::WorkerMessage::Regenerated(stats) => {
+                        self.slots[slot.index()].regeneration = Some(stats);
+                        self.apply_preload_policy();
+                        self.run_next_test();
+                    }
+                    worker::WorkerMessage::RegeneratedDiff(diff) => {
+                        self.slots[slot.index()].regeneration_diff = Some(diff);
+                        self.apply_preload_policy();
+                        self.run_next_test();
+                    }
+                    worker::WorkerMessage::ModelsAnalyzed(results) => {
+                        self.finish_cross_quant(slot, results);
+                        self.apply_preload_policy();
+                        self.run_next_test();
+                    }
                     worker::WorkerMessage::Error(error) => {
+                        self.auto_analyze = false;
+                        self.tokenizer.pending = false;
+                        self.slots[slot.index()].score_job = None;
+                        self.test_queue.clear();
                         if self.jit_phase != JitPhase::Idle {
                             self.jit_phase = JitPhase::Idle;
-                            self.jit_pending_text.clear();
+                            self.jit_pending = AnalysisRequest::default();
                         }
                         self.append_error(format!("{}: {}", slot.label(), error));
                     }
-                    worker::WorkerMessage::Started | worker::WorkerMessage::Progress { .. } => {}
+                    worker::WorkerMessage::SideJobFailed(error) => {
+                        self.test_queue.clear();
+                        self.apply_preload_policy();
+                        self.append_error(format!("{}: {}", slot.label(), error));
+                    }
+                    worker::WorkerMessage::Started
+                    | worker::WorkerMessage::Paused
+                    | worker::WorkerMessage::Resumed
+                    | worker::WorkerMessage::Progress { .. }
+                    | worker::WorkerMessage::Tokens(_)
+                    | worker::WorkerMessage::Memory(_)
+                    | worker::WorkerMessage::Resources(_) => {}
                 }
             }
         }
@@ -261,18 +2108,18 @@ impl PerplexApp {
                 if let Some(path) = self.settings.model_path_b.clone() {
                     self.jit_phase = JitPhase::RunningB;
                     let b = &mut self.slots[ModelSlot::B.index()];
-                    b.worker.load_model(path);
+                    b.worker.load_model(path, self.settings.inference.clone());
                     let _ = b
                         .worker
-                        .send_command(WorkerCommand::Analyze(self.jit_pending_text.clone()));
+                        .send_command(WorkerCommand::Analyze(self.jit_pending.clone()));
                 } else {
                     self.jit_phase = JitPhase::Idle;
-                    self.jit_pending_text.clear();
+                    self.jit_pending = AnalysisRequest::default();
                 }
             }
             (JitPhase::CleanupB, ModelSlot::B) => {
                 self.jit_phase = JitPhase::Idle;
-                self.jit_pending_text.clear();
+                self.jit_pending = AnalysisRequest::default();
             }
             _ => {}
         }
@@ -280,7 +2127,7 @@ impl PerplexApp {
 
     /// Whether a given slot should be preloaded under the current settings.
     fn should_preload(&self, slot: ModelSlot) -> bool {
-        if self.model_path(slot).is_none() {
+        if self.model_path(slot).is_none() || self.slots[slot.index()].unloaded {
             return false;
         }
         match self.settings.preload_mode {
@@ -303,7 +2150,8 @@ impl PerplexApp {
             let loading = self.slots[slot.index()].worker.is_loading;
             if should && !has && !loading {
                 if let Some(path) = self.model_path(slot).cloned() {
-                    self.slots[slot.index()].worker.load_model(path);
+                    let params = self.settings.inference.clone();
+                    self.slots[slot.index()].worker.load_model(path, params);
                 }
             } else if !should && has {
                 self.slots[slot.index()].worker.unload_model();
@@ -318,6 +2166,50 @@ impl PerplexApp {
             && self.settings.model_path_b.is_some()
     }
 
+    fn record_throughput(&mut self, slot: ModelSlot, result: &analysis::AnalysisResult) {
+        let Some(model) =
+            model_name_from_path(self.model_path(slot).map(|p| p.as_str())).map(str::to_string)
+        else {
+            return;
+        };
+        if self
+            .throughput
+            .record(&model, result.tokens.len(), result.processing_time_ms)
+        {
+            if let Err(e) = self.throughput.save() {
+                log::warn!("Failed to save throughput calibration: {}", e);
+            }
+        }
+    }
+
+    /// Time left in the running analysis, or before one starts, how long
+    /// the input would take with each model's measured speed.
+    fn time_estimate(&self) -> Option<TimeEstimate> {
+        if self.is_busy() {
+            return self
+                .slots
+                .iter()
+                .filter_map(|s| s.worker.remaining)
+                .max()
+                .map(TimeEstimate::Remaining);
+        }
+        let estimates = ModelSlot::ALL
+            .into_iter()
+            .filter_map(|slot| {
+                let model = model_name_from_path(self.model_path(slot).map(|p| p.as_str()))?;
+                let tokens = self.slots[slot.index()].token_count?;
+                self.throughput.estimate(model, tokens)
+            })
+            .collect::<Vec<_>>();
+        // Parallel models run side by side, otherwise one after the other.
+        let total = if self.is_parallel() {
+            estimates.into_iter().max()
+        } else {
+            estimates.into_iter().reduce(|a, b| a + b)
+        };
+        total.map(TimeEstimate::Total)
+    }
+
     fn has_any_model(&self) -> bool {
         self.settings.model_path_a.is_some() || self.settings.model_path_b.is_some()
     }
@@ -326,37 +2218,147 @@ impl PerplexApp {
         !self.input_text.is_empty() && self.has_any_model() && !self.is_busy()
     }
 
+    /// Pauses every running analysis, or resumes them if paused.
+    fn toggle_pause(&mut self) {
+        let pause = !self.is_paused();
+        for s in &self.slots {
+            if s.worker.is_analyzing {
+                let _ = s.worker.send_command(if pause {
+                    WorkerCommand::Pause
+                } else {
+                    WorkerCommand::Resume
+                });
+            }
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.slots.iter().any(|s| s.worker.is_paused)
+    }
+
     /// True when any work is in progress (analysis, loading, or JIT sequencing).
     fn is_busy(&self) -> bool {
         self.slots.iter().any(|s| s.worker.is_analyzing || s.worker.is_loading)
             || self.jit_phase != JitPhase::Idle
     }
+
+    fn handle_settings_action(&mut self, ctx: &egui::Context, action: SettingsAction) {
+        let Some(draft) = self.settings_draft.as_mut() else {
+            return;
+        };
+        match action {
+            SettingsAction::Browse(slot) => {
+                if let Some(path) = pick_gguf_model() {
+                    *draft.path_mut(slot) = path;
+                }
+            }
+            SettingsAction::Clear(slot) => draft.path_mut(slot).clear(),
+            SettingsAction::BrowsePreview => {
+                if let Some(path) = pick_gguf_model() {
+                    draft.path_preview = path;
+                }
+            }
+            SettingsAction::ClearPreview => draft.path_preview.clear(),
+            SettingsAction::BrowseWatchFolder => {
+                if let Some(folder) = rfd::FileDialog::new()
+                    .set_title("Select Watched Folder")
+                    .pick_folder()
+                {
+                    draft.settings.watch.folder = folder.display().to_string();
+                }
+            }
+            SettingsAction::BuildReference(slot) => self.build_reference(slot),
+            SettingsAction::ImportReference(slot) => self.import_reference(slot),
+            SettingsAction::Export => {
+                let settings = draft.to_settings();
+                self.export_settings(&settings);
+            }
+            SettingsAction::Import => {
+                if let Some(imported) = self.import_settings() {
+                    if let Some(draft) = self.settings_draft.as_mut() {
+                        draft.reset_to(&imported);
+                    }
+                }
+            }
+            SettingsAction::Apply | SettingsAction::ApplyAndClose => {
+                let new = draft.to_settings();
+                if matches!(action, SettingsAction::ApplyAndClose) {
+                    self.settings_draft = None;
+                }
+                self.apply_settings(ctx, new);
+            }
+            SettingsAction::Cancel => self.settings_draft = None,
+        }
+    }
 }
 
 impl eframe::App for PerplexApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.process_worker_messages();
+        self.poll_result_stats();
+        self.advance_queue();
+        self.poll_watched_folder(ctx);
+        self.notify_when_done(ctx);
+        self.take_paste(ctx);
+        self.handle_shortcuts(ctx);
+        if let Some(release) = self.update_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.update = release;
+            self.update_rx = None;
+        }
+
+        if let Some(due) = self.token_count_due {
+            let now = Instant::now();
+            if now >= due {
+                self.request_token_counts();
+            } else {
+                ctx.request_repaint_after(due - now);
+            }
+        }
+
+        if let Some(due) = self.live_analysis_due {
+            let now = Instant::now();
+            if now < due {
+                ctx.request_repaint_after(due - now);
+            } else if self.can_analyze() {
+                self.live_analysis_due = None;
+                self.start_analysis();
+            }
+        }
+
+        // Once the models are loaded, or failed to load, analyze now or not
+        // at all, rather than whenever the app is next idle.
+        if self.auto_analyze && !self.is_busy() {
+            self.auto_analyze = false;
+            if self.can_analyze() {
+                self.start_analysis();
+            }
+        }
 
         if self.is_busy() {
             ctx.request_repaint();
         }
 
+        ui_logs::render_log_panel(ctx, &mut self.log_panel);
+        if self.settings.show_resource_monitor {
+            ui_resources::render_resource_panel(
+                ctx,
+                &self.slots[0].worker.resource_samples,
+                &self.slots[1].worker.resource_samples,
+            );
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::Frame::none().inner_margin(20.0).show(ui, |ui| {
                 let header = ui_main::render_header(
                     ui,
                     self.settings.model_path_a.as_deref(),
                     self.settings.model_path_b.as_deref(),
-                    self.slots[0].worker.is_loading,
-                    self.slots[1].worker.is_loading,
+                    ModelStatus::of(&self.slots[0].worker),
+                    ModelStatus::of(&self.slots[1].worker),
+                    self.slots.iter().find_map(|s| s.worker.memory.as_ref()),
                 );
                 if header.settings {
-                    self.show_settings = true;
-                    for slot in ModelSlot::ALL {
-                        self.slots[slot.index()].settings_path_buffer =
-                            self.model_path(slot).cloned().unwrap_or_default();
-                    }
-                    self.settings_preload_buffer = self.settings.preload_mode;
+                    self.settings_draft = Some(SettingsDraft::new(&self.settings));
                 }
                 if header.eject_a {
                     self.clear_model(ModelSlot::A);
@@ -364,6 +2366,17 @@ impl eframe::App for PerplexApp {
                 if header.eject_b {
                     self.clear_model(ModelSlot::B);
                 }
+                if header.unload_a {
+                    self.unload_model(ModelSlot::A);
+                }
+                if header.unload_b {
+                    self.unload_model(ModelSlot::B);
+                }
+                if let Some(ref release) = self.update {
+                    if ui_main::render_update_banner(ui, release) {
+                        self.update = None;
+                    }
+                }
 
                 ui.add_space(12.0);
 
@@ -379,61 +2392,216 @@ impl eframe::App for PerplexApp {
                     self.select_model(ModelSlot::B);
                 }
 
+                ui_main::render_tab_bar(ui, &mut self.tab);
+                if self.tab == MainTab::Tokenizer {
+                    let configured = ModelSlot::ALL.map(|slot| self.model_path(slot).is_some());
+                    let action =
+                        ui_tokenizer::render_tokenizer_tab(ui, &mut self.tokenizer, configured);
+                    let slot = self.tokenizer.slot;
+                    if action.tokenize
+                        || (action.changed && self.slots[slot.index()].worker.is_ready())
+                    {
+                        self.request_token_list();
+                    }
+                    return;
+                }
+                if self.tab == MainTab::Trends {
+                    let action = ui_trends::render_trends_tab(
+                        ui,
+                        &mut self.trends,
+                        &self.store,
+                        self.settings.verdict.as_ref(),
+                    );
+                    if action.set_verdict.is_some() || action.clear_verdict {
+                        self.settings.verdict = action.set_verdict;
+                        self.save_settings();
+                    }
+                    return;
+                }
+
                 let available = ui.available_height();
-                let has_results = self.slots[0].result.is_some() || self.slots[1].result.is_some();
+                let has_results = self.slots[0].result.is_some()
+                    || self.slots[1].result.is_some()
+                    || self.preview.result.is_some();
                 let input_height = if has_results {
                     (available * 0.25).max(100.0)
                 } else {
                     (available * 0.35).max(120.0)
                 };
 
-                let not_busy = !self.is_busy();
-                if ui_main::render_text_input(
+                // Live mode keeps the editor usable while the previous
+                // revision is being analyzed.
+                let editable = !self.is_busy() || self.settings.live_analysis;
+                let input = ui_main::render_text_input(
                     ui,
                     &mut self.input_text,
-                    not_busy,
-                    input_height,
-                    self.slots[0].token_count,
-                    self.slots[1].token_count,
-                ) {
-                    // Live token counts when models are preloaded.
-                    let updated_text = self.input_text.clone();
+                    &mut self.prompt,
+                    &self.settings.prefix_presets,
+                    &mut self.settings.domain,
+                    InputView {
+                        enabled: editable,
+                        height: input_height,
+                        token_counts: (self.slots[0].token_count, self.slots[1].token_count),
+                    },
+                );
+                if input.save_preset || input.delete_preset {
+                    self.update_prefix_presets(input.save_preset);
+                }
+                if input.domain_changed {
                     for slot in ModelSlot::ALL {
-                        let s = &mut self.slots[slot.index()];
-                        if s.worker.is_ready() {
-                            let _ = s.worker.send_command(WorkerCommand::Tokenize(
-                                updated_text.clone(),
-                            ));
-                        }
+                        self.load_reference(slot);
+                    }
+                    self.save_settings();
+                }
+                if input.changed {
+                    // Live token counts when models are preloaded, refreshed
+                    // once typing pauses.
+                    self.token_count_due = Some(Instant::now() + TOKEN_COUNT_DEBOUNCE);
+                    if self.settings.live_analysis {
+                        self.live_analysis_due = Some(Instant::now() + LIVE_ANALYSIS_DELAY);
                     }
                 }
 
-                if ui_main::render_controls(
+                if self.settings.preprocess.is_active() {
+                    ui_main::render_preprocess_preview(
+                        ui,
+                        &self.input_text,
+                        &self.settings.preprocess,
+                    );
+                }
+
+                let can_run_tests = ResultTest::ALL
+                    .into_iter()
+                    .any(|test| self.can_start_test(test));
+                let controls = ui_main::render_controls(
                     ui,
                     self.can_analyze(),
-                    self.is_busy(),
-                    self.slots[0].worker.progress,
-                    self.slots[1].worker.progress,
-                ) {
+                    RunProgress {
+                        is_analyzing: self.is_busy(),
+                        paused: self
+                            .slots
+                            .iter()
+                            .any(|s| s.worker.is_analyzing)
+                            .then(|| self.is_paused()),
+                        bars: self
+                            .slots
+                            .each_ref()
+                            .map(|s| s.worker.progress.or(s.worker.side_progress)),
+                        estimate: self.time_estimate(),
+                    },
+                    &mut self.settings.live_analysis,
+                    can_run_tests,
+                    self.slots.iter().any(|s| s.result.is_some()),
+                );
+                if let Some(test) = controls.test {
+                    self.start_result_test(test);
+                }
+                if let Some(format) = controls.export {
+                    self.export_results(format);
+                }
+                if controls.pause_toggled {
+                    self.toggle_pause();
+                }
+                if controls.analyze {
+                    self.live_analysis_due = None;
                     self.start_analysis();
                 }
+                if controls.live_toggled {
+                    if !self.settings.live_analysis {
+                        self.live_analysis_due = None;
+                    }
+                    self.save_settings();
+                }
+
+                let queue = ui_main::render_queue(
+                    ui,
+                    &self.queue,
+                    &self.history,
+                    self.queue_running,
+                    self.can_analyze_queue(),
+                );
+                self.handle_queue_action(queue);
+                let store = ui_main::render_store(ui, &self.store.records, &mut self.store_view);
+                self.handle_store_action(store);
 
                 if let Some(ref error) = self.error_message {
-                    ui_main::render_error(ui, error);
+                    match ui_main::render_error(ui, error) {
+                        Some(ErrorAction::ShowLogs) => self.log_panel.open = true,
+                        Some(ErrorAction::SaveBundle) => {
+                            self.bundle_dialog = Some(BundleOptions::default());
+                        }
+                        None => {}
+                    }
                 }
 
                 // Re-check after start_analysis may have cleared results.
                 let has_results = self.slots[0].result.is_some() || self.slots[1].result.is_some();
                 if has_results {
+                    let paths = [
+                        self.settings.model_path_a.as_deref(),
+                        self.settings.model_path_b.as_deref(),
+                    ];
+                    let verdict = self.settings.verdict.as_ref();
+                    let [view_a, view_b] = ModelSlot::ALL.map(|slot| {
+                        let s = &self.slots[slot.index()];
+                        let model = model_name_from_path(paths[slot.index()]);
+                        s.result.as_ref().map(|result| ResultView {
+                            result,
+                            name: model.unwrap_or(slot.label()),
+                            reference: s.reference.as_ref(),
+                            speakers: &s.stats.speakers,
+                            token_types: &s.stats.token_types,
+                            classes: &s.stats.classes,
+                            perturbation: s.perturbation.as_ref(),
+                            watermark: s.stats.watermark.as_ref(),
+                            custom_metrics: &s.stats.custom_metrics,
+                            special_tokens: &s.stats.special_tokens,
+                            confusables: &s.stats.confusables,
+                            typography: s.stats.typography.as_ref(),
+                            text_stats: s.stats.text_stats.as_ref(),
+                            summary: &self.settings.summary_metrics,
+                            format: self.settings.number_format,
+                            token_colors: s.stats.token_colors.as_ref(),
+                            context_sweep: s.context_sweep.as_deref(),
+                            chunk_sweep: s.chunk_sweep.as_ref(),
+                            isolation: s.isolation.as_ref(),
+                            conditioning: s.conditioning.as_ref(),
+                            shuffle: s.shuffle.as_ref(),
+                            determinism: s.determinism.as_ref(),
+                            bos_sensitivity: s.bos_sensitivity.as_ref(),
+                            regeneration: s.regeneration.as_ref(),
+                            regeneration_diff: s.regeneration_diff.as_ref(),
+                            quant_variance: s.quant_variance.as_ref(),
+                            verdict: verdict.filter(|v| Some(v.model.as_str()) == model),
+                        })
+                    });
+                    let regenerate = ui_main::render_results(
+                        ui,
+                        view_a,
+                        view_b,
+                        self.significance.as_ref(),
+                        ui.available_height(),
+                        &mut self.display,
+                    );
+                    if let Some(token) = regenerate {
+                        self.regenerate_from(token);
+                    }
+                } else if let Some(ref result) = self.preview.result {
+                    let name = model_name_from_path(self.settings.preview_model_path.as_deref())
+                        .unwrap_or("Preview");
+                    ui_main::render_preview_notice(ui, name);
+                    let view = ResultView {
+                        summary: &self.settings.summary_metrics,
+                        format: self.settings.number_format,
+                        ..ResultView::bare(result, name)
+                    };
                     ui_main::render_results(
                         ui,
-                        self.slots[0].result.as_ref(),
-                        self.slots[1].result.as_ref(),
-                        model_name_from_path(self.settings.model_path_a.as_deref()),
-                        model_name_from_path(self.settings.model_path_b.as_deref()),
+                        Some(view),
+                        None,
+                        None,
                         ui.available_height(),
-                        &mut self.view_mode,
-                        &mut self.unified_color_mode,
+                        &mut self.display,
                     );
                 } else if !self.is_busy() {
                     ui_main::render_empty_state(ui, self.has_any_model());
@@ -441,50 +2609,33 @@ impl eframe::App for PerplexApp {
             });
         });
 
-        if self.show_settings {
-            let [slot_a, slot_b] = &mut self.slots;
-            let action = ui_settings::render_settings_window(
-                ctx,
-                &mut self.show_settings,
-                &mut slot_a.settings_path_buffer,
-                &mut slot_b.settings_path_buffer,
-                &mut self.settings_preload_buffer,
-            );
-            if let Some(action) = action {
-                match action {
-                    ui_settings::SettingsAction::Browse(slot) => {
-                        if let Some(path) = pick_gguf_model() {
-                            self.slots[slot.index()].settings_path_buffer = path;
-                        }
-                    }
-                    ui_settings::SettingsAction::Save => {
-                        self.show_settings = false;
-
-                        self.settings.preload_mode = self.settings_preload_buffer;
+        if let Some(mut state) = self.palette.take() {
+            let commands = self.palette_commands();
+            let action = ui_palette::render_palette(ctx, &mut state, &commands);
+            if !action.close {
+                self.palette = Some(state);
+            }
+            if let Some(command) = action.run {
+                self.run_command(ctx, command);
+            }
+        }
 
-                        for slot in ModelSlot::ALL {
-                            let buf = self.slots[slot.index()].settings_path_buffer.clone();
-                            if !buf.is_empty() {
-                                if self.model_path_mut(slot).as_deref() != Some(&buf) {
-                                    *self.model_path_mut(slot) = Some(buf);
-                                    self.slots[slot.index()].result = None;
-                                }
-                            } else {
-                                if self.model_path(slot).is_some() {
-                                    self.slots[slot.index()].worker.unload_model();
-                                }
-                                *self.model_path_mut(slot) = None;
-                                self.slots[slot.index()].result = None;
-                            }
-                        }
+        if let Some(mut options) = self.bundle_dialog.take() {
+            let mut open = true;
+            if ui_main::render_bundle_dialog(ctx, &mut options, &mut open) {
+                self.save_diagnostic_bundle(options);
+            } else if open {
+                self.bundle_dialog = Some(options);
+            }
+        }
 
-                        self.apply_preload_policy();
-                        self.save_settings();
-                    }
-                    ui_settings::SettingsAction::Clear(slot) => {
-                        self.slots[slot.index()].settings_path_buffer.clear();
-                    }
-                }
+        if let Some(draft) = self.settings_draft.as_mut() {
+            let references = [
+                self.slots[0].reference.as_ref(),
+                self.slots[1].reference.as_ref(),
+            ];
+            if let Some(action) = ui_settings::render_settings_window(ctx, draft, references) {
+                self.handle_settings_action(ctx, action);
             }
         }
     }
@@ -493,6 +2644,7 @@ impl eframe::App for PerplexApp {
         for s in &mut self.slots {
             s.worker.shutdown();
         }
+        self.preview.worker.shutdown();
     }
 }
 
@@ -504,6 +2656,34 @@ fn pick_gguf_model() -> Option<String> {
         .map(|p| p.to_string_lossy().to_string())
 }
 
+/// Shows, restores and focuses the window.
+fn raise_window(ctx: &egui::Context) {
+    ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+    ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+    ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+}
+
+/// Drops `value` on its own thread; freeing the tokens of a long analysis
+/// one by one takes long enough to stall a frame.
+fn drop_in_background<T: Send + 'static>(value: T) {
+    std::thread::spawn(move || drop(value));
+}
+
+/// The first non-empty line of `text`, cut to 40 characters.
+fn short_name(text: &str) -> String {
+    let first_line = text.lines().find(|l| !l.trim().is_empty());
+    let mut name: String = first_line
+        .unwrap_or_default()
+        .trim()
+        .chars()
+        .take(40)
+        .collect();
+    if name.len() < first_line.map_or(0, |l| l.trim().len()) {
+        name.push('…');
+    }
+    name
+}
+
 pub fn model_name_from_path(path: Option<&str>) -> Option<&str> {
     // Remove final .gguf if present
     path.and_then(|p| {
@@ -518,7 +2698,68 @@ pub fn model_name_from_path(path: Option<&str>) -> Option<&str> {
     })
 }
 
+/// Prints the throughput of each benchmark combination as it finishes,
+/// then the flags for the fastest one.
+fn run_benchmark(settings: &Settings) -> Result<(), String> {
+    let path = settings
+        .model_path_a
+        .as_deref()
+        .ok_or("No model configured; pass --model")?;
+    println!(
+        "Benchmarking {} with {} combinations",
+        model_name_from_path(Some(path)).unwrap_or(path),
+        benchmark::combinations(&settings.inference).len()
+    );
+    println!(
+        "{:>8} {:>8} {:>10} {:>12}",
+        "threads", "n_batch", "gpu layers", "tokens/s"
+    );
+    let runs = benchmark::run(Path::new(path), &settings.inference, |run| {
+        println!(
+            "{:>8} {:>8} {:>10} {:>12.1}",
+            run.params.n_threads.unwrap_or_default(),
+            run.params.n_batch,
+            run.params.n_gpu_layers.unwrap_or_default(),
+            run.tokens_per_sec()
+        );
+    })?;
+    if let Some(best) = benchmark::fastest(&runs) {
+        println!(
+            "Fastest: --threads {} --n-batch {} --n-gpu-layers {} ({:.1} tokens/s)",
+            best.params.n_threads.unwrap_or_default(),
+            best.params.n_batch,
+            best.params.n_gpu_layers.unwrap_or_default(),
+            best.tokens_per_sec()
+        );
+    }
+    Ok(())
+}
+
 fn main() -> eframe::Result<()> {
+    let cli = CliArgs::from_env();
+
+    // Headless modes: serve model A without opening the window.
+    if cli.serve.is_some() || cli.stdio || cli.benchmark {
+        logs::init();
+        let mut settings = Settings::load();
+        cli.apply_to(&mut settings);
+        let result = match cli.serve {
+            Some(ref addr) => server::run(addr, &settings, &cli.allow_origins),
+            None if cli.stdio => ipc::run(&settings),
+            None => run_benchmark(&settings),
+        };
+        if let Err(e) = result {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Hand the request to an open window rather than starting a second one.
+    if cli.paste_analyze && instance::send_paste_analyze() {
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1200.0, 800.0])
@@ -530,6 +2771,6 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "Perplex",
         options,
-        Box::new(|cc| Ok(Box::new(PerplexApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(PerplexApp::new(cc, cli)))),
     )
 }
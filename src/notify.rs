@@ -0,0 +1,55 @@
+use std::process::Command;
+
+/// Shows a desktop notification with the platform's command-line tool:
+/// `notify-send` on Linux, `osascript` on macOS and PowerShell on Windows.
+/// Runs in the background; failures, such as a missing tool, are only
+/// logged.
+pub fn send(title: &str, body: &str) {
+    let mut command = platform_command(title, body);
+    std::thread::spawn(move || match command.status() {
+        Ok(status) if !status.success() => log::warn!("Notification command failed: {}", status),
+        Ok(_) => {}
+        Err(e) => log::warn!("Failed to show notification: {}", e),
+    });
+}
+
+#[cfg(target_os = "macos")]
+fn platform_command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    ));
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(target_os = "windows")]
+fn platform_command(title: &str, body: &str) -> Command {
+    // A balloon tip from a temporary tray icon works without extra modules.
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         $n = New-Object System.Windows.Forms.NotifyIcon; \
+         $n.Icon = [System.Drawing.SystemIcons]::Information; \
+         $n.Visible = $true; \
+         $n.ShowBalloonTip(10000, '{}', '{}', 'Info'); \
+         Start-Sleep -Seconds 10; $n.Dispose()",
+        title.replace('\'', "''"),
+        body.replace('\'', "''")
+    );
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script]);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn platform_command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name=Perplex", title, body]);
+    command
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+/// Unit of information quantities: surprisal, entropy, log-likelihood.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InformationUnit {
+    Bits,
+    Nats,
+}
+
+impl InformationUnit {
+    pub fn label(self) -> &'static str {
+        match self {
+            InformationUnit::Bits => "bits",
+            InformationUnit::Nats => "nats",
+        }
+    }
+}
+
+/// How shares like top-1 accuracy are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShareStyle {
+    /// `45.2%`
+    Percent,
+    /// `0.452`
+    Fraction,
+}
+
+/// Precision and units of the metrics in the summary row and the exports.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NumberFormat {
+    /// Decimal places of plain numbers such as perplexity. Percentages get
+    /// one fewer and fractions one more, so all show the same precision.
+    pub decimals: usize,
+    pub information: InformationUnit,
+    pub shares: ShareStyle,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            decimals: 2,
+            information: InformationUnit::Bits,
+            shares: ShareStyle::Percent,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub const MAX_DECIMALS: usize = 6;
+
+    pub fn number(&self, value: f32) -> String {
+        format!("{:.*}", self.decimals, value)
+    }
+
+    /// `bits` in the chosen unit, without the unit.
+    pub fn information_value(&self, bits: f32) -> f32 {
+        match self.information {
+            InformationUnit::Bits => bits,
+            InformationUnit::Nats => bits * std::f32::consts::LN_2,
+        }
+    }
+
+    /// `bits` in the chosen unit, with the unit.
+    pub fn information(&self, bits: f32) -> String {
+        format!(
+            "{} {}",
+            self.number(self.information_value(bits)),
+            self.information.label()
+        )
+    }
+
+    /// [`Self::information`] for a quantity measured in nats.
+    pub fn information_nats(&self, nats: f32) -> String {
+        self.information(nats / std::f32::consts::LN_2)
+    }
+
+    pub fn share(&self, fraction: f32) -> String {
+        match self.shares {
+            ShareStyle::Percent => {
+                format!("{:.*}%", self.decimals.saturating_sub(1), fraction * 100.0)
+            }
+            ShareStyle::Fraction => format!("{:.*}", self.decimals + 1, fraction),
+        }
+    }
+}
@@ -0,0 +1,525 @@
+use std::path::Path;
+
+use egui::Color32;
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str};
+
+use crate::analysis::AnalysisResult;
+use crate::colors;
+use crate::number_format::NumberFormat;
+
+// A4 portrait, in points.
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+const MONO: Name = Name(b"F3");
+
+/// Size of the colored text. Courier advances 0.6 em per character, which
+/// keeps line wrapping exact without font metrics.
+const MONO_SIZE: f32 = 9.0;
+const MONO_ADVANCE: f32 = MONO_SIZE * 0.6;
+const MONO_LEADING: f32 = 12.0;
+const CHART_HEIGHT: f32 = 110.0;
+
+/// Rank buckets of the histogram as `(label, highest rank, representative
+/// rank for the color)`.
+pub const RANK_BUCKETS: [(&str, usize, usize); 6] = [
+    ("1", 1, 1),
+    ("2-5", 5, 3),
+    ("6-10", 10, 8),
+    ("11-50", 50, 30),
+    ("51-100", 100, 75),
+    (">100", usize::MAX, 200),
+];
+
+/// Consecutive tokens ranked worse than this form the surprising passages
+/// listed by [`describe`].
+const SURPRISING_RANK: usize = 50;
+const DESCRIBED_PASSAGES: usize = 5;
+
+/// One model's result in the report.
+pub struct ReportSection<'a> {
+    pub name: &'a str,
+    pub result: &'a AnalysisResult,
+    /// Precision and units of the summary metrics.
+    pub format: NumberFormat,
+}
+
+/// Writes a paginated PDF with, for each section, the summary metrics, a
+/// rank histogram, a surprisal chart and the text colored by rank.
+pub fn write_pdf(path: &Path, sections: &[ReportSection]) -> Result<(), String> {
+    let mut pages = PageWriter::new();
+    for (i, section) in sections.iter().enumerate() {
+        if i > 0 {
+            pages.new_page();
+        }
+        write_section(&mut pages, section);
+    }
+    std::fs::write(path, pages.finish()).map_err(|e| e.to_string())
+}
+
+fn write_section(pages: &mut PageWriter, section: &ReportSection) {
+    let result = section.result;
+    let scored = result.scored_with_context().get(1..).unwrap_or_default();
+
+    pages.heading(&format!("Perplex report: {}", section.name), 16.0);
+    pages.space(6.0);
+    for (label, value) in summary(result, &section.format) {
+        pages.line(REGULAR, 10.0, &format!("{}: {}", label, value), 14.0);
+    }
+
+    pages.space(12.0);
+    pages.heading("Rank distribution", 12.0);
+    rank_histogram(pages, &rank_counts(result));
+
+    pages.space(12.0);
+    pages.heading("Surprisal by position (bits)", 12.0);
+    let surprisals: Vec<f32> = scored.iter().map(|t| t.surprisal()).collect();
+    surprisal_chart(pages, &surprisals);
+
+    pages.space(12.0);
+    pages.heading("Text colored by rank", 12.0);
+    colored_text(pages, result);
+}
+
+/// Summary metrics of a result as `(label, value)` pairs.
+pub fn summary(result: &AnalysisResult, format: &NumberFormat) -> Vec<(&'static str, String)> {
+    let scored = result.scored_with_context().get(1..).unwrap_or_default();
+    let top1 = scored.iter().filter(|t| t.rank <= 1).count();
+    let mut ranks: Vec<usize> = scored.iter().map(|t| t.rank).collect();
+    ranks.sort_unstable();
+    let mut summary = vec![
+        ("Perplexity", format.number(result.perplexity())),
+        ("Scored tokens", scored.len().to_string()),
+        (
+            "Top-1 accuracy",
+            format.share(top1 as f32 / scored.len().max(1) as f32),
+        ),
+        (
+            "Median rank",
+            ranks
+                .get(ranks.len() / 2)
+                .copied()
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        ("Entropy", format.information(result.text_entropy())),
+    ];
+    if !result.unscored.is_empty() {
+        summary.insert(2, ("Unscored tokens", result.unscored.len().to_string()));
+    }
+    if result.has_prompt() {
+        summary.push((
+            "Log-likelihood",
+            format.information_nats(result.log_likelihood()),
+        ));
+    }
+    if let Some(language) = result.languages.first() {
+        summary.push(("Language", language.language.to_string()));
+    }
+    summary.push((
+        "Processing time",
+        format!("{:.1}s", result.processing_time_ms as f32 / 1000.0),
+    ));
+    summary
+}
+
+/// Number of scored tokens in each of the [`RANK_BUCKETS`].
+pub fn rank_counts(result: &AnalysisResult) -> [usize; RANK_BUCKETS.len()] {
+    let mut counts = [0; RANK_BUCKETS.len()];
+    for token in result.scored_with_context().iter().skip(1) {
+        let bucket = RANK_BUCKETS
+            .iter()
+            .position(|&(_, max, _)| token.rank <= max);
+        counts[bucket.unwrap_or(RANK_BUCKETS.len() - 1)] += 1;
+    }
+    counts
+}
+
+/// The result in words, for screen readers and anyone who can't rely on
+/// the colors: the summary metrics, the share of tokens in each rank bucket
+/// and the most surprising passages with their line numbers.
+pub fn describe(result: &AnalysisResult, format: &NumberFormat) -> String {
+    let mut lines: Vec<String> = summary(result, format)
+        .into_iter()
+        .map(|(label, value)| format!("{}: {}.", label, value))
+        .collect();
+
+    let counts = rank_counts(result);
+    let total = counts.iter().sum::<usize>().max(1);
+    let buckets: Vec<String> = RANK_BUCKETS
+        .iter()
+        .zip(counts)
+        .filter(|&(_, count)| count > 0)
+        .map(|(&(label, ..), count)| {
+            format!(
+                "{} ranked {} ({:.0}%)",
+                count,
+                label,
+                count as f32 / total as f32 * 100.0
+            )
+        })
+        .collect();
+    lines.push(format!("Tokens by rank: {}.", buckets.join(", ")));
+
+    let passages = surprising_passages(result);
+    if passages.is_empty() {
+        lines.push(format!(
+            "No token is ranked worse than {}.",
+            SURPRISING_RANK
+        ));
+    } else {
+        lines.push(format!(
+            "Most surprising passages (tokens ranked worse than {}):",
+            SURPRISING_RANK
+        ));
+        for (i, passage) in passages.iter().enumerate() {
+            lines.push(format!(
+                "{}. Line {}: \"{}\", {} tokens, {:.1} bits, worst rank {}.",
+                i + 1,
+                passage.line,
+                passage.text.trim(),
+                passage.tokens,
+                passage.surprisal,
+                passage.worst_rank
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+struct Passage {
+    /// One-based line of the analyzed text where the passage starts.
+    line: usize,
+    text: String,
+    tokens: usize,
+    surprisal: f32,
+    worst_rank: usize,
+}
+
+/// Runs of consecutive tokens ranked worse than [`SURPRISING_RANK`], the
+/// highest total surprisal first.
+fn surprising_passages(result: &AnalysisResult) -> Vec<Passage> {
+    let mut passages: Vec<Passage> = Vec::new();
+    let mut line = 1;
+    let mut in_passage = false;
+    let first_scored = result.first_scored();
+    for (i, token) in result.tokens.iter().enumerate().skip(result.prompt_tokens) {
+        let text = token.document_text();
+        if i >= first_scored && token.rank > SURPRISING_RANK {
+            if !in_passage {
+                let leading = text.len() - text.trim_start().len();
+                passages.push(Passage {
+                    line: line + text[..leading].matches('\n').count(),
+                    text: String::new(),
+                    tokens: 0,
+                    surprisal: 0.0,
+                    worst_rank: 0,
+                });
+                in_passage = true;
+            }
+            let passage = passages.last_mut().unwrap();
+            passage.text.push_str(text);
+            passage.tokens += 1;
+            passage.surprisal += token.surprisal();
+            passage.worst_rank = passage.worst_rank.max(token.rank);
+        } else {
+            in_passage = false;
+        }
+        line += text.matches('\n').count();
+    }
+    passages.sort_by(|a, b| b.surprisal.total_cmp(&a.surprisal));
+    passages.truncate(DESCRIBED_PASSAGES);
+    passages
+}
+
+fn rank_histogram(pages: &mut PageWriter, counts: &[usize]) {
+    let total: usize = counts.iter().sum();
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+
+    pages.ensure(CHART_HEIGHT + 30.0);
+    let bottom = pages.y - CHART_HEIGHT - 12.0;
+    let slot = CONTENT_WIDTH / RANK_BUCKETS.len() as f32;
+    for (i, (&(label, _, color_rank), &count)) in RANK_BUCKETS.iter().zip(counts).enumerate() {
+        let x = MARGIN + i as f32 * slot;
+        let height = CHART_HEIGHT * count as f32 / max as f32;
+        pages.fill_rect(
+            x + 8.0,
+            bottom,
+            slot - 16.0,
+            height,
+            colors::rank_to_color(color_rank),
+        );
+        let share = count as f32 / total.max(1) as f32 * 100.0;
+        pages.text(
+            REGULAR,
+            8.0,
+            x + 8.0,
+            bottom + height + 3.0,
+            &format!("{:.0}%", share),
+        );
+        pages.text(REGULAR, 9.0, x + 8.0, bottom - 11.0, label);
+    }
+    pages.hline(bottom);
+    pages.y = bottom - 18.0;
+}
+
+/// Line chart of per-token surprisal, averaged into at most one point per
+/// two points of page width.
+fn surprisal_chart(pages: &mut PageWriter, surprisals: &[f32]) {
+    if surprisals.is_empty() {
+        return;
+    }
+    let bins = surprisals.len().min((CONTENT_WIDTH / 2.0) as usize);
+    let points: Vec<f32> = (0..bins)
+        .map(|i| {
+            let part = &surprisals
+                [i * surprisals.len() / bins..((i + 1) * surprisals.len() / bins).max(i + 1)];
+            part.iter().sum::<f32>() / part.len() as f32
+        })
+        .collect();
+    let max = points.iter().copied().fold(1.0f32, f32::max).ceil();
+
+    pages.ensure(CHART_HEIGHT + 24.0);
+    let bottom = pages.y - CHART_HEIGHT - 4.0;
+    let step = CONTENT_WIDTH / (bins.max(2) - 1) as f32;
+    let content = &mut pages.content;
+    content.save_state();
+    let [r, g, b] = rgb(colors::WARNING);
+    content.set_stroke_rgb(r, g, b);
+    content.set_line_width(0.8);
+    for (i, &value) in points.iter().enumerate() {
+        let (x, y) = (
+            MARGIN + i as f32 * step,
+            bottom + CHART_HEIGHT * value / max,
+        );
+        if i == 0 {
+            content.move_to(x, y);
+        } else {
+            content.line_to(x, y);
+        }
+    }
+    content.stroke();
+    content.restore_state();
+    pages.hline(bottom);
+    pages.text(
+        REGULAR,
+        8.0,
+        MARGIN,
+        bottom + CHART_HEIGHT - 8.0,
+        &format!("{} bits", max),
+    );
+    pages.text(REGULAR, 8.0, MARGIN, bottom - 11.0, "start");
+    pages.text(
+        REGULAR,
+        8.0,
+        PAGE_WIDTH - MARGIN - 16.0,
+        bottom - 11.0,
+        "end",
+    );
+    pages.y = bottom - 18.0;
+}
+
+/// The text in a monospace font, each scored token on a background colored
+/// by its rank. Prompt tokens are printed in grey without a background.
+fn colored_text(pages: &mut PageWriter, result: &AnalysisResult) {
+    let columns = (CONTENT_WIDTH / MONO_ADVANCE) as usize;
+    let first_scored = result.prompt_tokens.max(1);
+    let mut line: Vec<(char, Option<Color32>)> = Vec::with_capacity(columns);
+    for (i, token) in result.tokens.iter().enumerate() {
+        let color = (i >= first_scored).then(|| colors::rank_to_color(token.rank));
+        let text = token.text.replace('\t', "    ");
+        let width = text.chars().take_while(|&c| c != '\n').count();
+        if !line.is_empty() && line.len() + width > columns {
+            pages.colored_line(&std::mem::take(&mut line));
+        }
+        for c in text.chars() {
+            if c == '\n' {
+                pages.colored_line(&std::mem::take(&mut line));
+                continue;
+            }
+            if line.len() == columns {
+                pages.colored_line(&std::mem::take(&mut line));
+            }
+            line.push((c, color));
+        }
+    }
+    if !line.is_empty() {
+        pages.colored_line(&line);
+    }
+}
+
+fn rgb(color: Color32) -> [f32; 3] {
+    [color.r(), color.g(), color.b()].map(|c| c as f32 / 255.0)
+}
+
+/// Encodes text for the standard fonts' WinAnsi encoding; characters it
+/// can't represent become `?`.
+fn win_ansi(text: &str) -> Vec<u8> {
+    let mut buf = [0u8; 4];
+    text.chars()
+        .map(|c| {
+            let (bytes, _, unmappable) = encoding_rs::WINDOWS_1252.encode(c.encode_utf8(&mut buf));
+            if unmappable || bytes.len() != 1 {
+                b'?'
+            } else {
+                bytes[0]
+            }
+        })
+        .collect()
+}
+
+/// Accumulates page content streams top to bottom, starting a new page
+/// whenever the next element doesn't fit.
+struct PageWriter {
+    pages: Vec<Vec<u8>>,
+    content: Content,
+    /// Baseline of the next line, measured from the bottom of the page.
+    y: f32,
+}
+
+impl PageWriter {
+    fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            content: Content::new(),
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    fn new_page(&mut self) {
+        let content = std::mem::replace(&mut self.content, Content::new());
+        self.pages.push(content.finish());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Starts a new page unless `height` more points fit on this one.
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+    }
+
+    fn space(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn text(&mut self, font: Name, size: f32, x: f32, y: f32, text: &str) {
+        self.content
+            .begin_text()
+            .set_font(font, size)
+            .next_line(x, y)
+            .show(Str(&win_ansi(text)))
+            .end_text();
+    }
+
+    fn line(&mut self, font: Name, size: f32, text: &str, leading: f32) {
+        self.ensure(leading);
+        self.y -= leading;
+        self.text(font, size, MARGIN, self.y, text);
+    }
+
+    fn heading(&mut self, text: &str, size: f32) {
+        // Keep headings with at least a few lines of what follows.
+        self.ensure(size * 1.6 + 40.0);
+        self.line(BOLD, size, text, size * 1.6);
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color32) {
+        let [r, g, b] = rgb(color);
+        self.content
+            .save_state()
+            .set_fill_rgb(r, g, b)
+            .rect(x, y, width, height)
+            .fill_nonzero()
+            .restore_state();
+    }
+
+    /// Horizontal axis line across the content width.
+    fn hline(&mut self, y: f32) {
+        self.content
+            .save_state()
+            .set_line_width(0.5)
+            .move_to(MARGIN, y)
+            .line_to(PAGE_WIDTH - MARGIN, y)
+            .stroke()
+            .restore_state();
+    }
+
+    /// One line of monospace text; runs of characters with the same color
+    /// share a background rectangle.
+    fn colored_line(&mut self, chars: &[(char, Option<Color32>)]) {
+        self.ensure(MONO_LEADING);
+        self.y -= MONO_LEADING;
+        let y = self.y;
+        let mut start = 0;
+        while start < chars.len() {
+            let color = chars[start].1;
+            let end = chars[start..]
+                .iter()
+                .position(|&(_, c)| c != color)
+                .map_or(chars.len(), |n| start + n);
+            let x = MARGIN + start as f32 * MONO_ADVANCE;
+            let run: String = chars[start..end].iter().map(|&(c, _)| c).collect();
+            match color {
+                Some(color) => {
+                    let width = (end - start) as f32 * MONO_ADVANCE;
+                    self.fill_rect(x, y - 3.0, width, MONO_LEADING, color);
+                    self.text(MONO, MONO_SIZE, x, y, &run);
+                }
+                None => {
+                    self.content.save_state().set_fill_rgb(0.45, 0.45, 0.45);
+                    self.text(MONO, MONO_SIZE, x, y, &run);
+                    self.content.restore_state();
+                }
+            }
+            start = end;
+        }
+    }
+
+    /// Assembles the document: catalog, page tree, the three standard
+    /// fonts and one content stream per page.
+    fn finish(mut self) -> Vec<u8> {
+        self.new_page();
+        let catalog_id = Ref::new(1);
+        let tree_id = Ref::new(2);
+        let font_ids = [Ref::new(3), Ref::new(4), Ref::new(5)];
+        let page_ids: Vec<Ref> = (0..self.pages.len())
+            .map(|i| Ref::new(6 + 2 * i as i32))
+            .collect();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(tree_id);
+        pdf.pages(tree_id)
+            .kids(page_ids.iter().copied())
+            .count(page_ids.len() as i32);
+        let base_fonts = [&b"Helvetica"[..], b"Helvetica-Bold", b"Courier"];
+        for (id, base) in font_ids.into_iter().zip(base_fonts) {
+            pdf.type1_font(id)
+                .base_font(Name(base))
+                .encoding_predefined(Name(b"WinAnsiEncoding"));
+        }
+        for (page_id, content) in page_ids.iter().zip(&self.pages) {
+            let content_id = Ref::new(page_id.get() + 1);
+            let mut page = pdf.page(*page_id);
+            page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .parent(tree_id)
+                .contents(content_id);
+            let mut resources = page.resources();
+            let mut fonts = resources.fonts();
+            for (name, id) in [REGULAR, BOLD, MONO].into_iter().zip(font_ids) {
+                fonts.pair(name, id);
+            }
+            fonts.finish();
+            resources.finish();
+            page.finish();
+            let compressed = miniz_oxide::deflate::compress_to_vec_zlib(content, 6);
+            pdf.stream(content_id, &compressed)
+                .filter(Filter::FlateDecode);
+        }
+        pdf.finish()
+    }
+}
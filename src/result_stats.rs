@@ -0,0 +1,84 @@
+use perplex_core::analysis::AnalysisResult;
+use perplex_core::confusables::{self, Confusable};
+use perplex_core::scripting::{self, CustomMetric, MetricValue};
+use perplex_core::speakers::{self, SpeakerStats};
+use perplex_core::special_tokens::{self, SpecialTokenUse};
+use perplex_core::token_types::{self, ClassStats, TokenTypeStats};
+use perplex_core::typography::TypographyStats;
+use perplex_core::utils::TextStats;
+use perplex_core::watermark::{self, WatermarkConfig, WatermarkStats};
+
+use crate::settings::Settings;
+
+/// The settings the statistics of a result depend on, copied so they can
+/// be computed off the UI thread.
+pub struct StatsOptions {
+    speaker_pattern: String,
+    watermark: WatermarkConfig,
+    custom_metrics: Vec<CustomMetric>,
+    color_expression: String,
+}
+
+impl StatsOptions {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            speaker_pattern: settings.speaker_pattern.trim().to_string(),
+            watermark: settings.watermark.clone(),
+            custom_metrics: settings.custom_metrics.clone(),
+            color_expression: settings.color_expression.trim().to_string(),
+        }
+    }
+}
+
+/// Everything shown next to a result that is computed from it rather than
+/// by the model. For long texts this takes long enough to stall frames, so
+/// it runs on a background thread.
+#[derive(Default)]
+pub struct ResultStats {
+    /// Per-speaker breakdown under the speaker pattern.
+    pub speakers: Vec<SpeakerStats>,
+    pub token_types: Vec<TokenTypeStats>,
+    pub classes: Vec<ClassStats>,
+    /// Watermark test, when enabled in the settings.
+    pub watermark: Option<WatermarkStats>,
+    /// The settings' custom metrics.
+    pub custom_metrics: Vec<MetricValue>,
+    /// Special tokens the text itself produced.
+    pub special_tokens: Vec<SpecialTokenUse>,
+    /// Mixed-script lookalike letters, with the index of their token.
+    pub confusables: Vec<(usize, Confusable)>,
+    /// Spacing and punctuation habits of the analyzed text.
+    pub typography: Option<TypographyStats>,
+    /// Model-free statistics of the analyzed text.
+    pub text_stats: Option<TextStats>,
+    /// The color expression evaluated on each token.
+    pub token_colors: Option<Result<Vec<f32>, String>>,
+}
+
+impl ResultStats {
+    pub fn of(result: &AnalysisResult, options: &StatsOptions) -> Self {
+        let scored = result.scored_with_context();
+        let speakers = (!options.speaker_pattern.is_empty())
+            .then(|| regex::Regex::new(&options.speaker_pattern))
+            .and_then(Result::ok)
+            .map(|regex| speakers::speaker_breakdown(scored, &regex))
+            .unwrap_or_default();
+        Self {
+            speakers,
+            token_types: token_types::token_type_report(scored),
+            classes: token_types::class_breakdown(scored),
+            watermark: options
+                .watermark
+                .enabled
+                .then(|| watermark::detect(scored, &options.watermark))
+                .flatten(),
+            custom_metrics: scripting::evaluate(&options.custom_metrics, result),
+            special_tokens: special_tokens::audit(result),
+            confusables: confusables::in_tokens(result),
+            typography: Some(TypographyStats::of(result)),
+            text_stats: Some(TextStats::of(result)),
+            token_colors: (!options.color_expression.is_empty())
+                .then(|| scripting::token_values(&options.color_expression, result)),
+        }
+    }
+}
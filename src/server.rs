@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use perplex_core::analysis::{AnalysisRequest, AnalysisResult};
+use perplex_core::llamacpp::LlamaAnalyzer;
+use perplex_core::preprocess;
+use perplex_core::worker::WorkerMessage;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::settings::Settings;
+
+#[derive(Deserialize)]
+struct TokenizeBody {
+    text: String,
+    #[serde(default = "default_add_bos")]
+    add_bos: bool,
+}
+
+fn default_add_bos() -> bool {
+    true
+}
+
+/// Largest request body accepted; bigger ones get 413.
+const MAX_BODY: u64 = 16 * 1024 * 1024;
+
+/// An error response: HTTP status and message.
+type ApiError = (u16, String);
+
+enum Reply {
+    Json(Value),
+    /// Run the analysis and stream its progress as server-sent events.
+    Stream(AnalysisRequest),
+}
+
+/// Serves the analysis API on `addr` with model A of `settings` kept
+/// loaded. Requests are handled one at a time; this only returns if the
+/// model fails to load or the address can't be bound. Browsers only let
+/// pages from `allowed_origins` call it; other clients aren't affected.
+pub fn run(addr: &str, settings: &Settings, allowed_origins: &[String]) -> Result<(), String> {
+    let (mut analyzer, model_name) = crate::ipc::load_analyzer(settings)?;
+    analyzer.set_stream_tokens(true);
+
+    let server = Server::http(addr).map_err(|e| format!("Failed to listen on {}: {}", addr, e))?;
+    println!("Serving {} on http://{}", model_name, addr);
+
+    for mut request in server.incoming_requests() {
+        let origin = allowed_origin(&request, allowed_origins);
+        let (status, body) = match handle(&mut request, &analyzer, settings, model_name) {
+            Ok(Reply::Stream(analysis)) => {
+                log::info!("{} {} -> stream", request.method(), request.url());
+                stream_analysis(request, &analyzer, analysis, origin);
+                continue;
+            }
+            Ok(Reply::Json(body)) => (200, body),
+            Err((status, message)) => (status, json!({ "error": message })),
+        };
+        log::info!("{} {} -> {}", request.method(), request.url(), status);
+        let mut response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json"));
+        if let Some(origin) = origin {
+            response = response
+                .with_header(header("Access-Control-Allow-Origin", &origin))
+                .with_header(header("Access-Control-Allow-Headers", "Content-Type"))
+                .with_header(header("Vary", "Origin"));
+        }
+        if let Err(e) = request.respond(response) {
+            log::warn!("Failed to send response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle(
+    request: &mut Request,
+    analyzer: &LlamaAnalyzer,
+    settings: &Settings,
+    model_name: &str,
+) -> Result<Reply, ApiError> {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| (400, format!("Failed to read request body: {}", e)))?;
+    if body.len() as u64 > MAX_BODY {
+        return Err((
+            413,
+            format!("Request body is larger than {} bytes", MAX_BODY),
+        ));
+    }
+
+    let json = match (request.method(), path.as_str()) {
+        // CORS preflight from browser clients.
+        (Method::Options, _) => Value::Null,
+        (Method::Get, "/health") => json!({ "model": model_name }),
+        (Method::Post, "/analyze") => {
+            let analysis = analysis_request(&body, settings)?;
+            let result = analyzer.analyze(&analysis, None).map_err(|e| (500, e))?;
+            analysis_json(&result)
+        }
+        (Method::Post, "/analyze/stream") => {
+            return Ok(Reply::Stream(analysis_request(&body, settings)?));
+        }
+        (Method::Post, "/tokenize") => {
+            let tokenize: TokenizeBody = parse_body(&body)?;
+            let tokens = analyzer
+                .list_tokens(&tokenize.text, tokenize.add_bos)
+                .map_err(|e| (500, e))?;
+            json!({ "tokens": tokens })
+        }
+        (_, "/health" | "/analyze" | "/analyze/stream" | "/tokenize") => {
+            return Err((405, "Method not allowed".into()));
+        }
+        _ => return Err((404, format!("No endpoint at {}", path))),
+    };
+    Ok(Reply::Json(json))
+}
+
+/// Parses an analysis request body and applies the configured
+/// preprocessing, as the GUI does.
+fn analysis_request(body: &str, settings: &Settings) -> Result<AnalysisRequest, ApiError> {
+    let mut analysis: AnalysisRequest = parse_body(body)?;
+    analysis.prompt = preprocess::apply(&analysis.prompt, &settings.preprocess);
+    analysis.text = preprocess::apply(&analysis.text, &settings.preprocess);
+    Ok(analysis)
+}
+
+/// Runs an analysis while streaming the worker messages it produces as
+/// server-sent events: `progress`, `tokens`, `memory` and `resources`
+/// while decoding, then one `completed` (same body as `/analyze`) or
+/// `error`. The response is written directly so each event is flushed as
+/// soon as it's produced. If the client goes away, the analysis stops
+/// before its next batch so the server can take the next request.
+fn stream_analysis(
+    request: Request,
+    analyzer: &LlamaAnalyzer,
+    analysis: AnalysisRequest,
+    origin: Option<String>,
+) {
+    let mut writer = request.into_writer();
+    let (tx, rx) = mpsc::channel();
+    let disconnected = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let disconnected = &disconnected;
+        scope.spawn(move || {
+            let analysis = analyzer.analyze_with(&analysis, Some(&tx), || {
+                if disconnected.load(Ordering::Relaxed) {
+                    return Err("Client disconnected".to_string());
+                }
+                Ok(())
+            });
+            let message = match analysis {
+                Ok(result) => WorkerMessage::Completed(result),
+                Err(e) => WorkerMessage::Error(e),
+            };
+            let _ = tx.send(message);
+        });
+
+        let cors = origin.map_or(String::new(), |origin| {
+            format!(
+                "Access-Control-Allow-Origin: {}\r\nVary: Origin\r\n",
+                origin
+            )
+        });
+        let head = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/event-stream\r\n\
+             Cache-Control: no-cache\r\n\
+             Connection: close\r\n\
+             {}\r\n",
+            cors
+        );
+        if let Err(e) = writer.write_all(head.as_bytes()) {
+            log::warn!("Failed to start event stream: {}", e);
+            disconnected.store(true, Ordering::Relaxed);
+            return;
+        }
+        for message in rx {
+            let Some((event, data)) = event_of(&message) else {
+                continue;
+            };
+            let sent =
+                write!(writer, "event: {}\ndata: {}\n\n", event, data).and_then(|_| writer.flush());
+            if let Err(e) = sent {
+                // The analysis stops before its next batch.
+                log::info!("Event stream client disconnected: {}", e);
+                disconnected.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+    });
+}
+
+/// Event name and JSON payload for the messages an analysis produces.
+fn event_of(message: &WorkerMessage) -> Option<(&'static str, Value)> {
+    Some(match message {
+        WorkerMessage::Progress { current, total } => {
+            ("progress", json!({ "current": current, "total": total }))
+        }
+        WorkerMessage::Tokens(tokens) => ("tokens", json!(tokens)),
+        WorkerMessage::Memory(usage) => ("memory", json!(usage)),
+        WorkerMessage::Resources(sample) => ("resources", json!(sample)),
+        WorkerMessage::Completed(result) => ("completed", analysis_json(result)),
+        WorkerMessage::Error(error) => ("error", json!({ "error": error })),
+        _ => return None,
+    })
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(body: &str) -> Result<T, ApiError> {
+    serde_json::from_str(body).map_err(|e| (400, format!("Invalid request body: {}", e)))
+}
+
+/// The result with its perplexity, plus the text of every token id that
+/// appears in `top_predictions` so clients don't need the vocabulary.
+fn analysis_json(result: &AnalysisResult) -> Value {
+    let pieces: BTreeMap<i32, &str> = result
+        .tokens
+        .iter()
+        .flat_map(|t| &t.top_predictions)
+        .map(|&(id, _)| (id, result.vocab.piece(id)))
+        .collect();
+    json!({
+        "perplexity": result.perplexity(),
+        "result": result,
+        "pieces": pieces,
+    })
+}
+
+/// The request's `Origin` if it's one of `allowed`, to be echoed back in
+/// `Access-Control-Allow-Origin`. Without it browsers keep pages from
+/// other origins from reading the responses.
+fn allowed_origin(request: &Request, allowed: &[String]) -> Option<String> {
+    let origin = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Origin"))?
+        .value
+        .as_str();
+    allowed
+        .iter()
+        .any(|a| a.trim_end_matches('/') == origin)
+        .then(|| origin.to_string())
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("static header is valid")
+}
@@ -1,9 +1,27 @@
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::hook::HookOptions;
+use crate::isolation::SegmentMode;
+use crate::keymap::Keymap;
+use crate::llamacpp::InferenceParams;
+use crate::number_format::NumberFormat;
+use crate::preprocess::PreprocessOptions;
+use crate::reference::Domain;
+use crate::scripting::CustomMetric;
+use crate::store::{StoreOptions, VerdictThreshold};
+use crate::text_report::TextExportOptions;
+use crate::tray::TrayOptions;
+use crate::ui_tokens::TooltipOptions;
+use crate::watch::WatchOptions;
+use crate::watermark::WatermarkConfig;
 
 const SETTINGS_FILE_NAME: &str = ".perplex_settings.json";
+/// Matches labels like `Alice:` or `Dr. Smith:` at the start of a line.
+const DEFAULT_SPEAKER_PATTERN: &str = r"^\s*([A-Z][\w .'-]{0,39}):";
+const DEFAULT_COLOR_EXPRESSION: &str = "clamp(surprisal / 12)";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PreloadMode {
@@ -25,12 +43,184 @@ impl std::fmt::Display for PreloadMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    System,
+    Dark,
+    Light,
+}
+
+impl std::fmt::Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Theme::System => write!(f, "Follow system"),
+            Theme::Dark => write!(f, "Dark"),
+            Theme::Light => write!(f, "Light"),
+        }
+    }
+}
+
+/// A number or indicator of the summary row above the results. Warnings
+/// and the results of follow-up tests are always shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SummaryMetric {
+    Time,
+    Perplexity,
+    LogLikelihood,
+    Reference,
+    BitsPerChar,
+    Top1Accuracy,
+    MedianRank,
+    Burstiness,
+    Entropy,
+    Language,
+    Position,
+    Repetition,
+    TextStats,
+}
+
+impl SummaryMetric {
+    /// In the order they appear in the row.
+    pub const ALL: [SummaryMetric; 13] = [
+        SummaryMetric::Time,
+        SummaryMetric::Perplexity,
+        SummaryMetric::LogLikelihood,
+        SummaryMetric::Reference,
+        SummaryMetric::BitsPerChar,
+        SummaryMetric::Top1Accuracy,
+        SummaryMetric::MedianRank,
+        SummaryMetric::Burstiness,
+        SummaryMetric::Entropy,
+        SummaryMetric::Language,
+        SummaryMetric::Position,
+        SummaryMetric::Repetition,
+        SummaryMetric::TextStats,
+    ];
+
+    pub const DEFAULT: [SummaryMetric; 9] = [
+        SummaryMetric::Time,
+        SummaryMetric::Perplexity,
+        SummaryMetric::LogLikelihood,
+        SummaryMetric::Reference,
+        SummaryMetric::Entropy,
+        SummaryMetric::Language,
+        SummaryMetric::Position,
+        SummaryMetric::Repetition,
+        SummaryMetric::TextStats,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SummaryMetric::Time => "Analysis time",
+            SummaryMetric::Perplexity => "Perplexity",
+            SummaryMetric::LogLikelihood => "Log-likelihood",
+            SummaryMetric::Reference => "Reference comparison",
+            SummaryMetric::BitsPerChar => "Bits per character",
+            SummaryMetric::Top1Accuracy => "Top-1 accuracy",
+            SummaryMetric::MedianRank => "Median rank",
+            SummaryMetric::Burstiness => "Burstiness",
+            SummaryMetric::Entropy => "Entropy",
+            SummaryMetric::Language => "Language",
+            SummaryMetric::Position => "Perplexity by position",
+            SummaryMetric::Repetition => "Repetition",
+            SummaryMetric::TextStats => "Text statistics",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            SummaryMetric::Time => "How long the analysis took",
+            SummaryMetric::Perplexity => "The headline number; lower is more predictable",
+            SummaryMetric::LogLikelihood => "Of the completion given the prompt, in prompt mode",
+            SummaryMetric::Reference => "Deviation from the reference corpus of the domain",
+            SummaryMetric::BitsPerChar => "Information per character, comparable across tokenizers",
+            SummaryMetric::Top1Accuracy => "Share of tokens the model predicted first",
+            SummaryMetric::MedianRank => "Rank of the typical token among the predictions",
+            SummaryMetric::Burstiness => "Spread of the surprisal from token to token",
+            SummaryMetric::Entropy => "Information needed to reconstruct the text",
+            SummaryMetric::Language => "Detected language, or how many were mixed",
+            SummaryMetric::Position => "Sparkline of perplexity by tenth of the text",
+            SummaryMetric::Repetition => "Share of tokens inside repeated sequences",
+            SummaryMetric::TextStats => "Characters, words, sentences and vocabulary, below",
+        }
+    }
+}
+
+impl From<Theme> for egui::ThemePreference {
+    fn from(theme: Theme) -> Self {
+        match theme {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Dark => egui::ThemePreference::Dark,
+            Theme::Light => egui::ThemePreference::Light,
+        }
+    }
+}
+
+/// A named context prefix (few-shot examples, style primer, system prompt)
+/// that can be loaded as the prompt of an analysis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrefixPreset {
+    pub name: String,
+    pub text: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Settings {
     pub model_path_a: Option<String>,
     pub model_path_b: Option<String>,
+    /// Small model whose quick analysis is shown while the configured
+    /// models are still working.
+    pub preview_model_path: Option<String>,
     pub preload_mode: PreloadMode,
+    pub inference: InferenceParams,
+    pub theme: Theme,
+    /// Re-run the analysis automatically after typing pauses.
+    pub live_analysis: bool,
+    /// Show CPU, RAM and throughput plots while analyzing.
+    pub show_resource_monitor: bool,
+    /// Show a desktop notification when an analysis or model load that
+    /// took at least `notify_after_secs` ends while the window is unfocused.
+    pub notify_when_done: bool,
+    pub notify_after_secs: u64,
+    /// Look for a newer release on GitHub at startup.
+    pub check_for_updates: bool,
+    /// Regex matching a speaker label at the start of a line; its first
+    /// capture group is the speaker name. Empty disables speaker stats.
+    pub speaker_pattern: String,
+    /// Domain whose reference corpus results are compared against.
+    pub domain: Domain,
+    /// Number of rewritten variants scored by the perturbation test.
+    pub perturbation_count: usize,
+    /// Fraction of tokens replaced in each perturbed variant.
+    pub perturbation_rate: f32,
+    /// How the context-isolation test splits the text.
+    pub isolation_segments: SegmentMode,
+    pub watermark: WatermarkConfig,
+    /// Scripted summary metrics shown next to the built-in ones.
+    pub custom_metrics: Vec<CustomMetric>,
+    /// Built-in metrics shown in the summary row.
+    pub summary_metrics: Vec<SummaryMetric>,
+    /// Precision and units of the summary row and the exports.
+    pub number_format: NumberFormat,
+    pub tooltips: TooltipOptions,
+    /// Rhai expression mapping each token to 0 (green) through 1 (red),
+    /// used by the Expression coloring.
+    pub color_expression: String,
+    pub prefix_presets: Vec<PrefixPreset>,
+    pub preprocess: PreprocessOptions,
+    pub keymap: Keymap,
+    pub text_export: TextExportOptions,
+    /// Handlebars template last used for a custom export.
+    pub export_template: Option<String>,
+    /// Command run after every finished analysis.
+    pub hook: HookOptions,
+    /// Folder whose new text files are analyzed automatically.
+    pub watch: WatchOptions,
+    pub store: StoreOptions,
+    pub tray: TrayOptions,
+    /// Threshold used to label new analyses, tuned in the ROC view.
+    pub verdict: Option<VerdictThreshold>,
 }
 
 impl Default for Settings {
@@ -38,7 +228,36 @@ impl Default for Settings {
         Self {
             model_path_a: None,
             model_path_b: None,
+            preview_model_path: None,
             preload_mode: PreloadMode::PreloadSingle,
+            inference: InferenceParams::default(),
+            theme: Theme::System,
+            live_analysis: false,
+            show_resource_monitor: false,
+            notify_when_done: true,
+            notify_after_secs: 30,
+            check_for_updates: false,
+            speaker_pattern: DEFAULT_SPEAKER_PATTERN.to_string(),
+            domain: Domain::General,
+            perturbation_count: 20,
+            perturbation_rate: 0.15,
+            isolation_segments: SegmentMode::Sentence,
+            watermark: WatermarkConfig::default(),
+            custom_metrics: Vec::new(),
+            summary_metrics: SummaryMetric::DEFAULT.to_vec(),
+            number_format: NumberFormat::default(),
+            tooltips: TooltipOptions::default(),
+            color_expression: DEFAULT_COLOR_EXPRESSION.to_string(),
+            prefix_presets: Vec::new(),
+            preprocess: PreprocessOptions::default(),
+            keymap: Keymap::default(),
+            text_export: TextExportOptions::default(),
+            export_template: None,
+            hook: HookOptions::default(),
+            watch: WatchOptions::default(),
+            store: StoreOptions::default(),
+            tray: TrayOptions::default(),
+            verdict: None,
         }
     }
 }
@@ -54,11 +273,9 @@ impl Settings {
     pub fn load() -> Self {
         let path = Self::config_file_path();
         if path.exists() {
-            if let Ok(content) = fs::read_to_string(&path) {
-                match serde_json::from_str::<Settings>(&content) {
-                    Ok(settings) => return settings,
-                    Err(e) => log::warn!("Failed to parse settings file: {}", e),
-                }
+            match Self::load_from(&path) {
+                Ok(settings) => return settings,
+                Err(e) => log::warn!("Failed to load settings file: {}", e),
             }
         }
 
@@ -66,7 +283,17 @@ impl Settings {
     }
 
     pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let path = Self::config_file_path();
+        self.save_to(&Self::config_file_path())
+    }
+
+    /// Reads settings from an arbitrary JSON file (e.g. a shared team configuration).
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str::<Settings>(&content)?)
+    }
+
+    /// Writes settings as pretty-printed JSON to an arbitrary file.
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
         let content = serde_json::to_string_pretty(self)?;
         fs::write(path, content)?;
         Ok(())
@@ -0,0 +1,524 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{self, AnalysisRequest, AnalysisResult, AnalyzedToken, Vocab};
+use crate::language;
+use crate::roc;
+
+const STORE_DIR_NAME: &str = ".perplex/store";
+const DATABASE_FILE_NAME: &str = "analyses.db";
+/// How long a connection waits for another one's write, such as a
+/// background [`record`] while the UI deletes an analysis.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One row per analysis in `analyses`, its tags in `tags` and, when kept,
+/// its text and tokens in `token_data`, so `sqlite3`, pandas or DuckDB can
+/// query the history directly.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS analyses (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    model TEXT NOT NULL,
+    model_path TEXT NOT NULL,
+    text_chars INTEGER NOT NULL,
+    scored_tokens INTEGER NOT NULL,
+    perplexity REAL NOT NULL,
+    bits_per_char REAL NOT NULL,
+    top1_accuracy REAL NOT NULL,
+    median_rank INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tags (
+    analysis_id INTEGER NOT NULL REFERENCES analyses (id),
+    tag TEXT NOT NULL,
+    PRIMARY KEY (analysis_id, tag)
+);
+CREATE TABLE IF NOT EXISTS token_data (
+    analysis_id INTEGER PRIMARY KEY REFERENCES analyses (id),
+    prompt TEXT NOT NULL,
+    text TEXT NOT NULL,
+    -- The tokens and the vocabulary pieces they refer to, as JSON.
+    tokens TEXT NOT NULL
+);
+";
+
+/// What the store keeps; set in the settings. Nothing is recorded unless
+/// the user turns it on.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoreOptions {
+    /// Record the metrics of every finished analysis.
+    pub enabled: bool,
+    /// Also keep the text and every token, so the analysis can be shown
+    /// again later. Takes far more space than the metrics.
+    pub token_data: bool,
+}
+
+/// A summary metric recorded for each stored analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Metric {
+    Perplexity,
+    BitsPerChar,
+    Top1Accuracy,
+    MedianRank,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [
+        Metric::Perplexity,
+        Metric::BitsPerChar,
+        Metric::Top1Accuracy,
+        Metric::MedianRank,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Metric::Perplexity => "Perplexity",
+            Metric::BitsPerChar => "Bits per character",
+            Metric::Top1Accuracy => "Top-1 accuracy",
+            Metric::MedianRank => "Median rank",
+        }
+    }
+
+    pub fn value(self, record: &StoredAnalysis) -> f32 {
+        match self {
+            Metric::Perplexity => record.perplexity,
+            Metric::BitsPerChar => record.bits_per_char,
+            Metric::Top1Accuracy => record.top1_accuracy,
+            Metric::MedianRank => record.median_rank as f32,
+        }
+    }
+
+    pub fn of(self, result: &AnalysisResult) -> f32 {
+        let scored = &result.tokens[result.first_scored()..];
+        match self {
+            Metric::Perplexity => result.perplexity(),
+            Metric::BitsPerChar => {
+                let chars: usize = result.tokens[result.prompt_tokens.min(result.tokens.len())..]
+                    .iter()
+                    .map(|t| t.document_text().chars().count())
+                    .sum();
+                -result.log_likelihood() / std::f32::consts::LN_2 / chars.max(1) as f32
+            }
+            Metric::Top1Accuracy => {
+                scored.iter().filter(|t| t.rank <= 1).count() as f32 / scored.len().max(1) as f32
+            }
+            Metric::MedianRank => {
+                let mut ranks: Vec<usize> = scored.iter().map(|t| t.rank).collect();
+                ranks.sort_unstable();
+                ranks.get(ranks.len() / 2).copied().unwrap_or_default() as f32
+            }
+        }
+    }
+
+    /// Generated text is more predictable, so it scores lower on this
+    /// metric than human text.
+    pub fn lower_means_generated(self) -> bool {
+        self != Metric::Top1Accuracy
+    }
+}
+
+/// A threshold on a metric, tuned on tagged analyses, used to label new
+/// analyses by `model` with one of the two tags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerdictThreshold {
+    pub model: String,
+    pub metric: Metric,
+    pub threshold: f32,
+    /// Tag of the texts the metric's low side (or high side, for top-1
+    /// accuracy) stands for, usually the generated ones.
+    pub positive: String,
+    pub negative: String,
+    pub true_positive_rate: f32,
+    pub false_positive_rate: f32,
+}
+
+impl VerdictThreshold {
+    /// The tag `result` falls under, with the metric's value.
+    pub fn verdict(&self, result: &AnalysisResult) -> (&str, f32) {
+        let value = self.metric.of(result);
+        let positive = roc::classify(value, self.threshold, self.metric.lower_means_generated());
+        (
+            if positive {
+                &self.positive
+            } else {
+                &self.negative
+            },
+            value,
+        )
+    }
+}
+
+/// The metadata and summary metrics of one model's analysis of a text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredAnalysis {
+    pub id: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub name: String,
+    pub model: String,
+    pub model_path: String,
+    /// Labels like "human" or "student-A" for comparing groups of texts.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub text_chars: usize,
+    pub scored_tokens: usize,
+    pub perplexity: f32,
+    /// Total surprisal of the text divided by its length in characters,
+    /// which unlike perplexity compares across tokenizers.
+    pub bits_per_char: f32,
+    pub top1_accuracy: f32,
+    pub median_rank: usize,
+    /// The text and tokens were saved alongside.
+    pub has_tokens: bool,
+}
+
+/// The token data of an analysis, besides its prompt and text. Only the
+/// vocabulary pieces its predictions refer to are kept, since the model may
+/// be gone by the time it is shown again.
+#[derive(Serialize, Deserialize)]
+struct StoredTokens {
+    tokens: Vec<AnalyzedToken>,
+    prompt_tokens: usize,
+    processing_time_ms: u64,
+    keeps_first_token: bool,
+    unscored: Vec<String>,
+    pieces: BTreeMap<i32, String>,
+}
+
+/// Every analysis recorded on this machine, oldest first, kept in a
+/// SQLite database in the user's home directory.
+pub struct AnalysisStore {
+    path: PathBuf,
+    /// Opened, and the database created, on first use.
+    connection: Option<Connection>,
+    pub records: Vec<StoredAnalysis>,
+}
+
+impl AnalysisStore {
+    /// Reads the stored analyses, if any. Nothing is created on disk until
+    /// the first one is recorded.
+    pub fn open() -> Self {
+        let path = env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join(STORE_DIR_NAME)
+            .join(DATABASE_FILE_NAME);
+        let mut store = Self {
+            path,
+            connection: None,
+            records: Vec::new(),
+        };
+        if store.path.exists() {
+            let records = store
+                .connection()
+                .and_then(|connection| read_records(connection).map_err(|e| e.to_string()));
+            match records {
+                Ok(records) => store.records = records,
+                Err(e) => log::warn!("Failed to read the analysis store: {}", e),
+            }
+        }
+        store
+    }
+
+    fn connection(&mut self) -> Result<&mut Connection, String> {
+        if self.connection.is_none() {
+            self.connection = Some(open_database(&self.path)?);
+        }
+        self.connection
+            .as_mut()
+            .ok_or_else(|| "The analysis store isn't open".to_string())
+    }
+
+    /// The database file, for [`record`] on another thread.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds a record written by [`record`], keeping them oldest first.
+    pub fn push(&mut self, record: StoredAnalysis) {
+        let at = self.records.partition_point(|r| r.id < record.id);
+        self.records.insert(at, record);
+    }
+
+    /// The text and result of a stored analysis that kept its tokens. The
+    /// prompt, if any, is kept in the store but not returned.
+    pub fn load(&mut self, id: u64) -> Result<(String, AnalysisResult), String> {
+        let (text, tokens): (String, String) = self
+            .connection()?
+            .query_row(
+                "SELECT text, tokens FROM token_data WHERE analysis_id = ?1",
+                [id as i64],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        let mut stored: StoredTokens = serde_json::from_str(&tokens).map_err(|e| e.to_string())?;
+        analysis::intern_texts(&mut stored.tokens);
+        let size = stored
+            .pieces
+            .keys()
+            .last()
+            .and_then(|&id| usize::try_from(id).ok())
+            .map_or(0, |id| id + 1);
+        let mut pieces = vec![String::new(); size];
+        for (id, piece) in stored.pieces {
+            if let Some(slot) = usize::try_from(id).ok().and_then(|i| pieces.get_mut(i)) {
+                *slot = piece;
+            }
+        }
+        let context_start = stored.prompt_tokens.saturating_sub(1);
+        let result = AnalysisResult {
+            languages: language::language_breakdown(
+                stored.tokens.get(context_start..).unwrap_or_default(),
+            ),
+            tokens: stored.tokens,
+            prompt_tokens: stored.prompt_tokens,
+            processing_time_ms: stored.processing_time_ms,
+            vocab: Arc::new(Vocab::new(pieces)),
+            keeps_first_token: stored.keeps_first_token,
+            unscored: stored.unscored,
+        };
+        Ok((text, result))
+    }
+
+    /// Every tag in use, sorted.
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .records
+            .iter()
+            .flat_map(|r| &r.tags)
+            .map(String::as_str)
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+
+    pub fn set_tags(&mut self, id: u64, tags: Vec<String>) -> Result<(), String> {
+        let connection = self.connection()?;
+        let transaction = connection.transaction().map_err(|e| e.to_string())?;
+        transaction
+            .execute("DELETE FROM tags WHERE analysis_id = ?1", [id as i64])
+            .map_err(|e| e.to_string())?;
+        insert_tags(&transaction, id, &tags).map_err(|e| e.to_string())?;
+        transaction.commit().map_err(|e| e.to_string())?;
+        if let Some(record) = self.records.iter_mut().find(|r| r.id == id) {
+            record.tags = tags;
+        }
+        Ok(())
+    }
+
+    /// Removes an analysis and its token data.
+    pub fn delete(&mut self, id: u64) -> Result<(), String> {
+        let connection = self.connection()?;
+        let transaction = connection.transaction().map_err(|e| e.to_string())?;
+        for table in ["tags", "token_data"] {
+            transaction
+                .execute(
+                    &format!("DELETE FROM {} WHERE analysis_id = ?1", table),
+                    [id as i64],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        transaction
+            .execute("DELETE FROM analyses WHERE id = ?1", [id as i64])
+            .map_err(|e| e.to_string())?;
+        transaction.commit().map_err(|e| e.to_string())?;
+        self.records.retain(|r| r.id != id);
+        Ok(())
+    }
+}
+
+/// Records `result`, the analysis of `request` by the model at
+/// `model_path`, in the database at `path`. It opens a connection of its
+/// own, so a long result can be written off the UI thread; the record is
+/// then added with [`AnalysisStore::push`].
+pub fn record(
+    path: &Path,
+    name: String,
+    tags: Vec<String>,
+    model_path: &str,
+    request: &AnalysisRequest,
+    result: &AnalysisResult,
+    options: StoreOptions,
+) -> Result<StoredAnalysis, String> {
+    let mut record = StoredAnalysis {
+        id: 0,
+        timestamp: now(),
+        name,
+        model: crate::model_name_from_path(Some(model_path))
+            .unwrap_or(model_path)
+            .to_string(),
+        model_path: model_path.to_string(),
+        tags,
+        text_chars: request.text.chars().count(),
+        scored_tokens: result.tokens.len() - result.first_scored(),
+        perplexity: Metric::Perplexity.of(result),
+        bits_per_char: Metric::BitsPerChar.of(result),
+        top1_accuracy: Metric::Top1Accuracy.of(result),
+        median_rank: Metric::MedianRank.of(result) as usize,
+        has_tokens: options.token_data,
+    };
+    let tokens = if options.token_data {
+        let tokens = StoredTokens {
+            pieces: result
+                .tokens
+                .iter()
+                .flat_map(|t| &t.top_predictions)
+                .map(|&(id, _)| (id, result.vocab.piece(id).to_string()))
+                .collect(),
+            tokens: result.tokens.clone(),
+            prompt_tokens: result.prompt_tokens,
+            processing_time_ms: result.processing_time_ms,
+            keeps_first_token: result.keeps_first_token,
+            unscored: result.unscored.clone(),
+        };
+        Some(serde_json::to_string(&tokens).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+
+    let mut connection = open_database(path)?;
+    let transaction = connection.transaction().map_err(|e| e.to_string())?;
+    transaction
+        .execute(
+            "INSERT INTO analyses (timestamp, name, model, model_path, text_chars, \
+             scored_tokens, perplexity, bits_per_char, top1_accuracy, median_rank) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                record.timestamp as i64,
+                record.name,
+                record.model,
+                record.model_path,
+                record.text_chars as i64,
+                record.scored_tokens as i64,
+                f64::from(record.perplexity),
+                f64::from(record.bits_per_char),
+                f64::from(record.top1_accuracy),
+                record.median_rank as i64,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    record.id = transaction.last_insert_rowid() as u64;
+    insert_tags(&transaction, record.id, &record.tags).map_err(|e| e.to_string())?;
+    if let Some(tokens) = tokens {
+        transaction
+            .execute(
+                "INSERT INTO token_data (analysis_id, prompt, text, tokens) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![record.id as i64, request.prompt, request.text, tokens],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    transaction.commit().map_err(|e| e.to_string())?;
+    Ok(record)
+}
+
+fn open_database(path: &Path) -> Result<Connection, String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let connection = Connection::open(path).map_err(|e| e.to_string())?;
+    connection
+        .busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| e.to_string())?;
+    connection
+        .execute_batch(SCHEMA)
+        .map_err(|e| e.to_string())?;
+    Ok(connection)
+}
+
+fn insert_tags(connection: &Connection, id: u64, tags: &[String]) -> rusqlite::Result<()> {
+    let mut statement =
+        connection.prepare("INSERT OR IGNORE INTO tags (analysis_id, tag) VALUES (?1, ?2)")?;
+    for tag in tags {
+        statement.execute(params![id as i64, tag])?;
+    }
+    Ok(())
+}
+
+/// Every stored analysis with its tags, oldest first.
+fn read_records(connection: &Connection) -> rusqlite::Result<Vec<StoredAnalysis>> {
+    let mut tags: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut statement = connection.prepare("SELECT analysis_id, tag FROM tags ORDER BY rowid")?;
+    let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?;
+    for row in rows {
+        let (id, tag) = row?;
+        tags.entry(id as u64).or_default().push(tag);
+    }
+
+    let mut statement = connection.prepare(
+        "SELECT id, timestamp, name, model, model_path, text_chars, scored_tokens, \
+         perplexity, bits_per_char, top1_accuracy, median_rank, \
+         EXISTS (SELECT 1 FROM token_data WHERE analysis_id = analyses.id) \
+         FROM analyses ORDER BY id",
+    )?;
+    let rows = statement.query_map([], |row| {
+        let id = row.get::<_, i64>(0)? as u64;
+        Ok(StoredAnalysis {
+            id,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            name: row.get(2)?,
+            model: row.get(3)?,
+            model_path: row.get(4)?,
+            tags: tags.remove(&id).unwrap_or_default(),
+            text_chars: row.get::<_, i64>(5)? as usize,
+            scored_tokens: row.get::<_, i64>(6)? as usize,
+            perplexity: row.get::<_, f64>(7)? as f32,
+            bits_per_char: row.get::<_, f64>(8)? as f32,
+            top1_accuracy: row.get::<_, f64>(9)? as f32,
+            median_rank: row.get::<_, i64>(10)? as usize,
+            has_tokens: row.get(11)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Comma-separated tags, trimmed, without empty or repeated ones.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim) {
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// `timestamp` as a UTC date and time, `YYYY-MM-DD HH:MM`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let minutes = timestamp % 86_400 / 60;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use handlebars::{handlebars_helper, Handlebars, JsonValue};
+use serde_json::{json, Value};
+
+use crate::report::{self, ReportSection};
+use crate::store::Metric;
+
+/// Top predictions listed per token; enough for most layouts while keeping
+/// the data a template walks through small.
+const MAX_PREDICTIONS: usize = 5;
+
+handlebars_helper!(fixed: |value: f64, digits: u64| format!("{:.*}", digits as usize, value));
+handlebars_helper!(percent: |value: f64| format!("{:.1}%", value * 100.0));
+handlebars_helper!(csv: |value: JsonValue| {
+    let text = match value {
+        JsonValue::String(s) => s.clone(),
+        JsonValue::Null => String::new(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+});
+
+/// Renders the results through a user's Handlebars template, so any text
+/// format can be produced without a built-in exporter. Output is
+/// HTML-escaped only when `path` ends in `.html` or `.htm`.
+pub fn write_templated(
+    path: &Path,
+    template_path: &Path,
+    sections: &[ReportSection],
+    metadata: &[(String, String)],
+) -> Result<(), String> {
+    let template = std::fs::read_to_string(template_path)
+        .map_err(|e| format!("Failed to read the template: {}", e))?;
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    let html = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+    if !html {
+        handlebars.register_escape_fn(handlebars::no_escape);
+    }
+    handlebars.register_helper("fixed", Box::new(fixed));
+    handlebars.register_helper("percent", Box::new(percent));
+    handlebars.register_helper("csv", Box::new(csv));
+    handlebars
+        .register_template_string("export", template)
+        .map_err(|e| e.to_string())?;
+    let output = handlebars
+        .render("export", &context(sections, metadata))
+        .map_err(|e| e.to_string())?;
+    std::fs::write(path, output).map_err(|e| e.to_string())
+}
+
+/// Extension of the file a template produces, read from the name before
+/// the template's own extension: `report.org.hbs` makes `.org` files.
+pub fn output_extension(template_path: &Path) -> &str {
+    template_path
+        .file_stem()
+        .map(Path::new)
+        .and_then(Path::extension)
+        .and_then(|e| e.to_str())
+        .unwrap_or("txt")
+}
+
+/// The data a template sees; documented in the README.
+fn context(sections: &[ReportSection], metadata: &[(String, String)]) -> Value {
+    let results: Vec<Value> = sections.iter().map(section_context).collect();
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "metadata": metadata
+            .iter()
+            .map(|(field, value)| json!({ "field": field, "value": value }))
+            .collect::<Vec<_>>(),
+        "results": results,
+    })
+}
+
+fn section_context(section: &ReportSection) -> Value {
+    let result = section.result;
+    let first_scored = result.first_scored();
+    let tokens: Vec<Value> = result
+        .tokens
+        .iter()
+        .enumerate()
+        .map(|(index, token)| {
+            let scored = index >= first_scored;
+            let predictions: Vec<Value> = token
+                .top_predictions
+                .iter()
+                .take(MAX_PREDICTIONS)
+                .map(|&(id, probability)| {
+                    json!({ "text": result.vocab.piece(id), "probability": probability })
+                })
+                .collect();
+            json!({
+                "index": index,
+                "text": token.text,
+                "special": token.special,
+                "prompt": index < result.prompt_tokens,
+                "scored": scored,
+                "rank": token.rank,
+                "probability": token.probability,
+                "surprisal": token.surprisal(),
+                "margin": token.top1_margin,
+                "unigram_surprisal": token.unigram_surprisal,
+                "repeated": token.repeated,
+                "predictions": predictions,
+            })
+        })
+        .collect();
+    let completion: String = result.tokens[result.prompt_tokens.min(result.tokens.len())..]
+        .iter()
+        .map(|t| t.document_text())
+        .collect();
+    json!({
+        "name": section.name,
+        "text": completion,
+        "perplexity": Metric::Perplexity.of(result),
+        "bits_per_char": Metric::BitsPerChar.of(result),
+        "top1_accuracy": Metric::Top1Accuracy.of(result),
+        "median_rank": Metric::MedianRank.of(result),
+        "entropy": result.text_entropy(),
+        "log_likelihood": result.has_prompt().then(|| result.log_likelihood()),
+        "scored_tokens": result.tokens.len() - first_scored,
+        "unscored": result.unscored,
+        "summary": report::summary(result, &section.format)
+            .into_iter()
+            .map(|(label, value)| json!({ "label": label, "value": value }))
+            .collect::<Vec<_>>(),
+        "tokens": tokens,
+    })
+}
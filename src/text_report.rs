@@ -0,0 +1,215 @@
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::AnalysisResult;
+use crate::report::{self, ReportSection};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextExportOptions {
+    /// Tokens ranked this or worse get an inline annotation.
+    pub min_rank: usize,
+    /// Append the perplexity and worst rank of each line to it.
+    pub line_summary: bool,
+}
+
+impl Default for TextExportOptions {
+    fn default() -> Self {
+        Self {
+            min_rank: 11,
+            line_summary: false,
+        }
+    }
+}
+
+/// Writes each section as the analyzed text with `token{r=137,p=0.2%}`
+/// annotations after poorly ranked tokens, under a `#` comment header.
+/// The layout follows the text line by line so revisions of a document
+/// diff cleanly.
+pub fn write_annotated(
+    path: &Path,
+    sections: &[ReportSection],
+    options: &TextExportOptions,
+) -> Result<(), String> {
+    let texts: Vec<String> = sections
+        .iter()
+        .map(|section| annotated_section(section, options))
+        .collect();
+    std::fs::write(path, texts.join("\n")).map_err(|e| e.to_string())
+}
+
+fn annotated_section(section: &ReportSection, options: &TextExportOptions) -> String {
+    let result = section.result;
+    let mut out = format!("# Perplex annotated text: {}\n", section.name);
+    for (label, value) in report::summary(result, &section.format) {
+        let _ = writeln!(out, "# {}: {}", label, value);
+    }
+    let _ = writeln!(
+        out,
+        "# Tokens ranked {} or worse are followed by {{r=rank,p=probability}}",
+        options.min_rank
+    );
+    if options.line_summary {
+        out.push_str("# Lines end with their perplexity and worst rank after `  # `\n");
+    }
+    out.push('\n');
+    out.push_str(&annotated_text(result, options));
+    out.push('\n');
+    out
+}
+
+/// Scored tokens of the line being written.
+#[derive(Default)]
+struct LineStats {
+    tokens: usize,
+    nll: f32,
+    worst_rank: usize,
+}
+
+impl LineStats {
+    fn add(&mut self, rank: usize, probability: f32) {
+        self.tokens += 1;
+        self.nll -= probability.max(f32::MIN_POSITIVE).ln();
+        self.worst_rank = self.worst_rank.max(rank);
+    }
+
+    fn write(&self, out: &mut String) {
+        if self.tokens > 0 {
+            let perplexity = (self.nll / self.tokens as f32).exp();
+            let _ = write!(out, "  # ppl={:.2} max_r={}", perplexity, self.worst_rank);
+        }
+    }
+}
+
+fn annotated_text(result: &AnalysisResult, options: &TextExportOptions) -> String {
+    let mut out = String::new();
+    let mut line = LineStats::default();
+    let first_scored = result.first_scored();
+    for (i, token) in result.tokens.iter().enumerate().skip(result.prompt_tokens) {
+        let text = token.document_text();
+        let scored = i >= first_scored;
+        // The annotation goes before any trailing newline, so it stays on
+        // the token's line.
+        let body = text.trim_end_matches('\n');
+        let newlines = text.len() - body.len();
+        write_lines(&mut out, body, &mut line, options);
+        if scored {
+            line.add(token.rank, token.probability);
+            if token.rank >= options.min_rank {
+                let _ = write!(
+                    out,
+                    "{{r={},p={}}}",
+                    token.rank,
+                    format_probability(token.probability)
+                );
+            }
+        }
+        write_lines(&mut out, &"\n".repeat(newlines), &mut line, options);
+    }
+    // Past the scoring limit: the text without annotations.
+    write_lines(&mut out, &result.unscored.concat(), &mut line, options);
+    if options.line_summary {
+        line.write(&mut out);
+    }
+    out
+}
+
+/// Appends `text`, ending the line summary at each newline.
+fn write_lines(out: &mut String, text: &str, line: &mut LineStats, options: &TextExportOptions) {
+    for (i, part) in text.split('\n').enumerate() {
+        if i > 0 {
+            if options.line_summary {
+                line.write(out);
+            }
+            out.push('\n');
+            *line = LineStats::default();
+        }
+        out.push_str(part);
+    }
+}
+
+/// Percent with enough decimals to stay nonzero for rare tokens.
+fn format_probability(probability: f32) -> String {
+    let percent = probability * 100.0;
+    if percent >= 1.0 {
+        format!("{:.0}%", percent)
+    } else if percent >= 0.1 {
+        format!("{:.1}%", percent)
+    } else {
+        format!("{:.2e}%", percent)
+    }
+}
+
+/// The metrics of tokens `range` as tab-separated values with a header
+/// row, for pasting into a spreadsheet. With several results, each gets
+/// its own columns, prefixed with its name.
+pub fn selection_tsv(results: &[(&str, &AnalysisResult)], range: RangeInclusive<usize>) -> String {
+    const COLUMNS: [&str; 6] = [
+        "rank",
+        "probability",
+        "surprisal_bits",
+        "top1_margin",
+        "top_prediction",
+        "top_probability",
+    ];
+    let mut out = String::from("index\ttoken");
+    for (name, _) in results {
+        for column in COLUMNS {
+            if results.len() > 1 {
+                let _ = write!(out, "\t{} {}", name, column);
+            } else {
+                let _ = write!(out, "\t{}", column);
+            }
+        }
+    }
+    out.push('\n');
+
+    for index in range {
+        let Some(text) = results
+            .iter()
+            .find_map(|(_, r)| r.tokens.get(index))
+            .map(|t| &*t.text)
+        else {
+            break;
+        };
+        let _ = write!(out, "{}\t{}", index, escape_tsv(text));
+        for (_, result) in results {
+            match result.tokens.get(index) {
+                Some(token) if index >= result.first_scored() => {
+                    let (top_id, top_probability) =
+                        token.top_predictions.first().copied().unwrap_or((-1, 0.0));
+                    let top = if top_id >= 0 {
+                        escape_tsv(result.vocab.piece(top_id))
+                    } else {
+                        String::new()
+                    };
+                    let _ = write!(
+                        out,
+                        "\t{}\t{}\t{:.3}\t{:.3}\t{}\t{}",
+                        token.rank,
+                        token.probability,
+                        token.surprisal(),
+                        token.top1_margin,
+                        top,
+                        top_probability
+                    );
+                }
+                // Prompt tokens and the first token have no prediction.
+                _ => out.push_str(&"\t".repeat(COLUMNS.len())),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Tabs and line breaks would split cells and rows.
+fn escape_tsv(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
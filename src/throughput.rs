@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const THROUGHPUT_FILE_NAME: &str = ".perplex/throughput.json";
+
+/// Runs shorter than this are dominated by setup time and don't say much
+/// about a model's speed.
+const MIN_CALIBRATION_TOKENS: usize = 64;
+
+/// Measured analysis speed of each model, so a run's length can be
+/// estimated before it starts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Throughput {
+    /// Tokens per second, by model name.
+    rates: BTreeMap<String, f32>,
+}
+
+impl Throughput {
+    fn file_path() -> PathBuf {
+        env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join(THROUGHPUT_FILE_NAME)
+    }
+
+    pub fn load() -> Self {
+        let Ok(content) = fs::read_to_string(Self::file_path()) else {
+            return Self::default();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Failed to parse throughput calibration: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::file_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Folds a finished run into the model's rate. Returns false when the
+    /// run was too short to count.
+    pub fn record(&mut self, model: &str, tokens: usize, elapsed_ms: u64) -> bool {
+        if tokens < MIN_CALIBRATION_TOKENS || elapsed_ms == 0 {
+            return false;
+        }
+        let rate = tokens as f32 / (elapsed_ms as f32 / 1000.0);
+        // Averaged with the previous rate to smooth out one-off slow runs.
+        let smoothed = match self.rates.get(model) {
+            Some(&previous) => (previous + rate) / 2.0,
+            None => rate,
+        };
+        self.rates.insert(model.to_string(), smoothed);
+        true
+    }
+
+    /// How long `tokens` would take with the model, if its speed is known.
+    pub fn estimate(&self, model: &str, tokens: usize) -> Option<Duration> {
+        let rate = *self.rates.get(model)?;
+        (rate > 0.0).then(|| Duration::from_secs_f32(tokens as f32 / rate))
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::mpsc;
+
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use serde::{Deserialize, Serialize};
+
+use crate::instance::RemoteCommand;
+
+/// Shortcut offered until the user picks another.
+pub const DEFAULT_SHORTCUT: &str = "Ctrl+Alt+P";
+
+/// The tray icon and the system-wide paste-and-analyze shortcut; set in the
+/// settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayOptions {
+    /// Show an icon in the system tray with a paste-and-analyze menu. Only
+    /// in builds with the `tray` feature.
+    pub icon: bool,
+    pub hotkey: bool,
+    /// Key combination such as `Ctrl+Alt+P`, parsed by `global-hotkey`.
+    pub shortcut: String,
+}
+
+impl Default for TrayOptions {
+    fn default() -> Self {
+        Self {
+            icon: false,
+            hotkey: false,
+            shortcut: DEFAULT_SHORTCUT.to_string(),
+        }
+    }
+}
+
+/// Keeps the registered shortcut and the tray icon alive while enabled.
+#[derive(Default)]
+pub struct Tray {
+    hotkey: Option<(GlobalHotKeyManager, HotKey)>,
+    #[cfg(all(feature = "tray", not(target_os = "linux")))]
+    icon: Option<tray_icon::TrayIcon>,
+}
+
+impl Tray {
+    /// Registers the shortcut in `options`, replacing the previous one.
+    /// Must run on the main thread, which macOS requires.
+    pub fn set_hotkey(&mut self, options: &TrayOptions) -> Result<(), String> {
+        if let Some((manager, hotkey)) = self.hotkey.take() {
+            if let Err(e) = manager.unregister(hotkey) {
+                log::warn!("Failed to unregister the global shortcut: {}", e);
+            }
+        }
+        if !options.hotkey {
+            return Ok(());
+        }
+        let hotkey: HotKey = options
+            .shortcut
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid shortcut '{}': {}", options.shortcut, e))?;
+        let manager = GlobalHotKeyManager::new().map_err(|e| e.to_string())?;
+        manager
+            .register(hotkey)
+            .map_err(|e| format!("Failed to register {}: {}", options.shortcut, e))?;
+        self.hotkey = Some((manager, hotkey));
+        Ok(())
+    }
+
+    /// Shows or removes the tray icon.
+    #[cfg(all(feature = "tray", target_os = "linux"))]
+    pub fn set_icon(&mut self, visible: bool) -> Result<(), String> {
+        gtk_thread::set_visible(visible)
+    }
+
+    #[cfg(all(feature = "tray", not(target_os = "linux")))]
+    pub fn set_icon(&mut self, visible: bool) -> Result<(), String> {
+        self.icon = None;
+        if visible {
+            self.icon = Some(icon::build()?);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "tray"))]
+    pub fn set_icon(&mut self, visible: bool) -> Result<(), String> {
+        if visible {
+            return Err("This build has no tray icon support; rebuild with --features tray".into());
+        }
+        Ok(())
+    }
+}
+
+/// Forwards shortcut presses and tray menu clicks to `tx`, waking the window.
+/// Call once; the threads live as long as the process.
+pub fn forward_events(ctx: egui::Context, tx: mpsc::Sender<RemoteCommand>) {
+    #[cfg(feature = "tray")]
+    {
+        let ctx = ctx.clone();
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            while let Ok(event) = tray_icon::menu::MenuEvent::receiver().recv() {
+                let Some(command) = icon::command(&event) else {
+                    continue;
+                };
+                if tx.send(command).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+    }
+    std::thread::spawn(move || {
+        while let Ok(event) = GlobalHotKeyEvent::receiver().recv() {
+            if event.state != HotKeyState::Pressed {
+                continue;
+            }
+            if tx.send(RemoteCommand::PasteAndAnalyze).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    });
+}
+
+#[cfg(feature = "tray")]
+mod icon {
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+    use crate::colors;
+    use crate::instance::RemoteCommand;
+
+    const MENU_PASTE: &str = "paste-analyze";
+    const MENU_SHOW: &str = "show";
+    const MENU_QUIT: &str = "quit";
+    /// Side of the generated icon in pixels.
+    const SIZE: u32 = 32;
+
+    pub fn build() -> Result<TrayIcon, String> {
+        let menu = Menu::with_items(&[
+            &MenuItem::with_id(MENU_PASTE, "Paste and analyze", true, None),
+            &MenuItem::with_id(MENU_SHOW, "Show Perplex", true, None),
+            &PredefinedMenuItem::separator(),
+            &MenuItem::with_id(MENU_QUIT, "Quit", true, None),
+        ])
+        .map_err(|e| e.to_string())?;
+        let icon = Icon::from_rgba(rgba(), SIZE, SIZE).map_err(|e| e.to_string())?;
+        TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("Perplex")
+            .with_icon(icon)
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn command(event: &MenuEvent) -> Option<RemoteCommand> {
+        if event.id == MENU_PASTE {
+            Some(RemoteCommand::PasteAndAnalyze)
+        } else if event.id == MENU_SHOW {
+            Some(RemoteCommand::Show)
+        } else if event.id == MENU_QUIT {
+            Some(RemoteCommand::Quit)
+        } else {
+            None
+        }
+    }
+
+    /// A filled circle in the accent color with an antialiased edge.
+    fn rgba() -> Vec<u8> {
+        let [r, g, b, _] = colors::ACCENT_PRIMARY.to_array();
+        let center = (SIZE as f32 - 1.0) / 2.0;
+        (0..SIZE * SIZE)
+            .flat_map(|i| {
+                let (x, y) = ((i % SIZE) as f32, (i / SIZE) as f32);
+                let distance = (x - center).hypot(y - center);
+                let alpha = (center + 0.5 - distance).clamp(0.0, 1.0);
+                [r, g, b, (alpha * 255.0) as u8]
+            })
+            .collect()
+    }
+}
+
+/// On Linux the tray icon lives on a GTK thread, as libappindicator needs a
+/// running GTK main loop and winit doesn't use GTK.
+#[cfg(all(feature = "tray", target_os = "linux"))]
+mod gtk_thread {
+    use std::sync::{mpsc, OnceLock};
+    use std::time::Duration;
+
+    use gtk::glib;
+
+    /// How often the GTK thread looks for show and hide requests.
+    const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+    static REQUESTS: OnceLock<mpsc::Sender<bool>> = OnceLock::new();
+
+    pub fn set_visible(visible: bool) -> Result<(), String> {
+        if !visible && REQUESTS.get().is_none() {
+            return Ok(());
+        }
+        REQUESTS
+            .get_or_init(|| {
+                let (tx, rx) = mpsc::channel();
+                std::thread::spawn(move || run(rx));
+                tx
+            })
+            .send(visible)
+            .map_err(|_| "The tray icon thread has stopped".to_string())
+    }
+
+    fn run(requests: mpsc::Receiver<bool>) {
+        if let Err(e) = gtk::init() {
+            log::warn!("Failed to start GTK for the tray icon: {}", e);
+            return;
+        }
+        let mut icon = None;
+        glib::timeout_add_local(POLL_INTERVAL, move || {
+            for visible in requests.try_iter() {
+                icon.take();
+                if !visible {
+                    continue;
+                }
+                match super::icon::build() {
+                    Ok(built) => {
+                        icon.replace(built);
+                    }
+                    Err(e) => log::warn!("Failed to show the tray icon: {}", e),
+                }
+            }
+            glib::ControlFlow::Continue
+        });
+        gtk::main();
+    }
+}
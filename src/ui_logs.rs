@@ -0,0 +1,127 @@
+use egui::{Color32, FontId, RichText, Ui};
+
+use crate::colors;
+use crate::logs::{self, LogEntry};
+
+const LEVELS: [log::Level; 4] = [
+    log::Level::Error,
+    log::Level::Warn,
+    log::Level::Info,
+    log::Level::Debug,
+];
+
+pub struct LogPanelState {
+    pub open: bool,
+    /// Most verbose level shown.
+    pub level: log::Level,
+}
+
+impl Default for LogPanelState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            level: log::Level::Info,
+        }
+    }
+}
+
+fn level_color(ui: &Ui, level: log::Level) -> Color32 {
+    match level {
+        log::Level::Error => colors::ERROR,
+        log::Level::Warn => colors::WARNING,
+        log::Level::Info => colors::text_primary(ui.visuals()),
+        log::Level::Debug | log::Level::Trace => colors::text_muted(ui.visuals()),
+    }
+}
+
+fn filtered_entries(level: log::Level) -> Vec<LogEntry> {
+    logs::snapshot()
+        .into_iter()
+        .filter(|e| e.level <= level)
+        .collect()
+}
+
+/// Bottom panel listing captured application and llama.cpp log records.
+pub fn render_log_panel(ctx: &egui::Context, state: &mut LogPanelState) {
+    egui::TopBottomPanel::bottom("logs_panel")
+        .resizable(state.open)
+        .min_height(if state.open { 160.0 } else { 0.0 })
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let arrow = if state.open { "⏷" } else { "⏵" };
+                if ui
+                    .selectable_label(
+                        false,
+                        RichText::new(format!("{} 📜 Logs", arrow)).size(13.0),
+                    )
+                    .clicked()
+                {
+                    state.open = !state.open;
+                }
+
+                if !state.open {
+                    if let Some(last) = logs::last_at_or_above(state.level) {
+                        ui.label(
+                            RichText::new(&last.message)
+                                .size(11.0)
+                                .color(level_color(ui, last.level)),
+                        );
+                    }
+                    return;
+                }
+
+                ui.add_space(12.0);
+                ui.label(RichText::new("Level:").size(12.0));
+                egui::ComboBox::from_id_salt("log_level")
+                    .selected_text(state.level.to_string())
+                    .width(80.0)
+                    .show_ui(ui, |ui| {
+                        for level in LEVELS {
+                            ui.selectable_value(&mut state.level, level, level.to_string());
+                        }
+                    });
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("🗑 Clear").clicked() {
+                        logs::clear();
+                    }
+                    if ui.button("📋 Copy").clicked() {
+                        let text = filtered_entries(state.level)
+                            .iter()
+                            .map(LogEntry::to_string)
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        ui.ctx().copy_text(text);
+                    }
+                });
+            });
+
+            if !state.open {
+                return;
+            }
+
+            ui.separator();
+
+            let entries = filtered_entries(state.level);
+            let font = FontId::monospace(11.0);
+            let row_height = ui.fonts(|f| f.row_height(&font));
+            egui::ScrollArea::vertical()
+                .id_salt("logs_scroll")
+                .auto_shrink(false)
+                .stick_to_bottom(true)
+                .show_rows(ui, row_height, entries.len(), |ui, range| {
+                    for entry in &entries[range] {
+                        // Truncated to one line so rows keep a fixed height;
+                        // the full message shows on hover.
+                        ui.add(
+                            egui::Label::new(
+                                RichText::new(entry.to_string())
+                                    .font(font.clone())
+                                    .color(level_color(ui, entry.level)),
+                            )
+                            .truncate(),
+                        );
+                    }
+                });
+        });
+}
@@ -1,5 +1,43 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
 use crate::analysis::AnalysisResult;
+use crate::bos::BosSensitivity;
 use crate::colors;
+use crate::conditioning::ConditioningStats;
+use crate::confusables::{self, Confusable};
+use crate::determinism::DeterminismStats;
+use crate::diagnostics::BundleOptions;
+use crate::invisible;
+use crate::isolation::IsolationStats;
+use crate::language::LanguageStats;
+use crate::logit_bias;
+use crate::number_format::NumberFormat;
+use crate::perturbation::PerturbationStats;
+use crate::preprocess::{self, PreprocessOptions};
+use crate::quantization::QuantVariance;
+use crate::reference::{Domain, ReferenceStats};
+use crate::regeneration::{DiffKind, RegenerationDiff, RegenerationStats};
+use crate::resources::{self, MemoryUsage};
+use crate::scripting::MetricValue;
+use crate::settings::{PrefixPreset, SummaryMetric};
+use crate::shuffle::ShuffleStats;
+use crate::significance::SignificanceTest;
+use crate::speakers::SpeakerStats;
+use crate::special_tokens::SpecialTokenUse;
+use crate::store::{self, Metric, StoredAnalysis, VerdictThreshold};
+use crate::template;
+use crate::token_types::{self, ClassStats, TokenTypeStats};
+use crate::typography::TypographyStats;
+use crate::ui_tokens::{TokenGesture, TooltipOptions};
+use crate::update::Release;
+use crate::utils::TextStats;
+use crate::watermark::{self, WatermarkStats};
+use crate::windowing::{self, ChunkSweep};
+use crate::worker::WorkerManager;
 use egui::{Color32, FontId, RichText, Ui, Vec2};
 
 // ── View mode enums ─────────────────────────────────────────────────────────
@@ -23,6 +61,7 @@ impl std::fmt::Display for ViewMode {
 pub enum UnifiedColorMode {
     AvgRank,
     AvgProbability,
+    AvgMargin,
     RankDivergence,
     ProbDivergence,
 }
@@ -32,12 +71,124 @@ impl std::fmt::Display for UnifiedColorMode {
         match self {
             UnifiedColorMode::AvgRank => write!(f, "Average rank"),
             UnifiedColorMode::AvgProbability => write!(f, "Average probability"),
+            UnifiedColorMode::AvgMargin => write!(f, "Average top-1 margin"),
             UnifiedColorMode::RankDivergence => write!(f, "Divergence rank"),
             UnifiedColorMode::ProbDivergence => write!(f, "Divergence probability"),
         }
     }
 }
 
+/// Token coloring in the split and single-model views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenColorMode {
+    Rank,
+    Margin,
+    /// The color expression from the Display settings.
+    Expression,
+}
+
+impl TokenColorMode {
+    pub const ALL: [TokenColorMode; 3] = [
+        TokenColorMode::Rank,
+        TokenColorMode::Margin,
+        TokenColorMode::Expression,
+    ];
+}
+
+impl std::fmt::Display for TokenColorMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenColorMode::Rank => write!(f, "Rank"),
+            TokenColorMode::Margin => write!(f, "Top-1 margin"),
+            TokenColorMode::Expression => write!(f, "Expression"),
+        }
+    }
+}
+
+/// How results are laid out and colored; owned by the app, edited by the
+/// controls above the results.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayOptions {
+    pub view_mode: ViewMode,
+    pub unified_color_mode: UnifiedColorMode,
+    pub color_mode: TokenColorMode,
+    pub highlight_repeats: bool,
+    /// Mark where evaluation windows begin and italicize tokens predicted
+    /// with a truncated context.
+    pub show_windows: bool,
+    /// Clicking a token shows the context the model predicted it from.
+    pub inspect_context: bool,
+    pub inspected: Option<InspectedToken>,
+    /// Tokens picked by clicking and dragging, or shift-clicking, when not
+    /// inspecting context.
+    pub selection: Option<TokenSelection>,
+    /// A drag that started on a token is extending the selection.
+    pub selecting: bool,
+    /// Token whose tooltip is kept open, with pinning on click.
+    pub pinned: Option<InspectedToken>,
+    /// From the settings.
+    pub tooltips: TooltipOptions,
+}
+
+/// A token in one of the shown results.
+#[derive(Debug, Clone, Copy)]
+pub struct InspectedToken {
+    /// Which of the shown results it was picked in, the first or the
+    /// second; `None` in the unified view, where it stands for both.
+    pub result: Option<usize>,
+    pub index: usize,
+}
+
+/// A span of tokens in one of the shown results, from the token the
+/// selection started at to the one it was extended to.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenSelection {
+    /// As in [`InspectedToken::result`].
+    pub result: Option<usize>,
+    pub anchor: usize,
+    pub end: usize,
+}
+
+impl TokenSelection {
+    fn at(token: InspectedToken) -> Self {
+        Self {
+            result: token.result,
+            anchor: token.index,
+            end: token.index,
+        }
+    }
+
+    pub fn range(&self) -> RangeInclusive<usize> {
+        self.anchor.min(self.end)..=self.anchor.max(self.end)
+    }
+
+    /// The selection as shown in result `index`; `None` for the unified
+    /// view.
+    fn within(selection: Option<Self>, index: Option<usize>) -> Option<RangeInclusive<usize>> {
+        selection
+            .filter(|s| index.is_none() || s.result.is_none_or(|r| Some(r) == index))
+            .map(|s| s.range())
+    }
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            view_mode: ViewMode::Split,
+            unified_color_mode: UnifiedColorMode::AvgRank,
+            color_mode: TokenColorMode::Rank,
+            highlight_repeats: false,
+            show_windows: true,
+            inspect_context: false,
+            inspected: None,
+            selection: None,
+            selecting: false,
+            pinned: None,
+            tooltips: TooltipOptions::default(),
+        }
+    }
+}
+
 // ── Header ──────────────────────────────────────────────────────────────────
 
 #[derive(Default)]
@@ -45,14 +196,49 @@ pub struct HeaderAction {
     pub settings: bool,
     pub eject_a: bool,
     pub eject_b: bool,
+    pub unload_a: bool,
+    pub unload_b: bool,
+}
+
+/// Whether a slot's model is in memory, as its header badge shows it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ModelStatus {
+    /// Selected but not in memory; loaded when an analysis needs it.
+    Unloaded,
+    Loading,
+    Loaded,
+    /// Loaded and running an analysis or side job, so it can't be
+    /// unloaded.
+    Busy,
+}
+
+impl ModelStatus {
+    pub fn of(worker: &WorkerManager) -> Self {
+        if worker.is_loading {
+            ModelStatus::Loading
+        } else if !worker.has_model {
+            ModelStatus::Unloaded
+        } else if worker.is_analyzing || worker.is_running_side_job {
+            ModelStatus::Busy
+        } else {
+            ModelStatus::Loaded
+        }
+    }
+}
+
+/// Clicked button of a model badge.
+enum BadgeClick {
+    Eject,
+    Unload,
 }
 
 pub fn render_header(
     ui: &mut Ui,
     model_path_a: Option<&str>,
     model_path_b: Option<&str>,
-    is_loading_a: bool,
-    is_loading_b: bool,
+    status_a: ModelStatus,
+    status_b: ModelStatus,
+    memory: Option<&MemoryUsage>,
 ) -> HeaderAction {
     let mut action = HeaderAction::default();
     ui.horizontal(|ui| {
@@ -65,12 +251,16 @@ pub fn render_header(
         ui.add_space(20.0);
 
         ui.vertical(|ui| {
-            if render_model_badge(ui, colors::INFO, model_path_a, is_loading_a) {
-                action.eject_a = true;
+            match render_model_badge(ui, colors::INFO, model_path_a, status_a) {
+                Some(BadgeClick::Eject) => action.eject_a = true,
+                Some(BadgeClick::Unload) => action.unload_a = true,
+                None => {}
             }
             ui.add_space(2.0);
-            if render_model_badge(ui, colors::WARNING, model_path_b, is_loading_b) {
-                action.eject_b = true;
+            match render_model_badge(ui, colors::WARNING, model_path_b, status_b) {
+                Some(BadgeClick::Eject) => action.eject_b = true,
+                Some(BadgeClick::Unload) => action.unload_b = true,
+                None => {}
             }
         });
 
@@ -81,6 +271,10 @@ pub fn render_header(
             {
                 action.settings = true;
             }
+            if let Some(memory) = memory {
+                ui.add_space(12.0);
+                render_memory_usage(ui, memory);
+            }
         });
     });
 
@@ -89,10 +283,85 @@ pub fn render_header(
     action
 }
 
-/// Returns true if the eject button was clicked.
-fn render_model_badge(ui: &mut Ui, color: Color32, path: Option<&str>, is_loading: bool) -> bool {
-    let mut ejected = false;
-    if is_loading {
+/// Compact RAM/VRAM readout; the hover lists each device.
+fn render_memory_usage(ui: &mut Ui, memory: &MemoryUsage) {
+    let mut text = format!("RAM {}", resources::format_bytes(memory.process_ram));
+    let (used, total) = memory
+        .devices
+        .iter()
+        .fold((0, 0), |(u, t), d| (u + d.used, t + d.total));
+    if total > 0 {
+        text.push_str(&format!(
+            "  ·  VRAM {} / {}",
+            resources::format_bytes(used),
+            resources::format_bytes(total)
+        ));
+    }
+    // Warn when the GPUs are nearly full: the next allocation is the one
+    // that fails.
+    let color = if total > 0 && used as f32 / total as f32 > 0.95 {
+        colors::WARNING
+    } else {
+        colors::text_muted(ui.visuals())
+    };
+    ui.label(RichText::new(format!("🖴 {}", text)).size(11.0).color(color))
+        .on_hover_ui(|ui| {
+            ui.label(format!(
+                "Process RAM: {} of {} system",
+                resources::format_bytes(memory.process_ram),
+                resources::format_bytes(memory.system_ram_total)
+            ));
+            for device in &memory.devices {
+                ui.label(format!(
+                    "{}: {} / {}",
+                    device.name,
+                    resources::format_bytes(device.used),
+                    resources::format_bytes(device.total)
+                ));
+            }
+            if memory.devices.is_empty() {
+                ui.label("No GPU devices reported");
+            }
+        });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MainTab {
+    Analyze,
+    Tokenizer,
+    Trends,
+}
+
+impl MainTab {
+    pub const ALL: [MainTab; 3] = [MainTab::Analyze, MainTab::Tokenizer, MainTab::Trends];
+}
+
+pub fn render_tab_bar(ui: &mut Ui, tab: &mut MainTab) {
+    ui.add_space(8.0);
+    ui.horizontal(|ui| {
+        ui.selectable_value(
+            tab,
+            MainTab::Analyze,
+            RichText::new("🔍 Analyze").size(14.0),
+        );
+        ui.selectable_value(
+            tab,
+            MainTab::Tokenizer,
+            RichText::new("🔤 Tokenizer").size(14.0),
+        );
+        ui.selectable_value(tab, MainTab::Trends, RichText::new("📈 Trends").size(14.0));
+    });
+}
+
+/// Returns the button that was clicked, if any.
+fn render_model_badge(
+    ui: &mut Ui,
+    color: Color32,
+    path: Option<&str>,
+    status: ModelStatus,
+) -> Option<BadgeClick> {
+    let mut clicked = None;
+    if status == ModelStatus::Loading {
         ui.horizontal(|ui| {
             ui.spinner();
             ui.label(RichText::new("Loading…").color(color).size(12.0));
@@ -100,11 +369,28 @@ fn render_model_badge(ui: &mut Ui, color: Color32, path: Option<&str>, is_loadin
     } else if let Some(p) = path {
         let name = crate::model_name_from_path(Some(p)).unwrap_or(p);
         ui.horizontal(|ui| {
-            ui.label(
-                RichText::new(format!("📦 {}", name))
-                    .color(color)
-                    .size(12.0),
-            );
+            if status == ModelStatus::Unloaded {
+                ui.label(
+                    RichText::new(format!("📦 {}", name))
+                        .color(colors::text_muted(ui.visuals()))
+                        .size(12.0),
+                )
+                .on_hover_text("Not in memory; loaded when an analysis needs it");
+            } else {
+                ui.label(
+                    RichText::new(format!("📦 {}", name))
+                        .color(color)
+                        .size(12.0),
+                );
+            }
+            if status == ModelStatus::Loaded
+                && ui
+                    .add(egui::Button::new(RichText::new("💤").size(12.0)).frame(false))
+                    .on_hover_text("Unload model to free memory; it's loaded again when needed")
+                    .clicked()
+            {
+                clicked = Some(BadgeClick::Unload);
+            }
             if ui
                 .add(
                     egui::Button::new(RichText::new("⏏").size(12.0))
@@ -113,7 +399,7 @@ fn render_model_badge(ui: &mut Ui, color: Color32, path: Option<&str>, is_loadin
                 .on_hover_text("Eject model")
                 .clicked()
             {
-                ejected = true;
+                clicked = Some(BadgeClick::Eject);
             }
         });
     } else {
@@ -123,7 +409,7 @@ fn render_model_badge(ui: &mut Ui, color: Color32, path: Option<&str>, is_loadin
                 .size(12.0),
         );
     }
-    ejected
+    clicked
 }
 
 // ── Model selection panel ───────────────────────────────────────────────────
@@ -177,22 +463,141 @@ pub fn render_model_panel(ui: &mut Ui, has_model_a: bool, has_model_b: bool) ->
 
 // ── Text input ──────────────────────────────────────────────────────────────
 
+/// Tail length when "Tail only" is first turned on.
+const DEFAULT_TAIL_TOKENS: usize = 256;
+
+/// Optional prompt that conditions the model without being scored.
+#[derive(Default)]
+pub struct PromptInput {
+    pub enabled: bool,
+    pub text: String,
+    /// Name typed for saving the prompt as a preset; also set when a
+    /// preset is loaded so it can be updated or deleted.
+    pub preset_name: String,
+    /// Score only this many tokens at the end of the text, the rest
+    /// conditioning the model, to iterate on an ending cheaply.
+    pub tail_tokens: Option<usize>,
+    /// Logit biases applied to every prediction, to see how the rankings
+    /// change when the model favors or avoids some tokens.
+    pub logit_bias: Option<String>,
+    /// Values of the `{{name}}` placeholders in the prompt. Kept when
+    /// another preset is loaded, so every text of a run gets the same ones.
+    pub variables: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct InputAction {
+    /// The completion or prompt was edited.
+    pub changed: bool,
+    /// Save the prompt under `preset_name`, replacing a preset of that name.
+    pub save_preset: bool,
+    pub delete_preset: bool,
+    /// Another reference domain was picked.
+    pub domain_changed: bool,
+}
+
+/// How the input field is shown this frame.
+pub struct InputView {
+    /// False while busy, unless live analysis is on.
+    pub enabled: bool,
+    pub height: f32,
+    /// Live token count of each slot's model.
+    pub token_counts: (Option<usize>, Option<usize>),
+}
+
 pub fn render_text_input(
     ui: &mut Ui,
     text: &mut String,
-    enabled: bool,
-    height: f32,
-    token_count_a: Option<usize>,
-    token_count_b: Option<usize>,
-) -> bool {
+    prompt: &mut PromptInput,
+    presets: &[PrefixPreset],
+    domain: &mut Domain,
+    view: InputView,
+) -> InputAction {
+    let InputView {
+        enabled,
+        height,
+        token_counts: (token_count_a, token_count_b),
+    } = view;
+    let mut action = InputAction::default();
     ui.add_space(12.0);
 
     ui.horizontal(|ui| {
         ui.label(
-            RichText::new("📝 Input Text")
-                .size(16.0)
-                .color(colors::text_primary(ui.visuals())),
+            RichText::new(if prompt.enabled {
+                "📝 Completion"
+            } else {
+                "📝 Input Text"
+            })
+            .size(16.0)
+            .color(colors::text_primary(ui.visuals())),
+        );
+
+        ui.add_space(12.0);
+        action.changed |= ui
+            .checkbox(
+                &mut prompt.enabled,
+                RichText::new("Prompt + completion").size(12.0),
+            )
+            .on_hover_text(
+                "Condition the model on a prompt and score only the completion, \
+                 as when evaluating generations",
+            )
+            .changed();
+
+        let mut tail_only = prompt.tail_tokens.is_some();
+        if ui
+            .checkbox(&mut tail_only, RichText::new("Tail only").size(12.0))
+            .on_hover_text(
+                "Score only the last tokens of the text; the text before them \
+                 conditions the model without being scored",
+            )
+            .changed()
+        {
+            prompt.tail_tokens = tail_only.then_some(DEFAULT_TAIL_TOKENS);
+            action.changed = true;
+        }
+        if let Some(ref mut tail) = prompt.tail_tokens {
+            action.changed |= ui
+                .add(
+                    egui::DragValue::new(tail)
+                        .range(1..=usize::MAX)
+                        .speed(16.0)
+                        .suffix(" tokens"),
+                )
+                .changed();
+        }
+
+        let mut biased = prompt.logit_bias.is_some();
+        if ui
+            .checkbox(&mut biased, RichText::new("Logit bias").size(12.0))
+            .on_hover_text(
+                "Shift the logits of chosen tokens before the softmax, e.g. to see how \
+                 the rankings change if the model can't use a token",
+            )
+            .changed()
+        {
+            prompt.logit_bias = biased.then(String::new);
+            action.changed = true;
+        }
+
+        ui.add_space(12.0);
+        ui.label(
+            RichText::new("Domain:")
+                .size(12.0)
+                .color(colors::text_muted(ui.visuals())),
+        )
+        .on_hover_text(
+            "Results are compared with the reference corpus of this domain, \
+             built in the Model settings",
         );
+        egui::ComboBox::from_id_salt("reference_domain")
+            .selected_text(RichText::new(domain.label()).size(12.0))
+            .width(90.0)
+            .show_ui(ui, |ui| {
+                for d in Domain::ALL {
+                    action.domain_changed |= ui.selectable_value(domain, d, d.label()).changed();
+                }
+            });
 
         let has_any = token_count_a.is_some() || token_count_b.is_some();
         if has_any {
@@ -245,380 +650,3229 @@ pub fn render_text_input(
 
     ui.add_space(4.0);
 
-    let scroll_height = (height - 40.0).max(80.0);
-    let mut changed = false;
+    let mut scroll_height = (height - 40.0).max(80.0);
+    if let Some(ref mut spec) = prompt.logit_bias {
+        scroll_height = (scroll_height - 28.0).max(80.0);
+        render_logit_bias_row(ui, spec, enabled, &mut action);
+        ui.add_space(4.0);
+    }
+    if prompt.enabled {
+        // The prompt takes a third of the input area.
+        let prompt_height = (scroll_height / 3.0).max(60.0);
+        scroll_height = (scroll_height - prompt_height - 36.0).max(80.0);
+        let has_variables = !template::variables(&prompt.text).is_empty();
+        if has_variables {
+            scroll_height = (scroll_height - 28.0).max(80.0);
+        }
+        render_preset_row(ui, prompt, presets, &mut action);
+        ui.add_space(4.0);
+        egui::ScrollArea::vertical()
+            .id_salt("prompt_input_scroll")
+            .max_height(prompt_height)
+            .show(ui, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::multiline(&mut prompt.text)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(2)
+                        .font(FontId::monospace(14.0))
+                        .hint_text(
+                            "Prompt (conditions the model, not scored); {{name}} adds a field…",
+                        )
+                        .interactive(enabled),
+                );
+                action.changed |= response.changed();
+            });
+        if has_variables {
+            ui.add_space(4.0);
+            render_variable_row(ui, prompt, enabled, &mut action);
+        }
+        ui.add_space(8.0);
+    }
 
+    let lookalikes = confusables::find(text);
     egui::ScrollArea::vertical()
         .id_salt("text_input_scroll")
         .max_height(scroll_height)
         .show(ui, |ui| {
-            let response = ui.add(
-                egui::TextEdit::multiline(text)
-                    .desired_width(f32::INFINITY)
-                    .desired_rows(6)
-                    .font(FontId::monospace(14.0))
-                    .hint_text("Paste your text here to analyze its perplexity…")
-                    .interactive(enabled),
-            );
-            changed = response.changed();
+            let mut layouter = confusable_layout;
+            let mut editor = egui::TextEdit::multiline(text)
+                .desired_width(f32::INFINITY)
+                .desired_rows(6)
+                .font(FontId::monospace(14.0))
+                .hint_text(if prompt.enabled {
+                    "Completion to score…"
+                } else {
+                    "Paste your text here to analyze its perplexity…"
+                })
+                .interactive(enabled);
+            if !lookalikes.is_empty() {
+                editor = editor.layouter(&mut layouter);
+            }
+            action.changed |= ui.add(editor).changed();
         });
+    render_invisible_row(ui, text, enabled, &mut action);
+    render_confusable_row(ui, &lookalikes);
 
-    changed
+    action
 }
 
-// ── Controls (analyze button + progress) ────────────────────────────────────
-
-pub fn render_controls(
-    ui: &mut Ui,
-    can_analyze: bool,
-    is_analyzing: bool,
-    progress_a: Option<f32>,
-    progress_b: Option<f32>,
-) -> bool {
-    ui.add_space(12.0);
+/// Counts the mixed-script lookalikes in the input; the editor highlights
+/// them.
+fn render_confusable_row(ui: &mut Ui, found: &[Confusable]) {
+    if found.is_empty() {
+        return;
+    }
+    ui.add_space(4.0);
+    ui.label(
+        RichText::new(format!(
+            "⚠ {} lookalike letter{} from another script",
+            found.len(),
+            if found.len() == 1 { "" } else { "s" }
+        ))
+        .color(colors::ERROR)
+        .size(12.0),
+    )
+    .on_hover_text(confusables_hover(found.iter(), found.len()));
+}
 
-    let mut clicked = false;
-    ui.horizontal(|ui| {
-        let label = if is_analyzing {
-            "⏳ Analyzing…"
-        } else {
-            "🔍 Analyze"
-        };
+/// The input with its mixed-script lookalikes on a red background.
+fn confusable_layout(ui: &Ui, text: &str, wrap_width: f32) -> std::sync::Arc<egui::Galley> {
+    let font_id = FontId::monospace(14.0);
+    let plain = egui::TextFormat::simple(font_id.clone(), ui.visuals().text_color());
+    let marked = egui::TextFormat {
+        background: colors::ERROR,
+        color: Color32::BLACK,
+        ..egui::TextFormat::simple(font_id, ui.visuals().text_color())
+    };
+    let mut job = egui::text::LayoutJob::default();
+    let mut cursor = 0;
+    for c in confusables::find(text) {
+        job.append(&text[cursor..c.offset], 0.0, plain.clone());
+        cursor = c.offset + c.char.len_utf8();
+        job.append(&text[c.offset..cursor], 0.0, marked.clone());
+    }
+    job.append(&text[cursor..], 0.0, plain);
+    job.wrap.max_width = wrap_width;
+    ui.fonts(|f| f.layout_job(job))
+}
 
+/// Warns about invisible characters in the input, with where they are,
+/// and offers to remove them.
+fn render_invisible_row(ui: &mut Ui, text: &mut String, enabled: bool, action: &mut InputAction) {
+    let found = invisible::scan(text);
+    if found.is_empty() {
+        return;
+    }
+    let total: usize = found.iter().map(|f| f.count).sum();
+    let mut details = String::from(
+        "Invisible characters change tokenization without changing what a reader sees, \
+         and often show where a text was copied from:\n",
+    );
+    for f in &found {
+        let positions: Vec<String> = f
+            .positions
+            .iter()
+            .map(|(line, column)| format!("{}:{}", line, column))
+            .collect();
+        details.push_str(&format!(
+            "\n{} {} ×{} at {}{}",
+            f.code(),
+            f.name,
+            f.count,
+            positions.join(", "),
+            if f.count > f.positions.len() {
+                ", …"
+            } else {
+                ""
+            }
+        ));
+    }
+    ui.add_space(4.0);
+    ui.horizontal_wrapped(|ui| {
+        let summary: Vec<String> = found
+            .iter()
+            .map(|f| format!("{} ×{}", f.name, f.count))
+            .collect();
+        ui.label(
+            RichText::new(format!(
+                "⚠ {} invisible character{}: {}",
+                total,
+                if total == 1 { "" } else { "s" },
+                summary.join(", ")
+            ))
+            .color(colors::WARNING)
+            .size(12.0),
+        )
+        .on_hover_text(details);
         if ui
             .add_enabled(
-                can_analyze && !is_analyzing,
-                egui::Button::new(RichText::new(label).size(18.0)).min_size(Vec2::new(140.0, 40.0)),
+                enabled,
+                egui::Button::new(RichText::new("🧹 Remove").size(12.0)),
             )
+            .on_hover_text("Delete every invisible character from the text")
             .clicked()
         {
-            clicked = true;
+            *text = invisible::remove(text);
+            action.changed = true;
         }
-
-        ui.add_space(16.0);
-
-        render_progress_bar(ui, "A", progress_a);
-        render_progress_bar(ui, "B", progress_b);
     });
-    clicked
 }
 
-fn render_progress_bar(ui: &mut Ui, label: &str, progress: Option<f32>) {
-    if let Some(pct) = progress {
-        ui.label(
-            RichText::new(format!("{}: {:3.0}%", label, pct * 100.0))
-                .font(FontId::monospace(12.0))
-                .color(colors::text_muted(ui.visuals())),
-        );
-        let bar = egui::ProgressBar::new(pct).fill(colors::progress_bar_fill(ui.visuals()));
-        ui.add_sized(Vec2::new(100.0, 16.0), bar);
-        ui.add_space(8.0);
-    }
+/// Collapsible read-only view of the input after preprocessing. Only
+/// computed while expanded.
+pub fn render_preprocess_preview(ui: &mut Ui, text: &str, options: &PreprocessOptions) {
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(RichText::new("🧹 Preprocessed input").size(12.0))
+        .id_salt("preprocess_preview")
+        .show(ui, |ui| {
+            let mut processed = preprocess::apply(text, options);
+            if processed == text {
+                ui.label(
+                    RichText::new("Preprocessing leaves this text unchanged.")
+                        .size(11.0)
+                        .color(colors::text_muted(ui.visuals())),
+                );
+                return;
+            }
+            egui::ScrollArea::vertical()
+                .id_salt("preprocess_preview_scroll")
+                .max_height(150.0)
+                .show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut processed)
+                            .desired_width(f32::INFINITY)
+                            .font(FontId::monospace(13.0))
+                            .interactive(false),
+                    );
+                });
+        });
 }
 
-// ── Results ─────────────────────────────────────────────────────────────────
+/// A text waiting in the analysis queue.
+pub struct QueuedText {
+    pub name: String,
+    pub text: String,
+    /// Stored with the analysis on top of the tags for new analyses.
+    pub tags: Vec<String>,
+    /// File in the watched folder the text was read from; its results are
+    /// written next to it.
+    pub source: Option<PathBuf>,
+}
 
-/// Check whether two analysis results have compatible tokenizers by comparing
-/// their token text sequences. When the tokenizers match, every token at the
-/// same index covers the same piece of text, which is required for the unified
-/// view and for index-based cross-model comparison in tooltips.
-fn tokenizers_match(a: &AnalysisResult, b: &AnalysisResult) -> bool {
-    if a.tokens.len() != b.tokens.len() {
-        return false;
-    }
-    a.tokens
-        .iter()
-        .zip(b.tokens.iter())
-        .all(|(ta, tb)| ta.text == tb.text)
+/// A finished queued analysis, kept so it can be shown again.
+pub struct HistoryEntry {
+    pub name: String,
+    pub text: String,
+    pub results: [Option<Arc<AnalysisResult>>; 2],
 }
 
-fn render_tokenizer_warning(ui: &mut Ui) {
-    egui::Frame::none()
-        .fill(colors::warning_bg(ui.visuals()))
-        .rounding(8.0)
-        .inner_margin(10.0)
+#[derive(Default)]
+pub struct QueueAction {
+    pub add_current: bool,
+    pub add_files: bool,
+    pub import: bool,
+    pub remove: Option<usize>,
+    pub run: bool,
+    pub stop: bool,
+    /// Index into the history of the entry to show.
+    pub show: Option<usize>,
+    pub clear_history: bool,
+}
+
+/// Collapsible queue of texts analyzed one after another, and the history
+/// of their results.
+pub fn render_queue(
+    ui: &mut Ui,
+    queue: &VecDeque<QueuedText>,
+    history: &[HistoryEntry],
+    running: bool,
+    can_run: bool,
+) -> QueueAction {
+    let mut action = QueueAction::default();
+    ui.add_space(4.0);
+    let title = if running {
+        format!("📋 Queue: running, {} waiting", queue.len())
+    } else {
+        format!("📋 Queue ({} waiting, {} done)", queue.len(), history.len())
+    };
+    egui::CollapsingHeader::new(RichText::new(title).size(12.0))
+        .id_salt("analysis_queue")
         .show(ui, |ui| {
             ui.horizontal(|ui| {
-                ui.label(RichText::new("⚠").size(16.0));
-                ui.add_space(6.0);
-                ui.label(
-                    RichText::new(
-                        "The two models use different tokenizers, \
-                         unified view is disabled and token comparison is unavailable.",
+                action.add_current = ui
+                    .button("➕ Add current text")
+                    .on_hover_text("Queue the text in the input field")
+                    .clicked();
+                action.add_files = ui.button("📂 Add files…").clicked();
+                action.import = ui
+                    .button("📥 Import labeled…")
+                    .on_hover_text(
+                        "Queue every text of a CSV or JSONL file with text and label \
+                         columns; each analysis is stored tagged with its label",
                     )
-                    .color(colors::WARNING)
-                    .size(12.0),
-                );
+                    .clicked();
+                if running {
+                    action.stop = ui
+                        .button("⏹ Stop")
+                        .on_hover_text("Finish the current text and leave the rest queued")
+                        .clicked();
+                } else {
+                    action.run = ui
+                        .add_enabled(
+                            can_run && !queue.is_empty(),
+                            egui::Button::new("▶ Run queue"),
+                        )
+                        .clicked();
+                }
+            });
+
+            let muted = colors::text_muted(ui.visuals());
+            for (i, item) in queue.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .small_button("✖")
+                        .on_hover_text("Remove from the queue")
+                        .clicked()
+                    {
+                        action.remove = Some(i);
+                    }
+                    ui.label(RichText::new(&item.name).size(12.0));
+                    ui.label(
+                        RichText::new(format!("{} chars", item.text.chars().count()))
+                            .size(11.0)
+                            .color(muted),
+                    );
+                    if !item.tags.is_empty() {
+                        ui.label(
+                            RichText::new(format!("🏷 {}", item.tags.join(", ")))
+                                .size(11.0)
+                                .color(muted),
+                        );
+                    }
+                });
+            }
+
+            if history.is_empty() {
+                return;
+            }
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("History").strong().size(12.0));
+                action.clear_history = ui.small_button("Clear").clicked();
             });
+            egui::Grid::new("analysis_history")
+                .num_columns(4)
+                .striped(true)
+                .spacing([12.0, 2.0])
+                .show(ui, |ui| {
+                    for header in ["Text", "PPL A", "PPL B", ""] {
+                        ui.label(RichText::new(header).strong().size(11.0));
+                    }
+                    ui.end_row();
+                    for (i, entry) in history.iter().enumerate() {
+                        ui.label(RichText::new(&entry.name).size(11.0));
+                        for result in &entry.results {
+                            let text = result
+                                .as_ref()
+                                .map_or("—".to_string(), |r| format!("{:.2}", r.perplexity()));
+                            ui.label(RichText::new(text).size(11.0));
+                        }
+                        if ui.small_button("Show").clicked() {
+                            action.show = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
         });
-    ui.add_space(4.0);
+    action
 }
 
-pub fn render_results(
-    ui: &mut Ui,
-    result_a: Option<&AnalysisResult>,
-    result_b: Option<&AnalysisResult>,
-    model_name_a: Option<&str>,
-    model_name_b: Option<&str>,
-    height: f32,
-    view_mode: &mut ViewMode,
-    unified_color_mode: &mut UnifiedColorMode,
-) {
-    ui.add_space(16.0);
-    ui.separator();
-    ui.add_space(8.0);
+#[derive(Default)]
+pub struct StoreAction {
+    /// Id of the stored analysis to show.
+    pub show: Option<u64>,
+    pub delete: Option<u64>,
+    pub set_tags: Option<(u64, Vec<String>)>,
+}
 
-    let both = result_a.is_some() && result_b.is_some();
+/// What the stored analyses list shows and the tags being edited.
+#[derive(Default)]
+pub struct StoreView {
+    pub filter: String,
+    /// Comma-separated tags given to each new analysis.
+    pub new_tags: String,
+    /// Stored analysis whose tags are being edited, with the edited text.
+    pub editing: Option<(u64, String)>,
+}
 
-    let tok_match = if both {
-        tokenizers_match(result_a.unwrap(), result_b.unwrap())
-    } else {
-        false
-    };
+/// Stored analyses shown at most, newest first.
+const MAX_STORED_ROWS: usize = 200;
 
-    // Force split view when tokenizers differ
-    if both && !tok_match && *view_mode == ViewMode::Unified {
-        *view_mode = ViewMode::Split;
-    }
+/// Collapsible list of the analyses in the store, filtered by name, model
+/// or tag.
+pub fn render_store(ui: &mut Ui, records: &[StoredAnalysis], view: &mut StoreView) -> StoreAction {
+    let mut action = StoreAction::default();
+    egui::CollapsingHeader::new(
+        RichText::new(format!("🗄 Stored analyses ({})", records.len())).size(12.0),
+    )
+    .id_salt("analysis_store")
+    .show(ui, |ui| {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Tag new analyses:").size(12.0));
+            ui.add(
+                egui::TextEdit::singleline(&mut view.new_tags)
+                    .hint_text("e.g. human, essay")
+                    .desired_width(160.0),
+            )
+            .on_hover_text("Comma-separated tags recorded with every analysis from now on");
+            ui.add_space(12.0);
+            ui.label(RichText::new("Filter:").size(12.0));
+            ui.add(
+                egui::TextEdit::singleline(&mut view.filter)
+                    .hint_text("name, model or tag")
+                    .desired_width(160.0),
+            );
+        });
+        let needle = view.filter.trim().to_lowercase();
+        let matches: Vec<&StoredAnalysis> = records
+            .iter()
+            .rev()
+            .filter(|r| {
+                needle.is_empty()
+                    || r.name.to_lowercase().contains(&needle)
+                    || r.model.to_lowercase().contains(&needle)
+                    || r.tags.iter().any(|t| t.to_lowercase() == needle)
+            })
+            .collect();
+        if matches.is_empty() {
+            ui.label(
+                RichText::new("No stored analyses")
+                    .size(11.0)
+                    .color(colors::text_muted(ui.visuals())),
+            );
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .id_salt("analysis_store_scroll")
+            .max_height(240.0)
+            .show(ui, |ui| {
+                egui::Grid::new("analysis_store_grid")
+                    .num_columns(8)
+                    .striped(true)
+                    .spacing([12.0, 2.0])
+                    .show(ui, |ui| {
+                        for header in [
+                            "Date (UTC)",
+                            "Text",
+                            "Model",
+                            "Tags",
+                            "PPL",
+                            "Bits/char",
+                            "",
+                            "",
+                        ] {
+                            ui.label(RichText::new(header).strong().size(11.0));
+                        }
+                        ui.end_row();
+                        for record in matches.iter().take(MAX_STORED_ROWS) {
+                            ui.label(
+                                RichText::new(store::format_timestamp(record.timestamp)).size(11.0),
+                            );
+                            ui.label(RichText::new(&record.name).size(11.0));
+                            ui.label(RichText::new(&record.model).size(11.0));
+                            render_tag_cell(ui, record, view, &mut action);
+                            ui.label(RichText::new(format!("{:.2}", record.perplexity)).size(11.0));
+                            ui.label(
+                                RichText::new(format!("{:.3}", record.bits_per_char)).size(11.0),
+                            );
+                            if ui
+                                .add_enabled(record.has_tokens, egui::Button::new("Show").small())
+                                .on_disabled_hover_text("Only the metrics were stored")
+                                .clicked()
+                            {
+                                action.show = Some(record.id);
+                            }
+                            if ui.small_button("🗑").on_hover_text("Delete").clicked() {
+                                action.delete = Some(record.id);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if matches.len() > MAX_STORED_ROWS {
+            ui.label(
+                RichText::new(format!(
+                    "Showing the latest {} of {}",
+                    MAX_STORED_ROWS,
+                    matches.len()
+                ))
+                .size(11.0)
+                .color(colors::text_muted(ui.visuals())),
+            );
+        }
+    });
+    action
+}
+
+/// A stored analysis's tags, edited in place after clicking them. The edit
+/// is saved on Enter or when the field loses focus.
+fn render_tag_cell(
+    ui: &mut Ui,
+    record: &StoredAnalysis,
+    view: &mut StoreView,
+    action: &mut StoreAction,
+) {
+    match &mut view.editing {
+        Some((id, text)) if *id == record.id => {
+            let response = ui.add(egui::TextEdit::singleline(text).desired_width(120.0));
+            if response.lost_focus() {
+                action.set_tags = Some((record.id, store::parse_tags(text)));
+                view.editing = None;
+            } else if !response.has_focus() {
+                response.request_focus();
+            }
+        }
+        _ => {
+            let tags = if record.tags.is_empty() {
+                "🏷".to_string()
+            } else {
+                record.tags.join(", ")
+            };
+            if ui
+                .add(egui::Label::new(RichText::new(tags).size(11.0)).sense(egui::Sense::click()))
+                .on_hover_text("Click to edit the tags, separated by commas")
+                .clicked()
+            {
+                view.editing = Some((record.id, record.tags.join(", ")));
+            }
+        }
+    }
+}
+
+/// Load, save and delete prefix presets for the prompt field.
+fn render_preset_row(
+    ui: &mut Ui,
+    prompt: &mut PromptInput,
+    presets: &[PrefixPreset],
+    action: &mut InputAction,
+) {
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new("Preset:")
+                .size(12.0)
+                .color(colors::text_muted(ui.visuals())),
+        );
+        egui::ComboBox::from_id_salt("prefix_preset")
+            .selected_text(RichText::new("Load…").size(12.0))
+            .width(140.0)
+            .show_ui(ui, |ui| {
+                if presets.is_empty() {
+                    ui.label(RichText::new("No saved presets").size(12.0));
+                }
+                for preset in presets {
+                    if ui.selectable_label(false, &preset.name).clicked() {
+                        prompt.text = preset.text.clone();
+                        prompt.preset_name = preset.name.clone();
+                        action.changed = true;
+                    }
+                }
+            });
+
+        ui.add(
+            egui::TextEdit::singleline(&mut prompt.preset_name)
+                .desired_width(140.0)
+                .hint_text("Preset name"),
+        );
+        let name = prompt.preset_name.trim();
+        let exists = presets.iter().any(|p| p.name == name);
+        if ui
+            .add_enabled(
+                !name.is_empty() && !prompt.text.is_empty(),
+                egui::Button::new(
+                    RichText::new(if exists { "💾 Update" } else { "💾 Save" }).size(12.0),
+                ),
+            )
+            .on_hover_text("Save the prompt as a named preset")
+            .clicked()
+        {
+            action.save_preset = true;
+        }
+        if exists
+            && ui
+                .button(RichText::new("🗑").size(12.0))
+                .on_hover_text("Delete this preset")
+                .clicked()
+        {
+            action.delete_preset = true;
+        }
+    });
+}
+
+/// One field per `{{name}}` placeholder of the prompt.
+fn render_variable_row(
+    ui: &mut Ui,
+    prompt: &mut PromptInput,
+    enabled: bool,
+    action: &mut InputAction,
+) {
+    let names: Vec<String> = template::variables(&prompt.text)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    ui.horizontal_wrapped(|ui| {
+        for name in names {
+            ui.label(
+                RichText::new(format!("{}:", name))
+                    .size(12.0)
+                    .color(colors::text_muted(ui.visuals())),
+            );
+            let value = prompt.variables.entry(name).or_default();
+            action.changed |= ui
+                .add_enabled(
+                    enabled,
+                    egui::TextEdit::singleline(value)
+                        .desired_width(140.0)
+                        .hint_text("not filled"),
+                )
+                .on_hover_text("Unfilled placeholders stay in the prompt as they are")
+                .changed();
+        }
+    });
+}
+
+/// The logit bias field, with the error if it doesn't parse.
+fn render_logit_bias_row(ui: &mut Ui, spec: &mut String, enabled: bool, action: &mut InputAction) {
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new("Bias:")
+                .size(12.0)
+                .color(colors::text_muted(ui.visuals())),
+        );
+        action.changed |= ui
+            .add_enabled(
+                enabled,
+                egui::TextEdit::singleline(spec)
+                    .desired_width(320.0)
+                    .font(FontId::monospace(12.0))
+                    .hint_text("\" the\" = -inf, 1234 = 2.5"),
+            )
+            .on_hover_text(
+                "Token = bias, separated by commas. A token is an id or a quoted text that \
+                 is a single token of the model; -inf bans it.",
+            )
+            .changed();
+        if let Err(e) = logit_bias::parse(spec) {
+            ui.label(RichText::new(e).color(colors::ERROR).size(12.0));
+        }
+    });
+}
+
+// ── Controls (analyze button + progress) ────────────────────────────────────
+
+/// Follow-up tests run on an existing result.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResultTest {
+    Perturbation,
+    ContextSweep,
+    ChunkSweep,
+    Isolation,
+    Conditioning,
+    Shuffle,
+    Regeneration,
+    RegenerateDiff,
+    CrossQuant,
+    Determinism,
+    BosSensitivity,
+}
+
+impl ResultTest {
+    pub const ALL: [ResultTest; 11] = [
+        ResultTest::Perturbation,
+        ResultTest::ContextSweep,
+        ResultTest::ChunkSweep,
+        ResultTest::Isolation,
+        ResultTest::Conditioning,
+        ResultTest::Shuffle,
+        ResultTest::Regeneration,
+        ResultTest::RegenerateDiff,
+        ResultTest::CrossQuant,
+        ResultTest::Determinism,
+        ResultTest::BosSensitivity,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ResultTest::Perturbation => "🧪 Perturbation test",
+            ResultTest::ContextSweep => "📏 Context sweep",
+            ResultTest::ChunkSweep => "🪟 Chunk-size sweep",
+            ResultTest::Isolation => "🧩 Context isolation",
+            ResultTest::Conditioning => "🎭 Compare prompts",
+            ResultTest::Shuffle => "🔀 Shuffled control",
+            ResultTest::Regeneration => "🔁 Regeneration similarity",
+            ResultTest::RegenerateDiff => "✍ Regenerate and diff",
+            ResultTest::CrossQuant => "📉 Cross-quant variance",
+            ResultTest::Determinism => "🎯 Verify determinism",
+            ResultTest::BosSensitivity => "🔤 BOS and leading-space sensitivity",
+        }
+    }
+
+    /// Whether the test runs on the side thread, so it can start while an
+    /// analysis is running.
+    pub fn runs_beside_analyses(self) -> bool {
+        matches!(
+            self,
+            ResultTest::ContextSweep
+                | ResultTest::ChunkSweep
+                | ResultTest::Regeneration
+                | ResultTest::RegenerateDiff
+                | ResultTest::CrossQuant
+        )
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ResultTest::Perturbation => {
+                "Score rewritten variants of the text and compare them with the original \
+                 (DetectGPT curvature)"
+            }
+            ResultTest::ContextSweep => {
+                "Re-score the text with limited context windows to see how much it relies on \
+                 long-range structure"
+            }
+            ResultTest::ChunkSweep => {
+                "Re-score the text with several evaluation window sizes and strides to check \
+                 whether the perplexity depends on the windowing choice"
+            }
+            ResultTest::Isolation => {
+                "Score each sentence on its own, without the preceding text, to see how much it \
+                 benefits from the document context"
+            }
+            ResultTest::Conditioning => {
+                "Score the text under each saved prompt preset to see which persona or \
+                 instruction it is most in-distribution for"
+            }
+            ResultTest::Shuffle => {
+                "Score the same sentences in random order; coherent text becomes clearly more \
+                 perplexing, so a small gap points to a list or incoherent text"
+            }
+            ResultTest::Regeneration => {
+                "Let the model continue the text's opening on its own and measure how close its \
+                 version is to the original; low perplexity plus a close regeneration is a strong \
+                 sign of machine authorship"
+            }
+            ResultTest::RegenerateDiff => {
+                "Have the model continue the text's opening itself and diff its version against \
+                 the actual text word by word; to start elsewhere, use the context inspector"
+            }
+            ResultTest::CrossQuant => {
+                "Analyze the text with other quants of the model and map how much each token's \
+                 probability varies between them, to see where quantization does damage"
+            }
+            ResultTest::Determinism => {
+                "Analyze the same text again and report any token whose probability changed, \
+                 to measure the noise floor of this backend and configuration"
+            }
+            ResultTest::BosSensitivity => {
+                "Analyze the text with and without the BOS token and a leading space; these \
+                 invisible tokenizer settings can move perplexity more than expected"
+            }
+        }
+    }
+}
+
+/// Files the current results can be exported to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    PdfReport,
+    HtmlReport,
+    AnnotatedText,
+    Workbook,
+    Template,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 5] = [
+        ExportFormat::PdfReport,
+        ExportFormat::HtmlReport,
+        ExportFormat::AnnotatedText,
+        ExportFormat::Workbook,
+        ExportFormat::Template,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::PdfReport => "📄 PDF report",
+            ExportFormat::HtmlReport => "🌐 Interactive HTML report",
+            ExportFormat::AnnotatedText => "📝 Annotated plain text",
+            ExportFormat::Workbook => "📊 Excel workbook",
+            ExportFormat::Template => "🧩 Custom template…",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ExportFormat::PdfReport => {
+                "Summary metrics, rank histogram, surprisal chart and the colored text, \
+                 one section per model"
+            }
+            ExportFormat::HtmlReport => {
+                "A single standalone page with hover tooltips, a sortable token table and the \
+                 charts, viewable in any browser"
+            }
+            ExportFormat::AnnotatedText => {
+                "The text with inline rank and probability annotations, readable without \
+                 colors and easy to diff across revisions of a document"
+            }
+            ExportFormat::Workbook => {
+                "Sheets with the summary metrics, every token, aggregates per sentence and \
+                 the settings of the run, ready for filtering and pivot tables"
+            }
+            ExportFormat::Template => {
+                "Any text format, such as org-mode, Markdown, CSV or custom HTML, rendered \
+                 from a Handlebars template you pick"
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ControlsAction {
+    pub analyze: bool,
+    pub pause_toggled: bool,
+    pub live_toggled: bool,
+    pub test: Option<ResultTest>,
+    pub export: Option<ExportFormat>,
+}
+
+/// How long an analysis will take.
+pub enum TimeEstimate {
+    /// Before starting, from each model's measured speed.
+    Total(Duration),
+    /// While running, from the progress so far.
+    Remaining(Duration),
+}
+
+/// State of the running work, shown next to the Analyze button.
+pub struct RunProgress {
+    /// Work is in progress: an analysis, or loading models for one.
+    pub is_analyzing: bool,
+    /// Whether the running analysis is paused; `None` when none is running.
+    pub paused: Option<bool>,
+    /// Progress of each slot's running job.
+    pub bars: [Option<f32>; 2],
+    pub estimate: Option<TimeEstimate>,
+}
+
+/// Formats a duration as `1h 02m`, `3m 05s` or `42s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+pub fn render_controls(
+    ui: &mut Ui,
+    can_analyze: bool,
+    progress: RunProgress,
+    live_analysis: &mut bool,
+    can_run_tests: bool,
+    has_results: bool,
+) -> ControlsAction {
+    let RunProgress {
+        is_analyzing,
+        paused,
+        bars,
+        estimate,
+    } = progress;
+    ui.add_space(12.0);
+
+    let mut action = ControlsAction::default();
+    ui.horizontal(|ui| {
+        let label = if paused == Some(true) {
+            "⏸ Paused"
+        } else if is_analyzing {
+            "⏳ Analyzing…"
+        } else {
+            "🔍 Analyze"
+        };
+
+        if ui
+            .add_enabled(
+                can_analyze && !is_analyzing,
+                egui::Button::new(RichText::new(label).size(18.0)).min_size(Vec2::new(140.0, 40.0)),
+            )
+            .clicked()
+        {
+            action.analyze = true;
+        }
+
+        let estimate = match estimate {
+            Some(TimeEstimate::Total(total)) if can_analyze && !is_analyzing => Some((
+                format!("≈ {}", format_duration(total)),
+                "Estimated from this model's speed in earlier analyses",
+            )),
+            Some(TimeEstimate::Remaining(left)) if is_analyzing => Some((
+                format!("≈ {} left", format_duration(left)),
+                "Estimated from the progress so far",
+            )),
+            _ => None,
+        };
+        if let Some((text, hint)) = estimate {
+            ui.label(
+                RichText::new(text)
+                    .size(13.0)
+                    .color(colors::text_muted(ui.visuals())),
+            )
+            .on_hover_text(hint);
+        }
+
+        if let Some(paused) = paused {
+            let (label, hint) = if paused {
+                ("▶ Resume", "Continue the analysis from where it stopped")
+            } else {
+                (
+                    "⏸ Pause",
+                    "Stop after the current batch, leaving the GPU to other work until \
+                     resumed; the model stays loaded",
+                )
+            };
+            if ui
+                .button(RichText::new(label).size(13.0))
+                .on_hover_text(hint)
+                .clicked()
+            {
+                action.pause_toggled = true;
+            }
+        }
+
+        ui.add_space(8.0);
+
+        action.live_toggled = ui
+            .checkbox(live_analysis, RichText::new("⚡ Live").size(13.0))
+            .on_hover_text("Re-analyze automatically when you stop typing")
+            .changed();
+
+        ui.add_space(8.0);
+
+        ui.add_enabled_ui(can_run_tests, |ui| {
+            ui.menu_button(RichText::new("🧪 Tests").size(13.0), |ui| {
+                for test in ResultTest::ALL {
+                    let enabled = !is_analyzing || test.runs_beside_analyses();
+                    if ui
+                        .add_enabled(enabled, egui::Button::new(test.label()))
+                        .on_hover_text(test.description())
+                        .on_disabled_hover_text("Available once the analysis is done")
+                        .clicked()
+                    {
+                        action.test = Some(test);
+                        ui.close_menu();
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Follow-up tests on the current result");
+        });
+
+        ui.add_enabled_ui(has_results && !is_analyzing, |ui| {
+            ui.menu_button(RichText::new("📤 Export").size(13.0), |ui| {
+                for format in ExportFormat::ALL {
+                    if ui
+                        .button(format.label())
+                        .on_hover_text(format.description())
+                        .clicked()
+                    {
+                        action.export = Some(format);
+                        ui.close_menu();
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Save the current results to a file");
+        });
+
+        ui.add_space(16.0);
+
+        render_progress_bar(ui, "A", bars[0]);
+        render_progress_bar(ui, "B", bars[1]);
+    });
+    action
+}
+
+fn render_progress_bar(ui: &mut Ui, label: &str, progress: Option<f32>) {
+    if let Some(pct) = progress {
+        ui.label(
+            RichText::new(format!("{}: {:3.0}%", label, pct * 100.0))
+                .font(FontId::monospace(12.0))
+                .color(colors::text_muted(ui.visuals())),
+        );
+        let bar = egui::ProgressBar::new(pct).fill(colors::progress_bar_fill(ui.visuals()));
+        ui.add_sized(Vec2::new(100.0, 16.0), bar);
+        ui.add_space(8.0);
+    }
+}
+
+// ── Results ─────────────────────────────────────────────────────────────────
+
+/// Check whether two analysis results have compatible tokenizers by comparing
+/// their token text sequences. When the tokenizers match, every token at the
+/// same index covers the same piece of text, which is required for the unified
+/// view and for index-based cross-model comparison in tooltips.
+fn tokenizers_match(a: &AnalysisResult, b: &AnalysisResult) -> bool {
+    if a.tokens.len() != b.tokens.len() {
+        return false;
+    }
+    a.tokens
+        .iter()
+        .zip(b.tokens.iter())
+        .all(|(ta, tb)| ta.text == tb.text)
+}
+
+/// One line on whether the perplexity difference between the two results
+/// is statistically significant.
+fn render_significance(ui: &mut Ui, test: &SignificanceTest, name_a: &str, name_b: &str) {
+    let (ppl_a, ppl_b) = test.perplexities;
+    let lower = if ppl_a <= ppl_b { name_a } else { name_b };
+    let (text, color) = if test.is_significant() {
+        (
+            format!(
+                "📐 {} has the lower perplexity ({:.2} vs {:.2}), p = {:.4}",
+                lower,
+                ppl_a.min(ppl_b),
+                ppl_a.max(ppl_b),
+                test.p_value
+            ),
+            colors::SUCCESS,
+        )
+    } else {
+        (
+            format!(
+                "📐 No significant perplexity difference ({:.2} vs {:.2}), p = {:.4}",
+                ppl_a, ppl_b, test.p_value
+            ),
+            colors::text_muted(ui.visuals()),
+        )
+    };
+    ui.add_space(4.0);
+    ui.label(RichText::new(text).color(color).size(12.0))
+        .on_hover_text(format!(
+            "{} permutation test over per-token surprisals, {} permutations ({} vs {} tokens).\n\
+             The p-value is the chance of a difference at least this large if both results \
+             came from the same model; below {} it is unlikely to be noise.",
+            if test.paired { "Paired" } else { "Unpaired" },
+            test.permutations,
+            test.tokens.0,
+            test.tokens.1,
+            crate::significance::SIGNIFICANCE_LEVEL
+        ));
+}
+
+fn render_tokenizer_warning(ui: &mut Ui) {
+    egui::Frame::none()
+        .fill(colors::warning_bg(ui.visuals()))
+        .rounding(8.0)
+        .inner_margin(10.0)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("⚠").size(16.0));
+                ui.add_space(6.0);
+                ui.label(
+                    RichText::new(
+                        "The two models use different tokenizers, \
+                         unified view is disabled and token comparison is unavailable.",
+                    )
+                    .color(colors::WARNING)
+                    .size(12.0),
+                );
+            });
+        });
+    ui.add_space(4.0);
+}
+
+/// One model's result together with what's needed to display it.
+pub struct ResultView<'a> {
+    pub result: &'a AnalysisResult,
+    pub name: &'a str,
+    pub reference: Option<&'a ReferenceStats>,
+    pub speakers: &'a [SpeakerStats],
+    pub token_types: &'a [TokenTypeStats],
+    pub classes: &'a [ClassStats],
+    pub perturbation: Option<&'a PerturbationStats>,
+    pub watermark: Option<&'a WatermarkStats>,
+    pub custom_metrics: &'a [MetricValue],
+    /// Special tokens the text itself produced.
+    pub special_tokens: &'a [SpecialTokenUse],
+    /// Mixed-script lookalike letters, with the index of their token.
+    pub confusables: &'a [(usize, Confusable)],
+    pub typography: Option<&'a TypographyStats>,
+    pub text_stats: Option<&'a TextStats>,
+    /// Built-in metrics to show in the summary row.
+    pub summary: &'a [SummaryMetric],
+    pub format: NumberFormat,
+    /// Value of the color expression for each token, when one is set.
+    pub token_colors: Option<&'a Result<Vec<f32>, String>>,
+    pub context_sweep: Option<&'a [(u32, f32)]>,
+    pub chunk_sweep: Option<&'a ChunkSweep>,
+    pub isolation: Option<&'a IsolationStats>,
+    pub conditioning: Option<&'a ConditioningStats>,
+    pub shuffle: Option<&'a ShuffleStats>,
+    pub regeneration: Option<&'a RegenerationStats>,
+    pub regeneration_diff: Option<&'a RegenerationDiff>,
+    pub quant_variance: Option<&'a QuantVariance>,
+    pub determinism: Option<&'a DeterminismStats>,
+    pub bos_sensitivity: Option<&'a BosSensitivity>,
+    /// Threshold tuned for this result's model.
+    pub verdict: Option<&'a VerdictThreshold>,
+}
+
+impl<'a> ResultView<'a> {
+    /// A result without reference data or follow-up tests.
+    pub fn bare(result: &'a AnalysisResult, name: &'a str) -> Self {
+        Self {
+            result,
+            name,
+            reference: None,
+            speakers: &[],
+            token_types: &[],
+            classes: &[],
+            perturbation: None,
+            watermark: None,
+            custom_metrics: &[],
+            special_tokens: &[],
+            confusables: &[],
+            typography: None,
+            text_stats: None,
+            summary: &SummaryMetric::DEFAULT,
+            format: NumberFormat::default(),
+            token_colors: None,
+            context_sweep: None,
+            chunk_sweep: None,
+            isolation: None,
+            conditioning: None,
+            shuffle: None,
+            regeneration: None,
+            regeneration_diff: None,
+            quant_variance: None,
+            determinism: None,
+            bos_sensitivity: None,
+            verdict: None,
+        }
+    }
+}
+
+/// Marks the results below as the preview model's approximation.
+pub fn render_preview_notice(ui: &mut Ui, name: &str) {
+    ui.add_space(12.0);
+    ui.horizontal(|ui| {
+        ui.spinner();
+        ui.label(
+            RichText::new(format!(
+                "Quick preview from {}; refining with the full model…",
+                name
+            ))
+            .size(13.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+    });
+}
+
+/// Returns a token to regenerate the text from, picked in the context
+/// inspector.
+pub fn render_results(
+    ui: &mut Ui,
+    view_a: Option<ResultView>,
+    view_b: Option<ResultView>,
+    significance: Option<&SignificanceTest>,
+    height: f32,
+    options: &mut DisplayOptions,
+) -> Option<InspectedToken> {
+    let view_mode = &mut options.view_mode;
+    let unified_color_mode = &mut options.unified_color_mode;
+    ui.add_space(16.0);
+    ui.separator();
+    ui.add_space(8.0);
+
+    let both = view_a.is_some() && view_b.is_some();
+
+    let tok_match = match (&view_a, &view_b) {
+        (Some(a), Some(b)) => tokenizers_match(a.result, b.result),
+        _ => false,
+    };
+
+    // Force split view when tokenizers differ
+    if both && !tok_match && *view_mode == ViewMode::Unified {
+        *view_mode = ViewMode::Split;
+    }
+
+    if both {
+        // Tokenizer mismatch warning
+        if !tok_match {
+            render_tokenizer_warning(ui);
+        }
+
+        ui.horizontal(|ui| {
+            // Segmented control: Split | Unified
+            ui.label(
+                RichText::new("View:")
+                    .size(12.0)
+                    .color(colors::text_muted(ui.visuals())),
+            );
+            ui.add_space(4.0);
+
+            let split_selected = *view_mode == ViewMode::Split;
+
+            if ui
+                .selectable_label(split_selected, RichText::new("🔀 Split").size(12.0))
+                .clicked()
+            {
+                *view_mode = ViewMode::Split;
+            }
+
+            // Only allow unified view when tokenizers match
+            if tok_match {
+                let unified_selected = *view_mode == ViewMode::Unified;
+                if ui
+                    .selectable_label(unified_selected, RichText::new("⊞ Unified").size(12.0))
+                    .clicked()
+                {
+                    *view_mode = ViewMode::Unified;
+                }
+            } else {
+                ui.add_enabled_ui(false, |ui| {
+                    ui.selectable_label(false, RichText::new("⊞ Unified").size(12.0))
+                        .on_disabled_hover_text("Unified view requires matching tokenizers");
+                });
+            }
+
+            if *view_mode == ViewMode::Unified {
+                ui.add_space(16.0);
+                ui.label(
+                    RichText::new("Color:")
+                        .size(12.0)
+                        .color(colors::text_muted(ui.visuals())),
+                );
+                egui::ComboBox::from_id_salt("unified_color_mode")
+                    .selected_text(RichText::new(unified_color_mode.to_string()).size(12.0))
+                    .width(130.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            unified_color_mode,
+                            UnifiedColorMode::AvgRank,
+                            "Average rank",
+                        );
+                        ui.selectable_value(
+                            unified_color_mode,
+                            UnifiedColorMode::AvgProbability,
+                            "Average probability",
+                        );
+                        ui.selectable_value(
+                            unified_color_mode,
+                            UnifiedColorMode::AvgMargin,
+                            "Average top-1 margin",
+                        );
+                        ui.selectable_value(
+                            unified_color_mode,
+                            UnifiedColorMode::RankDivergence,
+                            "Divergence rank",
+                        );
+                        ui.selectable_value(
+                            unified_color_mode,
+                            UnifiedColorMode::ProbDivergence,
+                            "Divergence probability",
+                        );
+                    });
+            }
+        });
+        if let (Some(test), Some(a), Some(b)) = (significance, &view_a, &view_b) {
+            render_significance(ui, test, a.name, b.name);
+        }
+        ui.add_space(4.0);
+    }
+
+    // Legend (varies by mode)
+    let unified = both && options.view_mode == ViewMode::Unified;
+    if unified {
+        match options.unified_color_mode {
+            UnifiedColorMode::AvgProbability => render_prob_legend(ui),
+            UnifiedColorMode::AvgMargin => render_margin_legend(ui),
+            UnifiedColorMode::RankDivergence | UnifiedColorMode::ProbDivergence => {
+                render_divergence_legend(ui)
+            }
+            UnifiedColorMode::AvgRank => render_legend(ui),
+        }
+    } else {
+        match options.color_mode {
+            TokenColorMode::Rank => render_legend(ui),
+            TokenColorMode::Margin => render_margin_legend(ui),
+            TokenColorMode::Expression => {
+                let error = [&view_a, &view_b]
+                    .into_iter()
+                    .flatten()
+                    .find_map(|view| view.token_colors?.as_ref().err());
+                render_expression_legend(ui, error.map(String::as_str));
+            }
+        }
+    }
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        if !unified {
+            ui.label(
+                RichText::new("Color:")
+                    .size(12.0)
+                    .color(colors::text_muted(ui.visuals())),
+            );
+            egui::ComboBox::from_id_salt("token_color_mode")
+                .selected_text(RichText::new(options.color_mode.to_string()).size(12.0))
+                .width(110.0)
+                .show_ui(ui, |ui| {
+                    for mode in TokenColorMode::ALL {
+                        ui.selectable_value(&mut options.color_mode, mode, mode.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Top-1 margin: how far the actual token's log-probability fell \
+                     below the model's top prediction. Expression: a formula set in \
+                     the Display settings",
+                );
+            ui.add_space(12.0);
+        }
+        ui.checkbox(
+            &mut options.highlight_repeats,
+            RichText::new("Underline repeated n-grams").size(12.0),
+        )
+        .on_hover_text(format!(
+            "Underline tokens that belong to a {}-token sequence seen earlier in the text",
+            crate::analysis::REPEAT_NGRAM
+        ));
+        let windowed = [&view_a, &view_b]
+            .into_iter()
+            .flatten()
+            .any(|view| view.result.is_windowed());
+        if windowed {
+            ui.add_space(12.0);
+            ui.checkbox(
+                &mut options.show_windows,
+                RichText::new("Show context windows").size(12.0),
+            )
+            .on_hover_text(
+                "Mark where each evaluation window begins (▏) and italicize tokens \
+                 predicted without the whole preceding text",
+            );
+        }
+        ui.add_space(12.0);
+        if ui
+            .checkbox(
+                &mut options.inspect_context,
+                RichText::new("Click to inspect context").size(12.0),
+            )
+            .on_hover_text(
+                "Click a token to see exactly which tokens the model conditioned on \
+                 to predict it",
+            )
+            .changed()
+        {
+            options.inspected = None;
+        }
+    });
+    ui.add_space(12.0);
+    if let Some(selection) = options.selection {
+        render_selection_bar(ui, selection, &view_a, &view_b, options);
+    }
+
+    let gesture = match (&view_a, &view_b) {
+        (Some(a), Some(b)) => {
+            if unified {
+                render_unified_result(ui, a, b, height, options)
+            } else {
+                render_dual_results(ui, a, b, height, tok_match, options)
+            }
+        }
+        (Some(view), None) | (None, Some(view)) => render_single_result(ui, view, height, options),
+        (None, None) => None,
+    };
+    if !ui.input(|i| i.pointer.primary_down()) {
+        options.selecting = false;
+    }
+    if let Some((token, gesture)) = gesture {
+        apply_token_gesture(options, token, gesture);
+    }
+
+    let mut regenerate = None;
+    if let Some(inspected) = options.inspected {
+        let views: Vec<&ResultView> = [&view_a, &view_b]
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .filter(|&(i, _)| inspected.result.is_none_or(|r| r == i))
+            .map(|(_, view)| view)
+            .collect();
+        let mut open = true;
+        if render_context_inspector(ui.ctx(), &views, inspected.index, &mut open) {
+            regenerate = Some(inspected);
+        }
+        if !open {
+            options.inspected = None;
+        }
+    }
+    if let Some(pinned) = options.pinned.filter(|_| options.tooltips.pin_on_click) {
+        let views: Vec<&ResultView> = [&view_a, &view_b]
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .filter(|&(i, _)| pinned.result.is_none_or(|r| r == i))
+            .map(|(_, view)| view)
+            .collect();
+        let mut open = true;
+        crate::ui_tokens::render_pinned_tooltip(
+            ui.ctx(),
+            &views,
+            pinned.index,
+            &options.tooltips,
+            &mut open,
+        );
+        if !open {
+            options.pinned = None;
+        }
+    }
+    regenerate
+}
+
+/// Clicks inspect a token when inspecting context and select tokens
+/// otherwise; shift-clicking or dragging extends the selection.
+fn apply_token_gesture(options: &mut DisplayOptions, token: InspectedToken, gesture: TokenGesture) {
+    if options.inspect_context {
+        if matches!(gesture, TokenGesture::Click | TokenGesture::ShiftClick) {
+            options.inspected = Some(token);
+        }
+        return;
+    }
+    if options.tooltips.pin_on_click && gesture == TokenGesture::Click {
+        options.pinned = Some(token);
+    }
+    let same_result = options.selection.filter(|s| s.result == token.result);
+    match gesture {
+        TokenGesture::Click => options.selection = Some(TokenSelection::at(token)),
+        TokenGesture::DragStart => {
+            options.selection = Some(TokenSelection::at(token));
+            options.selecting = true;
+        }
+        TokenGesture::DragOver => {
+            if let (true, Some(selection)) = (options.selecting, same_result) {
+                options.selection = Some(TokenSelection {
+                    end: token.index,
+                    ..selection
+                });
+            }
+        }
+        TokenGesture::ShiftClick => {
+            options.selection = Some(match same_result {
+                Some(selection) => TokenSelection {
+                    end: token.index,
+                    ..selection
+                },
+                None => TokenSelection::at(token),
+            });
+        }
+    }
+}
+
+/// Size of the selection with buttons to copy its metrics and clear it.
+fn render_selection_bar(
+    ui: &mut Ui,
+    selection: TokenSelection,
+    view_a: &Option<ResultView>,
+    view_b: &Option<ResultView>,
+    options: &mut DisplayOptions,
+) {
+    let views: Vec<&ResultView> = [view_a, view_b]
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter(|&(i, _)| selection.result.is_none_or(|r| r == i))
+        .map(|(_, view)| view)
+        .collect();
+    let range = selection.range();
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new(format!("{} tokens selected", range.clone().count()))
+                .size(12.0)
+                .color(colors::text_muted(ui.visuals())),
+        );
+        if ui
+            .small_button("📋 Copy as TSV")
+            .on_hover_text(
+                "Copy the rank, probability, surprisal, top-1 margin and top prediction \
+                 of each selected token, to paste into a spreadsheet",
+            )
+            .clicked()
+        {
+            let results: Vec<(&str, &AnalysisResult)> =
+                views.iter().map(|view| (view.name, view.result)).collect();
+            ui.ctx()
+                .copy_text(crate::text_report::selection_tsv(&results, range.clone()));
+        }
+        if ui.small_button("✕ Clear").clicked() {
+            options.selection = None;
+            options.selecting = false;
+        }
+    });
+    ui.add_space(6.0);
+}
+
+/// Window showing the tokens the model saw when predicting token `index`
+/// of each result: the whole preceding text, or only part of it when the
+/// text was scored in windows. Returns true to regenerate the text from
+/// the token.
+fn render_context_inspector(
+    ctx: &egui::Context,
+    views: &[&ResultView],
+    index: usize,
+    open: &mut bool,
+) -> bool {
+    let mut regenerate = false;
+    egui::Window::new("🔎 Context inspector")
+        .open(open)
+        .collapsible(false)
+        .default_width(520.0)
+        .show(ctx, |ui| {
+            for (i, view) in views.iter().enumerate() {
+                let result = view.result;
+                let Some(token) = result.tokens.get(index) else {
+                    continue;
+                };
+                if views.len() > 1 {
+                    if i > 0 {
+                        ui.separator();
+                    }
+                    ui.label(
+                        RichText::new(format!("📦 {}", view.name))
+                            .strong()
+                            .size(13.0),
+                    );
+                }
+                let context = result.context_of(index);
+                ui.label(
+                    RichText::new(format!(
+                        "Token #{} (rank {}) was predicted from {} tokens",
+                        index,
+                        token.rank,
+                        context.token_count()
+                    ))
+                    .size(12.0),
+                );
+                let hidden = context.hidden();
+                if hidden > 0 {
+                    ui.label(
+                        RichText::new(format!(
+                            "⚠ {} earlier tokens were outside the context window{}",
+                            hidden,
+                            if context.anchor.is_some() {
+                                "; the first token was kept"
+                            } else {
+                                ""
+                            }
+                        ))
+                        .size(12.0)
+                        .color(colors::WARNING),
+                    );
+                } else {
+                    ui.label(
+                        RichText::new("The whole preceding text was in context")
+                            .size(12.0)
+                            .color(colors::text_muted(ui.visuals())),
+                    );
+                }
+                ui.add_space(4.0);
+
+                let muted = colors::text_muted(ui.visuals());
+                let font = FontId::monospace(12.0);
+                let piece = |t: &crate::analysis::AnalyzedToken| match t.special {
+                    Some(ref label) => format!("⟨{}⟩", label),
+                    None => t.text.to_string(),
+                };
+                let mut job = egui::text::LayoutJob::default();
+                if let Some(anchor) = context.anchor {
+                    job.append(
+                        &format!("{} ⋯ ", piece(&result.tokens[anchor])),
+                        0.0,
+                        egui::TextFormat::simple(font.clone(), muted),
+                    );
+                }
+                let text: String = result.tokens[context.range].iter().map(piece).collect();
+                job.append(
+                    &text,
+                    0.0,
+                    egui::TextFormat::simple(font.clone(), colors::text_primary(ui.visuals())),
+                );
+                job.append(
+                    &piece(token),
+                    0.0,
+                    egui::TextFormat {
+                        background: colors::rank_to_color(token.rank),
+                        ..egui::TextFormat::simple(font, Color32::BLACK)
+                    },
+                );
+                egui::ScrollArea::vertical()
+                    .id_salt(("context_inspector", i))
+                    .max_height(240.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        ui.label(job);
+                    });
+                if !token.top_predictions.is_empty() {
+                    ui.add_space(4.0);
+                    ui.label(RichText::new("Top predictions").strong().size(11.0));
+                    crate::ui_tokens::render_top_k_chart(ui, token, &result.vocab);
+                }
+                if !token.rank_curve.is_empty() {
+                    ui.add_space(4.0);
+                    ui.label(
+                        RichText::new("Probability mass by rank")
+                            .strong()
+                            .size(11.0),
+                    );
+                    crate::ui_tokens::render_rank_curve(ui, token, ("rank_curve", i));
+                }
+            }
+            ui.add_space(6.0);
+            regenerate = ui
+                .button("✍ Regenerate from here")
+                .on_hover_text(
+                    "Have the model continue the text from this token and diff its version \
+                     against the actual text",
+                )
+                .clicked();
+        });
+    regenerate
+}
+
+fn render_dual_results(
+    ui: &mut Ui,
+    view_a: &ResultView,
+    view_b: &ResultView,
+    height: f32,
+    tokenizers_compatible: bool,
+    options: &DisplayOptions,
+) -> Option<(InspectedToken, TokenGesture)> {
+    let (result_a, result_b) = (view_a.result, view_b.result);
+    let (label_a, label_b) = (view_a.name, view_b.name);
+    let scroll_height = (height - 120.0).max(100.0);
+
+    // When tokenizers differ, don't pass the other model's tokens for
+    // index-based comparison — the indices don't correspond to the same text.
+    let other_b = if tokenizers_compatible {
+        Some(result_b)
+    } else {
+        None
+    };
+    let other_a = if tokenizers_compatible {
+        Some(result_a)
+    } else {
+        None
+    };
+
+    egui::ScrollArea::vertical()
+        .id_salt("results_dual_scroll")
+        .max_height(scroll_height)
+        .show(ui, |ui| {
+            ui.columns(2, |columns| {
+                let clicked_a = columns[0]
+                    .vertical(|ui| {
+                        render_column_header(ui, label_a, colors::INFO);
+                        render_stats_bar(ui, view_a);
+                        render_result_sections(ui, view_a, "_a");
+                        ui.add_space(8.0);
+                        crate::ui_tokens::render_analyzed_tokens(
+                            ui,
+                            view_a,
+                            other_b.map(|r| (r, label_b)),
+                            options,
+                            TokenSelection::within(options.selection, Some(0)),
+                        )
+                    })
+                    .inner
+                    .map(|(index, gesture)| {
+                        let token = InspectedToken {
+                            result: Some(0),
+                            index,
+                        };
+                        (token, gesture)
+                    });
+
+                let clicked_b = columns[1]
+                    .vertical(|ui| {
+                        render_column_header(ui, label_b, colors::WARNING);
+                        render_stats_bar(ui, view_b);
+                        render_result_sections(ui, view_b, "_b");
+                        ui.add_space(8.0);
+                        crate::ui_tokens::render_analyzed_tokens(
+                            ui,
+                            view_b,
+                            other_a.map(|r| (r, label_a)),
+                            options,
+                            TokenSelection::within(options.selection, Some(1)),
+                        )
+                    })
+                    .inner
+                    .map(|(index, gesture)| {
+                        let token = InspectedToken {
+                            result: Some(1),
+                            index,
+                        };
+                        (token, gesture)
+                    });
+                clicked_a.or(clicked_b)
+            })
+        })
+        .inner
+}
+
+fn render_single_result(
+    ui: &mut Ui,
+    view: &ResultView,
+    height: f32,
+    options: &DisplayOptions,
+) -> Option<(InspectedToken, TokenGesture)> {
+    render_column_header(ui, view.name, colors::INFO);
+    ui.add_space(8.0);
+
+    render_stats_bar(ui, view);
+    render_result_sections(ui, view, "");
+    ui.add_space(12.0);
+
+    let scroll_height = (height - 160.0).max(100.0);
+    egui::ScrollArea::vertical()
+        .id_salt("results_single_scroll")
+        .max_height(scroll_height)
+        .show(ui, |ui| {
+            crate::ui_tokens::render_analyzed_tokens(
+                ui,
+                view,
+                None,
+                options,
+                TokenSelection::within(options.selection, Some(0)),
+            )
+        })
+        .inner
+        .map(|(index, gesture)| {
+            let token = InspectedToken {
+                result: Some(0),
+                index,
+            };
+            (token, gesture)
+        })
+}
+
+fn render_column_header(ui: &mut Ui, label: &str, color: Color32) {
+    ui.label(
+        RichText::new(format!("📦 {}", label))
+            .strong()
+            .size(14.0)
+            .color(color),
+    );
+    ui.add_space(6.0);
+}
 
-    if both {
-        // Tokenizer mismatch warning
-        if !tok_match {
-            render_tokenizer_warning(ui);
+/// Repetition score above which the stats bar flags the text as looping.
+const HIGH_REPETITION: f32 = 0.2;
+/// Normalized perturbation curvature above which text is flagged as likely
+/// machine-generated.
+const HIGH_CURVATURE: f32 = 2.0;
+/// Shuffled-to-original perplexity ratio below which the text is flagged
+/// as not depending on its sentence order.
+const LOW_SHUFFLE_RATIO: f32 = 1.05;
+/// Lookalike letters listed with their position in the hover text.
+const MAX_LISTED_CONFUSABLES: usize = 10;
+
+/// Enough significant digits for metrics of any scale.
+fn format_metric(value: f64) -> String {
+    if value == value.trunc() && value.abs() < 1e9 {
+        format!("{}", value as i64)
+    } else if value.abs() >= 100.0 {
+        format!("{:.1}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+/// Counts of mixed-script lookalikes per script, and the first few with
+/// their positions.
+fn confusables_hover<'a>(
+    found: impl Iterator<Item = &'a Confusable> + Clone,
+    count: usize,
+) -> String {
+    let mut text = String::from(
+        "Letters from another script that look like the ones around them, a common \
+         trick to evade filters. The model sees rare tokens where the text looks \
+         ordinary, which inflates perplexity.\n",
+    );
+    for script in confusables::Script::ALL {
+        let n = found.clone().filter(|c| c.script == script).count();
+        if n > 0 {
+            text.push_str(&format!("\n{}: {}", script.label(), n));
         }
+    }
+    text.push('\n');
+    for c in found.clone().take(MAX_LISTED_CONFUSABLES) {
+        text.push_str(&format!("\n{}:{} {}", c.line, c.column, c.describe()));
+    }
+    if count > MAX_LISTED_CONFUSABLES {
+        text.push_str("\n…");
+    }
+    text
+}
 
-        ui.horizontal(|ui| {
-            // Segmented control: Split | Unified
+/// Which markers in the text became special tokens, and where.
+fn special_tokens_hover(uses: &[SpecialTokenUse]) -> String {
+    let mut text = String::from(
+        "The text contains markers the model reads as control tokens rather than as \
+         text, e.g. chat template markers pasted along with it:\n",
+    );
+    for special in uses {
+        let positions: Vec<String> = special
+            .positions
+            .iter()
+            .map(|p| format!("line {}:{} (token {})", p.line, p.column, p.index))
+            .collect();
+        text.push_str(&format!(
+            "\n{} ({}) ×{}: {}{}",
+            special.text,
+            special.label,
+            special.count,
+            positions.join(", "),
+            if special.count > special.positions.len() {
+                ", …"
+            } else {
+                ""
+            }
+        ));
+    }
+    text
+}
+
+fn render_stats_bar(ui: &mut Ui, view: &ResultView) {
+    let (result, reference) = (view.result, view.reference);
+    let shown = |metric| view.summary.contains(&metric);
+    let format = &view.format;
+    ui.horizontal_wrapped(|ui| {
+        if shown(SummaryMetric::Time) {
             ui.label(
-                RichText::new("View:")
-                    .size(12.0)
-                    .color(colors::text_muted(ui.visuals())),
+                RichText::new(format!(
+                    "⏱ {:.1}s",
+                    result.processing_time_ms as f32 / 1000.0
+                ))
+                .color(colors::text_muted(ui.visuals()))
+                .size(12.0),
             );
-            ui.add_space(4.0);
+            ui.add_space(10.0);
+        }
 
-            let split_selected = *view_mode == ViewMode::Split;
+        if shown(SummaryMetric::Perplexity) {
+            ui.label(
+                RichText::new(format!("PPL: {}", format.number(result.perplexity())))
+                    .color(colors::WARNING)
+                    .size(12.0),
+            )
+            .on_hover_text("Perplexity (lower = more predictable)");
+        }
 
-            if ui
-                .selectable_label(split_selected, RichText::new("🔀 Split").size(12.0))
-                .clicked()
-            {
-                *view_mode = ViewMode::Split;
+        if !result.unscored.is_empty() {
+            ui.add_space(10.0);
+            let scored = result.tokens.len() - result.first_scored();
+            ui.label(
+                RichText::new(format!(
+                    "⚠ {} scored, {} not",
+                    scored,
+                    result.unscored.len()
+                ))
+                .color(colors::WARNING)
+                .size(12.0),
+            )
+            .on_hover_text(
+                "The text is longer than the scoring limit set in the Inference settings; \
+                 the struck-through tokens at the end weren't analyzed",
+            );
+        }
+
+        if !view.special_tokens.is_empty() {
+            ui.add_space(10.0);
+            let count: usize = view.special_tokens.iter().map(|u| u.count).sum();
+            ui.label(
+                RichText::new(format!(
+                    "⚠ {} special token{} in the text",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                ))
+                .color(colors::WARNING)
+                .size(12.0),
+            )
+            .on_hover_text(special_tokens_hover(view.special_tokens));
+        }
+
+        if !view.confusables.is_empty() {
+            ui.add_space(10.0);
+            let count = view.confusables.len();
+            ui.label(
+                RichText::new(format!(
+                    "⚠ {} lookalike letter{}",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                ))
+                .color(colors::ERROR)
+                .size(12.0),
+            )
+            .on_hover_text(confusables_hover(
+                view.confusables.iter().map(|(_, c)| c),
+                count,
+            ));
+        }
+
+        if result.has_prompt() && shown(SummaryMetric::LogLikelihood) {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new(format!(
+                    "LL: {}",
+                    format.information_nats(result.log_likelihood())
+                ))
+                .color(colors::INFO)
+                .size(12.0),
+            )
+            .on_hover_text("Total log-likelihood of the completion given the prompt");
+        }
+
+        if let Some(reference) = reference.filter(|_| shown(SummaryMetric::Reference)) {
+            let ppl = result.perplexity();
+            if let Some(percentile) = reference.percentile(ppl) {
+                let domain = reference.domain.label().to_lowercase();
+                let text = match reference.deviation(ppl) {
+                    Some(deviation) => {
+                        format!("{:+.1}σ vs {} (p{:.0})", deviation, domain, percentile)
+                    }
+                    None => format!("p{:.0} vs {}", percentile, domain),
+                };
+                ui.add_space(10.0);
+                ui.label(
+                    RichText::new(text)
+                        .color(colors::text_muted(ui.visuals()))
+                        .size(12.0),
+                )
+                .on_hover_text(format!(
+                    "Deviation from the {} {} reference texts, in standard deviations of their \
+                     log perplexity; negative is more predictable than usual for the domain. \
+                     {:.0}% of them scored a perplexity at or below this one (median {})",
+                    reference.perplexities.len(),
+                    domain,
+                    percentile,
+                    format.number(reference.median().unwrap_or_default())
+                ));
             }
+        }
 
-            // Only allow unified view when tokenizers match
-            if tok_match {
-                let unified_selected = *view_mode == ViewMode::Unified;
-                if ui
-                    .selectable_label(unified_selected, RichText::new("⊞ Unified").size(12.0))
-                    .clicked()
-                {
-                    *view_mode = ViewMode::Unified;
+        if shown(SummaryMetric::BitsPerChar) {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new(format!(
+                    "{}/char",
+                    format.information(Metric::BitsPerChar.of(result))
+                ))
+                .color(colors::text_primary(ui.visuals()))
+                .size(12.0),
+            )
+            .on_hover_text(
+                "Information per character of the text; unlike perplexity, comparable between \
+                 models with different tokenizers",
+            );
+        }
+
+        if shown(SummaryMetric::Top1Accuracy) {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new(format!(
+                    "Top-1: {}",
+                    format.share(Metric::Top1Accuracy.of(result))
+                ))
+                .color(colors::text_primary(ui.visuals()))
+                .size(12.0),
+            )
+            .on_hover_text("Share of tokens that were the model's first prediction");
+        }
+
+        if shown(SummaryMetric::MedianRank) {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new(format!("Median rank: {:.0}", Metric::MedianRank.of(result)))
+                    .color(colors::text_primary(ui.visuals()))
+                    .size(12.0),
+            )
+            .on_hover_text("Rank of the typical token among the model's predictions");
+        }
+
+        if shown(SummaryMetric::Burstiness) {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new(format!(
+                    "Burstiness: {}",
+                    format.information(result.burstiness())
+                ))
+                .color(colors::text_primary(ui.visuals()))
+                .size(12.0),
+            )
+            .on_hover_text(
+                "Standard deviation of the surprisal from token to token. Human writing mixes \
+                 predictable stretches with surprising turns and is usually burstier than \
+                 generated text.",
+            );
+        }
+
+        if shown(SummaryMetric::Entropy) {
+            ui.add_space(10.0);
+            ui.label(
+                RichText::new(format!(
+                    "Entropy: {}",
+                    format.information(result.text_entropy())
+                ))
+                .color(colors::ACCENT_PRIMARY)
+                .size(12.0),
+            )
+            .on_hover_text("Information needed to reconstruct the text using this model");
+        }
+
+        for metric in view.custom_metrics {
+            ui.add_space(10.0);
+            match &metric.value {
+                Ok(value) => ui
+                    .label(
+                        RichText::new(format!("{}: {}", metric.name, format_metric(*value)))
+                            .color(colors::text_primary(ui.visuals()))
+                            .size(12.0),
+                    )
+                    .on_hover_text("Custom metric, defined in the Analysis settings"),
+                Err(e) => ui
+                    .label(
+                        RichText::new(format!("⚠ {}", metric.name))
+                            .color(colors::ERROR)
+                            .size(12.0),
+                    )
+                    .on_hover_text(format!("The metric's script failed: {}", e)),
+            };
+        }
+
+        match result.languages.as_slice() {
+            _ if !shown(SummaryMetric::Language) => {}
+            [] => {}
+            [only] => {
+                ui.add_space(10.0);
+                ui.label(
+                    RichText::new(format!("🌐 {}", only.language))
+                        .color(colors::text_muted(ui.visuals()))
+                        .size(12.0),
+                )
+                .on_hover_text("Detected language");
+            }
+            languages => {
+                ui.add_space(10.0);
+                ui.label(
+                    RichText::new(format!("🌐 {} languages", languages.len()))
+                        .color(colors::WARNING)
+                        .size(12.0),
+                )
+                .on_hover_ui(|ui| render_language_table(ui, languages));
+            }
+        }
+
+        if let Some(positional) = result
+            .positional()
+            .filter(|_| shown(SummaryMetric::Position))
+        {
+            ui.add_space(10.0);
+            render_decile_sparkline(ui, &positional.deciles).on_hover_ui(|ui| {
+                ui.label(
+                    RichText::new("Perplexity by position (tenths)")
+                        .strong()
+                        .size(11.0),
+                );
+                for (i, ppl) in positional.deciles.iter().enumerate() {
+                    ui.label(
+                        RichText::new(format!("{:>3}–{:>3}%  {:.2}", i * 10, (i + 1) * 10, ppl))
+                            .monospace()
+                            .size(11.0),
+                    );
                 }
+            });
+            if let Some(drift) = positional.drift {
+                let direction = if drift.perplexity < drift.rest_perplexity {
+                    "more predictable"
+                } else {
+                    "less predictable"
+                };
+                ui.label(
+                    RichText::new(format!("⚠ {} third {}", drift.third_name(), direction))
+                        .color(colors::ERROR)
+                        .size(12.0),
+                )
+                .on_hover_text(format!(
+                    "PPL {:.2} in the {} third vs {:.2} in the rest of the text",
+                    drift.perplexity,
+                    drift.third_name().to_lowercase(),
+                    drift.rest_perplexity
+                ));
+            }
+        }
+
+        if let Some((perturbation, curvature)) =
+            view.perturbation.and_then(|p| Some((p, p.curvature()?)))
+        {
+            ui.add_space(10.0);
+            let color = if curvature >= HIGH_CURVATURE {
+                colors::ERROR
             } else {
-                ui.add_enabled_ui(false, |ui| {
-                    ui.selectable_label(false, RichText::new("⊞ Unified").size(12.0))
-                        .on_disabled_hover_text("Unified view requires matching tokenizers");
-                });
+                colors::text_muted(ui.visuals())
+            };
+            ui.label(
+                RichText::new(format!("🧪 Curvature: {:.2}σ", curvature))
+                    .color(color)
+                    .size(12.0),
+            )
+            .on_hover_text(format!(
+                "Log-likelihood per token: {:.3} original vs {:.3} mean of {} perturbed variants.\n\
+                 Machine-generated text tends to score well above its rewrites (high curvature); \
+                 human text scores about the same.",
+                perturbation.original,
+                perturbation.mean_perturbed(),
+                perturbation.perturbed.len()
+            ));
+        }
+
+        if let Some(shuffle) = view.shuffle {
+            ui.add_space(10.0);
+            let ratio = shuffle.ratio();
+            let color = if ratio < LOW_SHUFFLE_RATIO {
+                colors::WARNING
+            } else {
+                colors::text_muted(ui.visuals())
+            };
+            ui.label(
+                RichText::new(format!(
+                    "🔀 Shuffled: {:.2} vs {:.2} ({:.2}×)",
+                    shuffle.shuffled, shuffle.original, ratio
+                ))
+                .color(color)
+                .size(12.0),
+            )
+            .on_hover_text(format!(
+                "Perplexity with the {} sentences in random order vs in their original order.\n\
+                 Coherent text relies on its order and gets clearly more perplexing when \
+                 shuffled; a ratio near 1× means the model gains little from it, as with lists \
+                 or incoherent text.",
+                shuffle.sentences
+            ));
+        }
+
+        if let Some(verdict) = view.verdict {
+            ui.add_space(10.0);
+            let (tag, value) = verdict.verdict(result);
+            let color = if tag == verdict.positive {
+                colors::ERROR
+            } else {
+                colors::SUCCESS
+            };
+            egui::Frame::none()
+                .fill(color.gamma_multiply(0.15))
+                .rounding(4.0)
+                .inner_margin(egui::Margin::symmetric(6.0, 1.0))
+                .show(ui, |ui| {
+                    ui.label(
+                        RichText::new(format!("⚖ Likely {}", tag))
+                            .color(color)
+                            .strong()
+                            .size(12.0),
+                    );
+                })
+                .response
+                .on_hover_text(format!(
+                    "{} {:.3} vs a threshold of {:.3}, tuned on tagged analyses. At this \
+                     threshold {:.0}% of the {} texts and {:.0}% of the {} texts were labeled \
+                     {}.",
+                    verdict.metric.label(),
+                    value,
+                    verdict.threshold,
+                    verdict.true_positive_rate * 100.0,
+                    verdict.positive,
+                    verdict.false_positive_rate * 100.0,
+                    verdict.negative,
+                    verdict.positive
+                ));
+        }
+
+        if let Some(wm) = view.watermark {
+            ui.add_space(10.0);
+            let detected = wm.z_score >= watermark::Z_THRESHOLD;
+            let (text, color) = if detected {
+                (format!("💧 Watermark: z={:.1}", wm.z_score), colors::ERROR)
+            } else {
+                (
+                    format!("💧 No watermark (z={:.1})", wm.z_score),
+                    colors::text_muted(ui.visuals()),
+                )
+            };
+            ui.label(RichText::new(text).color(color).size(12.0))
+                .on_hover_text(format!(
+                    "{} of {} tokens ({:.0}%) are on the green list; {:.0}% expected without a \
+                     watermark. z ≥ {:.0} indicates a watermark.",
+                    wm.green,
+                    wm.scored,
+                    wm.green_fraction() * 100.0,
+                    wm.gamma * 100.0,
+                    watermark::Z_THRESHOLD
+                ));
+        }
+
+        let repetition = result.repetition();
+        if repetition.score > 0.0 && shown(SummaryMetric::Repetition) {
+            ui.add_space(10.0);
+            let color = if repetition.score >= HIGH_REPETITION {
+                colors::ERROR
+            } else {
+                colors::text_muted(ui.visuals())
+            };
+            ui.label(
+                RichText::new(format!("↻ Repetition: {}", format.share(repetition.score)))
+                    .color(color)
+                    .size(12.0),
+            )
+            .on_hover_text(format!(
+                "Share of tokens inside a repeated {}-token sequence; \
+                 longest repeated run: {} tokens.\n\
+                 Repetition lowers perplexity without the text being natural.",
+                crate::analysis::REPEAT_NGRAM,
+                repetition.longest_run
+            ));
+        }
+    });
+    if let Some(stats) = view.text_stats.filter(|_| shown(SummaryMetric::TextStats)) {
+        render_text_stats(ui, stats);
+    }
+}
+
+/// Model-free statistics of the text, in a muted row under the metrics.
+fn render_text_stats(ui: &mut Ui, stats: &TextStats) {
+    ui.horizontal_wrapped(|ui| {
+        let muted = colors::text_muted(ui.visuals());
+        let items = [
+            (
+                format!("📄 {} characters", stats.characters),
+                "Characters of the analyzed text, prompt excluded",
+            ),
+            (format!("{} words", stats.words), "Runs of non-whitespace"),
+            (
+                format!("{} sentences", stats.sentences),
+                "Split at terminal punctuation",
+            ),
+            (
+                format!("{:.1} words/sentence", stats.mean_sentence_length()),
+                "Average sentence length",
+            ),
+            (
+                format!("TTR {:.2}", stats.type_token_ratio()),
+                "Type-token ratio: distinct words over words. Lower means a more repetitive \
+                 vocabulary; it falls with length, so compare texts of similar size.",
+            ),
+            (
+                format!("{} unique tokens", stats.unique_tokens),
+                "Distinct tokens the model read the text as",
+            ),
+        ];
+        for (i, (text, hover)) in items.into_iter().enumerate() {
+            if i > 0 {
+                ui.label(RichText::new("·").color(muted).size(11.0));
             }
+            ui.label(RichText::new(text).color(muted).size(11.0))
+                .on_hover_text(hover);
+        }
+    });
+}
 
-            if *view_mode == ViewMode::Unified {
-                ui.add_space(16.0);
+/// Collapsible detail sections under the stats bar. `id_suffix` keeps the
+/// widget ids of side-by-side results apart.
+fn render_result_sections(ui: &mut Ui, view: &ResultView, id_suffix: &str) {
+    render_speaker_table(ui, view.speakers, &format!("speakers{}", id_suffix));
+    render_class_table(ui, view.classes, &format!("classes{}", id_suffix));
+    render_token_type_table(ui, view.token_types, &format!("token_types{}", id_suffix));
+    render_context_sweep(
+        ui,
+        view.context_sweep,
+        &format!("context_sweep{}", id_suffix),
+    );
+    render_chunk_sweep(ui, view.chunk_sweep, &format!("chunk_sweep{}", id_suffix));
+    render_isolation_table(ui, view.isolation, &format!("isolation{}", id_suffix));
+    render_conditioning(ui, view.conditioning, &format!("conditioning{}", id_suffix));
+    render_regeneration(ui, view.regeneration, &format!("regeneration{}", id_suffix));
+    render_regeneration_diff(
+        ui,
+        view.regeneration_diff,
+        &format!("regeneration_diff{}", id_suffix),
+    );
+    render_quant_variance(
+        ui,
+        view.quant_variance,
+        view.result,
+        &format!("quant_variance{}", id_suffix),
+    );
+    render_determinism(ui, view.determinism, &format!("determinism{}", id_suffix));
+    render_bos_sensitivity(ui, view.bos_sensitivity, &format!("bos{}", id_suffix));
+    render_typography(ui, view.typography, &format!("typography{}", id_suffix));
+    render_description(
+        ui,
+        view.result,
+        &view.format,
+        &format!("description{}", id_suffix),
+    );
+}
+
+/// Collapsible report of spacing and punctuation habits, with the notable
+/// ones listed first.
+fn render_typography(ui: &mut Ui, stats: Option<&TypographyStats>, id: &str) {
+    let Some(stats) = stats else {
+        return;
+    };
+    let anomalies = stats.anomalies();
+    ui.add_space(4.0);
+    let title = match anomalies.len() {
+        0 => "✒ Typography: nothing unusual".to_string(),
+        1 => "✒ Typography: 1 anomaly".to_string(),
+        n => format!("✒ Typography: {} anomalies", n),
+    };
+    let color = if anomalies.is_empty() {
+        colors::text_muted(ui.visuals())
+    } else {
+        colors::WARNING
+    };
+    egui::CollapsingHeader::new(RichText::new(title).color(color).size(12.0))
+        .id_salt(id)
+        .show(ui, |ui| {
+            for anomaly in &anomalies {
                 ui.label(
-                    RichText::new("Color:")
-                        .size(12.0)
-                        .color(colors::text_muted(ui.visuals())),
+                    RichText::new(format!("⚠ {}", anomaly))
+                        .size(11.0)
+                        .color(colors::WARNING),
                 );
-                egui::ComboBox::from_id_salt("unified_color_mode")
-                    .selected_text(RichText::new(unified_color_mode.to_string()).size(12.0))
-                    .width(130.0)
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            unified_color_mode,
-                            UnifiedColorMode::AvgRank,
-                            "Average rank",
-                        );
-                        ui.selectable_value(
-                            unified_color_mode,
-                            UnifiedColorMode::AvgProbability,
-                            "Average probability",
-                        );
-                        ui.selectable_value(
-                            unified_color_mode,
-                            UnifiedColorMode::RankDivergence,
-                            "Divergence rank",
-                        );
-                        ui.selectable_value(
-                            unified_color_mode,
-                            UnifiedColorMode::ProbDivergence,
-                            "Divergence probability",
-                        );
-                    });
             }
+            ui.add_space(4.0);
+            egui::Grid::new(id)
+                .num_columns(2)
+                .striped(true)
+                .spacing([12.0, 2.0])
+                .show(ui, |ui| {
+                    let rows = [
+                        ("Double spaces", stats.double_spaces.to_string()),
+                        ("Non-breaking spaces", stats.non_breaking_spaces.to_string()),
+                        ("Tabs", stats.tabs.to_string()),
+                        (
+                            "Lines ending in whitespace",
+                            stats.trailing_whitespace.to_string(),
+                        ),
+                        (
+                            "Spaces before punctuation",
+                            stats.space_before_punctuation.to_string(),
+                        ),
+                        (
+                            "Double quotes, straight / curly",
+                            format!(
+                                "{} / {}",
+                                stats.straight_double_quotes, stats.curly_double_quotes
+                            ),
+                        ),
+                        (
+                            "Apostrophes, straight / curly",
+                            format!(
+                                "{} / {}",
+                                stats.straight_single_quotes, stats.curly_single_quotes
+                            ),
+                        ),
+                        (
+                            "Em dashes",
+                            format!(
+                                "{} ({:.1} per 1,000 words)",
+                                stats.em_dashes,
+                                stats.em_dash_rate()
+                            ),
+                        ),
+                        ("En dashes", stats.en_dashes.to_string()),
+                        ("Spaced hyphens", stats.spaced_hyphens.to_string()),
+                        (
+                            "Ellipses, … / ...",
+                            format!("{} / {}", stats.ellipsis_chars, stats.triple_dots),
+                        ),
+                    ];
+                    for (label, value) in rows {
+                        ui.label(RichText::new(label).size(11.0));
+                        ui.label(RichText::new(value).size(11.0));
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// Collapsible plain-text description of the result, readable by screen
+/// readers where the color heatmap is not.
+fn render_description(ui: &mut Ui, result: &AnalysisResult, format: &NumberFormat, id: &str) {
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(RichText::new("♿ Describe results").size(12.0))
+        .id_salt(id)
+        .show(ui, |ui| {
+            let description = crate::report::describe(result, format);
+            if ui.small_button("📋 Copy").clicked() {
+                ui.ctx().copy_text(description.clone());
+            }
+            ui.add(egui::Label::new(RichText::new(description).size(12.0)).selectable(true));
         });
+}
+
+/// Collapsible table of the text's perplexity under each prompt preset,
+/// most predictable first.
+fn render_conditioning(ui: &mut Ui, stats: Option<&ConditioningStats>, id: &str) {
+    let Some(stats) = stats else {
+        return;
+    };
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(
+        RichText::new(format!(
+            "🎭 Prompt comparison ({} prompts)",
+            stats.scores.len()
+        ))
+        .size(12.0),
+    )
+    .id_salt(id)
+    .default_open(true)
+    .show(ui, |ui| {
+        if let Some(best) = stats.best() {
+            ui.label(
+                RichText::new(format!(
+                    "Most in-distribution under \"{}\": perplexity {:.2} vs {:.2} without a prompt",
+                    best.name, best.perplexity, stats.unconditioned
+                ))
+                .size(11.0)
+                .color(colors::text_muted(ui.visuals())),
+            );
+        }
+        egui::Grid::new(id)
+            .num_columns(3)
+            .striped(true)
+            .spacing([12.0, 2.0])
+            .show(ui, |ui| {
+                for header in ["Prompt", "Perplexity", "vs. no prompt"] {
+                    ui.label(RichText::new(header).strong().size(11.0));
+                }
+                ui.end_row();
+                ui.label(RichText::new("(none)").italics().size(11.0));
+                ui.label(RichText::new(format!("{:.2}", stats.unconditioned)).size(11.0));
+                ui.label(RichText::new("1.00×").size(11.0));
+                ui.end_row();
+                for score in &stats.scores {
+                    let ratio = score.ratio(stats.unconditioned);
+                    let color = if ratio < 1.0 {
+                        colors::SUCCESS
+                    } else {
+                        colors::WARNING
+                    };
+                    ui.label(RichText::new(&score.name).size(11.0));
+                    ui.label(RichText::new(format!("{:.2}", score.perplexity)).size(11.0));
+                    ui.label(
+                        RichText::new(format!("{:.2}×", ratio))
+                            .color(color)
+                            .size(11.0),
+                    );
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+/// Collapsible summary of a determinism check with the tokens that
+/// diverged most between the two runs.
+/// Collapsible comparison of the text with the model's greedy
+/// regeneration of it.
+fn render_regeneration(ui: &mut Ui, stats: Option<&RegenerationStats>, id: &str) {
+    let Some(stats) = stats else {
+        return;
+    };
+    ui.add_space(4.0);
+    let color = if stats.is_similar() {
+        colors::WARNING
+    } else {
+        colors::text_muted(ui.visuals())
+    };
+    egui::CollapsingHeader::new(
+        RichText::new(format!(
+            "🔁 Regeneration similarity: {:.2}",
+            stats.similarity
+        ))
+        .color(color)
+        .size(12.0),
+    )
+    .id_salt(id)
+    .show(ui, |ui| {
+        ui.label(
+            RichText::new(format!(
+                "Cosine similarity of the model's hidden states over the original and over its \
+                 own greedy continuation of the first {} tokens. {}",
+                stats.opening_tokens,
+                if stats.is_similar() {
+                    "The model writes something close to this text by itself; with a low \
+                     perplexity, a strong sign of machine authorship."
+                } else {
+                    "The model's own continuation goes elsewhere."
+                }
+            ))
+            .size(11.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
         ui.add_space(4.0);
-    }
+        egui::ScrollArea::vertical()
+            .id_salt(id)
+            .max_height(160.0)
+            .show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.spacing_mut().item_spacing.x = 0.0;
+                    ui.label(
+                        RichText::new(&stats.opening)
+                            .monospace()
+                            .size(12.0)
+                            .color(colors::text_muted(ui.visuals())),
+                    )
+                    .on_hover_text("Opening from the original text");
+                    ui.label(RichText::new(&stats.regenerated).monospace().size(12.0))
+                        .on_hover_text("The model's continuation");
+                });
+            });
+    });
+}
 
-    // Legend (varies by mode)
-    if both && *view_mode == ViewMode::Unified {
-        match *unified_color_mode {
-            UnifiedColorMode::AvgProbability => render_prob_legend(ui),
-            UnifiedColorMode::RankDivergence | UnifiedColorMode::ProbDivergence => {
-                render_divergence_legend(ui)
-            }
-            UnifiedColorMode::AvgRank => render_legend(ui),
-        }
-    } else {
-        render_legend(ui);
-    }
-    ui.add_space(12.0);
+/// Characters of the shared text shown before a regeneration diff.
+const DIFF_PREFIX_CHARS: usize = 200;
 
-    if both {
-        if *view_mode == ViewMode::Unified {
-            render_unified_result(
-                ui,
-                result_a.unwrap(),
-                result_b.unwrap(),
-                model_name_a,
-                model_name_b,
-                height,
-                *unified_color_mode,
+/// Collapsible word diff of the actual text against the model's greedy
+/// continuation: shared words highlighted, the text's own words struck
+/// through and the model's underlined.
+fn render_regeneration_diff(ui: &mut Ui, diff: Option<&RegenerationDiff>, id: &str) {
+    let Some(diff) = diff else {
+        return;
+    };
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(
+        RichText::new(format!(
+            "✍ Regeneration diff: {:.0}% of words matched",
+            diff.matched_fraction() * 100.0
+        ))
+        .size(12.0),
+    )
+    .id_salt(id)
+    .default_open(true)
+    .show(ui, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(
+                RichText::new(format!(
+                    "From token #{}: {} of {} words of the text also appear in the model's \
+                     continuation.",
+                    diff.start_token, diff.matched_words, diff.original_words
+                ))
+                .size(11.0)
+                .color(colors::text_muted(ui.visuals())),
             );
-        } else {
-            render_dual_results(
-                ui,
-                result_a.unwrap(),
-                result_b.unwrap(),
-                model_name_a,
-                model_name_b,
-                height,
-                tok_match,
+            ui.label(
+                RichText::new("shared")
+                    .size(11.0)
+                    .background_color(colors::SUCCESS),
             );
-        }
-    } else {
-        let (result, name) = if let Some(r) = result_a {
-            (r, model_name_a.unwrap_or("Model A"))
-        } else {
-            (result_b.unwrap(), model_name_b.unwrap_or("Model B"))
+            ui.label(
+                RichText::new("text only")
+                    .size(11.0)
+                    .strikethrough()
+                    .color(colors::ERROR),
+            );
+            ui.label(
+                RichText::new("model only")
+                    .size(11.0)
+                    .underline()
+                    .color(colors::INFO),
+            );
+        });
+        ui.add_space(4.0);
+
+        let font = FontId::monospace(12.0);
+        let mut job = egui::text::LayoutJob::default();
+        // The end of the shared prefix, for orientation.
+        let prefix: String = {
+            let chars: Vec<char> = diff.prefix.chars().collect();
+            let tail = &chars[chars.len().saturating_sub(DIFF_PREFIX_CHARS)..];
+            let ellipsis = if tail.len() < chars.len() { "…" } else { "" };
+            format!("{}{}", ellipsis, tail.iter().collect::<String>())
         };
-        render_single_result(ui, result, name, height);
-    }
+        job.append(
+            &prefix,
+            0.0,
+            egui::TextFormat::simple(font.clone(), colors::text_muted(ui.visuals())),
+        );
+        for span in &diff.spans {
+            let plain = egui::TextFormat::simple(font.clone(), colors::text_primary(ui.visuals()));
+            let format = match span.kind {
+                DiffKind::Same => egui::TextFormat {
+                    background: colors::SUCCESS.gamma_multiply(0.4),
+                    ..plain
+                },
+                DiffKind::Original => egui::TextFormat {
+                    color: colors::ERROR,
+                    strikethrough: egui::Stroke::new(1.0, colors::ERROR),
+                    ..plain
+                },
+                DiffKind::Generated => egui::TextFormat {
+                    color: colors::INFO,
+                    underline: egui::Stroke::new(1.0, colors::INFO),
+                    ..plain
+                },
+            };
+            job.append(&span.text, 0.0, format);
+        }
+        egui::ScrollArea::vertical()
+            .id_salt(id)
+            .max_height(220.0)
+            .show(ui, |ui| {
+                ui.label(job);
+            });
+    });
 }
 
-fn render_dual_results(
+/// Tokens shown in the cross-quant variance map; longer texts are cut
+/// short to keep the layout cheap.
+const MAX_VARIANCE_MAP_TOKENS: usize = 4000;
+
+/// Collapsible cross-quant report: perplexity per quant, mean variance per
+/// token group, the text shaded by variance and the most damaged tokens.
+fn render_quant_variance(
     ui: &mut Ui,
-    result_a: &AnalysisResult,
-    result_b: &AnalysisResult,
-    model_name_a: Option<&str>,
-    model_name_b: Option<&str>,
-    height: f32,
-    tokenizers_compatible: bool,
+    variance: Option<&QuantVariance>,
+    result: &AnalysisResult,
+    id: &str,
 ) {
-    let label_a = model_name_a.unwrap_or("Model A");
-    let label_b = model_name_b.unwrap_or("Model B");
-    let scroll_height = (height - 120.0).max(100.0);
+    let Some(variance) = variance else {
+        return;
+    };
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(
+        RichText::new(format!(
+            "📉 Cross-quant variance ({} quants)",
+            variance.quants.len()
+        ))
+        .size(12.0),
+    )
+    .id_salt(id)
+    .default_open(true)
+    .show(ui, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            for quant in &variance.quants {
+                ui.label(
+                    RichText::new(format!("{}: PPL {:.2}", quant.name, quant.perplexity))
+                        .size(11.0),
+                );
+                ui.add_space(8.0);
+            }
+        });
+        ui.add_space(4.0);
+        egui::Grid::new(format!("{}_groups", id))
+            .num_columns(3)
+            .striped(true)
+            .spacing([12.0, 2.0])
+            .show(ui, |ui| {
+                for header in ["Tokens", "Count", "Mean variance"] {
+                    ui.label(RichText::new(header).strong().size(11.0));
+                }
+                ui.end_row();
+                for group in &variance.groups {
+                    ui.label(RichText::new(&group.name).size(11.0));
+                    ui.label(RichText::new(group.tokens.to_string()).size(11.0));
+                    ui.label(
+                        RichText::new(format!("{:.4}", group.mean_variance))
+                            .color(colors::WARNING)
+                            .size(11.0),
+                    );
+                    ui.end_row();
+                }
+            });
 
-    // When tokenizers differ, don't pass the other model's tokens for
-    // index-based comparison — the indices don't correspond to the same text.
-    let other_b = if tokenizers_compatible {
-        Some(result_b.tokens.as_slice())
-    } else {
-        None
+        ui.add_space(6.0);
+        ui.label(
+            RichText::new(
+                "Variance of each token's log-probability across the quants; the darker the \
+                 shade, the more quantization changed the prediction.",
+            )
+            .size(11.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+        let font = FontId::monospace(12.0);
+        let mut job = egui::text::LayoutJob::default();
+        let max = variance.max_variance.max(f32::MIN_POSITIVE);
+        for (token, &v) in result
+            .tokens
+            .iter()
+            .zip(&variance.variances)
+            .take(MAX_VARIANCE_MAP_TOKENS)
+        {
+            // Square root, so moderate damage is still visible next to the
+            // single worst token.
+            let shade = (v / max).sqrt();
+            job.append(
+                token.document_text(),
+                0.0,
+                egui::TextFormat {
+                    background: colors::ERROR.gamma_multiply(shade),
+                    ..egui::TextFormat::simple(font.clone(), colors::text_primary(ui.visuals()))
+                },
+            );
+        }
+        if result.tokens.len() > MAX_VARIANCE_MAP_TOKENS {
+            job.append(
+                "…",
+                0.0,
+                egui::TextFormat::simple(font.clone(), colors::text_muted(ui.visuals())),
+            );
+        }
+        egui::ScrollArea::vertical()
+            .id_salt(id)
+            .max_height(220.0)
+            .show(ui, |ui| {
+                ui.label(job);
+            });
+
+        ui.add_space(6.0);
+        egui::Grid::new(format!("{}_worst", id))
+            .num_columns(3 + variance.quants.len())
+            .striped(true)
+            .spacing([12.0, 2.0])
+            .show(ui, |ui| {
+                for header in ["#", "Token", "Variance"] {
+                    ui.label(RichText::new(header).strong().size(11.0));
+                }
+                for quant in &variance.quants {
+                    ui.label(RichText::new(&quant.name).strong().size(11.0));
+                }
+                ui.end_row();
+                for token in &variance.worst {
+                    ui.label(RichText::new(token.index.to_string()).size(11.0));
+                    ui.label(
+                        RichText::new(format!("{:?}", token.text))
+                            .monospace()
+                            .size(11.0),
+                    );
+                    ui.label(
+                        RichText::new(format!("{:.4}", token.variance))
+                            .color(colors::WARNING)
+                            .size(11.0),
+                    );
+                    for p in &token.probabilities {
+                        ui.label(RichText::new(format!("{:.1}%", p * 100.0)).size(11.0));
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+/// Collapsible table of the metrics under each BOS and leading-space
+/// setting, relative to the analysis shown.
+fn render_bos_sensitivity(ui: &mut Ui, stats: Option<&BosSensitivity>, id: &str) {
+    let Some(stats) = stats else {
+        return;
     };
-    let other_a = if tokenizers_compatible {
-        Some(result_a.tokens.as_slice())
+    ui.add_space(4.0);
+    let spread = stats.spread();
+    let color = if spread > 1.05 {
+        colors::WARNING
     } else {
-        None
+        colors::SUCCESS
     };
+    egui::CollapsingHeader::new(
+        RichText::new(format!(
+            "🔤 BOS and leading space: perplexity varies {:.2}×",
+            spread
+        ))
+        .color(color)
+        .size(12.0),
+    )
+    .id_salt(id)
+    .default_open(true)
+    .show(ui, |ui| {
+        ui.label(
+            RichText::new(format!(
+                "Relative to the analysis shown (perplexity {:.2}), which adds BOS and keeps \
+                 the text as typed",
+                stats.baseline
+            ))
+            .size(11.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+        egui::Grid::new(id)
+            .num_columns(6)
+            .striped(true)
+            .spacing([12.0, 2.0])
+            .show(ui, |ui| {
+                for header in [
+                    "Setting",
+                    "First token",
+                    "Tokens",
+                    "Perplexity",
+                    "Top-1",
+                    "Median rank",
+                ] {
+                    ui.label(RichText::new(header).strong().size(11.0));
+                }
+                ui.end_row();
+                for score in &stats.scores {
+                    let ratio = score.ratio(stats.baseline);
+                    let color = if (ratio - 1.0).abs() > 0.05 {
+                        colors::WARNING
+                    } else {
+                        ui.visuals().text_color()
+                    };
+                    ui.label(RichText::new(score.setting.label()).size(11.0));
+                    ui.label(
+                        RichText::new(format!("{:?}", score.first_token))
+                            .monospace()
+                            .size(11.0),
+                    );
+                    ui.label(RichText::new(score.tokens.to_string()).size(11.0));
+                    ui.label(
+                        RichText::new(format!("{:.2} ({:.2}×)", score.perplexity, ratio))
+                            .color(color)
+                            .size(11.0),
+                    );
+                    ui.label(
+                        RichText::new(format!("{:.1}%", score.top1_accuracy * 100.0)).size(11.0),
+                    );
+                    ui.label(RichText::new(score.median_rank.to_string()).size(11.0));
+                    ui.end_row();
+                }
+            });
+    });
+}
 
-    egui::ScrollArea::vertical()
-        .id_salt("results_dual_scroll")
-        .max_height(scroll_height)
+fn render_determinism(ui: &mut Ui, stats: Option<&DeterminismStats>, id: &str) {
+    let Some(stats) = stats else {
+        return;
+    };
+    ui.add_space(4.0);
+    let (title, color) = if stats.is_deterministic() {
+        (
+            "🎯 Determinism: identical runs".to_string(),
+            colors::SUCCESS,
+        )
+    } else {
+        (
+            format!(
+                "🎯 Determinism: {} of {} tokens diverged",
+                stats.diverged, stats.compared
+            ),
+            colors::WARNING,
+        )
+    };
+    egui::CollapsingHeader::new(RichText::new(title).color(color).size(12.0))
+        .id_salt(id)
+        .default_open(!stats.is_deterministic())
         .show(ui, |ui| {
-            ui.columns(2, |columns| {
-                columns[0].vertical(|ui| {
-                    render_column_header(ui, label_a, colors::INFO);
-                    render_stats_bar(ui, result_a);
-                    ui.add_space(8.0);
-                    crate::ui_tokens::render_analyzed_tokens(
-                        ui,
-                        &result_a.tokens,
-                        other_b,
-                        label_a,
-                        label_b,
-                    );
+            ui.label(
+                RichText::new(format!(
+                    "Max probability difference {:.2e}, mean {:.2e}; {} rank changes; \
+                     perplexity {:.4} vs {:.4}",
+                    stats.max_difference,
+                    stats.mean_difference,
+                    stats.ranks_changed,
+                    stats.perplexities.0,
+                    stats.perplexities.1
+                ))
+                .size(11.0)
+                .color(colors::text_muted(ui.visuals())),
+            );
+            if stats.worst.is_empty() {
+                return;
+            }
+            egui::Grid::new(id)
+                .num_columns(5)
+                .striped(true)
+                .spacing([12.0, 2.0])
+                .show(ui, |ui| {
+                    for header in ["#", "Token", "First run", "Second run", "Difference"] {
+                        ui.label(RichText::new(header).strong().size(11.0));
+                    }
+                    ui.end_row();
+                    for divergence in &stats.worst {
+                        ui.label(RichText::new(divergence.index.to_string()).size(11.0));
+                        ui.label(RichText::new(format!("{:?}", divergence.text)).size(11.0));
+                        ui.label(RichText::new(format!("{:.6}", divergence.first)).size(11.0));
+                        ui.label(RichText::new(format!("{:.6}", divergence.second)).size(11.0));
+                        let difference = RichText::new(format!("{:.2e}", divergence.difference()))
+                            .color(colors::WARNING)
+                            .size(11.0);
+                        if divergence.rank_changed {
+                            ui.label(difference)
+                                .on_hover_text("The token's rank changed");
+                        } else {
+                            ui.label(difference);
+                        }
+                        ui.end_row();
+                    }
                 });
+        });
+}
+
+/// Collapsible comparison of each segment's perplexity in the document
+/// and on its own, most context-dependent segments first.
+fn render_isolation_table(ui: &mut Ui, isolation: Option<&IsolationStats>, id: &str) {
+    let Some(isolation) = isolation else {
+        return;
+    };
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(
+        RichText::new(format!(
+            "🧩 Context isolation ({} segments)",
+            isolation.segments.len()
+        ))
+        .size(12.0),
+    )
+    .id_salt(id)
+    .default_open(true)
+    .show(ui, |ui| {
+        ui.label(
+            RichText::new(format!(
+                "Without the preceding text, segments are {:.2}× as perplexing on average",
+                isolation.mean_benefit()
+            ))
+            .size(11.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+        let mut segments: Vec<_> = isolation.segments.iter().collect();
+        segments.sort_by(|a, b| b.benefit().total_cmp(&a.benefit()));
+        egui::ScrollArea::vertical()
+            .id_salt(id)
+            .max_height(240.0)
+            .show(ui, |ui| {
+                egui::Grid::new(id)
+                    .num_columns(5)
+                    .striped(true)
+                    .spacing([12.0, 2.0])
+                    .show(ui, |ui| {
+                        for header in ["Segment", "Tokens", "In context", "Isolated", "Benefit"] {
+                            ui.label(RichText::new(header).strong().size(11.0));
+                        }
+                        ui.end_row();
+                        for segment in segments {
+                            let preview: String = segment.text.chars().take(60).collect();
+                            let preview = if preview.len() < segment.text.len() {
+                                format!("{}…", preview)
+                            } else {
+                                preview
+                            };
+                            ui.label(RichText::new(preview).size(11.0))
+                                .on_hover_text(&segment.text);
+                            ui.label(RichText::new(segment.tokens.to_string()).size(11.0));
+                            ui.label(
+                                RichText::new(format!("{:.2}", segment.in_context)).size(11.0),
+                            );
+                            ui.label(RichText::new(format!("{:.2}", segment.isolated)).size(11.0));
+                            ui.label(
+                                RichText::new(format!("{:.2}×", segment.benefit()))
+                                    .color(colors::WARNING)
+                                    .size(11.0),
+                            );
+                            ui.end_row();
+                        }
+                    });
+            });
+    });
+}
 
-                columns[1].vertical(|ui| {
-                    render_column_header(ui, label_b, colors::WARNING);
-                    render_stats_bar(ui, result_b);
-                    ui.add_space(8.0);
-                    crate::ui_tokens::render_analyzed_tokens(
-                        ui,
-                        &result_b.tokens,
-                        other_a,
-                        label_b,
-                        label_a,
+/// Collapsible grid of perplexity by evaluation window and stride. Rows
+/// whose perplexity depends on the stride are highlighted, and a warning
+/// is shown when the full-context result hasn't converged.
+fn render_chunk_sweep(ui: &mut Ui, sweep: Option<&ChunkSweep>, id: &str) {
+    let Some(sweep) = sweep else {
+        return;
+    };
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(RichText::new("🪟 Chunk-size sweep").size(12.0))
+        .id_salt(id)
+        .default_open(true)
+        .show(ui, |ui| {
+            if let Some((window, change)) = sweep.full_context_drift() {
+                ui.label(
+                    RichText::new(format!(
+                        "⚠ Unstable: perplexity changes by {:.0}% between a {}-token window and \
+                         the full text, so it depends on how much context the model sees",
+                        change * 100.0,
+                        window
+                    ))
+                    .size(11.0)
+                    .color(colors::WARNING),
+                );
+            }
+            egui::Grid::new(id)
+                .num_columns(windowing::CHUNK_SWEEP_STRIDES.len() + 1)
+                .striped(true)
+                .spacing([12.0, 2.0])
+                .show(ui, |ui| {
+                    ui.label(RichText::new("Window \\ stride").strong().size(11.0));
+                    for fraction in windowing::CHUNK_SWEEP_STRIDES {
+                        ui.label(
+                            RichText::new(format!("{:.0}%", fraction * 100.0))
+                                .strong()
+                                .size(11.0),
+                        );
+                    }
+                    ui.end_row();
+                    for window in sweep.windows() {
+                        let sensitive = sweep.is_stride_sensitive(window);
+                        let label = ui.label(RichText::new(window.to_string()).size(11.0));
+                        if sensitive {
+                            label.on_hover_text(format!(
+                                "Perplexity varies by {:.0}% across strides at this window",
+                                sweep.stride_spread(window) * 100.0
+                            ));
+                        }
+                        for point in sweep.row(window) {
+                            let color = if sensitive {
+                                colors::WARNING
+                            } else {
+                                colors::text_primary(ui.visuals())
+                            };
+                            ui.label(
+                                RichText::new(format!("{:.2}", point.perplexity))
+                                    .size(11.0)
+                                    .color(color),
+                            )
+                            .on_hover_text(format!("Stride {} tokens", point.stride));
+                        }
+                        ui.end_row();
+                    }
+                    ui.label(RichText::new("Full text").size(11.0));
+                    ui.label(
+                        RichText::new(format!("{:.2}", sweep.full_perplexity))
+                            .size(11.0)
+                            .color(colors::WARNING),
                     );
+                    ui.end_row();
                 });
-            });
         });
 }
 
-fn render_single_result(ui: &mut Ui, result: &AnalysisResult, name: &str, height: f32) {
-    render_column_header(ui, name, colors::INFO);
-    ui.add_space(8.0);
+/// Collapsible perplexity per token class, shown when the text mixes
+/// classes enough for the aggregate to hide differences.
+fn render_class_table(ui: &mut Ui, classes: &[ClassStats], id: &str) {
+    if classes.len() < 2 {
+        return;
+    }
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(RichText::new("🔣 Token classes").size(12.0))
+        .id_salt(id)
+        .show(ui, |ui| {
+            egui::Grid::new(id)
+                .num_columns(4)
+                .striped(true)
+                .spacing([12.0, 2.0])
+                .show(ui, |ui| {
+                    for header in ["Class", "Tokens", "Share", "PPL"] {
+                        ui.label(RichText::new(header).strong().size(11.0));
+                    }
+                    ui.end_row();
+                    for c in classes {
+                        ui.label(RichText::new(c.class.to_string()).size(11.0));
+                        ui.label(RichText::new(c.tokens.to_string()).size(11.0));
+                        ui.label(RichText::new(format!("{:.0}%", c.share * 100.0)).size(11.0));
+                        ui.label(
+                            RichText::new(format!("{:.2}", c.perplexity))
+                                .color(colors::WARNING)
+                                .size(11.0),
+                        );
+                        ui.end_row();
+                    }
+                });
+        });
+}
 
-    render_stats_bar(ui, result);
-    ui.add_space(12.0);
+/// Collapsible report of the most frequent token types, plus every type the
+/// model keeps mispredicting.
+fn render_token_type_table(ui: &mut Ui, types: &[TokenTypeStats], id: &str) {
+    if types.is_empty() {
+        return;
+    }
+    let mispredicted = types.iter().filter(|t| t.mispredicted()).count();
+    ui.add_space(4.0);
+    let mut title = format!("🔠 Token types ({})", types.len());
+    if mispredicted > 0 {
+        title.push_str(&format!(" · {} mispredicted", mispredicted));
+    }
+    egui::CollapsingHeader::new(RichText::new(title).size(12.0))
+        .id_salt(id)
+        .show(ui, |ui| {
+            let frequent = types.iter().take(token_types::FREQUENT_LIMIT);
+            let rows: Vec<&TokenTypeStats> = frequent
+                .chain(
+                    types
+                        .iter()
+                        .skip(token_types::FREQUENT_LIMIT)
+                        .filter(|t| t.mispredicted()),
+                )
+                .collect();
+            egui::Grid::new(id)
+                .num_columns(4)
+                .striped(true)
+                .spacing([12.0, 2.0])
+                .show(ui, |ui| {
+                    for header in ["Token", "Count", "Avg rank", "Surprisal"] {
+                        ui.label(RichText::new(header).strong().size(11.0));
+                    }
+                    ui.end_row();
+                    for t in rows {
+                        let mut text = RichText::new(format!("«{}»", t.text.replace('\n', "↵")))
+                            .monospace()
+                            .size(11.0);
+                        if t.mispredicted() {
+                            text = text.color(colors::ERROR);
+                        }
+                        let label = ui.label(text);
+                        if t.mispredicted() {
+                            label.on_hover_text(format!(
+                                "Mispredicted in {:.0}% of its occurrences: the model may not \
+                                 know this word",
+                                t.miss_rate * 100.0
+                            ));
+                        }
+                        ui.label(RichText::new(t.count.to_string()).size(11.0));
+                        ui.label(RichText::new(format!("{:.1}", t.mean_rank)).size(11.0));
+                        ui.label(RichText::new(format!("{:.2} bits", t.mean_surprisal)).size(11.0));
+                        ui.end_row();
+                    }
+                });
+        });
+}
 
-    let scroll_height = (height - 160.0).max(100.0);
-    egui::ScrollArea::vertical()
-        .id_salt("results_single_scroll")
-        .max_height(scroll_height)
+/// Collapsible per-speaker comparison, shown for transcripts with at least
+/// two labelled speakers.
+fn render_speaker_table(ui: &mut Ui, speakers: &[SpeakerStats], id: &str) {
+    if speakers.len() < 2 {
+        return;
+    }
+    ui.add_space(4.0);
+    let title = RichText::new(format!("👥 Speakers ({})", speakers.len())).size(12.0);
+    egui::CollapsingHeader::new(title)
+        .id_salt(id)
+        .default_open(true)
         .show(ui, |ui| {
-            crate::ui_tokens::render_analyzed_tokens(ui, &result.tokens, None, name, "");
+            egui::Grid::new(id)
+                .num_columns(5)
+                .striped(true)
+                .spacing([12.0, 2.0])
+                .show(ui, |ui| {
+                    for header in ["Speaker", "Tokens", "PPL", "Surprisal", "± std"] {
+                        ui.label(RichText::new(header).strong().size(11.0));
+                    }
+                    ui.end_row();
+                    for s in speakers {
+                        ui.label(RichText::new(&s.name).size(11.0));
+                        ui.label(RichText::new(s.tokens.to_string()).size(11.0));
+                        ui.label(
+                            RichText::new(format!("{:.2}", s.perplexity))
+                                .color(colors::WARNING)
+                                .size(11.0),
+                        );
+                        ui.label(RichText::new(format!("{:.2} bits", s.mean_surprisal)).size(11.0));
+                        ui.label(RichText::new(format!("{:.2}", s.surprisal_std)).size(11.0));
+                        ui.end_row();
+                    }
+                });
         });
 }
 
-fn render_column_header(ui: &mut Ui, label: &str, color: Color32) {
-    ui.label(
-        RichText::new(format!("📦 {}", label))
-            .strong()
-            .size(14.0)
-            .color(color),
-    );
-    ui.add_space(6.0);
+/// Collapsible plot of perplexity against the maximum context length.
+fn render_context_sweep(ui: &mut Ui, points: Option<&[(u32, f32)]>, id: &str) {
+    let Some(points) = points else {
+        return;
+    };
+    ui.add_space(4.0);
+    egui::CollapsingHeader::new(RichText::new("📏 Context sweep").size(12.0))
+        .id_salt(id)
+        .default_open(true)
+        .show(ui, |ui| {
+            // Context lengths roughly double, so plot them on a log2 axis.
+            let series: Vec<[f64; 2]> = points
+                .iter()
+                .map(|&(ctx, ppl)| [(ctx as f64).log2(), ppl as f64])
+                .collect();
+            egui_plot::Plot::new(id)
+                .height(140.0)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .allow_scroll(false)
+                .x_axis_label("Context (tokens)")
+                .y_axis_label("PPL")
+                .x_axis_formatter(|mark, _| format!("{:.0}", mark.value.exp2()))
+                .label_formatter(|_, point| {
+                    format!("{:.0} tokens\nPPL {:.2}", point.x.exp2(), point.y)
+                })
+                .show(ui, |plot_ui| {
+                    plot_ui
+                        .line(egui_plot::Line::new(series.clone()).color(colors::ACCENT_PRIMARY));
+                    plot_ui.points(
+                        egui_plot::Points::new(series)
+                            .radius(3.0)
+                            .color(colors::ACCENT_PRIMARY),
+                    );
+                });
+        });
 }
 
-fn render_stats_bar(ui: &mut Ui, result: &AnalysisResult) {
-    ui.horizontal_wrapped(|ui| {
-        ui.label(
-            RichText::new(format!(
-                "⏱ {:.1}s",
-                result.processing_time_ms as f32 / 1000.0
-            ))
-            .color(colors::text_muted(ui.visuals()))
-            .size(12.0),
+/// Tiny bar chart of perplexity per decile, on a log scale.
+fn render_decile_sparkline(ui: &mut Ui, deciles: &[f32]) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(Vec2::new(60.0, 14.0), egui::Sense::hover());
+    let max = deciles
+        .iter()
+        .map(|p| p.ln_1p())
+        .fold(f32::MIN_POSITIVE, f32::max);
+    let bar_width = rect.width() / deciles.len() as f32;
+    for (i, ppl) in deciles.iter().enumerate() {
+        let height = (ppl.ln_1p() / max * rect.height()).max(1.0);
+        let left = rect.left() + i as f32 * bar_width;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(left + 0.5, rect.bottom() - height),
+            egui::pos2(left + bar_width - 0.5, rect.bottom()),
         );
+        ui.painter().rect_filled(bar, 0.0, colors::ACCENT_PRIMARY);
+    }
+    response
+}
 
-        ui.add_space(10.0);
-
-        ui.label(
-            RichText::new(format!("PPL: {:.2}", result.perplexity()))
-                .color(colors::WARNING)
-                .size(12.0),
-        )
-        .on_hover_text("Perplexity (lower = more predictable)");
-
-        ui.add_space(10.0);
-
-        ui.label(
-            RichText::new(format!("Entropy: {:.0} bits", result.text_entropy()))
-                .color(colors::ACCENT_PRIMARY)
-                .size(12.0),
-        )
-        .on_hover_text("Information needed to reconstruct the text using this model");
-    });
+fn render_language_table(ui: &mut Ui, languages: &[LanguageStats]) {
+    ui.label(
+        RichText::new("Mixed-language text: the overall perplexity blends these.")
+            .size(11.0)
+            .color(colors::text_muted(ui.visuals())),
+    );
+    ui.add_space(4.0);
+    egui::Grid::new("language_table")
+        .num_columns(3)
+        .spacing([12.0, 2.0])
+        .show(ui, |ui| {
+            ui.label(RichText::new("Language").strong().size(11.0));
+            ui.label(RichText::new("Tokens").strong().size(11.0));
+            ui.label(RichText::new("PPL").strong().size(11.0));
+            ui.end_row();
+            for stats in languages {
+                ui.label(RichText::new(stats.language).size(11.0));
+                ui.label(RichText::new(stats.tokens.to_string()).size(11.0));
+                ui.label(RichText::new(format!("{:.2}", stats.perplexity)).size(11.0));
+                ui.end_row();
+            }
+        });
 }
 
 // ── Legend ───────────────────────────────────────────────────────────────────
@@ -650,6 +3904,43 @@ fn render_divergence_legend(ui: &mut Ui) {
     ]);
 }
 
+fn render_margin_legend(ui: &mut Ui) {
+    render_legend_row(
+        ui,
+        "Legend (top-1 margin):",
+        &[
+            (colors::margin_to_color(0.0), "Top pick"),
+            (colors::margin_to_color(1.0), "Near miss"),
+            (colors::margin_to_color(4.0), "Missed"),
+            (colors::margin_to_color(10.0), "Blindsided"),
+        ],
+    );
+}
+
+/// Legend of the color expression; tokens fall back to rank colors while
+/// it fails.
+fn render_expression_legend(ui: &mut Ui, error: Option<&str>) {
+    if let Some(error) = error {
+        ui.label(
+            RichText::new("⚠ The color expression failed; showing rank colors")
+                .color(colors::ERROR)
+                .size(12.0),
+        )
+        .on_hover_text(error);
+        render_legend(ui);
+        return;
+    }
+    render_legend_row(
+        ui,
+        "Legend (expression):",
+        &[
+            (colors::scale_to_color(0.0), "0"),
+            (colors::scale_to_color(0.5), "0.5"),
+            (colors::scale_to_color(1.0), "1"),
+        ],
+    );
+}
+
 fn render_prob_legend(ui: &mut Ui) {
     render_legend_row(ui, "Legend (probability):", &[
         (colors::prob_to_color(0.75), ">50%"),
@@ -670,16 +3961,11 @@ fn legend_swatch(ui: &mut Ui, color: Color32, label: &str) {
 
 fn render_unified_result(
     ui: &mut Ui,
-    result_a: &AnalysisResult,
-    result_b: &AnalysisResult,
-    model_name_a: Option<&str>,
-    model_name_b: Option<&str>,
+    view_a: &ResultView,
+    view_b: &ResultView,
     height: f32,
-    color_mode: UnifiedColorMode,
-) {
-    let label_a = model_name_a.unwrap_or("Model A");
-    let label_b = model_name_b.unwrap_or("Model B");
-
+    options: &DisplayOptions,
+) -> Option<(InspectedToken, TokenGesture)> {
     let scroll_height = (height - 140.0).max(100.0);
     egui::ScrollArea::vertical()
         .id_salt("results_unified_scroll")
@@ -688,13 +3974,23 @@ fn render_unified_result(
         .show(ui, |ui| {
             crate::ui_tokens::render_unified_tokens(
                 ui,
-                &result_a.tokens,
-                &result_b.tokens,
-                label_a,
-                label_b,
-                color_mode,
-            );
-        });
+                view_a.result,
+                view_b.result,
+                (view_a.name, view_b.name),
+                options,
+                TokenSelection::within(options.selection, None),
+            )
+        })
+        .inner
+        .map(|(index, gesture)| {
+            (
+                InspectedToken {
+                    result: None,
+                    index,
+                },
+                gesture,
+            )
+        })
 }
 
 // ── Empty state & error ─────────────────────────────────────────────────────
@@ -727,9 +4023,16 @@ pub fn render_empty_state(ui: &mut Ui, has_any_model: bool) {
     });
 }
 
-pub fn render_error(ui: &mut Ui, error: &str) {
+/// Links offered next to an error message.
+pub enum ErrorAction {
+    ShowLogs,
+    SaveBundle,
+}
+
+pub fn render_error(ui: &mut Ui, error: &str) -> Option<ErrorAction> {
     ui.add_space(12.0);
 
+    let mut action = None;
     egui::Frame::none()
         .fill(colors::error_bg(ui.visuals()))
         .rounding(8.0)
@@ -739,6 +4042,90 @@ pub fn render_error(ui: &mut Ui, error: &str) {
                 ui.label(RichText::new("❌").size(18.0));
                 ui.add_space(8.0);
                 ui.label(RichText::new(error).color(colors::ERROR).size(14.0));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.link("📜 Show logs").clicked() {
+                        action = Some(ErrorAction::ShowLogs);
+                    }
+                    if ui
+                        .link("💾 Save diagnostic bundle")
+                        .on_hover_text("Zip of logs, settings, model metadata and the failing parameters, to attach to a bug report")
+                        .clicked()
+                    {
+                        action = Some(ErrorAction::SaveBundle);
+                    }
+                });
+            });
+        });
+    action
+}
+
+/// Options for a diagnostic bundle about to be saved. Returns true to save.
+pub fn render_bundle_dialog(
+    ctx: &egui::Context,
+    options: &mut BundleOptions,
+    open: &mut bool,
+) -> bool {
+    let mut save = false;
+    egui::Window::new("Save diagnostic bundle")
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label("Logs, settings, model metadata and the inference parameters are included.");
+            ui.add_space(6.0);
+            ui.checkbox(
+                &mut options.redact_paths,
+                "Redact model paths and home directory",
+            );
+            ui.checkbox(
+                &mut options.include_text,
+                "Include the analyzed text and prompt",
+            );
+            ui.add_space(6.0);
+            if ui.button("💾 Save…").clicked() {
+                save = true;
+            }
+        });
+    save
+}
+
+/// Non-blocking notice of a newer release with its changelog. Returns true
+/// when dismissed.
+pub fn render_update_banner(ui: &mut Ui, release: &Release) -> bool {
+    let mut dismissed = false;
+    ui.add_space(8.0);
+    egui::Frame::none()
+        .fill(colors::secondary_bg(ui.visuals()))
+        .rounding(8.0)
+        .inner_margin(10.0)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(
+                    RichText::new(format!(
+                        "⬆ Perplex {} is available (you have {})",
+                        release.version,
+                        env!("CARGO_PKG_VERSION")
+                    ))
+                    .strong()
+                    .size(13.0),
+                );
+                ui.hyperlink_to("Release page", &release.url);
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    dismissed = ui.small_button("✖").on_hover_text("Dismiss").clicked();
+                });
             });
+            if !release.changelog.trim().is_empty() {
+                egui::CollapsingHeader::new(RichText::new("Changelog").size(12.0))
+                    .id_salt("update_changelog")
+                    .show(ui, |ui| {
+                        egui::ScrollArea::vertical()
+                            .max_height(200.0)
+                            .show(ui, |ui| {
+                                ui.label(RichText::new(release.changelog.trim()).size(12.0));
+                            });
+                    });
+            }
         });
+    dismissed
 }
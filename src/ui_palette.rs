@@ -0,0 +1,179 @@
+use egui::{Key, Modifiers, RichText};
+
+use crate::colors;
+use crate::ui_main::{
+    ExportFormat, MainTab, ResultTest, TokenColorMode, UnifiedColorMode, ViewMode,
+};
+use crate::ModelSlot;
+
+/// Every action reachable from the command palette.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Analyze,
+    PasteAndAnalyze,
+    Cancel,
+    TogglePause,
+    OpenFile,
+    LoadModel(ModelSlot),
+    EjectModel(ModelSlot),
+    UnloadModel(ModelSlot),
+    Export(ExportFormat),
+    Test(ResultTest),
+    ViewMode(ViewMode),
+    ColorMode(TokenColorMode),
+    UnifiedColorMode(UnifiedColorMode),
+    ToggleRepeats,
+    ToggleLive,
+    AddToQueue,
+    RunQueue,
+    Tab(MainTab),
+    Settings,
+    ToggleLogs,
+    ToggleResourceMonitor,
+}
+
+impl Command {
+    pub fn label(self) -> String {
+        match self {
+            Command::Analyze => "Analyze".to_string(),
+            Command::PasteAndAnalyze => "Paste clipboard and analyze".to_string(),
+            Command::Cancel => "Cancel analysis".to_string(),
+            Command::TogglePause => "Pause / resume analysis".to_string(),
+            Command::OpenFile => "Open text file…".to_string(),
+            Command::LoadModel(slot) => format!("Load {}…", slot.label()),
+            Command::EjectModel(slot) => format!("Eject {}", slot.label()),
+            Command::UnloadModel(slot) => format!("Unload {} to free memory", slot.label()),
+            Command::Export(format) => format!("Export: {}", format.label()),
+            Command::Test(test) => format!("Test: {}", test.label()),
+            Command::ViewMode(mode) => format!("Compare view: {}", mode),
+            Command::ColorMode(mode) => format!("Coloring: {}", mode),
+            Command::UnifiedColorMode(mode) => format!("Unified coloring: {}", mode),
+            Command::ToggleRepeats => "Toggle repeat highlighting".to_string(),
+            Command::ToggleLive => "Toggle live analysis".to_string(),
+            Command::AddToQueue => "Add current text to the queue".to_string(),
+            Command::RunQueue => "Run queue".to_string(),
+            Command::Tab(MainTab::Analyze) => "Go to the Analyze tab".to_string(),
+            Command::Tab(MainTab::Tokenizer) => "Go to the Tokenizer tab".to_string(),
+            Command::Tab(MainTab::Trends) => "Go to the Trends tab".to_string(),
+            Command::Settings => "Open settings".to_string(),
+            Command::ToggleLogs => "Toggle log panel".to_string(),
+            Command::ToggleResourceMonitor => "Toggle resource monitor".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PaletteState {
+    pub query: String,
+    selected: usize,
+}
+
+#[derive(Default)]
+pub struct PaletteAction {
+    pub run: Option<Command>,
+    pub close: bool,
+}
+
+/// Floating search box over `commands`, best fuzzy matches first. Arrow
+/// keys move the selection, Enter runs it and Escape closes the palette.
+pub fn render_palette(
+    ctx: &egui::Context,
+    state: &mut PaletteState,
+    commands: &[Command],
+) -> PaletteAction {
+    let mut matches: Vec<(i32, Command, String)> = commands
+        .iter()
+        .filter_map(|&command| {
+            let label = command.label();
+            Some((fuzzy_score(&state.query, &label)?, command, label))
+        })
+        .collect();
+    matches.sort_by_key(|(score, ..)| std::cmp::Reverse(*score));
+
+    let (up, down, enter, escape) = ctx.input_mut(|input| {
+        (
+            input.consume_key(Modifiers::NONE, Key::ArrowUp),
+            input.consume_key(Modifiers::NONE, Key::ArrowDown),
+            input.consume_key(Modifiers::NONE, Key::Enter),
+            input.consume_key(Modifiers::NONE, Key::Escape),
+        )
+    });
+    if up {
+        state.selected = state.selected.saturating_sub(1);
+    }
+    if down {
+        state.selected += 1;
+    }
+    state.selected = state.selected.min(matches.len().saturating_sub(1));
+
+    let mut action = PaletteAction {
+        close: escape,
+        ..Default::default()
+    };
+    egui::Window::new("Command palette")
+        .title_bar(false)
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+        .fixed_size([420.0, 0.0])
+        .show(ctx, |ui| {
+            let edit = ui.add(
+                egui::TextEdit::singleline(&mut state.query)
+                    .hint_text("Type a command…")
+                    .desired_width(f32::INFINITY),
+            );
+            edit.request_focus();
+            if edit.changed() {
+                state.selected = 0;
+            }
+            ui.add_space(4.0);
+
+            if matches.is_empty() {
+                ui.label(
+                    RichText::new("No matching command")
+                        .size(12.0)
+                        .color(colors::text_muted(ui.visuals())),
+                );
+            }
+            egui::ScrollArea::vertical()
+                .max_height(320.0)
+                .show(ui, |ui| {
+                    for (i, (_, command, label)) in matches.iter().enumerate() {
+                        let response = ui.selectable_label(i == state.selected, label);
+                        if i == state.selected && (up || down) {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            action.run = Some(*command);
+                        }
+                    }
+                });
+        });
+
+    if enter {
+        action.run = matches.get(state.selected).map(|&(_, command, _)| command);
+    }
+    action.close |= action.run.is_some();
+    action
+}
+
+/// Scores `label` for a query whose characters must all appear in it, in
+/// order and ignoring case; `None` if they don't. Consecutive characters
+/// and word starts score higher, so "exh" ranks "Export: HTML" first.
+fn fuzzy_score(query: &str, label: &str) -> Option<i32> {
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let i = next + label[next..].iter().position(|&l| l == c)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == i) {
+            score += 5;
+        }
+        if i == 0 || !label[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(i);
+        next = i + 1;
+    }
+    Some(score)
+}
@@ -0,0 +1,80 @@
+use egui::{Color32, RichText};
+use egui_plot::{Line, Plot, PlotPoints};
+
+use crate::colors;
+use crate::resources::ResourceSample;
+
+const PLOT_HEIGHT: f32 = 90.0;
+
+/// Small bottom panel plotting the resource monitor samples of each slot.
+pub fn render_resource_panel(
+    ctx: &egui::Context,
+    samples_a: &[ResourceSample],
+    samples_b: &[ResourceSample],
+) {
+    egui::TopBottomPanel::bottom("resource_panel").show(ctx, |ui| {
+        ui.add_space(4.0);
+        ui.label(RichText::new("📈 Resources").size(13.0));
+        if samples_a.is_empty() && samples_b.is_empty() {
+            ui.label(
+                RichText::new("Samples appear here while an analysis runs.")
+                    .size(11.0)
+                    .color(colors::text_muted(ui.visuals())),
+            );
+            ui.add_space(4.0);
+            return;
+        }
+
+        let series = [(samples_a, colors::INFO), (samples_b, colors::WARNING)];
+        ui.columns(3, |columns| {
+            render_metric(&mut columns[0], "CPU (cores)", &series, |s| {
+                s.cpu_cores as f64
+            });
+            render_metric(&mut columns[1], "RAM (GiB)", &series, |s| {
+                s.ram as f64 / (1024.0 * 1024.0 * 1024.0)
+            });
+            render_metric(&mut columns[2], "Tokens/s", &series, |s| {
+                s.tokens_per_sec as f64
+            });
+        });
+        ui.add_space(4.0);
+    });
+}
+
+fn render_metric(
+    ui: &mut egui::Ui,
+    title: &str,
+    series: &[(&[ResourceSample], Color32)],
+    value: impl Fn(&ResourceSample) -> f64,
+) {
+    let latest = series
+        .iter()
+        .filter_map(|(samples, _)| samples.last())
+        .map(&value)
+        .fold(0.0, f64::max);
+    ui.label(
+        RichText::new(format!("{}: {:.1}", title, latest))
+            .size(11.0)
+            .color(colors::text_muted(ui.visuals())),
+    );
+    Plot::new(title)
+        .height(PLOT_HEIGHT)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .include_y(0.0)
+        .show_axes([false, true])
+        .label_formatter(|_, point| format!("{:.1}s\n{:.2}", point.x, point.y))
+        .show(ui, |plot_ui| {
+            for (samples, color) in series {
+                if samples.is_empty() {
+                    continue;
+                }
+                let points: PlotPoints = samples
+                    .iter()
+                    .map(|s| [s.elapsed as f64, value(s)])
+                    .collect();
+                plot_ui.line(Line::new(points).color(*color));
+            }
+        });
+}
@@ -1,72 +1,1016 @@
 use egui::RichText;
 
-use crate::settings::PreloadMode;
+use crate::colors;
+use crate::hook::HookInput;
+use crate::isolation::SegmentMode;
+use crate::keymap::{Action, Keymap};
+use crate::llamacpp::GpuSplit;
+use crate::number_format::{InformationUnit, NumberFormat, ShareStyle};
+use crate::preprocess::Normalization;
+use crate::reference::{Domain, ReferenceStats};
+use crate::scripting::{self, CustomMetric};
+use crate::settings::{PreloadMode, Settings, SummaryMetric, Theme};
+use crate::tray;
+use crate::ui_tokens::TooltipOptions;
 use crate::ModelSlot;
 
+const N_CTX_RANGE: std::ops::RangeInclusive<u32> = 256..=1_048_576;
+const CONSTANT_CONTEXT_RANGE: std::ops::RangeInclusive<u32> = 1..=32_768;
+const N_BATCH_RANGE: std::ops::RangeInclusive<u32> = 1..=65_536;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SettingsTab {
+    Model,
+    Inference,
+    Analysis,
+    Display,
+    Shortcuts,
+    Export,
+}
+
+impl SettingsTab {
+    const ALL: [SettingsTab; 6] = [
+        SettingsTab::Model,
+        SettingsTab::Inference,
+        SettingsTab::Analysis,
+        SettingsTab::Display,
+        SettingsTab::Shortcuts,
+        SettingsTab::Export,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SettingsTab::Model => "📦 Model",
+            SettingsTab::Inference => "⚙ Inference",
+            SettingsTab::Analysis => "🔬 Analysis",
+            SettingsTab::Display => "🎨 Display",
+            SettingsTab::Shortcuts => "⌨ Shortcuts",
+            SettingsTab::Export => "📤 Export",
+        }
+    }
+}
+
+/// Uncommitted copy of the settings edited by the dialog. Nothing takes
+/// effect until the draft is applied.
+pub struct SettingsDraft {
+    pub tab: SettingsTab,
+    pub path_a: String,
+    pub path_b: String,
+    pub path_preview: String,
+    pub settings: Settings,
+}
+
+impl SettingsDraft {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            tab: SettingsTab::Model,
+            path_a: settings.model_path_a.clone().unwrap_or_default(),
+            path_b: settings.model_path_b.clone().unwrap_or_default(),
+            path_preview: settings.preview_model_path.clone().unwrap_or_default(),
+            settings: settings.clone(),
+        }
+    }
+
+    /// Replaces the draft contents, keeping the selected tab.
+    pub fn reset_to(&mut self, settings: &Settings) {
+        let tab = self.tab;
+        *self = Self::new(settings);
+        self.tab = tab;
+    }
+
+    pub fn path_mut(&mut self, slot: ModelSlot) -> &mut String {
+        match slot {
+            ModelSlot::A => &mut self.path_a,
+            ModelSlot::B => &mut self.path_b,
+        }
+    }
+
+    /// Returns a human-readable message for every invalid field.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        for (label, path) in [
+            ("Model A", &self.path_a),
+            ("Model B", &self.path_b),
+            ("Preview model", &self.path_preview),
+        ] {
+            let path = path.trim();
+            if !path.is_empty() && !std::path::Path::new(path).is_file() {
+                errors.push(format!("{}: file not found", label));
+            }
+        }
+
+        let inference = &self.settings.inference;
+        if !N_CTX_RANGE.contains(&inference.n_ctx) {
+            errors.push(format!(
+                "Context size must be between {} and {}",
+                N_CTX_RANGE.start(),
+                N_CTX_RANGE.end()
+            ));
+        }
+        if !N_BATCH_RANGE.contains(&inference.n_batch) {
+            errors.push(format!(
+                "Batch size must be between {} and {}",
+                N_BATCH_RANGE.start(),
+                N_BATCH_RANGE.end()
+            ));
+        }
+        if let Some(folder) = self.settings.watch.folder() {
+            if !folder.is_dir() {
+                errors.push("Watched folder: folder not found".to_string());
+            }
+        }
+        if let Err(e) = scripting::check(&self.settings.color_expression) {
+            errors.push(format!("Color expression: {}", e));
+        }
+        for metric in &self.settings.custom_metrics {
+            if metric.name.trim().is_empty() {
+                errors.push("Every custom metric needs a name".to_string());
+            } else if let Err(e) = metric.check() {
+                errors.push(format!("Metric \"{}\": {}", metric.name, e));
+            }
+        }
+        if inference
+            .low_memory_window
+            .is_some_and(|w| !N_CTX_RANGE.contains(&w))
+        {
+            errors.push(format!(
+                "Low-memory window must be between {} and {}",
+                N_CTX_RANGE.start(),
+                N_CTX_RANGE.end()
+            ));
+        }
+        if inference
+            .constant_context
+            .is_some_and(|k| !CONSTANT_CONTEXT_RANGE.contains(&k))
+        {
+            errors.push(format!(
+                "Constant context must be between {} and {} tokens",
+                CONSTANT_CONTEXT_RANGE.start(),
+                CONSTANT_CONTEXT_RANGE.end()
+            ));
+        }
+        if inference.max_tokens.is_some_and(|n| n < 2) {
+            errors.push("Scoring limit must be at least 2 tokens".to_string());
+        }
+        if inference.n_threads.is_some_and(|n| n < 1) {
+            errors.push("Thread count must be at least 1".to_string());
+        }
+
+        let pattern = self.settings.speaker_pattern.trim();
+        if !pattern.is_empty() {
+            if let Err(e) = regex::Regex::new(pattern) {
+                let message = e.to_string();
+                errors.push(format!("Speaker pattern: {}", error_summary(&message)));
+            }
+        }
+
+        errors.extend(self.settings.keymap.errors());
+
+        errors
+    }
+
+    /// Builds the settings that applying this draft would produce.
+    pub fn to_settings(&self) -> Settings {
+        let to_path = |buf: &str| {
+            let buf = buf.trim();
+            (!buf.is_empty()).then(|| buf.to_string())
+        };
+        Settings {
+            model_path_a: to_path(&self.path_a),
+            model_path_b: to_path(&self.path_b),
+            preview_model_path: to_path(&self.path_preview),
+            ..self.settings.clone()
+        }
+    }
+}
+
 pub enum SettingsAction {
     Browse(ModelSlot),
-    Save,
     Clear(ModelSlot),
+    BrowsePreview,
+    ClearPreview,
+    BrowseWatchFolder,
+    BuildReference(ModelSlot),
+    ImportReference(ModelSlot),
+    Export,
+    Import,
+    Apply,
+    ApplyAndClose,
+    Cancel,
 }
 
+/// `references` holds the stored reference of each slot's applied model.
 pub fn render_settings_window(
     ctx: &egui::Context,
-    open: &mut bool,
-    path_buffer_a: &mut String,
-    path_buffer_b: &mut String,
-    preload_mode: &mut PreloadMode,
+    draft: &mut SettingsDraft,
+    references: [Option<&ReferenceStats>; 2],
 ) -> Option<SettingsAction> {
     let mut action = None;
+    let mut open = true;
+    let errors = draft.validate();
 
     egui::Window::new("Settings")
-        .open(open)
-        .min_size([400.0, 280.0])
+        .open(&mut open)
+        .min_size([420.0, 300.0])
+        .collapsible(false)
         .show(ctx, |ui| {
-            ui.heading("Model Configuration");
-            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                for tab in SettingsTab::ALL {
+                    ui.selectable_value(&mut draft.tab, tab, tab.label());
+                }
+            });
+            ui.separator();
+            ui.add_space(6.0);
 
-            render_model_group(ui, "Model A", path_buffer_a, &mut action, ModelSlot::A);
+            match draft.tab {
+                SettingsTab::Model => render_model_tab(ui, draft, references, &mut action),
+                SettingsTab::Inference => render_inference_tab(ui, draft),
+                SettingsTab::Analysis => render_analysis_tab(ui, draft),
+                SettingsTab::Display => render_display_tab(ui, draft),
+                SettingsTab::Shortcuts => render_shortcuts_tab(ui, draft),
+                SettingsTab::Export => render_export_tab(ui, draft, &mut action),
+            }
 
-            ui.add_space(8.0);
+            ui.add_space(12.0);
 
-            render_model_group(ui, "Model B", path_buffer_b, &mut action, ModelSlot::B);
+            for error in &errors {
+                ui.label(
+                    RichText::new(format!("⚠ {}", error))
+                        .color(colors::ERROR)
+                        .size(12.0),
+                );
+            }
 
-            ui.add_space(12.0);
+            ui.separator();
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let valid = errors.is_empty();
+                if ui.add_enabled(valid, egui::Button::new("OK")).clicked() {
+                    action = Some(SettingsAction::ApplyAndClose);
+                }
+                if ui.add_enabled(valid, egui::Button::new("Apply")).clicked() {
+                    action = Some(SettingsAction::Apply);
+                }
+                if ui.button("Cancel").clicked() {
+                    action = Some(SettingsAction::Cancel);
+                }
+            });
+        });
 
-            ui.heading("Loading Mode");
-            ui.add_space(6.0);
+    if !open {
+        action = Some(SettingsAction::Cancel);
+    }
+
+    action
+}
+
+// ── Tabs ────────────────────────────────────────────────────────────────────
+
+fn render_model_tab(
+    ui: &mut egui::Ui,
+    draft: &mut SettingsDraft,
+    references: [Option<&ReferenceStats>; 2],
+    action: &mut Option<SettingsAction>,
+) {
+    render_model_group(
+        ui,
+        "Model A",
+        &mut draft.path_a,
+        action,
+        SettingsAction::Browse(ModelSlot::A),
+        SettingsAction::Clear(ModelSlot::A),
+    );
+    render_reference_row(
+        ui,
+        references[0],
+        draft.settings.domain,
+        action,
+        ModelSlot::A,
+    );
+
+    ui.add_space(8.0);
+
+    render_model_group(
+        ui,
+        "Model B",
+        &mut draft.path_b,
+        action,
+        SettingsAction::Browse(ModelSlot::B),
+        SettingsAction::Clear(ModelSlot::B),
+    );
+    render_reference_row(
+        ui,
+        references[1],
+        draft.settings.domain,
+        action,
+        ModelSlot::B,
+    );
 
-            egui::ComboBox::from_id_salt("preload_mode")
-                .selected_text(preload_mode.to_string())
-                .width(280.0)
+    ui.add_space(8.0);
+
+    render_model_group(
+        ui,
+        "Preview Model (optional)",
+        &mut draft.path_preview,
+        action,
+        SettingsAction::BrowsePreview,
+        SettingsAction::ClearPreview,
+    );
+    ui.label(
+        RichText::new(
+            "A small model that shows an approximate heatmap right away, replaced when Model A or B finishes.",
+        )
+        .size(12.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+
+    ui.add_space(12.0);
+
+    ui.heading("Loading Mode");
+    ui.add_space(6.0);
+
+    let preload_mode = &mut draft.settings.preload_mode;
+    egui::ComboBox::from_id_salt("preload_mode")
+        .selected_text(preload_mode.to_string())
+        .width(280.0)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(
+                preload_mode,
+                PreloadMode::PreloadAll,
+                PreloadMode::PreloadAll.to_string(),
+            );
+            ui.selectable_value(
+                preload_mode,
+                PreloadMode::PreloadSingle,
+                PreloadMode::PreloadSingle.to_string(),
+            );
+            ui.selectable_value(
+                preload_mode,
+                PreloadMode::NoPreload,
+                PreloadMode::NoPreload.to_string(),
+            );
+        });
+}
+
+fn render_inference_tab(ui: &mut egui::Ui, draft: &mut SettingsDraft) {
+    let inference = &mut draft.settings.inference;
+
+    egui::Grid::new("inference_grid")
+        .num_columns(2)
+        .spacing([12.0, 8.0])
+        .show(ui, |ui| {
+            ui.label("GPU layers");
+            ui.horizontal(|ui| {
+                let mut custom = inference.n_gpu_layers.is_some();
+                if ui.checkbox(&mut custom, "Custom").changed() {
+                    inference.n_gpu_layers = custom.then_some(99);
+                }
+                if let Some(ref mut n) = inference.n_gpu_layers {
+                    ui.add(egui::DragValue::new(n).range(0..=999));
+                } else {
+                    ui.label(RichText::new("llama.cpp default").weak());
+                }
+            });
+            ui.end_row();
+
+            ui.label("Multiple GPUs").on_hover_text(
+                "Spread the offloaded layers over all GPUs, or keep the model on one card",
+            );
+            egui::ComboBox::from_id_salt("gpu_split")
+                .selected_text(inference.split_mode.to_string())
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(
-                        preload_mode,
-                        PreloadMode::PreloadAll,
-                        PreloadMode::PreloadAll.to_string(),
-                    );
-                    ui.selectable_value(
-                        preload_mode,
-                        PreloadMode::PreloadSingle,
-                        PreloadMode::PreloadSingle.to_string(),
+                    for mode in GpuSplit::ALL {
+                        ui.selectable_value(&mut inference.split_mode, mode, mode.to_string());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Main GPU").on_hover_text(
+                "Index of the GPU, in the order the resource monitor lists them, that holds the model with \"Main GPU only\"",
+            );
+            ui.add(egui::DragValue::new(&mut inference.main_gpu).range(0..=15));
+            ui.end_row();
+
+            ui.label("Context size")
+                .on_hover_text("Minimum context; grown automatically for longer texts");
+            ui.add(egui::DragValue::new(&mut inference.n_ctx).speed(64.0));
+            ui.end_row();
+
+            ui.label("Low-memory mode").on_hover_text(
+                "Score long texts in windows of this many tokens, freeing the KV cache between \
+                 windows. Memory stays bounded, but tokens don't see text before their window.",
+            );
+            ui.horizontal(|ui| {
+                let mut enabled = inference.low_memory_window.is_some();
+                if ui.checkbox(&mut enabled, "Enabled").changed() {
+                    inference.low_memory_window = enabled.then_some(1024);
+                }
+                if let Some(ref mut window) = inference.low_memory_window {
+                    ui.add(egui::DragValue::new(window).speed(64.0).suffix(" tokens"));
+                    ui.checkbox(&mut inference.rolling_context, "Rolling context")
+                        .on_hover_text(
+                            "Shift the KV cache to drop the oldest tokens when it's full, so \
+                             every token keeps a long context, instead of starting each window \
+                             empty. Not every model supports this.",
+                        );
+                } else {
+                    ui.label(RichText::new("Whole text in one context").weak());
+                }
+            });
+            ui.end_row();
+
+            ui.label("Constant context").on_hover_text(
+                "Predict every token from exactly this many preceding tokens, plus the first \
+                 one, so early and late tokens are scored alike. Tokens with less before them \
+                 count as prompt. Every token needs a decode of its own, so an analysis costs \
+                 about this many times more compute. Overrides low-memory mode.",
+            );
+            ui.horizontal(|ui| {
+                let mut enabled = inference.constant_context.is_some();
+                if ui.checkbox(&mut enabled, "Enabled").changed() {
+                    inference.constant_context = enabled.then_some(128);
+                }
+                if let Some(ref mut k) = inference.constant_context {
+                    ui.add(egui::DragValue::new(k).speed(8.0).suffix(" tokens"));
+                    ui.label(
+                        RichText::new(format!("≈{}× the compute", k))
+                            .color(colors::WARNING)
+                            .size(11.0),
                     );
+                } else {
+                    ui.label(RichText::new("Every earlier token").weak());
+                }
+            });
+            ui.end_row();
+
+            ui.label("Scoring limit").on_hover_text(
+                "Score only the first tokens of long texts, prompt included; the rest is shown \
+                 struck through and left out of every metric",
+            );
+            ui.horizontal(|ui| {
+                let mut enabled = inference.max_tokens.is_some();
+                if ui.checkbox(&mut enabled, "Enabled").changed() {
+                    inference.max_tokens = enabled.then_some(4096);
+                }
+                if let Some(ref mut limit) = inference.max_tokens {
+                    ui.add(egui::DragValue::new(limit).speed(64.0).suffix(" tokens"));
+                } else {
+                    ui.label(RichText::new("Whole text").weak());
+                }
+            });
+            ui.end_row();
+
+            ui.label("Batch size");
+            ui.add(egui::DragValue::new(&mut inference.n_batch).speed(8.0));
+            ui.end_row();
+
+            ui.label("Threads");
+            ui.horizontal(|ui| {
+                let mut custom = inference.n_threads.is_some();
+                if ui.checkbox(&mut custom, "Custom").changed() {
+                    inference.n_threads = custom.then(|| {
+                        std::thread::available_parallelism().map_or(4, |n| n.get() as i32)
+                    });
+                }
+                if let Some(ref mut n) = inference.n_threads {
+                    ui.add(egui::DragValue::new(n).range(1..=512));
+                } else {
+                    ui.label(RichText::new("Automatic").weak());
+                }
+            });
+            ui.end_row();
+        });
+
+    ui.add_space(8.0);
+    ui.label(
+        RichText::new("Changing these reloads any loaded model.")
+            .size(12.0)
+            .color(colors::text_muted(ui.visuals())),
+    );
+}
+
+fn render_analysis_tab(ui: &mut egui::Ui, draft: &mut SettingsDraft) {
+    ui.label(RichText::new("Preprocessing").strong());
+    ui.add_space(4.0);
+    let preprocess = &mut draft.settings.preprocess;
+    ui.horizontal(|ui| {
+        ui.label("Unicode normalization");
+        egui::ComboBox::from_id_salt("normalization")
+            .selected_text(preprocess.normalization.to_string())
+            .show_ui(ui, |ui| {
+                for option in [Normalization::None, Normalization::Nfc, Normalization::Nfkc] {
+                    ui.selectable_value(&mut preprocess.normalization, option, option.to_string());
+                }
+            });
+    });
+    ui.checkbox(
+        &mut preprocess.plain_punctuation,
+        "Replace smart quotes and dashes with ASCII",
+    );
+    ui.checkbox(&mut preprocess.collapse_newlines, "Collapse blank lines");
+    ui.checkbox(&mut preprocess.strip_markdown, "Strip Markdown formatting");
+    ui.label(
+        RichText::new(
+            "Applied to the input and prompt before tokenization. A preview appears under the input.",
+        )
+        .size(12.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+
+    ui.add_space(12.0);
+    ui.label("Speaker label pattern");
+    ui.add(
+        egui::TextEdit::singleline(&mut draft.settings.speaker_pattern)
+            .font(egui::TextStyle::Monospace)
+            .hint_text("e.g. ^(\\w+):")
+            .desired_width(f32::INFINITY),
+    );
+    ui.label(
+        RichText::new(
+            "Regex matched at the start of each line of a transcript. The first capture group \
+             names the speaker; results then include per-speaker statistics. Leave empty to disable.",
+        )
+        .size(12.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+
+    ui.add_space(12.0);
+    ui.label(RichText::new("Perturbation test").strong());
+    ui.add_space(4.0);
+    egui::Grid::new("perturbation_grid")
+        .num_columns(2)
+        .spacing([12.0, 8.0])
+        .show(ui, |ui| {
+            ui.label("Variants");
+            ui.add(egui::DragValue::new(&mut draft.settings.perturbation_count).range(2..=200));
+            ui.end_row();
+
+            ui.label("Replaced tokens");
+            let mut percent = draft.settings.perturbation_rate * 100.0;
+            if ui
+                .add(
+                    egui::DragValue::new(&mut percent)
+                        .range(1.0..=50.0)
+                        .suffix("%"),
+                )
+                .changed()
+            {
+                draft.settings.perturbation_rate = percent / 100.0;
+            }
+            ui.end_row();
+        });
+
+    ui.add_space(12.0);
+    ui.horizontal(|ui| {
+        ui.label("Context isolation segments");
+        egui::ComboBox::from_id_salt("isolation_segments")
+            .selected_text(draft.settings.isolation_segments.to_string())
+            .show_ui(ui, |ui| {
+                for mode in SegmentMode::ALL {
                     ui.selectable_value(
-                        preload_mode,
-                        PreloadMode::NoPreload,
-                        PreloadMode::NoPreload.to_string(),
+                        &mut draft.settings.isolation_segments,
+                        mode,
+                        mode.to_string(),
                     );
-                });
+                }
+            });
+    });
 
-            ui.add_space(12.0);
+    ui.add_space(12.0);
+    let watermark = &mut draft.settings.watermark;
+    ui.checkbox(
+        &mut watermark.enabled,
+        RichText::new("Watermark detection").strong(),
+    );
+    ui.add_space(4.0);
+    ui.add_enabled_ui(watermark.enabled, |ui| {
+        egui::Grid::new("watermark_grid")
+            .num_columns(2)
+            .spacing([12.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Key");
+                ui.add(egui::DragValue::new(&mut watermark.key));
+                ui.end_row();
 
-            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                if ui.button("💾 Save").clicked() {
-                    action = Some(SettingsAction::Save);
+                ui.label("Green list fraction (γ)");
+                ui.add(
+                    egui::DragValue::new(&mut watermark.gamma)
+                        .range(0.05..=0.95)
+                        .speed(0.01),
+                );
+                ui.end_row();
+
+                ui.label("Context width");
+                ui.add(egui::DragValue::new(&mut watermark.context_width).range(1..=8));
+                ui.end_row();
+            });
+        ui.label(
+            RichText::new(
+                "Tests for a green/red list watermark seeded by the preceding tokens. \
+                 Only generators using the same key, γ and hash are detected.",
+            )
+            .size(12.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+    });
+
+    ui.add_space(12.0);
+    render_custom_metrics(ui, &mut draft.settings.custom_metrics);
+}
+
+/// Editor for the scripted metrics shown in the stats bar.
+fn render_custom_metrics(ui: &mut egui::Ui, metrics: &mut Vec<CustomMetric>) {
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("Custom metrics").strong());
+        if ui.small_button("➕ Add").clicked() {
+            metrics.push(CustomMetric {
+                name: format!("Metric {}", metrics.len() + 1),
+                script: "mean(surprisals)".to_string(),
+            });
+        }
+    });
+    ui.add_space(4.0);
+    let mut remove = None;
+    for (i, metric) in metrics.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut metric.name)
+                    .hint_text("Name")
+                    .desired_width(120.0),
+            );
+            if ui
+                .small_button("🗑")
+                .on_hover_text("Remove this metric")
+                .clicked()
+            {
+                remove = Some(i);
+            }
+            ui.add(
+                egui::TextEdit::multiline(&mut metric.script)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_rows(1)
+                    .desired_width(f32::INFINITY),
+            );
+        });
+    }
+    if let Some(i) = remove {
+        metrics.remove(i);
+    }
+    let variables = scripting::VARIABLES
+        .iter()
+        .map(|(name, meaning)| format!("`{}`: {}", name, meaning))
+        .collect::<Vec<_>>()
+        .join("; ");
+    ui.label(
+        RichText::new(format!(
+            "Rhai scripts evaluating to a number, shown in the stats bar. Arrays over the scored \
+             tokens: {}. Helpers: sum, mean, median, stdev; arrays also have map, filter and \
+             reduce. Example: mean(surprisals.filter(|s| s > 10.0))",
+            variables
+        ))
+        .size(12.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+}
+
+fn render_display_tab(ui: &mut egui::Ui, draft: &mut SettingsDraft) {
+    let theme = &mut draft.settings.theme;
+    ui.horizontal(|ui| {
+        ui.label("Theme");
+        egui::ComboBox::from_id_salt("theme")
+            .selected_text(theme.to_string())
+            .show_ui(ui, |ui| {
+                for option in [Theme::System, Theme::Dark, Theme::Light] {
+                    ui.selectable_value(theme, option, option.to_string());
                 }
             });
+    });
+    ui.add_space(8.0);
+    ui.checkbox(
+        &mut draft.settings.show_resource_monitor,
+        "Show resource monitor",
+    )
+    .on_hover_text(
+        "Plot CPU, RAM and tokens/sec during analysis, e.g. to tune threads and batch size",
+    );
+    ui.checkbox(
+        &mut draft.settings.notify_when_done,
+        "Notify when work finishes in the background",
+    )
+    .on_hover_text(
+        "Show a desktop notification when an analysis or model load finishes or fails \
+         while the Perplex window is unfocused",
+    );
+    ui.add_enabled_ui(draft.settings.notify_when_done, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Only for work taking at least");
+            ui.add(
+                egui::DragValue::new(&mut draft.settings.notify_after_secs)
+                    .range(0..=3600)
+                    .suffix(" s"),
+            );
         });
+    });
+    ui.checkbox(
+        &mut draft.settings.check_for_updates,
+        "Check for updates on startup",
+    )
+    .on_hover_text("Ask GitHub for the latest release; nothing else is sent");
 
-    action
+    ui.add_space(12.0);
+    ui.label(RichText::new("Summary row").strong());
+    ui.add_space(4.0);
+    ui.horizontal_wrapped(|ui| {
+        let summary = &mut draft.settings.summary_metrics;
+        for metric in SummaryMetric::ALL {
+            let mut checked = summary.contains(&metric);
+            if ui
+                .checkbox(&mut checked, metric.label())
+                .on_hover_text(metric.description())
+                .changed()
+            {
+                if checked {
+                    summary.push(metric);
+                } else {
+                    summary.retain(|&m| m != metric);
+                }
+            }
+        }
+    });
+    ui.label(
+        RichText::new(
+            "Numbers shown above the results. Warnings, custom metrics and the results of \
+             follow-up tests are always shown.",
+        )
+        .size(12.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+    ui.add_space(4.0);
+    let format = &mut draft.settings.number_format;
+    ui.horizontal(|ui| {
+        ui.label("Decimals");
+        ui.add(egui::DragValue::new(&mut format.decimals).range(0..=NumberFormat::MAX_DECIMALS))
+            .on_hover_text(
+                "Decimal places of perplexity and other numbers; raise it to compare close \
+                 quants. Percentages get one fewer, fractions one more.",
+            );
+        ui.add_space(12.0);
+        ui.label("Information in");
+        for unit in [InformationUnit::Bits, InformationUnit::Nats] {
+            ui.radio_value(&mut format.information, unit, unit.label());
+        }
+        ui.add_space(12.0);
+        ui.label("Shares as");
+        ui.radio_value(&mut format.shares, ShareStyle::Percent, "percent");
+        ui.radio_value(&mut format.shares, ShareStyle::Fraction, "fraction");
+    });
+    ui.label(
+        RichText::new("Applies to the summary row and to the metrics of every export.")
+            .size(12.0)
+            .color(colors::text_muted(ui.visuals())),
+    );
+
+    ui.add_space(12.0);
+    ui.label(RichText::new("Token tooltips").strong());
+    ui.add_space(4.0);
+    let tooltips = &mut draft.settings.tooltips;
+    ui.horizontal(|ui| {
+        ui.label("Delay");
+        ui.add(
+            egui::DragValue::new(&mut tooltips.delay_ms)
+                .range(0..=3000)
+                .speed(10.0)
+                .suffix(" ms"),
+        );
+        ui.add_space(12.0);
+        ui.label("Width");
+        ui.add(
+            egui::DragValue::new(&mut tooltips.width)
+                .range(TooltipOptions::WIDTH_RANGE)
+                .speed(4.0)
+                .suffix(" px"),
+        );
+    });
+    ui.checkbox(
+        &mut tooltips.instant_between,
+        "Skip the delay when moving from one token to the next",
+    )
+    .on_hover_text(
+        "Turn off so that scanning a dense heatmap with the mouse doesn't open a tooltip \
+         for every token passed",
+    );
+    ui.checkbox(&mut tooltips.pin_on_click, "Pin a token's tooltip on click")
+        .on_hover_text(
+            "Keep the tooltip open in a window until it's closed or another token is clicked",
+        );
+    ui.horizontal(|ui| {
+        ui.label("Show");
+        ui.checkbox(&mut tooltips.margin, "Top-1 margin");
+        ui.checkbox(&mut tooltips.surprisal, "Surprisal");
+        ui.checkbox(&mut tooltips.predictions, "Top predictions");
+    });
+
+    ui.add_space(12.0);
+    ui.label(RichText::new("Color expression").strong());
+    ui.add_space(4.0);
+    ui.add(
+        egui::TextEdit::singleline(&mut draft.settings.color_expression)
+            .font(egui::TextStyle::Monospace)
+            .hint_text("e.g. clamp(surprisal / 12)")
+            .desired_width(f32::INFINITY),
+    );
+    let fields = scripting::TOKEN_FIELDS
+        .iter()
+        .map(|(name, meaning)| format!("`{}`: {}", name, meaning))
+        .collect::<Vec<_>>()
+        .join("; ");
+    ui.label(
+        RichText::new(format!(
+            "Rhai expression evaluated for each token when coloring by Expression: 0 is green, \
+             1 is red. Variables: {}. clamp(x) limits x to 0–1.",
+            fields
+        ))
+        .size(12.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+}
+
+fn render_shortcuts_tab(ui: &mut egui::Ui, draft: &mut SettingsDraft) {
+    ui.label(
+        RichText::new("Type a key with optional modifiers, e.g. Ctrl+Shift+P or F5. Ctrl is Cmd on macOS; leave a field empty to unbind it.")
+            .size(12.0)
+            .color(colors::text_muted(ui.visuals())),
+    );
+    ui.add_space(8.0);
+
+    let keymap = &mut draft.settings.keymap;
+    egui::Grid::new("keymap")
+        .num_columns(2)
+        .spacing([16.0, 6.0])
+        .show(ui, |ui| {
+            for action in Action::ALL {
+                ui.label(action.label());
+                ui.add(egui::TextEdit::singleline(keymap.binding_mut(action)).desired_width(160.0));
+                ui.end_row();
+            }
+        });
+    ui.add_space(8.0);
+    if ui.button("Restore defaults").clicked() {
+        *keymap = Keymap::default();
+    }
+
+    ui.add_space(12.0);
+    let tray = &mut draft.settings.tray;
+    ui.checkbox(
+        &mut tray.hotkey,
+        RichText::new("System-wide paste-and-analyze shortcut").strong(),
+    );
+    ui.add_space(4.0);
+    ui.add_enabled_ui(tray.hotkey, |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut tray.shortcut)
+                .hint_text(tray::DEFAULT_SHORTCUT)
+                .desired_width(160.0),
+        );
+    });
+    ui.add(egui::Checkbox::new(&mut tray.icon, "Show an icon in the system tray"))
+        .on_hover_text("Its menu pastes and analyzes, raises the window or quits. Needs a build with the tray feature.");
+    ui.label(
+        RichText::new(
+            "Works while Perplex is in the background: the window comes to the front and \
+             analyzes the clipboard with the loaded model. On Linux the shortcut needs X11.",
+        )
+        .size(12.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+}
+
+fn render_export_tab(
+    ui: &mut egui::Ui,
+    draft: &mut SettingsDraft,
+    action: &mut Option<SettingsAction>,
+) {
+    ui.label(
+        RichText::new("Share a configuration as a JSON file. Imported settings are loaded into this dialog and take effect once applied.")
+            .size(12.0)
+            .color(colors::text_muted(ui.visuals())),
+    );
+    ui.add_space(8.0);
+
+    ui.horizontal(|ui| {
+        if ui
+            .button("📤 Export…")
+            .on_hover_text("Save the configuration shown in this dialog to a JSON file")
+            .clicked()
+        {
+            *action = Some(SettingsAction::Export);
+        }
+        if ui
+            .button("📥 Import…")
+            .on_hover_text("Load a configuration from a JSON file into this dialog")
+            .clicked()
+        {
+            *action = Some(SettingsAction::Import);
+        }
+    });
+
+    ui.add_space(12.0);
+    ui.label(RichText::new("Annotated plain text").strong());
+    ui.add_space(4.0);
+    let text_export = &mut draft.settings.text_export;
+    ui.horizontal(|ui| {
+        ui.label("Annotate tokens ranked");
+        ui.add(egui::DragValue::new(&mut text_export.min_rank).range(1..=100_000));
+        ui.label("or worse");
+    });
+    ui.checkbox(
+        &mut text_export.line_summary,
+        "End each line with its perplexity and worst rank",
+    );
+
+    ui.add_space(12.0);
+    ui.label(RichText::new("Analysis store").strong());
+    ui.add_space(4.0);
+    let store = &mut draft.settings.store;
+    ui.checkbox(&mut store.enabled, "Record every analysis")
+        .on_hover_text(
+            "Keep the metrics of each finished analysis in the SQLite database \
+             ~/.perplex/store/analyses.db",
+        );
+    ui.add_enabled(
+        store.enabled,
+        egui::Checkbox::new(&mut store.token_data, "Also keep the text and tokens"),
+    )
+    .on_hover_text("Lets stored analyses be shown again, at the cost of much more disk space");
+
+    ui.add_space(12.0);
+    let hook = &mut draft.settings.hook;
+    ui.checkbox(
+        &mut hook.enabled,
+        RichText::new("Run a command after each analysis").strong(),
+    );
+    ui.add_space(4.0);
+    ui.add_enabled_ui(hook.enabled, |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut hook.command)
+                .font(egui::TextStyle::Monospace)
+                .hint_text("e.g. curl -s -X POST -d @- https://example.com/webhook")
+                .desired_width(f32::INFINITY),
+        );
+        ui.horizontal(|ui| {
+            ui.label("Pass the result as");
+            egui::ComboBox::from_id_salt("hook_input")
+                .selected_text(hook.input.to_string())
+                .show_ui(ui, |ui| {
+                    for input in HookInput::ALL {
+                        ui.selectable_value(&mut hook.input, input, input.to_string());
+                    }
+                });
+        });
+        ui.label(
+            RichText::new(
+                "Runs through the shell once per model with the result as JSON: the model, \
+                 the text's name, the summary metrics and every token. PERPLEX_MODEL and \
+                 PERPLEX_PERPLEXITY are set too. Output and failures go to the log.",
+            )
+            .size(12.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+    });
+
+    ui.add_space(12.0);
+    let watch = &mut draft.settings.watch;
+    ui.checkbox(
+        &mut watch.enabled,
+        RichText::new("Analyze texts dropped into a folder").strong(),
+    );
+    ui.add_space(4.0);
+    ui.add_enabled_ui(watch.enabled, |ui| {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut watch.folder)
+                    .hint_text("Inbox folder")
+                    .desired_width(ui.available_width() - 80.0),
+            );
+            if ui.button("Browse…").clicked() {
+                *action = Some(SettingsAction::BrowseWatchFolder);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Write next to each text:");
+            ui.checkbox(&mut watch.json, "JSON");
+            ui.checkbox(&mut watch.html, "HTML report");
+        });
+        ui.label(
+            RichText::new(
+                "New .txt and .md files are queued with every loaded model; notes.txt gets \
+                 notes.perplex.json and notes.perplex.html. Files whose results are newer \
+                 than the text are skipped.",
+            )
+            .size(12.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+    });
 }
 
 fn render_model_group(
@@ -74,7 +1018,8 @@ fn render_model_group(
     label: &str,
     path_buffer: &mut String,
     action: &mut Option<SettingsAction>,
-    slot: ModelSlot,
+    browse: SettingsAction,
+    clear: SettingsAction,
 ) {
     ui.group(|ui| {
         ui.label(RichText::new(label).strong());
@@ -92,11 +1037,61 @@ fn render_model_group(
 
         ui.horizontal(|ui| {
             if ui.button("📂 Browse…").clicked() {
-                *action = Some(SettingsAction::Browse(slot));
+                *action = Some(browse);
             }
             if !path_buffer.is_empty() && ui.button("❌ Clear").clicked() {
-                *action = Some(SettingsAction::Clear(slot));
+                *action = Some(clear);
             }
         });
     });
 }
+
+/// Reference-corpus status and controls for the applied model of a slot,
+/// in the domain picked next to the input.
+fn render_reference_row(
+    ui: &mut egui::Ui,
+    reference: Option<&ReferenceStats>,
+    domain: Domain,
+    action: &mut Option<SettingsAction>,
+    slot: ModelSlot,
+) {
+    ui.horizontal(|ui| {
+        let status = match reference {
+            Some(r) => format!(
+                "{} reference: {} texts, median PPL {:.2}",
+                r.domain.label(),
+                r.perplexities.len(),
+                r.median().unwrap_or_default()
+            ),
+            None => format!("No {} reference corpus", domain.label().to_lowercase()),
+        };
+        ui.label(
+            RichText::new(status)
+                .size(12.0)
+                .color(colors::text_muted(ui.visuals())),
+        );
+
+        if ui
+            .small_button("Build from files…")
+            .on_hover_text(
+                "Score known-human texts with this model to place results on a percentile scale",
+            )
+            .clicked()
+        {
+            *action = Some(SettingsAction::BuildReference(slot));
+        }
+        if ui
+            .small_button("Import…")
+            .on_hover_text("Use a reference file built elsewhere for this model")
+            .clicked()
+        {
+            *action = Some(SettingsAction::ImportReference(slot));
+        }
+    });
+}
+
+/// Regex errors span several lines with a caret diagram and end with the
+/// actual message; the dialog only has room for that last line.
+fn error_summary(message: &str) -> &str {
+    message.lines().last().unwrap_or(message)
+}
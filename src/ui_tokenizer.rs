@@ -0,0 +1,159 @@
+use egui::{FontId, RichText, Ui};
+
+use crate::colors;
+use crate::llamacpp::TokenInfo;
+use crate::ModelSlot;
+
+const ROW_HEIGHT: f32 = 18.0;
+
+pub struct TokenizerState {
+    pub text: String,
+    pub add_bos: bool,
+    pub slot: ModelSlot,
+    /// Tokens of the last run and the slot that produced them.
+    pub tokens: Option<(ModelSlot, Vec<TokenInfo>)>,
+    pub pending: bool,
+}
+
+impl Default for TokenizerState {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            add_bos: true,
+            slot: ModelSlot::A,
+            tokens: None,
+            pending: false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TokenizerAction {
+    pub tokenize: bool,
+    /// The text or options changed; re-tokenize if the model is ready.
+    pub changed: bool,
+}
+
+/// Tokenizer playground: tokenizes text with a model's vocabulary without
+/// running inference.
+pub fn render_tokenizer_tab(
+    ui: &mut Ui,
+    state: &mut TokenizerState,
+    configured: [bool; 2],
+) -> TokenizerAction {
+    let mut action = TokenizerAction::default();
+    ui.add_space(12.0);
+
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("Model").size(13.0));
+        for slot in ModelSlot::ALL {
+            if configured[slot.index()] {
+                action.changed |= ui
+                    .selectable_value(&mut state.slot, slot, slot.label())
+                    .changed();
+            }
+        }
+        ui.add_space(12.0);
+        action.changed |= ui
+            .checkbox(&mut state.add_bos, "Add BOS")
+            .on_hover_text("Prepend the beginning-of-sequence token, as analysis does")
+            .changed();
+        ui.add_space(12.0);
+        let can_tokenize = configured[state.slot.index()] && !state.pending;
+        if ui
+            .add_enabled(can_tokenize, egui::Button::new("🔤 Tokenize"))
+            .clicked()
+        {
+            action.tokenize = true;
+        }
+        if state.pending {
+            ui.spinner();
+        }
+    });
+
+    ui.add_space(8.0);
+    egui::ScrollArea::vertical()
+        .id_salt("tokenizer_input_scroll")
+        .max_height(160.0)
+        .show(ui, |ui| {
+            action.changed |= ui
+                .add(
+                    egui::TextEdit::multiline(&mut state.text)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(5)
+                        .font(FontId::monospace(14.0))
+                        .hint_text("Text to tokenize…"),
+                )
+                .changed();
+        });
+
+    let Some((slot, tokens)) = &state.tokens else {
+        return action;
+    };
+
+    ui.add_space(12.0);
+    let special = tokens.iter().filter(|t| t.special).count();
+    let bytes: usize = tokens.iter().map(|t| t.bytes).sum();
+    let chars = state.text.chars().count();
+    ui.label(
+        RichText::new(format!(
+            "{}: {} tokens ({} special) · {} bytes · {:.2} chars/token",
+            slot.label(),
+            tokens.len(),
+            special,
+            bytes,
+            chars as f32 / tokens.len().max(1) as f32
+        ))
+        .size(12.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+    ui.add_space(6.0);
+
+    ui.horizontal(|ui| {
+        for (header, width) in [("#", 50.0), ("ID", 70.0), ("Bytes", 50.0), ("Token", 0.0)] {
+            render_cell(ui, RichText::new(header).strong().size(12.0), width);
+        }
+    });
+    ui.separator();
+    egui::ScrollArea::vertical()
+        .id_salt("tokenizer_rows")
+        .auto_shrink([false, true])
+        .show_rows(ui, ROW_HEIGHT, tokens.len(), |ui, range| {
+            for i in range {
+                let token = &tokens[i];
+                ui.horizontal(|ui| {
+                    render_cell(ui, RichText::new(i.to_string()).size(12.0), 50.0);
+                    render_cell(
+                        ui,
+                        RichText::new(token.id.to_string()).monospace().size(12.0),
+                        70.0,
+                    );
+                    render_cell(ui, RichText::new(token.bytes.to_string()).size(12.0), 50.0);
+                    let text = token.text.replace('\n', "↵").replace('\t', "→");
+                    let mut label = RichText::new(format!("«{}»", text)).monospace().size(12.0);
+                    if token.special {
+                        label = label.color(colors::WARNING).strong();
+                    }
+                    ui.label(label);
+                    if token.special {
+                        ui.label(
+                            RichText::new("special")
+                                .size(10.0)
+                                .color(colors::text_muted(ui.visuals())),
+                        );
+                    }
+                });
+            }
+        });
+
+    action
+}
+
+/// Fixed-width table cell; a width of zero takes the rest of the row.
+fn render_cell(ui: &mut Ui, text: RichText, width: f32) {
+    if width > 0.0 {
+        ui.add_sized([width, ROW_HEIGHT], egui::Label::new(text));
+    } else {
+        ui.label(text);
+    }
+}
@@ -1,7 +1,54 @@
-use crate::analysis::AnalyzedToken;
+use crate::analysis::{AnalysisResult, AnalyzedToken, Vocab, CURVE_RANKS};
 use crate::colors;
-use crate::ui_main::UnifiedColorMode;
+use crate::confusables::Confusable;
+use crate::ui_main::{DisplayOptions, ResultView, TokenColorMode, UnifiedColorMode};
 use egui::{Color32, RichText, Ui, Vec2};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// How token tooltips behave and what they show. The rank is always
+/// shown.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TooltipOptions {
+    /// Hover time before a tooltip opens.
+    pub delay_ms: u32,
+    /// Open the next tooltip at once after one was shown. Off, moving over
+    /// a dense heatmap doesn't flash a tooltip for every token passed.
+    pub instant_between: bool,
+    /// Clicking a token keeps its tooltip open in a window.
+    pub pin_on_click: bool,
+    pub width: f32,
+    pub margin: bool,
+    pub surprisal: bool,
+    pub predictions: bool,
+}
+
+impl Default for TooltipOptions {
+    fn default() -> Self {
+        Self {
+            delay_ms: 500,
+            instant_between: true,
+            pin_on_click: false,
+            width: 340.0,
+            margin: true,
+            surprisal: true,
+            predictions: true,
+        }
+    }
+}
+
+impl TooltipOptions {
+    pub const WIDTH_RANGE: RangeInclusive<f32> = 240.0..=720.0;
+
+    /// Applies the timing to every tooltip of the app.
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.style_mut(|style| {
+            style.interaction.tooltip_delay = self.delay_ms as f32 / 1000.0;
+            style.interaction.tooltip_grace_time = if self.instant_between { 0.2 } else { 0.0 };
+        });
+    }
+}
 
 // ── Shared helpers ──────────────────────────────────────────────────────────
 
@@ -9,19 +56,141 @@ fn format_display_text(text: &str) -> String {
     text.replace('\n', "↵").replace('\t', "→")
 }
 
-fn render_token_label(ui: &mut Ui, display_text: &str, bg_color: Color32) -> egui::Response {
-    ui.add(
-        egui::Label::new(
-            RichText::new(display_text)
-                .color(Color32::BLACK)
-                .background_color(bg_color)
-                .size(14.0)
-                .family(egui::FontFamily::Monospace),
-        )
-        .sense(egui::Sense::hover()),
+/// Tooltip header of a token: its text, or its badge for special tokens.
+fn token_header(token: &AnalyzedToken) -> String {
+    match token.special {
+        Some(ref label) => format!("⟨{}⟩", label),
+        None => token.text.to_string(),
+    }
+}
+
+/// Control tokens render as an outlined badge with their name instead of
+/// their raw piece, colored like a regular token when scored.
+fn render_special_badge(
+    ui: &mut Ui,
+    label: &str,
+    color: Option<Color32>,
+    sense: egui::Sense,
+) -> egui::Response {
+    let stroke_color = color.unwrap_or_else(|| colors::text_muted(ui.visuals()));
+    let text = RichText::new(format!("⟨{}⟩", label))
+        .size(11.0)
+        .family(egui::FontFamily::Monospace)
+        .color(colors::text_muted(ui.visuals()));
+    egui::Frame::none()
+        .stroke(egui::Stroke::new(1.5, stroke_color))
+        .rounding(3.0)
+        .inner_margin(egui::Margin::symmetric(3.0, 0.0))
+        .outer_margin(egui::Margin::symmetric(1.0, 0.0))
+        .show(ui, |ui| ui.add(egui::Label::new(text).selectable(false)))
+        .response
+        .interact(sense)
+}
+
+fn render_token_label(
+    ui: &mut Ui,
+    display_text: &str,
+    bg_color: Color32,
+    (underline, italics): (bool, bool),
+    sense: egui::Sense,
+) -> egui::Response {
+    let mut text = RichText::new(display_text)
+        .color(Color32::BLACK)
+        .background_color(bg_color)
+        .size(14.0)
+        .family(egui::FontFamily::Monospace);
+    if underline {
+        text = text.underline();
+    }
+    if italics {
+        text = text.italics();
+    }
+    ui.add(egui::Label::new(text).sense(sense).selectable(false))
+}
+
+/// Thin marker before the first token scored in a new evaluation window,
+/// so a spike right after it can be told apart from genuine surprisal.
+fn render_window_marker(ui: &mut Ui, context_start: usize) {
+    ui.label(RichText::new("▏").size(14.0).color(colors::INFO))
+        .on_hover_text(format!(
+            "New context window: from here on the model saw the text from token #{}",
+            context_start
+        ));
+}
+
+/// Tooltip line for a token predicted without the whole preceding text.
+fn render_context_note(ui: &mut Ui, token: &AnalyzedToken) {
+    if token.context_start > 0 {
+        ui.label(
+            RichText::new(format!(
+                "✂ Truncated context: started at token #{}",
+                token.context_start
+            ))
+            .size(11.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+    }
+}
+
+/// Scored tokens are clickable while context inspection is on.
+fn token_sense(options: &DisplayOptions) -> egui::Sense {
+    if options.inspect_context {
+        egui::Sense::click()
+    } else {
+        egui::Sense::click_and_drag()
+    }
+}
+
+/// How the pointer picked a token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenGesture {
+    Click,
+    ShiftClick,
+    DragStart,
+    /// The pointer is over the token while a drag is in progress.
+    DragOver,
+}
+
+fn token_gesture(ui: &Ui, response: &egui::Response) -> Option<TokenGesture> {
+    if response.clicked() {
+        return Some(if ui.input(|i| i.modifiers.shift) {
+            TokenGesture::ShiftClick
+        } else {
+            TokenGesture::Click
+        });
+    }
+    if response.drag_started() {
+        return Some(TokenGesture::DragStart);
+    }
+    let dragging = ui.input(|i| i.pointer.primary_down()) && ui.ctx().dragged_id().is_some();
+    (dragging && ui.rect_contains_pointer(response.rect)).then_some(TokenGesture::DragOver)
+}
+
+/// Outlines a token that is part of the selection.
+fn paint_selected(ui: &Ui, response: &egui::Response) {
+    ui.painter().rect_stroke(
+        response.rect,
+        0.0,
+        egui::Stroke::new(1.5, colors::ACCENT_PRIMARY),
+    );
+}
+
+/// What a screen reader announces for a scored token, which otherwise
+/// carries its rank only as a color.
+fn token_description(token: &AnalyzedToken) -> String {
+    format!(
+        "{}, rank {}, probability {:.1}%",
+        token_header(token),
+        token.rank,
+        token.probability * 100.0
     )
 }
 
+fn describe_widget(response: &egui::Response, description: impl Fn() -> String) {
+    response
+        .widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Label, true, description()));
+}
+
 fn render_tooltip_header(ui: &mut Ui, token_text: &str) {
     let display = format_display_text(token_text);
     ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
@@ -38,80 +207,213 @@ fn render_tooltip_header(ui: &mut Ui, token_text: &str) {
 
 // ── Split-view token rendering ──────────────────────────────────────────────
 
+/// `other` is the other model's result and name, when its tokens line up
+/// with this one's. Tokens in `selected` are outlined. Returns the index of
+/// a token picked with the pointer and how.
 pub fn render_analyzed_tokens(
     ui: &mut Ui,
-    tokens: &[AnalyzedToken],
-    other_tokens: Option<&[AnalyzedToken]>,
-    self_label: &str,
-    other_label: &str,
-) {
+    view: &ResultView,
+    other: Option<(&AnalysisResult, &str)>,
+    options: &DisplayOptions,
+    selected: Option<RangeInclusive<usize>>,
+) -> Option<(usize, TokenGesture)> {
+    let (result, self_label) = (view.result, view.name);
+    let (other_result, other_label) = other.unzip();
+    let other_label = other_label.unwrap_or_default();
+    let expression = view.token_colors.and_then(|c| c.as_ref().ok());
+    let mut picked = None;
     ui.horizontal_wrapped(|ui| {
         ui.spacing_mut().item_spacing = Vec2::new(0.0, 4.0);
 
-        for (i, token) in tokens.iter().enumerate() {
-            let other = other_tokens.and_then(|ot| ot.get(i));
-            render_token(ui, token, other, self_label, other_label);
+        let mut window_start = 0;
+        for (i, token) in result.tokens.iter().enumerate() {
+            let new_window = token.context_start != window_start;
+            window_start = token.context_start;
+            if i < result.prompt_tokens {
+                render_prompt_token(ui, token);
+                continue;
+            }
+            if new_window && options.show_windows {
+                render_window_marker(ui, token.context_start);
+            }
+            let other = other_result.and_then(|r| Some((r.tokens.get(i)?, &*r.vocab)));
+            let is_selected = selected.as_ref().is_some_and(|s| s.contains(&i));
+            let bg_color = match options.color_mode {
+                TokenColorMode::Rank => colors::rank_to_color(token.rank),
+                TokenColorMode::Margin => colors::margin_to_color(token.top1_margin),
+                TokenColorMode::Expression => match expression.and_then(|v| v.get(i)) {
+                    Some(&value) => colors::scale_to_color(value),
+                    None => colors::rank_to_color(token.rank),
+                },
+            };
+            let lookalikes = {
+                let start = view.confusables.partition_point(|(t, _)| *t < i);
+                let end = view.confusables.partition_point(|(t, _)| *t <= i);
+                &view.confusables[start..end]
+            };
+            if let Some(gesture) = render_token(
+                ui,
+                (token, &result.vocab),
+                other,
+                bg_color,
+                (self_label, other_label),
+                options,
+                (is_selected, lookalikes),
+            ) {
+                picked = Some((i, gesture));
+            }
         }
+        render_unscored(ui, &result.unscored);
     });
+    picked
 }
 
 fn render_token(
     ui: &mut Ui,
-    token: &AnalyzedToken,
-    other_token: Option<&AnalyzedToken>,
-    self_label: &str,
-    other_label: &str,
-) {
-    let bg_color = colors::rank_to_color(token.rank);
-    let display_text = format_display_text(&token.text);
-
-    let response = render_token_label(ui, &display_text, bg_color);
+    (token, vocab): (&AnalyzedToken, &Vocab),
+    other_token: Option<(&AnalyzedToken, &Vocab)>,
+    bg_color: Color32,
+    (self_label, other_label): (&str, &str),
+    options: &DisplayOptions,
+    (selected, lookalikes): (bool, &[(usize, Confusable)]),
+) -> Option<TokenGesture> {
+    let sense = token_sense(options);
+    let response = match token.special {
+        Some(ref label) => render_special_badge(ui, label, Some(bg_color), sense),
+        None => {
+            let display_text = format_display_text(&token.text);
+            let underline = options.highlight_repeats && token.repeated;
+            let truncated = options.show_windows && token.context_start > 0;
+            render_token_label(ui, &display_text, bg_color, (underline, truncated), sense)
+        }
+    };
+    describe_widget(&response, || token_description(token));
+    if selected {
+        paint_selected(ui, &response);
+    } else if !lookalikes.is_empty() {
+        ui.painter()
+            .rect_stroke(response.rect, 0.0, egui::Stroke::new(1.5, colors::ERROR));
+    }
+    let gesture = token_gesture(ui, &response);
 
     response.on_hover_ui(|ui| {
-        ui.set_max_width(340.0);
-        ui.set_min_width(340.0);
+        ui.set_max_width(options.tooltips.width);
+        ui.set_min_width(options.tooltips.width);
 
-        render_tooltip_header(ui, &token.text);
+        render_tooltip_header(ui, &token_header(token));
+        render_context_note(ui, token);
+        for (_, lookalike) in lookalikes {
+            ui.label(
+                RichText::new(format!("⚠ {}", lookalike.describe()))
+                    .size(11.0)
+                    .color(colors::ERROR),
+            );
+        }
 
         if let Some(other) = other_token {
-            render_comparison_tooltip(ui, token, other, self_label, other_label);
+            render_comparison_tooltip(
+                ui,
+                (token, vocab),
+                other,
+                (self_label, other_label),
+                &options.tooltips,
+            );
         } else {
-            render_single_tooltip(ui, token);
+            render_single_tooltip(ui, token, vocab, &options.tooltips);
         }
     });
 
     if token.text.contains('\n') {
         ui.end_row();
     }
+    gesture
+}
+
+/// Text past the scoring limit, in a neutral style so it isn't mistaken
+/// for analyzed tokens.
+fn render_unscored(ui: &mut Ui, pieces: &[String]) {
+    for piece in pieces {
+        let response = ui.label(
+            RichText::new(format_display_text(piece))
+                .color(colors::text_muted(ui.visuals()))
+                .strikethrough()
+                .size(14.0)
+                .family(egui::FontFamily::Monospace),
+        );
+        describe_widget(&response, || format!("{}, not scored", piece));
+        response.on_hover_text("Not scored: past the scoring limit");
+        if piece.contains('\n') {
+            ui.end_row();
+        }
+    }
+}
+
+/// Prompt tokens condition the model but aren't scored, so they get no
+/// color or tooltip.
+fn render_prompt_token(ui: &mut Ui, token: &AnalyzedToken) {
+    if let Some(ref label) = token.special {
+        let response = render_special_badge(ui, label, None, egui::Sense::hover());
+        describe_widget(&response, || format!("{}, prompt", token_header(token)));
+        response.on_hover_text("Prompt (not scored)");
+        return;
+    }
+    let response = ui.label(
+        RichText::new(format_display_text(&token.text))
+            .color(colors::text_muted(ui.visuals()))
+            .size(14.0)
+            .family(egui::FontFamily::Monospace),
+    );
+    describe_widget(&response, || format!("{}, prompt", token.text));
+    response.on_hover_text("Prompt (not scored)");
+    if token.text.contains('\n') {
+        ui.end_row();
+    }
 }
 
 // ── Unified-view token rendering ────────────────────────────────────────────
 
+/// Tokens in `selected` are outlined. Returns the index of a token picked
+/// with the pointer and how.
 pub fn render_unified_tokens(
     ui: &mut Ui,
-    tokens_a: &[AnalyzedToken],
-    tokens_b: &[AnalyzedToken],
-    label_a: &str,
-    label_b: &str,
-    color_mode: UnifiedColorMode,
-) {
+    result_a: &AnalysisResult,
+    result_b: &AnalysisResult,
+    (label_a, label_b): (&str, &str),
+    options: &DisplayOptions,
+    selected: Option<RangeInclusive<usize>>,
+) -> Option<(usize, TokenGesture)> {
+    let (tokens_a, tokens_b) = (&result_a.tokens, &result_b.tokens);
+    let (vocab_a, vocab_b) = (&*result_a.vocab, &*result_b.vocab);
+    let sense = token_sense(options);
+    let mut picked = None;
     ui.horizontal_wrapped(|ui| {
         ui.spacing_mut().item_spacing = Vec2::new(0.0, 4.0);
 
+        let mut window_start = 0;
         let len = tokens_a.len().max(tokens_b.len());
         for i in 0..len {
             let tok_a = tokens_a.get(i);
             let tok_b = tokens_b.get(i);
 
             let display_token = tok_a.or(tok_b).unwrap();
-            let display_text = format_display_text(&display_token.text);
-
+            let new_window = display_token.context_start != window_start;
+            window_start = display_token.context_start;
+            if i < result_a.prompt_tokens.max(result_b.prompt_tokens) {
+                render_prompt_token(ui, display_token);
+                continue;
+            }
+            if new_window && options.show_windows {
+                render_window_marker(ui, display_token.context_start);
+            }
             let bg_color = match (tok_a, tok_b) {
-                (Some(a), Some(b)) => match color_mode {
+                (Some(a), Some(b)) => match options.unified_color_mode {
                     UnifiedColorMode::AvgRank => colors::average_rank_color(a.rank, b.rank),
                     UnifiedColorMode::AvgProbability => {
                         colors::average_prob_color(a.probability, b.probability)
                     }
+                    UnifiedColorMode::AvgMargin => {
+                        colors::margin_to_color((a.top1_margin + b.top1_margin) / 2.0)
+                    }
                     UnifiedColorMode::RankDivergence => {
                         colors::rank_divergence_color(a.rank, b.rank)
                     }
@@ -124,18 +426,52 @@ pub fn render_unified_tokens(
                 (None, None) => unreachable!(),
             };
 
-            let response = render_token_label(ui, &display_text, bg_color);
+            let response = match display_token.special {
+                Some(ref label) => render_special_badge(ui, label, Some(bg_color), sense),
+                None => {
+                    let display_text = format_display_text(&display_token.text);
+                    let underline = options.highlight_repeats && display_token.repeated;
+                    let truncated = options.show_windows && display_token.context_start > 0;
+                    render_token_label(ui, &display_text, bg_color, (underline, truncated), sense)
+                }
+            };
+            if selected.as_ref().is_some_and(|s| s.contains(&i)) {
+                paint_selected(ui, &response);
+            }
+            if let Some(gesture) = token_gesture(ui, &response) {
+                picked = Some((i, gesture));
+            }
+            describe_widget(&response, || match (tok_a, tok_b) {
+                (Some(a), Some(b)) => format!(
+                    "{}, {} rank {}, {} rank {}",
+                    token_header(display_token),
+                    label_a,
+                    a.rank,
+                    label_b,
+                    b.rank
+                ),
+                _ => token_description(display_token),
+            });
 
             response.on_hover_ui(|ui| {
-                ui.set_max_width(320.0);
-                ui.set_min_width(320.0);
+                ui.set_max_width(options.tooltips.width);
+                ui.set_min_width(options.tooltips.width);
 
-                render_tooltip_header(ui, &display_token.text);
+                render_tooltip_header(ui, &token_header(display_token));
+                render_context_note(ui, display_token);
 
                 if let (Some(a), Some(b)) = (tok_a, tok_b) {
-                    render_comparison_tooltip(ui, a, b, label_a, label_b);
-                } else if let Some(t) = tok_a.or(tok_b) {
-                    render_single_tooltip(ui, t);
+                    render_comparison_tooltip(
+                        ui,
+                        (a, vocab_a),
+                        (b, vocab_b),
+                        (label_a, label_b),
+                        &options.tooltips,
+                    );
+                } else if let Some(a) = tok_a {
+                    render_single_tooltip(ui, a, vocab_a, &options.tooltips);
+                } else if let Some(b) = tok_b {
+                    render_single_tooltip(ui, b, vocab_b, &options.tooltips);
                 }
             });
 
@@ -143,17 +479,64 @@ pub fn render_unified_tokens(
                 ui.end_row();
             }
         }
+        let unscored = if result_a.unscored.len() >= result_b.unscored.len() {
+            &result_a.unscored
+        } else {
+            &result_b.unscored
+        };
+        render_unscored(ui, unscored);
     });
+    picked
 }
 
 // ── Tooltips ────────────────────────────────────────────────────────────────
 
+/// A token's tooltip kept open in a window, for the token at `index` of
+/// each view; with two views, side by side as when hovering the unified
+/// view.
+pub fn render_pinned_tooltip(
+    ctx: &egui::Context,
+    views: &[&ResultView],
+    index: usize,
+    tooltips: &TooltipOptions,
+    open: &mut bool,
+) {
+    let tokens: Vec<(&AnalyzedToken, &Vocab, &str)> = views
+        .iter()
+        .filter_map(|v| Some((v.result.tokens.get(index)?, &*v.result.vocab, v.name)))
+        .collect();
+    let Some(&(first, _, _)) = tokens.first() else {
+        *open = false;
+        return;
+    };
+    egui::Window::new(format!("📌 {}", format_display_text(&token_header(first))))
+        .id(egui::Id::new("pinned_tooltip"))
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .default_width(tooltips.width)
+        .show(ctx, |ui| {
+            ui.set_max_width(tooltips.width);
+            render_context_note(ui, first);
+            match tokens[..] {
+                [(a, vocab_a, label_a), (b, vocab_b, label_b)] => render_comparison_tooltip(
+                    ui,
+                    (a, vocab_a),
+                    (b, vocab_b),
+                    (label_a, label_b),
+                    tooltips,
+                ),
+                _ => render_single_tooltip(ui, first, tokens[0].1, tooltips),
+            }
+        });
+}
+
 fn render_comparison_tooltip(
     ui: &mut Ui,
-    token: &AnalyzedToken,
-    other: &AnalyzedToken,
-    self_label: &str,
-    other_label: &str,
+    (token, vocab): (&AnalyzedToken, &Vocab),
+    (other, other_vocab): (&AnalyzedToken, &Vocab),
+    (self_label, other_label): (&str, &str),
+    tooltips: &TooltipOptions,
 ) {
     ui.separator();
     ui.add_space(4.0);
@@ -186,8 +569,29 @@ fn render_comparison_tooltip(
             render_prob_label(ui, token.probability);
             render_prob_label(ui, other.probability);
             ui.end_row();
+
+            if tooltips.margin {
+                ui.label(RichText::new("Margin").size(11.0))
+                    .on_hover_text("Log-probability gap to the top prediction, in nats");
+                ui.label(RichText::new(format!("{:.2}", token.top1_margin)).size(11.0));
+                ui.label(RichText::new(format!("{:.2}", other.top1_margin)).size(11.0));
+                ui.end_row();
+            }
+
+            if tooltips.surprisal {
+                ui.label(RichText::new("vs unigram").size(11.0))
+                    .on_hover_text(
+                        "Model surprisal minus the token's frequency surprisal in this text",
+                    );
+                render_relative_surprisal(ui, token);
+                render_relative_surprisal(ui, other);
+                ui.end_row();
+            }
         });
 
+    if !tooltips.predictions {
+        return;
+    }
     ui.add_space(6.0);
     ui.separator();
     ui.add_space(4.0);
@@ -200,7 +604,7 @@ fn render_comparison_tooltip(
                     .size(11.0)
                     .color(colors::INFO),
             );
-            render_prediction_list(ui, &token.top_predictions);
+            render_prediction_list(ui, &token.top_predictions, vocab);
         });
 
         ui.add_space(12.0);
@@ -214,18 +618,52 @@ fn render_comparison_tooltip(
                     .size(11.0)
                     .color(colors::WARNING),
             );
-            render_prediction_list(ui, &other.top_predictions);
+            render_prediction_list(ui, &other.top_predictions, other_vocab);
         });
     });
 }
 
-fn render_single_tooltip(ui: &mut Ui, token: &AnalyzedToken) {
+fn render_single_tooltip(
+    ui: &mut Ui,
+    token: &AnalyzedToken,
+    vocab: &Vocab,
+    tooltips: &TooltipOptions,
+) {
     ui.label(RichText::new(format!("Rank: {}", token.rank)).size(12.0));
+    if token.rank > 1 && tooltips.margin {
+        ui.label(
+            RichText::new(format!(
+                "Top-1 margin: {:.2} nats below the top prediction",
+                token.top1_margin
+            ))
+            .size(12.0),
+        );
+    }
+    if token.repeated {
+        ui.label(
+            RichText::new("↻ Repeats earlier text")
+                .size(11.0)
+                .color(colors::WARNING),
+        );
+    }
+    if token.probability > 0.0 && tooltips.surprisal {
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!(
+                    "Surprisal: {:.1} bits (unigram {:.1})",
+                    token.surprisal(),
+                    token.unigram_surprisal
+                ))
+                .size(12.0),
+            );
+            render_relative_surprisal(ui, token);
+        });
+    }
 
-    if !token.top_predictions.is_empty() {
+    if !token.top_predictions.is_empty() && tooltips.predictions {
         ui.add_space(6.0);
         ui.label(RichText::new("Top Predictions:").strong().size(11.0));
-        render_prediction_list(ui, &token.top_predictions);
+        render_prediction_list(ui, &token.top_predictions, vocab);
     }
 }
 
@@ -258,14 +696,34 @@ fn render_prob_label(ui: &mut Ui, prob: f32) {
     );
 }
 
-fn render_prediction_list(ui: &mut Ui, predictions: &[(String, f32)]) {
+/// Signed difference to the unigram baseline: red when the model was more
+/// surprised than the token's frequency explains, green when less.
+fn render_relative_surprisal(ui: &mut Ui, token: &AnalyzedToken) {
+    if token.probability <= 0.0 {
+        ui.label(RichText::new("—").size(11.0));
+        return;
+    }
+    let delta = token.relative_surprisal();
+    let color = if delta > 0.0 {
+        colors::ERROR
+    } else {
+        colors::SUCCESS
+    };
+    ui.label(
+        RichText::new(format!("{:+.1} bits", delta))
+            .size(11.0)
+            .color(color),
+    );
+}
+
+fn render_prediction_list(ui: &mut Ui, predictions: &[(i32, f32)], vocab: &Vocab) {
     if predictions.is_empty() {
         ui.label(RichText::new("—").size(11.0));
         return;
     }
-    for (i, (pred_text, prob)) in predictions.iter().enumerate() {
-        let display = pred_text.replace('\n', "↵").replace('\t', "→");
-        let pct = if *prob < 0.01 {
+    for (i, &(id, prob)) in predictions.iter().enumerate() {
+        let display = format_display_text(vocab.piece(id));
+        let pct = if prob < 0.01 {
             "<1%".to_string()
         } else {
             format!("{:.0}%", prob * 100.0)
@@ -281,3 +739,154 @@ fn render_prediction_list(ui: &mut Ui, predictions: &[(String, f32)]) {
         });
     }
 }
+
+// ── Top-k chart ─────────────────────────────────────────────────────────────
+
+const CHART_BAR_WIDTH: f32 = 180.0;
+const CHART_LABEL_WIDTH: f32 = 110.0;
+
+/// Bar chart of the model's top predictions for `token`, with the actual
+/// token highlighted, or added as a last bar when it ranked lower, and the
+/// running total of probability beside each bar.
+pub fn render_top_k_chart(ui: &mut Ui, token: &AnalyzedToken, vocab: &Vocab) {
+    if token.top_predictions.is_empty() {
+        return;
+    }
+    let actual_in_top = token.top_predictions.iter().any(|&(id, _)| id == token.id);
+    let mut cumulative = 0.0;
+    for (i, &(id, prob)) in token.top_predictions.iter().enumerate() {
+        cumulative += prob;
+        render_chart_bar(
+            ui,
+            i + 1,
+            vocab.piece(id),
+            prob,
+            Some(cumulative),
+            id == token.id,
+        );
+    }
+    if !actual_in_top {
+        render_chart_bar(ui, token.rank, &token.text, token.probability, None, true);
+    }
+    ui.label(
+        RichText::new(format!(
+            "The top {} predictions hold {:.0}% of the probability",
+            token.top_predictions.len(),
+            cumulative * 100.0
+        ))
+        .size(11.0)
+        .color(colors::text_muted(ui.visuals())),
+    );
+}
+
+fn render_chart_bar(
+    ui: &mut Ui,
+    rank: usize,
+    piece: &str,
+    prob: f32,
+    cumulative: Option<f32>,
+    actual: bool,
+) {
+    ui.horizontal(|ui| {
+        let mut label = RichText::new(format!("{}. {}", rank, format_display_text(piece)))
+            .monospace()
+            .size(11.0);
+        if actual {
+            label = label.strong();
+        }
+        ui.add_sized(
+            [CHART_LABEL_WIDTH, 14.0],
+            egui::Label::new(label).truncate(),
+        );
+        let (rect, _) =
+            ui.allocate_exact_size(Vec2::new(CHART_BAR_WIDTH, 12.0), egui::Sense::hover());
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, colors::secondary_bg(ui.visuals()));
+        let fill = if actual {
+            colors::rank_to_color(rank)
+        } else {
+            colors::INFO.gamma_multiply(0.6)
+        };
+        let mut bar = rect;
+        bar.set_width(CHART_BAR_WIDTH * prob.clamp(0.0, 1.0));
+        painter.rect_filled(bar, 2.0, fill);
+        ui.label(RichText::new(format!("{:.1}%", prob * 100.0)).size(11.0));
+        if let Some(cumulative) = cumulative {
+            ui.label(
+                RichText::new(format!("Σ {:.0}%", cumulative * 100.0))
+                    .size(10.0)
+                    .color(colors::text_muted(ui.visuals())),
+            );
+        }
+    });
+}
+
+/// Cumulative probability of the top-N predictions against N, on a log
+/// scale, with the actual token's rank marked: a rank on the flat part of
+/// the curve sat among many near-equal alternatives, one past its bend was
+/// deep in the tail.
+pub fn render_rank_curve(ui: &mut Ui, token: &AnalyzedToken, id: impl std::hash::Hash) {
+    if token.rank_curve.is_empty() {
+        return;
+    }
+    let series: Vec<[f64; 2]> = CURVE_RANKS
+        .iter()
+        .zip(&token.rank_curve)
+        .map(|(&rank, &mass)| [(rank as f64).log2(), mass as f64])
+        .collect();
+    let summary = match curve_mass_at(&token.rank_curve, token.rank) {
+        Some(mass) => format!(
+            "The top {} predictions, down to the actual token, hold about {:.1}% of the \
+             probability",
+            token.rank,
+            mass * 100.0
+        ),
+        None => format!(
+            "The actual token ranked {}, past the last charted rank",
+            token.rank
+        ),
+    };
+    ui.label(
+        RichText::new(summary)
+            .size(11.0)
+            .color(colors::text_muted(ui.visuals())),
+    );
+    egui_plot::Plot::new(id)
+        .height(120.0)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .include_y(0.0)
+        .include_y(1.0)
+        .x_axis_label("Rank")
+        .y_axis_label("Cumulative probability")
+        .x_axis_formatter(|mark, _| format!("{:.0}", mark.value.exp2()))
+        .y_axis_formatter(|mark, _| format!("{:.0}%", mark.value * 100.0))
+        .label_formatter(|_, point| format!("Top {:.0}: {:.1}%", point.x.exp2(), point.y * 100.0))
+        .show(ui, |plot_ui| {
+            plot_ui.line(egui_plot::Line::new(series.clone()).color(colors::ACCENT_PRIMARY));
+            plot_ui.points(
+                egui_plot::Points::new(series)
+                    .radius(2.5)
+                    .color(colors::ACCENT_PRIMARY),
+            );
+            plot_ui.vline(
+                egui_plot::VLine::new((token.rank as f64).log2())
+                    .color(colors::rank_to_color(token.rank))
+                    .name("Actual token"),
+            );
+        });
+}
+
+/// Cumulative probability at `rank`, interpolated on the log scale between
+/// the recorded ranks. `None` past the last one.
+fn curve_mass_at(curve: &[f32], rank: usize) -> Option<f32> {
+    let ranks = &CURVE_RANKS[..curve.len()];
+    let i = ranks.iter().position(|&r| r >= rank)?;
+    if i == 0 || ranks[i] == rank {
+        return Some(curve[i]);
+    }
+    let (low, high) = ((ranks[i - 1] as f32).ln(), (ranks[i] as f32).ln());
+    let t = ((rank as f32).ln() - low) / (high - low);
+    Some(curve[i - 1] + (curve[i] - curve[i - 1]) * t)
+}
@@ -0,0 +1,612 @@
+use std::collections::BTreeMap;
+
+use egui::{Color32, RichText, Ui};
+use egui_plot::{BoxElem, BoxPlot, BoxSpread, Legend, Line, Plot, PlotPoints, Points};
+
+use crate::colors;
+use crate::roc::RocCurve;
+use crate::store::{self, AnalysisStore, Metric, StoredAnalysis, VerdictThreshold};
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+const SECONDS_PER_WEEK: u64 = 7 * 86_400;
+/// Colors of the models' series, reused when there are more models.
+const SERIES_COLORS: [Color32; 5] = [
+    colors::INFO,
+    colors::WARNING,
+    colors::SUCCESS,
+    colors::ACCENT_PRIMARY,
+    colors::ERROR,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrendView {
+    OverTime,
+    /// Distribution of the metric within each tag.
+    ByTag,
+    /// How well each metric tells two tags apart.
+    Roc,
+}
+
+pub struct TrendsState {
+    pub view: TrendView,
+    /// Model whose analyses are plotted; `None` plots every model.
+    pub model: Option<String>,
+    /// Only analyses with this tag are plotted over time.
+    pub tag: Option<String>,
+    pub metric: Metric,
+    /// Also draw the mean of each week.
+    pub weekly: bool,
+    /// Tag of the generated texts in the ROC view.
+    pub positive: Option<String>,
+    /// Tag of the human texts in the ROC view.
+    pub negative: Option<String>,
+    /// Threshold picked on the metric; `None` for the best one.
+    pub threshold: Option<f32>,
+}
+
+#[derive(Default)]
+pub struct TrendsAction {
+    pub set_verdict: Option<VerdictThreshold>,
+    pub clear_verdict: bool,
+}
+
+impl Default for TrendsState {
+    fn default() -> Self {
+        Self {
+            view: TrendView::OverTime,
+            model: None,
+            tag: None,
+            metric: Metric::Perplexity,
+            weekly: true,
+            positive: None,
+            negative: None,
+            threshold: None,
+        }
+    }
+}
+
+/// Stored analyses plotted over time, one series per model, to follow how
+/// a writer's texts or a model's outputs change across weeks; or compared
+/// across tags. `verdict` is the threshold currently used to label new
+/// analyses.
+pub fn render_trends_tab(
+    ui: &mut Ui,
+    state: &mut TrendsState,
+    store: &AnalysisStore,
+    verdict: Option<&VerdictThreshold>,
+) -> TrendsAction {
+    let mut action = TrendsAction::default();
+    ui.add_space(12.0);
+    let records = &store.records;
+    let tags = store.tags();
+    let mut models: Vec<&str> = records.iter().map(|r| r.model.as_str()).collect();
+    models.sort_unstable();
+    models.dedup();
+
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut state.view, TrendView::OverTime, "Over time");
+        ui.selectable_value(&mut state.view, TrendView::ByTag, "By tag");
+        ui.selectable_value(&mut state.view, TrendView::Roc, "ROC");
+        ui.add_space(12.0);
+        ui.label(RichText::new("Model").size(13.0));
+        egui::ComboBox::from_id_salt("trend_model")
+            .selected_text(state.model.as_deref().unwrap_or("All models"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut state.model, None, "All models");
+                for model in &models {
+                    ui.selectable_value(&mut state.model, Some(model.to_string()), *model);
+                }
+            });
+        ui.add_space(12.0);
+        ui.label(RichText::new("Metric").size(13.0));
+        egui::ComboBox::from_id_salt("trend_metric")
+            .selected_text(state.metric.label())
+            .show_ui(ui, |ui| {
+                for metric in Metric::ALL {
+                    ui.selectable_value(&mut state.metric, metric, metric.label());
+                }
+            });
+        if state.view == TrendView::OverTime {
+            ui.add_space(12.0);
+            ui.label(RichText::new("Tag").size(13.0));
+            egui::ComboBox::from_id_salt("trend_tag")
+                .selected_text(state.tag.as_deref().unwrap_or("Any"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut state.tag, None, "Any");
+                    for tag in &tags {
+                        ui.selectable_value(&mut state.tag, Some(tag.to_string()), *tag);
+                    }
+                });
+            ui.add_space(12.0);
+            ui.checkbox(&mut state.weekly, "Weekly mean");
+        }
+    });
+    ui.add_space(8.0);
+
+    let filtered = records
+        .iter()
+        .filter(|r| state.model.as_deref().is_none_or(|m| m == r.model));
+    match state.view {
+        TrendView::ByTag => {
+            let filtered: Vec<&StoredAnalysis> = filtered.collect();
+            render_tag_comparison(ui, &filtered, &tags, state.metric);
+            return action;
+        }
+        TrendView::Roc => {
+            let filtered: Vec<&StoredAnalysis> = filtered.collect();
+            render_roc(ui, state, &filtered, &tags, verdict, &mut action);
+            return action;
+        }
+        TrendView::OverTime => {}
+    }
+    let mut series: BTreeMap<&str, Vec<&StoredAnalysis>> = BTreeMap::new();
+    for record in filtered {
+        if state
+            .tag
+            .as_ref()
+            .is_none_or(|tag| record.tags.contains(tag))
+        {
+            series.entry(&record.model).or_default().push(record);
+        }
+    }
+    if series.is_empty() {
+        let message = if records.is_empty() {
+            "No stored analyses yet. Finished analyses are recorded here while the \
+             analysis store is enabled in the Export settings."
+        } else {
+            "No stored analyses match the model and tag."
+        };
+        ui.label(
+            RichText::new(message)
+                .size(12.0)
+                .color(colors::text_muted(ui.visuals())),
+        );
+        return action;
+    }
+
+    let metric = state.metric;
+    let height = (ui.available_height() - 160.0).max(200.0);
+    Plot::new("trends_plot")
+        .height(height)
+        .legend(Legend::default())
+        .x_axis_formatter(|mark, _| {
+            store::format_timestamp((mark.value * SECONDS_PER_DAY).max(0.0) as u64)[..10]
+                .to_string()
+        })
+        .label_formatter(move |name, point| {
+            let date = store::format_timestamp((point.x * SECONDS_PER_DAY).max(0.0) as u64);
+            format!("{}\n{}\n{}: {:.3}", name, date, metric.label(), point.y)
+        })
+        .show(ui, |plot_ui| {
+            for (i, (model, records)) in series.iter().enumerate() {
+                let color = SERIES_COLORS[i % SERIES_COLORS.len()];
+                let points: Vec<[f64; 2]> = records
+                    .iter()
+                    .map(|r| [r.timestamp as f64 / SECONDS_PER_DAY, metric.value(r) as f64])
+                    .collect();
+                plot_ui.points(
+                    Points::new(PlotPoints::from(points))
+                        .radius(3.0)
+                        .color(color)
+                        .name(*model),
+                );
+                if state.weekly {
+                    let weeks: Vec<[f64; 2]> = weekly_means(records, metric)
+                        .into_iter()
+                        .map(|(week, mean)| {
+                            let middle = week * SECONDS_PER_WEEK + SECONDS_PER_WEEK / 2;
+                            [middle as f64 / SECONDS_PER_DAY, mean]
+                        })
+                        .collect();
+                    plot_ui.line(Line::new(PlotPoints::from(weeks)).color(color).name(*model));
+                }
+            }
+        });
+
+    ui.add_space(8.0);
+    egui::Grid::new("trends_summary")
+        .num_columns(5)
+        .striped(true)
+        .spacing([16.0, 2.0])
+        .show(ui, |ui| {
+            for header in ["Model", "Analyses", "Mean", "Latest week", "First – last"] {
+                ui.label(RichText::new(header).strong().size(12.0));
+            }
+            ui.end_row();
+            for (model, records) in &series {
+                let mean = records.iter().map(|r| metric.value(r) as f64).sum::<f64>()
+                    / records.len() as f64;
+                let latest = weekly_means(records, metric)
+                    .last()
+                    .map_or(0.0, |&(_, mean)| mean);
+                let (first, last) = records.iter().fold((u64::MAX, 0), |(lo, hi), r| {
+                    (lo.min(r.timestamp), hi.max(r.timestamp))
+                });
+                ui.label(RichText::new(*model).size(12.0));
+                ui.label(RichText::new(records.len().to_string()).size(12.0));
+                ui.label(RichText::new(format!("{:.3}", mean)).size(12.0));
+                ui.label(RichText::new(format!("{:.3}", latest)).size(12.0));
+                ui.label(
+                    RichText::new(format!(
+                        "{} – {}",
+                        &store::format_timestamp(first)[..10],
+                        &store::format_timestamp(last)[..10]
+                    ))
+                    .size(12.0),
+                );
+                ui.end_row();
+            }
+        });
+    action
+}
+
+/// Summary statistics of a metric's values.
+struct Distribution {
+    count: usize,
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    q1: f64,
+    median: f64,
+    q3: f64,
+    max: f64,
+}
+
+impl Distribution {
+    fn of(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let n = values.len();
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let variance =
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n.max(2) - 1) as f64;
+        // Linear interpolation between the closest ranks.
+        let quantile = |q: f64| {
+            let position = q * (n - 1) as f64;
+            let (low, high) = (position.floor() as usize, position.ceil() as usize);
+            values[low] + (values[high] - values[low]) * (position - low as f64)
+        };
+        Some(Self {
+            count: n,
+            mean,
+            std_dev: variance.sqrt(),
+            min: values[0],
+            q1: quantile(0.25),
+            median: quantile(0.5),
+            q3: quantile(0.75),
+            max: values[n - 1],
+        })
+    }
+}
+
+/// A box plot of the metric for each tag and a table of their summary
+/// statistics. Analyses with several tags count in each of them.
+fn render_tag_comparison(ui: &mut Ui, records: &[&StoredAnalysis], tags: &[&str], metric: Metric) {
+    let groups: Vec<(&str, Distribution)> = tags
+        .iter()
+        .filter_map(|&tag| {
+            let values: Vec<f64> = records
+                .iter()
+                .filter(|r| r.tags.iter().any(|t| t == tag))
+                .map(|r| metric.value(r) as f64)
+                .collect();
+            Some((tag, Distribution::of(values)?))
+        })
+        .collect();
+    if groups.is_empty() {
+        ui.label(
+            RichText::new(
+                "No tagged analyses. Tag stored analyses in the list below the queue, or \
+                 set tags for new analyses there before running them.",
+            )
+            .size(12.0)
+            .color(colors::text_muted(ui.visuals())),
+        );
+        return;
+    }
+
+    let names: Vec<String> = groups.iter().map(|(tag, _)| tag.to_string()).collect();
+    let height = (ui.available_height() - 60.0 - 20.0 * groups.len() as f32).max(200.0);
+    Plot::new("tag_box_plot")
+        .height(height)
+        .allow_drag(false)
+        .x_axis_formatter(move |mark, _| {
+            let index = mark.value.round();
+            if (mark.value - index).abs() < 1e-6 && index >= 0.0 {
+                names.get(index as usize).cloned().unwrap_or_default()
+            } else {
+                String::new()
+            }
+        })
+        .show(ui, |plot_ui| {
+            for (i, (tag, d)) in groups.iter().enumerate() {
+                let color = SERIES_COLORS[i % SERIES_COLORS.len()];
+                let spread = BoxSpread::new(d.min, d.q1, d.median, d.q3, d.max);
+                plot_ui.box_plot(
+                    BoxPlot::new(vec![BoxElem::new(i as f64, spread)
+                        .name(*tag)
+                        .box_width(0.5)
+                        .whisker_width(0.3)])
+                    .color(color)
+                    .name(*tag),
+                );
+            }
+        });
+
+    ui.add_space(8.0);
+    egui::Grid::new("tag_summary")
+        .num_columns(8)
+        .striped(true)
+        .spacing([16.0, 2.0])
+        .show(ui, |ui| {
+            for header in [
+                "Tag",
+                "Analyses",
+                "Mean",
+                "Std. dev.",
+                "Min",
+                "Median",
+                "IQR",
+                "Max",
+            ] {
+                ui.label(RichText::new(header).strong().size(12.0));
+            }
+            ui.end_row();
+            for (tag, d) in &groups {
+                ui.label(RichText::new(*tag).size(12.0));
+                ui.label(RichText::new(d.count.to_string()).size(12.0));
+                for value in [d.mean, d.std_dev, d.min, d.median] {
+                    ui.label(RichText::new(format!("{:.3}", value)).size(12.0));
+                }
+                ui.label(RichText::new(format!("{:.3} – {:.3}", d.q1, d.q3)).size(12.0));
+                ui.label(RichText::new(format!("{:.3}", d.max)).size(12.0));
+                ui.end_row();
+            }
+        });
+}
+
+/// ROC curves of every metric for telling the texts of one tag from those
+/// of another, and a threshold on the chosen metric that can be adopted to
+/// label new analyses.
+fn render_roc(
+    ui: &mut Ui,
+    state: &mut TrendsState,
+    records: &[&StoredAnalysis],
+    tags: &[&str],
+    verdict: Option<&VerdictThreshold>,
+    action: &mut TrendsAction,
+) {
+    let muted = colors::text_muted(ui.visuals());
+    ui.horizontal(|ui| {
+        for (label, id, tag) in [
+            ("Generated", "roc_positive", &mut state.positive),
+            ("Human", "roc_negative", &mut state.negative),
+        ] {
+            ui.label(RichText::new(label).size(13.0));
+            egui::ComboBox::from_id_salt(id)
+                .selected_text(tag.as_deref().unwrap_or("Pick a tag"))
+                .show_ui(ui, |ui| {
+                    for &name in tags {
+                        ui.selectable_value(tag, Some(name.to_string()), name);
+                    }
+                });
+            ui.add_space(12.0);
+        }
+    });
+    if let Some(verdict) = verdict {
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!(
+                    "New analyses by {} are labeled {} when their {} is {} {:.3}, else {}.",
+                    verdict.model,
+                    verdict.positive,
+                    verdict.metric.label().to_lowercase(),
+                    if verdict.metric.lower_means_generated() {
+                        "at most"
+                    } else {
+                        "at least"
+                    },
+                    verdict.threshold,
+                    verdict.negative
+                ))
+                .size(12.0)
+                .color(muted),
+            );
+            action.clear_verdict = ui.small_button("Stop labeling").clicked();
+        });
+    }
+    ui.add_space(8.0);
+
+    let (Some(model), Some(positive), Some(negative)) =
+        (&state.model, &state.positive, &state.negative)
+    else {
+        ui.label(
+            RichText::new(
+                "Pick a model, since metrics aren't comparable across models, and the tags \
+                 of the generated and the human texts.",
+            )
+            .size(12.0)
+            .color(muted),
+        );
+        return;
+    };
+    // Texts carrying both tags would count on both sides.
+    let values = |tag: &str, other: &str, metric: Metric| -> Vec<f32> {
+        records
+            .iter()
+            .filter(|r| r.tags.iter().any(|t| t == tag) && !r.tags.iter().any(|t| t == other))
+            .map(|r| metric.value(r))
+            .collect()
+    };
+    let curves: Vec<(Metric, RocCurve)> = Metric::ALL
+        .into_iter()
+        .filter_map(|metric| {
+            let curve = RocCurve::new(
+                &values(positive, negative, metric),
+                &values(negative, positive, metric),
+                metric.lower_means_generated(),
+            )?;
+            Some((metric, curve))
+        })
+        .collect();
+    let Some((_, selected)) = curves.iter().find(|(m, _)| *m == state.metric) else {
+        ui.label(
+            RichText::new(format!(
+                "Both tags need analyses by {} that don't carry the other tag.",
+                model
+            ))
+            .size(12.0)
+            .color(muted),
+        );
+        return;
+    };
+
+    let (low, high) = selected.score_range();
+    let threshold = state
+        .threshold
+        .filter(|t| (low..=high).contains(t))
+        .unwrap_or(selected.best().threshold.clamp(low, high));
+    let point = selected.at(threshold);
+
+    ui.columns(2, |columns| {
+        Plot::new("roc_plot")
+            .height(260.0)
+            .data_aspect(1.0)
+            .include_x(0.0)
+            .include_x(1.0)
+            .include_y(0.0)
+            .include_y(1.0)
+            .legend(Legend::default())
+            .x_axis_label("False positive rate")
+            .y_axis_label("True positive rate")
+            .show(&mut columns[0], |plot_ui| {
+                plot_ui.line(
+                    Line::new(PlotPoints::from(vec![[0.0, 0.0], [1.0, 1.0]]))
+                        .color(muted)
+                        .style(egui_plot::LineStyle::dashed_loose()),
+                );
+                for (i, (metric, curve)) in curves.iter().enumerate() {
+                    let points: Vec<[f64; 2]> = curve
+                        .points
+                        .iter()
+                        .map(|p| [p.false_positive_rate as f64, p.true_positive_rate as f64])
+                        .collect();
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(points))
+                            .color(SERIES_COLORS[i % SERIES_COLORS.len()])
+                            .name(format!("{} (AUC {:.3})", metric.label(), curve.auc)),
+                    );
+                }
+                plot_ui.points(
+                    Points::new(PlotPoints::from(vec![[
+                        point.false_positive_rate as f64,
+                        point.true_positive_rate as f64,
+                    ]]))
+                    .radius(5.0)
+                    .color(colors::ERROR),
+                );
+            });
+
+        let ui = &mut columns[1];
+        egui::Grid::new("roc_summary")
+            .num_columns(5)
+            .striped(true)
+            .spacing([12.0, 2.0])
+            .show(ui, |ui| {
+                for header in ["Metric", "AUC", "Best threshold", "TPR", "FPR"] {
+                    ui.label(RichText::new(header).strong().size(12.0));
+                }
+                ui.end_row();
+                for (metric, curve) in &curves {
+                    let best = curve.best();
+                    ui.label(RichText::new(metric.label()).size(12.0));
+                    ui.label(RichText::new(format!("{:.3}", curve.auc)).size(12.0));
+                    ui.label(RichText::new(format!("{:.3}", best.threshold)).size(12.0));
+                    ui.label(
+                        RichText::new(format!("{:.0}%", best.true_positive_rate * 100.0))
+                            .size(12.0),
+                    );
+                    ui.label(
+                        RichText::new(format!("{:.0}%", best.false_positive_rate * 100.0))
+                            .size(12.0),
+                    );
+                    ui.end_row();
+                }
+            });
+        ui.label(
+            RichText::new(format!(
+                "{} {} and {} {} texts",
+                selected.positives, positive, selected.negatives, negative
+            ))
+            .size(11.0)
+            .color(muted),
+        );
+
+        ui.add_space(12.0);
+        ui.label(RichText::new(format!("{} threshold", state.metric.label())).strong());
+        let mut value = threshold;
+        if ui
+            .add(egui::Slider::new(&mut value, low..=high).max_decimals(3))
+            .changed()
+        {
+            state.threshold = Some(value);
+        }
+        ui.label(
+            RichText::new(format!(
+                "Labels {:.0}% of the {} texts and {:.0}% of the {} texts as {}.",
+                point.true_positive_rate * 100.0,
+                positive,
+                point.false_positive_rate * 100.0,
+                negative,
+                positive
+            ))
+            .size(12.0),
+        );
+        ui.horizontal(|ui| {
+            if ui
+                .button("Best")
+                .on_hover_text("The threshold with the largest gap between the two rates")
+                .clicked()
+            {
+                state.threshold = None;
+            }
+            if ui
+                .button("✔ Label new analyses")
+                .on_hover_text(format!(
+                    "Show a verdict badge with every new analysis by {}",
+                    model
+                ))
+                .clicked()
+            {
+                action.set_verdict = Some(VerdictThreshold {
+                    model: model.clone(),
+                    metric: state.metric,
+                    threshold,
+                    positive: positive.clone(),
+                    negative: negative.clone(),
+                    true_positive_rate: point.true_positive_rate,
+                    false_positive_rate: point.false_positive_rate,
+                });
+            }
+        });
+    });
+}
+
+/// Mean of the metric in each week with analyses, as `(week number since
+/// the epoch, mean)` in order.
+fn weekly_means(records: &[&StoredAnalysis], metric: Metric) -> Vec<(u64, f64)> {
+    let mut weeks: BTreeMap<u64, (f64, usize)> = BTreeMap::new();
+    for record in records {
+        let week = weeks
+            .entry(record.timestamp / SECONDS_PER_WEEK)
+            .or_default();
+        week.0 += metric.value(record) as f64;
+        week.1 += 1;
+    }
+    weeks
+        .into_iter()
+        .map(|(week, (sum, count))| (week, sum / count as f64))
+        .collect()
+}
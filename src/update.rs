@@ -0,0 +1,93 @@
+use std::process::Command;
+use std::sync::mpsc;
+
+use serde::Deserialize;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/Belluxx/Perplex/releases/latest";
+
+/// A published release newer than the running build.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub version: String,
+    pub url: String,
+    /// Release notes, in Markdown.
+    pub changelog: String,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// Checks for a newer release in the background. The receiver yields
+/// one message, `None` when the running build is current; failures are
+/// only logged since the check is a courtesy.
+pub fn check_in_background(ctx: egui::Context) -> mpsc::Receiver<Option<Release>> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let release = match latest_release() {
+            Ok(release) => {
+                Some(release).filter(|r| is_newer(&r.version, env!("CARGO_PKG_VERSION")))
+            }
+            Err(e) => {
+                log::warn!("Update check failed: {}", e);
+                None
+            }
+        };
+        if let Some(ref release) = release {
+            log::info!("Perplex {} is available", release.version);
+        }
+        let _ = tx.send(release);
+        ctx.request_repaint();
+    });
+    rx
+}
+
+/// Fetches the latest release with `curl`, which ships with current
+/// Linux, macOS and Windows, so no TLS stack has to be bundled.
+fn latest_release() -> Result<Release, String> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "--max-time", "10"])
+        .args(["-H", "Accept: application/vnd.github+json"])
+        .args([
+            "-H",
+            concat!("User-Agent: perplex/", env!("CARGO_PKG_VERSION")),
+        ])
+        .arg(RELEASES_URL)
+        .output()
+        .map_err(|e| format!("failed to run curl: {}", e))?;
+    if !output.status.success() {
+        // No release published yet also lands here, as a 404.
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let release: GithubRelease =
+        serde_json::from_slice(&output.stdout).map_err(|e| format!("invalid response: {}", e))?;
+    Ok(Release {
+        version: release.tag_name.trim_start_matches('v').to_string(),
+        url: release.html_url,
+        changelog: release.body.unwrap_or_default(),
+    })
+}
+
+/// Compares dotted version numbers; a pre-release suffix like `-rc1` is
+/// ignored.
+fn is_newer(candidate: &str, current: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        let mut parts: Vec<u64> = version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect();
+        // So that 1.2.0 equals 1.2.
+        while parts.last() == Some(&0) {
+            parts.pop();
+        }
+        parts
+    };
+    parse(candidate) > parse(current)
+}
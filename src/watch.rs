@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::hook;
+use crate::html_report;
+use crate::report::ReportSection;
+
+/// How often the folder is listed.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// A file must go this long without changing before it is read, so one
+/// still being copied in isn't analyzed half-written.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+const TEXT_EXTENSIONS: [&str; 2] = ["txt", "md"];
+/// Inserted before the extension of the files written next to a text, e.g.
+/// `notes.perplex.json` for `notes.txt`.
+const OUTPUT_SUFFIX: &str = "perplex";
+
+/// An inbox folder whose new text files are analyzed automatically; set in
+/// the settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchOptions {
+    pub enabled: bool,
+    pub folder: String,
+    /// Write the results as JSON next to each text.
+    pub json: bool,
+    /// Write the interactive HTML report next to each text.
+    pub html: bool,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            folder: String::new(),
+            json: true,
+            html: false,
+        }
+    }
+}
+
+impl WatchOptions {
+    pub fn folder(&self) -> Option<&Path> {
+        let folder = self.folder.trim();
+        (self.enabled && !folder.is_empty()).then(|| Path::new(folder))
+    }
+}
+
+/// Finds text files dropped into the watched folder.
+#[derive(Default)]
+pub struct FolderWatcher {
+    /// Files already handed out, with their modification time, so each
+    /// version is queued once per session; one dropped again under the same
+    /// name is a new version.
+    seen: HashSet<(PathBuf, SystemTime)>,
+    next_scan: Option<Instant>,
+}
+
+impl FolderWatcher {
+    /// Text files that appeared in `folder` since the last call and have
+    /// settled, skipping those whose results are already newer than them.
+    /// Lists the folder at most every [`POLL_INTERVAL`].
+    pub fn poll(&mut self, folder: &Path) -> Result<Vec<PathBuf>, String> {
+        let now = Instant::now();
+        if self.next_scan.is_some_and(|next| now < next) {
+            return Ok(Vec::new());
+        }
+        self.next_scan = Some(now + POLL_INTERVAL);
+
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(folder).map_err(|e| e.to_string())? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            let is_text = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| TEXT_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+            if !is_text {
+                continue;
+            }
+            let Some(modified) = modified(&path) else {
+                continue;
+            };
+            if modified.elapsed().unwrap_or_default() < SETTLE_TIME
+                || !self.seen.insert((path.clone(), modified))
+            {
+                continue;
+            }
+            let done = ["json", "html"]
+                .into_iter()
+                .filter_map(|extension| modified_output(&path, extension))
+                .any(|output| output >= modified);
+            if !done {
+                found.push(path);
+            }
+        }
+        found.sort();
+        Ok(found)
+    }
+
+    /// Forgets the files handed out, e.g. after the folder changed.
+    pub fn reset(&mut self) {
+        self.seen.clear();
+        self.next_scan = None;
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn modified_output(source: &Path, extension: &str) -> Option<SystemTime> {
+    modified(&output_path(source, extension))
+}
+
+/// `notes.txt` → `notes.perplex.<extension>` in the same folder.
+pub fn output_path(source: &Path, extension: &str) -> PathBuf {
+    let stem = source
+        .file_stem()
+        .map_or_else(|| "text".into(), |s| s.to_string_lossy());
+    source.with_file_name(format!("{}.{}.{}", stem, OUTPUT_SUFFIX, extension))
+}
+
+/// Writes the results of a watched file next to it, in the formats picked
+/// in the settings.
+pub fn write_outputs(
+    options: &WatchOptions,
+    source: &Path,
+    sections: &[ReportSection],
+) -> Result<(), String> {
+    let name = source
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    if options.json {
+        let results: Vec<serde_json::Value> = sections
+            .iter()
+            .map(|section| hook::payload(section.name, &name, section.result))
+            .collect();
+        let content = serde_json::to_string_pretty(&json!({ "file": name, "results": results }))
+            .map_err(|e| e.to_string())?;
+        std::fs::write(output_path(source, "json"), content).map_err(|e| e.to_string())?;
+    }
+    if options.html {
+        html_report::write_html(&output_path(source, "html"), sections)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,401 @@
+use std::fmt::Write as _;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::analysis;
+use crate::diagnostics::ZipWriter;
+use crate::report::{self, ReportSection};
+use crate::shuffle;
+
+/// A spreadsheet cell. Text is written inline, so the workbook needs no
+/// shared string table.
+enum Cell {
+    Text(String),
+    Number(f64),
+    Empty,
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        Cell::Text(text.to_string())
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Cell::Text(text)
+    }
+}
+
+impl From<f32> for Cell {
+    fn from(value: f32) -> Self {
+        if value.is_finite() {
+            Cell::Number(value as f64)
+        } else {
+            Cell::Empty
+        }
+    }
+}
+
+impl From<usize> for Cell {
+    fn from(value: usize) -> Self {
+        Cell::Number(value as f64)
+    }
+}
+
+/// A worksheet whose first row is a bold header, kept in view while
+/// scrolling.
+struct Sheet {
+    name: &'static str,
+    header: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Sheet {
+    fn new(name: &'static str, header: &[&str]) -> Self {
+        Self {
+            name,
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+}
+
+/// Writes an Excel workbook with a summary sheet comparing the results, the
+/// metrics of every token, aggregates per sentence and the run metadata.
+/// `metadata` lists the settings the results were produced with.
+pub fn write_xlsx(
+    path: &Path,
+    sections: &[ReportSection],
+    metadata: &[(String, String)],
+) -> Result<(), String> {
+    let sheets = [
+        summary_sheet(sections),
+        token_sheet(sections),
+        sentence_sheet(sections),
+        metadata_sheet(sections, metadata),
+    ];
+
+    let mut zip = ZipWriter::default();
+    let mut content_types = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>"#,
+    );
+    let mut workbook = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>"#,
+    );
+    let mut relationships = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    );
+    for (i, sheet) in sheets.iter().enumerate() {
+        let n = i + 1;
+        let _ = write!(
+            content_types,
+            r#"<Override PartName="/xl/worksheets/sheet{n}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#
+        );
+        let _ = write!(
+            workbook,
+            r#"<sheet name="{}" sheetId="{n}" r:id="rId{n}"/>"#,
+            sheet.name
+        );
+        let _ = write!(
+            relationships,
+            r#"<Relationship Id="rId{n}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{n}.xml"/>"#
+        );
+        zip.add(
+            &format!("xl/worksheets/sheet{}.xml", n),
+            worksheet_xml(sheet).as_bytes(),
+        );
+    }
+    content_types.push_str("</Types>");
+    workbook.push_str("</sheets></workbook>");
+    let _ = write!(
+        relationships,
+        r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#,
+        sheets.len() + 1
+    );
+
+    zip.add("[Content_Types].xml", content_types.as_bytes());
+    zip.add(
+        "_rels/.rels",
+        br#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#,
+    );
+    zip.add("xl/workbook.xml", workbook.as_bytes());
+    zip.add("xl/_rels/workbook.xml.rels", relationships.as_bytes());
+    zip.add("xl/styles.xml", STYLES.as_bytes());
+    std::fs::write(path, zip.finish()).map_err(|e| e.to_string())
+}
+
+/// A regular and a bold font; cell style 1 is the bold header.
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts><fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills><borders count="1"><border/></borders><cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs><cellXfs count="2"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/><xf numFmtId="0" fontId="1" fillId="0" borderId="0" xfId="0" applyFont="1"/></cellXfs></styleSheet>"#;
+
+/// The metrics of [`report::summary`], one column per model. Values that
+/// are plain numbers are written as numbers so they can be charted.
+fn summary_sheet(sections: &[ReportSection]) -> Sheet {
+    let mut header = vec!["Metric"];
+    header.extend(sections.iter().map(|s| s.name));
+    let mut sheet = Sheet::new("Summary", &header);
+
+    let summaries: Vec<Vec<(&str, String)>> = sections
+        .iter()
+        .map(|s| report::summary(s.result, &s.format))
+        .collect();
+    let mut labels: Vec<&str> = Vec::new();
+    for (label, _) in summaries.iter().flatten() {
+        if !labels.contains(label) {
+            labels.push(label);
+        }
+    }
+    for label in labels {
+        let mut row = vec![Cell::from(label)];
+        row.extend(summaries.iter().map(
+            |summary| match summary.iter().find(|(l, _)| *l == label) {
+                Some((_, value)) => match value.parse::<f64>() {
+                    Ok(number) => Cell::Number(number),
+                    Err(_) => Cell::from(value.as_str()),
+                },
+                None => Cell::Empty,
+            },
+        ));
+        sheet.rows.push(row);
+    }
+    sheet
+}
+
+/// Every token of every result. Prompt tokens and the first token have no
+/// prediction, so their metrics are left empty.
+fn token_sheet(sections: &[ReportSection]) -> Sheet {
+    let mut sheet = Sheet::new(
+        "Tokens",
+        &[
+            "Model",
+            "Index",
+            "Token",
+            "Rank",
+            "Probability",
+            "Surprisal (bits)",
+            "Top-1 margin (nats)",
+            "Unigram surprisal (bits)",
+            "Repeated",
+            "Top prediction",
+            "Top probability",
+            "Context start",
+        ],
+    );
+    for section in sections {
+        let result = section.result;
+        for (index, token) in result.tokens.iter().enumerate() {
+            let mut row = vec![
+                Cell::from(section.name),
+                Cell::from(index),
+                Cell::from(&*token.text),
+            ];
+            if index >= result.first_scored() {
+                let (top_id, top_probability) =
+                    token.top_predictions.first().copied().unwrap_or((-1, 0.0));
+                row.extend([
+                    Cell::from(token.rank),
+                    Cell::from(token.probability),
+                    Cell::from(token.surprisal()),
+                    Cell::from(token.top1_margin),
+                    Cell::from(token.unigram_surprisal),
+                    Cell::from(if token.repeated { "yes" } else { "no" }),
+                    if top_id >= 0 {
+                        Cell::from(result.vocab.piece(top_id))
+                    } else {
+                        Cell::Empty
+                    },
+                    Cell::from(top_probability),
+                    Cell::from(token.context_start),
+                ]);
+            }
+            sheet.rows.push(row);
+        }
+    }
+    sheet
+}
+
+/// Perplexity and surprisal of each sentence of the completion, split as
+/// by [`shuffle::sentences`].
+fn sentence_sheet(sections: &[ReportSection]) -> Sheet {
+    let mut sheet = Sheet::new(
+        "Sentences",
+        &[
+            "Model",
+            "Sentence",
+            "Tokens",
+            "Perplexity",
+            "Mean surprisal (bits)",
+            "Max surprisal (bits)",
+            "Top-1 accuracy",
+            "Worst rank",
+            "Text",
+        ],
+    );
+    for section in sections {
+        let result = section.result;
+        let completion = &result.tokens[result.prompt_tokens.min(result.tokens.len())..];
+        let text: String = completion.iter().map(|t| t.document_text()).collect();
+        let mut ends = Vec::new();
+        let mut end = 0;
+        for sentence in shuffle::sentences(&text) {
+            end += sentence.len();
+            ends.push((end, sentence.trim()));
+        }
+
+        // Tokens go to the sentence their first visible character is in,
+        // since the whitespace between sentences ends the earlier one.
+        // Only scored tokens are counted.
+        let first = result.first_scored() - (result.tokens.len() - completion.len());
+        let mut groups: Vec<Range<usize>> = vec![0..0; ends.len()];
+        let mut offset = 0;
+        let mut sentence = 0;
+        for (i, token) in completion.iter().enumerate() {
+            let piece = token.document_text();
+            let start = offset + piece.len() - piece.trim_start().len();
+            offset += piece.len();
+            while sentence + 1 < ends.len() && start >= ends[sentence].0 {
+                sentence += 1;
+            }
+            if let Some(group) = groups.get_mut(sentence).filter(|_| i >= first) {
+                if group.end == 0 {
+                    group.start = i;
+                }
+                group.end = i + 1;
+            }
+        }
+
+        for (number, (range, (_, text))) in groups.iter().zip(&ends).enumerate() {
+            let tokens = &completion[range.clone()];
+            if tokens.is_empty() {
+                continue;
+            }
+            let n = tokens.len() as f32;
+            let top1 = tokens.iter().filter(|t| t.rank <= 1).count();
+            sheet.rows.push(vec![
+                Cell::from(section.name),
+                Cell::from(number + 1),
+                Cell::from(tokens.len()),
+                Cell::from(analysis::perplexity_of(tokens)),
+                Cell::from(tokens.iter().map(|t| t.surprisal()).sum::<f32>() / n),
+                Cell::from(tokens.iter().map(|t| t.surprisal()).fold(0.0, f32::max)),
+                Cell::from(top1 as f32 / n),
+                Cell::from(tokens.iter().map(|t| t.rank).max().unwrap_or_default()),
+                Cell::from(*text),
+            ]);
+        }
+    }
+    sheet
+}
+
+/// The settings shared by the run, then what each result records about
+/// itself.
+fn metadata_sheet(sections: &[ReportSection], metadata: &[(String, String)]) -> Sheet {
+    let mut sheet = Sheet::new("Metadata", &["Field", "Value"]);
+    let mut push = |field: String, value: Cell| sheet.rows.push(vec![Cell::from(field), value]);
+    push(
+        "Perplex version".to_string(),
+        Cell::from(env!("CARGO_PKG_VERSION")),
+    );
+    for (field, value) in metadata {
+        push(field.clone(), Cell::from(value.as_str()));
+    }
+    for section in sections {
+        let result = section.result;
+        let name = section.name;
+        push(format!("{}: tokens", name), Cell::from(result.tokens.len()));
+        push(
+            format!("{}: prompt tokens", name),
+            Cell::from(result.prompt_tokens),
+        );
+        push(
+            format!("{}: unscored tokens", name),
+            Cell::from(result.unscored.len()),
+        );
+        push(
+            format!("{}: scored in windows", name),
+            Cell::from(if result.is_windowed() { "yes" } else { "no" }),
+        );
+        push(
+            format!("{}: processing time (s)", name),
+            Cell::from(result.processing_time_ms as f32 / 1000.0),
+        );
+    }
+    sheet
+}
+
+fn worksheet_xml(sheet: &Sheet) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetViews><sheetView workbookViewId="0"><pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/></sheetView></sheetViews><sheetData>"#,
+    );
+    let header: Vec<Cell> = sheet
+        .header
+        .iter()
+        .map(|h| Cell::from(h.as_str()))
+        .collect();
+    write_row(&mut xml, 1, &header, true);
+    for (i, row) in sheet.rows.iter().enumerate() {
+        write_row(&mut xml, i + 2, row, false);
+    }
+    xml.push_str("</sheetData></worksheet>");
+    xml
+}
+
+fn write_row(xml: &mut String, number: usize, cells: &[Cell], bold: bool) {
+    let _ = write!(xml, r#"<row r="{}">"#, number);
+    let style = if bold { r#" s="1""# } else { "" };
+    for (column, cell) in cells.iter().enumerate() {
+        let reference = format!("{}{}", column_name(column), number);
+        match cell {
+            Cell::Text(text) => {
+                let _ = write!(
+                    xml,
+                    r#"<c r="{}" t="inlineStr"{}><is><t xml:space="preserve">{}</t></is></c>"#,
+                    reference,
+                    style,
+                    escape_xml(text)
+                );
+            }
+            Cell::Number(value) => {
+                let _ = write!(xml, r#"<c r="{}"{}><v>{}</v></c>"#, reference, style, value);
+            }
+            Cell::Empty => {}
+        }
+    }
+    xml.push_str("</row>");
+}
+
+/// A, B, …, Z, AA, AB, … for column `index` counted from zero.
+fn column_name(index: usize) -> String {
+    let mut name = Vec::new();
+    let mut n = index + 1;
+    while n > 0 {
+        name.push(b'A' + ((n - 1) % 26) as u8);
+        n = (n - 1) / 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+/// Escapes markup and drops the control characters XML can't represent,
+/// which tokens of raw bytes may decode to.
+fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}